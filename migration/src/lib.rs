@@ -8,6 +8,39 @@ mod m20241110_170457_sources;
 mod m20241111_110838_medias;
 mod m20250826_090400_add_last_scheduled_refresh_to_sources;
 mod m20260105_000000_drop_loco_job_tables;
+mod m20261014_100000_add_video_id_to_medias;
+mod m20261014_100100_add_client_options_to_sources;
+mod m20261014_100200_add_download_archive_to_sources;
+mod m20261014_100300_add_max_items_to_sources;
+mod m20261014_100400_add_duration_and_file_size_to_medias;
+mod m20261014_100500_add_thumbnail_to_sources;
+mod m20261014_100600_add_download_weight_to_sources;
+mod m20261014_100700_add_last_video_at_to_sources;
+mod m20261014_100800_add_index_checkpoint_to_sources;
+mod m20261014_100900_add_sidecar_options_to_sources;
+mod m20261014_101000_settings;
+mod m20261014_101100_add_webhook_url_to_sources;
+mod m20261014_101200_add_max_height_to_sources;
+mod m20261014_101300_add_last_download_at_to_sources;
+mod m20261014_101400_add_downloaded_format_to_medias;
+mod m20261014_101500_add_members_only_to_sources;
+mod m20261014_101600_add_start_from_now_to_sources;
+mod m20261014_101700_add_refresh_backoff_to_sources;
+mod m20261014_101800_add_write_subtitles_to_sources;
+mod m20261014_101900_add_available_at_to_medias;
+mod m20261014_102000_add_min_age_to_sources;
+mod m20261014_102100_add_media_root_to_sources;
+mod m20261014_102200_add_embed_options_to_sources;
+mod m20261014_102300_add_duplicate_of_to_medias;
+mod m20261014_102400_add_download_limits;
+mod m20261014_102500_add_concurrent_fragments_to_sources;
+mod m20261014_102600_add_download_paused_to_sources;
+mod m20261014_102700_add_last_refresh_duration_to_sources;
+mod m20261014_102800_add_aac_audio_to_sources;
+mod m20261014_102900_add_refresh_error_to_sources;
+mod m20261014_103000_add_age_limit_to_sources;
+mod m20261014_103100_add_trashed_at_to_medias;
+mod m20261014_103200_add_sponsorblock_api_to_sources;
 pub struct Migrator;
 
 #[async_trait::async_trait]
@@ -15,11 +48,44 @@ impl MigratorTrait for Migrator {
     fn migrations() -> Vec<Box<dyn MigrationTrait>> {
         vec![
             // inject-below (do not remove this comment)
-            Box::new(m20260105_000000_drop_loco_job_tables::Migration),
-            Box::new(m20241111_110838_medias::Migration),
-            Box::new(m20241110_170457_sources::Migration),
             Box::new(m20220101_000001_users::Migration),
+            Box::new(m20241110_170457_sources::Migration),
+            Box::new(m20241111_110838_medias::Migration),
             Box::new(m20250826_090400_add_last_scheduled_refresh_to_sources::Migration),
+            Box::new(m20260105_000000_drop_loco_job_tables::Migration),
+            Box::new(m20261014_100000_add_video_id_to_medias::Migration),
+            Box::new(m20261014_100100_add_client_options_to_sources::Migration),
+            Box::new(m20261014_100200_add_download_archive_to_sources::Migration),
+            Box::new(m20261014_100300_add_max_items_to_sources::Migration),
+            Box::new(m20261014_100400_add_duration_and_file_size_to_medias::Migration),
+            Box::new(m20261014_100500_add_thumbnail_to_sources::Migration),
+            Box::new(m20261014_100600_add_download_weight_to_sources::Migration),
+            Box::new(m20261014_100700_add_last_video_at_to_sources::Migration),
+            Box::new(m20261014_100800_add_index_checkpoint_to_sources::Migration),
+            Box::new(m20261014_100900_add_sidecar_options_to_sources::Migration),
+            Box::new(m20261014_101000_settings::Migration),
+            Box::new(m20261014_101100_add_webhook_url_to_sources::Migration),
+            Box::new(m20261014_101200_add_max_height_to_sources::Migration),
+            Box::new(m20261014_101300_add_last_download_at_to_sources::Migration),
+            Box::new(m20261014_101400_add_downloaded_format_to_medias::Migration),
+            Box::new(m20261014_101500_add_members_only_to_sources::Migration),
+            Box::new(m20261014_101600_add_start_from_now_to_sources::Migration),
+            Box::new(m20261014_101700_add_refresh_backoff_to_sources::Migration),
+            Box::new(m20261014_101800_add_write_subtitles_to_sources::Migration),
+            Box::new(m20261014_101900_add_available_at_to_medias::Migration),
+            Box::new(m20261014_102000_add_min_age_to_sources::Migration),
+            Box::new(m20261014_102100_add_media_root_to_sources::Migration),
+            Box::new(m20261014_102200_add_embed_options_to_sources::Migration),
+            Box::new(m20261014_102300_add_duplicate_of_to_medias::Migration),
+            Box::new(m20261014_102400_add_download_limits::Migration),
+            Box::new(m20261014_102500_add_concurrent_fragments_to_sources::Migration),
+            Box::new(m20261014_102600_add_download_paused_to_sources::Migration),
+            Box::new(m20261014_102700_add_last_refresh_duration_to_sources::Migration),
+            Box::new(m20261014_102800_add_aac_audio_to_sources::Migration),
+            Box::new(m20261014_102900_add_refresh_error_to_sources::Migration),
+            Box::new(m20261014_103000_add_age_limit_to_sources::Migration),
+            Box::new(m20261014_103100_add_trashed_at_to_medias::Migration),
+            Box::new(m20261014_103200_add_sponsorblock_api_to_sources::Migration),
             // inject-above (do not remove this comment)
         ]
     }
@@ -0,0 +1,53 @@
+use loco_rs::schema::*;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        m.alter_table(
+            Table::alter()
+                .table(Medias::Table)
+                .add_column(string_null(Medias::VideoId))
+                .to_owned(),
+        )
+        .await?;
+        // NULLs never collide, so rows indexed before this column existed stay valid.
+        m.create_index(
+            Index::create()
+                .name("idx-medias-source_id-video_id")
+                .table(Medias::Table)
+                .col(Medias::SourceId)
+                .col(Medias::VideoId)
+                .unique()
+                .to_owned(),
+        )
+        .await
+    }
+
+    async fn down(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        m.drop_index(
+            Index::drop()
+                .name("idx-medias-source_id-video_id")
+                .table(Medias::Table)
+                .to_owned(),
+        )
+        .await?;
+        m.alter_table(
+            Table::alter()
+                .table(Medias::Table)
+                .drop_column(Medias::VideoId)
+                .to_owned(),
+        )
+        .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Medias {
+    Table,
+    SourceId,
+    VideoId,
+}
@@ -0,0 +1,46 @@
+use loco_rs::schema::*;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        // SQLite only accepts a single change per ALTER TABLE statement.
+        m.alter_table(
+            Table::alter()
+                .table(Medias::Table)
+                .add_column(string_null(Medias::DownloadedFormat))
+                .to_owned(),
+        )
+        .await?;
+        m.alter_table(
+            Table::alter()
+                .table(Medias::Table)
+                .add_column(integer_null(Medias::DownloadedHeight))
+                .to_owned(),
+        )
+        .await
+    }
+
+    async fn down(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        for column in [Medias::DownloadedFormat, Medias::DownloadedHeight] {
+            m.alter_table(
+                Table::alter()
+                    .table(Medias::Table)
+                    .drop_column(column)
+                    .to_owned(),
+            )
+            .await?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Medias {
+    Table,
+    DownloadedFormat,
+    DownloadedHeight,
+}
@@ -0,0 +1,46 @@
+use loco_rs::schema::*;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        // SQLite only accepts a single change per ALTER TABLE statement.
+        m.alter_table(
+            Table::alter()
+                .table(Sources::Table)
+                .add_column(boolean(Sources::StartFromNow).default(false))
+                .to_owned(),
+        )
+        .await?;
+        m.alter_table(
+            Table::alter()
+                .table(Sources::Table)
+                .add_column(timestamp_null(Sources::DownloadBaseline))
+                .to_owned(),
+        )
+        .await
+    }
+
+    async fn down(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        for column in [Sources::DownloadBaseline, Sources::StartFromNow] {
+            m.alter_table(
+                Table::alter()
+                    .table(Sources::Table)
+                    .drop_column(column)
+                    .to_owned(),
+            )
+            .await?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Sources {
+    Table,
+    StartFromNow,
+    DownloadBaseline,
+}
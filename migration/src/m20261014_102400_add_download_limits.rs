@@ -0,0 +1,61 @@
+use loco_rs::schema::*;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        // SQLite only accepts a single change per ALTER TABLE statement.
+        for column in [Sources::MaxDurationMinutes, Sources::MaxFilesizeMb] {
+            m.alter_table(
+                Table::alter()
+                    .table(Sources::Table)
+                    .add_column(integer_null(column))
+                    .to_owned(),
+            )
+            .await?;
+        }
+        m.alter_table(
+            Table::alter()
+                .table(Medias::Table)
+                .add_column(string_null(Medias::SkipReason))
+                .to_owned(),
+        )
+        .await
+    }
+
+    async fn down(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        m.alter_table(
+            Table::alter()
+                .table(Medias::Table)
+                .drop_column(Medias::SkipReason)
+                .to_owned(),
+        )
+        .await?;
+        for column in [Sources::MaxFilesizeMb, Sources::MaxDurationMinutes] {
+            m.alter_table(
+                Table::alter()
+                    .table(Sources::Table)
+                    .drop_column(column)
+                    .to_owned(),
+            )
+            .await?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Sources {
+    Table,
+    MaxDurationMinutes,
+    MaxFilesizeMb,
+}
+
+#[derive(DeriveIden)]
+enum Medias {
+    Table,
+    SkipReason,
+}
@@ -48,6 +48,8 @@ impl Hooks for App {
         environment: &Environment,
         config: loco_rs::config::Config,
     ) -> Result<BootResult> {
+        // Every mode runs yt-dlp, conflicting settings must not be dropped by any of them.
+        crate::ytdlp::ClientOptions::init_global()?;
         create_app::<Self, Migrator>(mode, environment, config).await
     }
 
@@ -62,13 +64,16 @@ impl Hooks for App {
     }
 
     fn routes(_ctx: &AppContext) -> AppRoutes {
-        AppRoutes::with_default_routes()
+        AppRoutes::empty()
+            .add_route(controllers::monitoring::routes())
             .add_route(Routes::new().add("/", get(redirect_to_sources)))
             .add_route(controllers::media::routes())
             .add_route(controllers::source::routes())
             .add_route(controllers::auth::routes())
             .add_route(controllers::metrics::routes())
             .add_route(controllers::status::routes())
+            .add_route(controllers::settings::routes())
+            .add_route(controllers::share::routes())
     }
 
     async fn connect_workers(ctx: &AppContext, queue: &Queue) -> Result<()> {
@@ -83,6 +88,8 @@ impl Hooks for App {
     }
 
     fn register_tasks(tasks: &mut Tasks) {
+        tasks.register(tasks::prune::Prune);
+        tasks.register(tasks::redownload_media::RedownloadMedia);
         tasks.register(tasks::refresh_indexes::RefreshIndexes);
         tasks.register(tasks::seed::SeedData);
         // tasks-inject (do not remove)
@@ -0,0 +1,140 @@
+use chrono::{DateTime, Utc};
+use sea_orm::{sea_query::Order, ColumnTrait, EntityTrait, QueryFilter, QueryOrder, Select};
+use serde::{Deserialize, Serialize};
+
+/// Rows on a page unless `per_page` says otherwise
+pub const DEFAULT_PER_PAGE: u64 = 24;
+/// Most rows a single page may hold
+pub const MAX_PER_PAGE: u64 = 100;
+
+/// Column a list is sorted by
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SortKey {
+    #[default]
+    Id,
+    CreatedAt,
+    UpdatedAt,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+/// Sorting and filtering shared by the source and media lists
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct ListParams {
+    #[serde(default)]
+    pub sort: SortKey,
+    #[serde(default)]
+    pub order: SortOrder,
+    /// Only rows changed at or after this time
+    #[serde(default)]
+    pub updated_since: Option<DateTime<Utc>>,
+    /// Page of the list, starting at 1
+    #[serde(default)]
+    pub page: Option<u64>,
+    #[serde(default)]
+    pub per_page: Option<u64>,
+    /// Text the listed rows are searched for
+    #[serde(default)]
+    pub q: Option<String>,
+}
+
+impl ListParams {
+    #[must_use]
+    pub fn order(&self) -> Order {
+        match self.order {
+            SortOrder::Asc => Order::Asc,
+            SortOrder::Desc => Order::Desc,
+        }
+    }
+
+    /// Filters `query` on `updated_since` and sorts it, ties broken by the newest id
+    ///
+    /// The columns are the ones of the listed entity the sort keys map to.
+    #[must_use]
+    pub fn apply_to<E: EntityTrait>(
+        &self,
+        query: Select<E>,
+        id: E::Column,
+        created_at: E::Column,
+        updated_at: E::Column,
+    ) -> Select<E> {
+        let sort = match self.sort {
+            SortKey::Id => id,
+            SortKey::CreatedAt => created_at,
+            SortKey::UpdatedAt => updated_at,
+        };
+        let query = match self.updated_since {
+            Some(since) => query.filter(updated_at.gte(since)),
+            None => query,
+        };
+        query.order_by(sort, self.order()).order_by(id, Order::Desc)
+    }
+
+    /// Requested page, the first one unless set
+    #[must_use]
+    pub fn page(&self) -> u64 {
+        self.page.unwrap_or(1).max(1)
+    }
+
+    /// Rows per page, [`DEFAULT_PER_PAGE`] unless set and at most [`MAX_PER_PAGE`]
+    #[must_use]
+    pub fn per_page(&self) -> u64 {
+        self.per_page
+            .unwrap_or(DEFAULT_PER_PAGE)
+            .clamp(1, MAX_PER_PAGE)
+    }
+
+    /// Trimmed search text, `None` when nothing is searched
+    #[must_use]
+    pub fn search(&self) -> Option<&str> {
+        self.q.as_deref().map(str::trim).filter(|q| !q.is_empty())
+    }
+}
+
+/// Where a page sits in the whole list, passed to the templates
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct PageInfo {
+    pub page: u64,
+    pub per_page: u64,
+    pub total_items: u64,
+    pub total_pages: u64,
+}
+
+impl PageInfo {
+    #[must_use]
+    pub fn new(page: u64, per_page: u64, total_items: u64) -> Self {
+        Self {
+            page,
+            per_page,
+            total_items,
+            total_pages: total_items.div_ceil(per_page.max(1)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ListParams, PageInfo, MAX_PER_PAGE};
+
+    #[test]
+    fn page_params_are_clamped() {
+        let params = ListParams {
+            page: Some(0),
+            per_page: Some(1_000),
+            q: Some("  ".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(params.page(), 1);
+        assert_eq!(params.per_page(), MAX_PER_PAGE);
+        assert_eq!(params.search(), None);
+        assert_eq!(PageInfo::new(2, 10, 21).total_pages, 3);
+        assert_eq!(PageInfo::new(1, 10, 0).total_pages, 0);
+    }
+}
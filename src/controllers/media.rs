@@ -2,21 +2,30 @@
 #![allow(clippy::unnecessary_struct_initialization)]
 #![allow(clippy::unused_async)]
 use axum::{
-    body::Bytes,
     debug_handler,
+    extract::Query,
     http::{header, HeaderMap, StatusCode},
     response::Redirect,
 };
-use futures_util::stream;
-use loco_rs::prelude::*;
+use futures_util::TryStreamExt;
+use loco_rs::{controller::ErrorDetail, prelude::*};
 use sea_orm::{sea_query::Order, EntityTrait, QueryOrder, Set};
+use serde::{Deserialize, Serialize};
 use std::path::Component;
 use tokio::io::{AsyncReadExt, AsyncSeekExt, SeekFrom};
+use tokio_util::io::ReaderStream;
+use tracing::{info, warn};
 
 use crate::{
-    models::_entities::medias::{ActiveModel, Column, Entity, Model},
+    controllers::listing::ListParams,
+    job_tracking::manager::TaskManager,
+    models::{
+        _entities::medias::{ActiveModel, Column, Entity, Model},
+        sources::media_root_of,
+    },
+    services::{share, transcode, trash},
     views,
-    workers::fetch_media::{FetchMediaWorker, FetchMediaWorkerArgs},
+    workers::fetch_media::{media_downloads, FetchMediaWorker, FetchMediaWorkerArgs},
 };
 
 async fn load_item(
@@ -34,19 +43,37 @@ async fn load_item(
         .ok_or_else(|| Error::NotFound)
 }
 
+fn list_query(params: &ListParams) -> sea_orm::Select<Entity> {
+    params.apply_to(
+        Entity::find().filter(Column::TrashedAt.is_null()),
+        Column::Id,
+        Column::CreatedAt,
+        Column::UpdatedAt,
+    )
+}
+
 #[debug_handler]
 pub async fn list(
     ViewEngine(v): ViewEngine<TeraView>,
+    Query(params): Query<ListParams>,
     State(ctx): State<AppContext>,
 ) -> Result<Response> {
-    let items = Entity::find()
+    let items = list_query(&params)
         .find_also_related(crate::models::_entities::sources::Entity)
-        .order_by(Column::Id, Order::Desc)
         .all(&ctx.db)
         .await?;
     views::media::list(&v, &items)
 }
 
+/// GET /medias/list.json - Lists the medias with their timestamps
+#[debug_handler]
+pub async fn list_json(
+    Query(params): Query<ListParams>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    format::json(list_query(&params).all(&ctx.db).await?)
+}
+
 #[debug_handler]
 pub async fn show(
     Path(id): Path<i32>,
@@ -54,7 +81,12 @@ pub async fn show(
     State(ctx): State<AppContext>,
 ) -> Result<Response> {
     let (item, source) = load_item(&ctx, id).await?;
-    views::media::show(&v, &item, source.as_ref())
+    let subtitles = item
+        .media_path
+        .as_deref()
+        .map(|path| crate::ytdlp::subtitle_languages(&media_root_of(source.as_ref()).join(path)))
+        .unwrap_or_default();
+    views::media::show(&v, &item, source.as_ref(), &subtitles)
 }
 
 fn content_type_for(path: &std::path::Path) -> &'static str {
@@ -68,6 +100,14 @@ fn content_type_for(path: &std::path::Path) -> &'static str {
         Some("mkv") => "video/x-matroska",
         Some("mov") => "video/quicktime",
         Some("avi") => "video/x-msvideo",
+        Some("jpg") => "image/jpeg",
+        Some("webp") => "image/webp",
+        Some("png") => "image/png",
+        Some("m4a") => "audio/mp4",
+        Some("mp3") => "audio/mpeg",
+        Some("opus" | "ogg") => "audio/ogg",
+        Some("vtt") => "text/vtt; charset=utf-8",
+        Some("json") => "application/json",
         _ => "application/octet-stream",
     }
 }
@@ -123,6 +163,30 @@ fn parse_range_header(range: &str, file_size: u64) -> Option<(u64, u64)> {
     Some((start, end))
 }
 
+/// Returns the single byte range requested in `headers` for a file of `file_size` bytes
+///
+/// `Ok(None)` serves the whole file, which is also the answer to unsupported
+/// units and multiple ranges. `Err` means the range cannot be satisfied.
+fn requested_range(
+    headers: &HeaderMap,
+    file_size: u64,
+) -> std::result::Result<Option<(u64, u64)>, ()> {
+    let range_header = headers
+        .get(header::RANGE)
+        .and_then(|value| value.to_str().ok());
+    let is_bytes_range = range_header.is_some_and(|value| value.trim().starts_with("bytes="));
+    let is_multi_range = range_header
+        .filter(|_| is_bytes_range)
+        .is_some_and(is_multi_range_header);
+    if is_multi_range || !is_bytes_range {
+        return Ok(None);
+    }
+    range_header
+        .and_then(|value| parse_range_header(value, file_size))
+        .map(Some)
+        .ok_or(())
+}
+
 fn range_not_satisfiable_response(file_size: u64) -> Response {
     let mut response = Response::new(axum::body::Body::empty());
     *response.status_mut() = StatusCode::RANGE_NOT_SATISFIABLE;
@@ -137,78 +201,287 @@ fn range_not_satisfiable_response(file_size: u64) -> Response {
     response
 }
 
+/// Size of the chunks a media file is read and sent in
+const STREAM_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Streams the next `remaining` bytes of `file`
+///
+/// Only the chunk being sent is held in memory, so a multi-GB video is never
+/// buffered whole, whether it is sent in full or as a range.
 fn stream_body(file: tokio::fs::File, remaining: u64) -> axum::body::Body {
-    let stream = stream::unfold((file, remaining), |(mut file, mut remaining)| async move {
-        if remaining == 0 {
-            return None;
+    let stream = ReaderStream::with_capacity(file.take(remaining), STREAM_CHUNK_SIZE)
+        .inspect_ok(|chunk| TaskManager::global().record_stream_bytes(chunk.len() as u64));
+    axum::body::Body::from_stream(stream)
+}
+
+/// Resolves the stored `media_path` under the media root of its source,
+/// rejecting paths that could escape it
+fn media_file_path(
+    source: Option<&crate::models::_entities::sources::Model>,
+    media_path: Option<&str>,
+) -> Result<std::path::PathBuf> {
+    let Some(media_path) = media_path else {
+        return Err(Error::NotFound);
+    };
+
+    let rel_path = std::path::PathBuf::from(media_path);
+    if rel_path.is_absolute()
+        || rel_path
+            .components()
+            .any(|component| matches!(component, Component::ParentDir | Component::Prefix(_)))
+    {
+        return Err(Error::NotFound);
+    }
+
+    Ok(media_root_of(source).join(&rel_path))
+}
+
+/// Cache validators of a file, the `ETag` changes whenever its size or mtime does
+struct Validators {
+    etag: String,
+    last_modified: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl Validators {
+    fn from_metadata(metadata: &std::fs::Metadata) -> Self {
+        let modified = metadata.modified().ok();
+        let mtime_nanos = modified
+            .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+            .map_or(0, |since_epoch| since_epoch.as_nanos());
+        Self {
+            etag: format!("\"{:x}-{:x}\"", metadata.len(), mtime_nanos),
+            last_modified: modified.map(chrono::DateTime::<chrono::Utc>::from),
         }
-        let chunk_size = match usize::try_from(remaining) {
-            Ok(remaining) => std::cmp::min(16 * 1024, remaining),
-            Err(_) => 16 * 1024,
-        };
-        let mut buffer = vec![0u8; chunk_size];
-        match file.read(&mut buffer).await {
-            Ok(0) => None,
-            Ok(read) => {
-                remaining = remaining.saturating_sub(read as u64);
-                buffer.truncate(read);
-                Some((
-                    Ok::<Bytes, std::io::Error>(Bytes::copy_from_slice(&buffer)),
-                    (file, remaining),
-                ))
+    }
+
+    /// Evaluates `If-None-Match`, falling back to `If-Modified-Since` when it is absent
+    fn is_not_modified(&self, headers: &HeaderMap) -> bool {
+        if let Some(if_none_match) = headers.get(header::IF_NONE_MATCH) {
+            return if_none_match.to_str().is_ok_and(|value| {
+                value
+                    .split(',')
+                    .map(str::trim)
+                    .any(|tag| tag == "*" || tag.strip_prefix("W/").unwrap_or(tag) == self.etag)
+            });
+        }
+        let since = headers
+            .get(header::IF_MODIFIED_SINCE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| chrono::DateTime::parse_from_rfc2822(value).ok());
+        match (since, self.last_modified) {
+            // HTTP dates only carry whole seconds.
+            (Some(since), Some(modified)) => modified.timestamp() <= since.timestamp(),
+            _ => false,
+        }
+    }
+
+    fn apply(&self, headers: &mut HeaderMap) {
+        if let Ok(value) = header::HeaderValue::from_str(&self.etag) {
+            headers.insert(header::ETAG, value);
+        }
+        if let Some(modified) = self.last_modified {
+            let value = modified.format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+            if let Ok(value) = header::HeaderValue::from_str(&value) {
+                headers.insert(header::LAST_MODIFIED, value);
             }
-            Err(err) => Some((Err(err), (file, remaining))),
         }
-    });
+    }
 
-    axum::body::Body::from_stream(stream)
+    fn not_modified_response(&self) -> Response {
+        let mut response = Response::new(axum::body::Body::empty());
+        *response.status_mut() = StatusCode::NOT_MODIFIED;
+        self.apply(response.headers_mut());
+        response
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct StreamParams {
+    /// `1` or `true` transcodes the file to mp4 for browsers that cannot play it
+    #[serde(default)]
+    pub transcode: Option<String>,
+    /// Seconds into the media a transcode starts at
+    #[serde(default)]
+    pub start: Option<f64>,
+}
+
+impl StreamParams {
+    fn transcode(&self) -> bool {
+        self.transcode.as_deref().is_some_and(|value| {
+            matches!(
+                value.trim().to_ascii_lowercase().as_str(),
+                "1" | "true" | "yes"
+            )
+        })
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ShareParams {
+    /// Hours the link stays valid, up to [`share::MAX_SHARE_HOURS`]
+    #[serde(default)]
+    pub hours: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ShareLink {
+    pub url: String,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// GET /medias/{id}/share - Returns a stream link of the media that expires
+///
+/// The link points at `/share/{token}`, the token is signed with
+/// [`share::share_secret`] and opens the stream of this one media until
+/// `expires_at`. Without `LOCALTUBE_SHARE_SECRET` sharing is disabled.
+#[debug_handler]
+pub async fn share(
+    Path(id): Path<i32>,
+    Query(params): Query<ShareParams>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    let (item, _) = load_item(&ctx, id).await?;
+    let secret = share::share_secret().ok_or_else(|| {
+        Error::CustomError(
+            StatusCode::FORBIDDEN,
+            ErrorDetail::new(
+                "Forbidden",
+                "Sharing is disabled, set LOCALTUBE_SHARE_SECRET to enable it",
+            ),
+        )
+    })?;
+    let hours = params
+        .hours
+        .unwrap_or(share::DEFAULT_SHARE_HOURS)
+        .clamp(1, share::MAX_SHARE_HOURS);
+    let expires_at = chrono::Utc::now() + chrono::Duration::hours(i64::from(hours));
+    let token = share::sign(&secret, item.id, expires_at.timestamp());
+    format::json(ShareLink {
+        url: format!("{}/share/{token}", ctx.config.server.full_url()),
+        expires_at,
+    })
 }
 
+/// GET /medias/{id}/stream - Serves the media file, optionally transcoded with `?transcode=1`
 #[debug_handler]
 pub async fn stream(
     Path(id): Path<i32>,
+    Query(params): Query<StreamParams>,
     State(ctx): State<AppContext>,
     headers: HeaderMap,
 ) -> Result<Response> {
-    let (item, _) = load_item(&ctx, id).await?;
-    let Some(media_path) = item.media_path else {
-        return Err(Error::NotFound);
-    };
+    if params.transcode() {
+        return serve_transcode(&ctx, id, params.start, true).await;
+    }
+    serve_stream(&ctx, id, &headers, true).await
+}
 
-    let rel_path = std::path::PathBuf::from(media_path);
-    if rel_path.is_absolute()
-        || rel_path
-            .components()
-            .any(|component| matches!(component, Component::ParentDir | Component::Prefix(_)))
+/// Streams the media through ffmpeg as fragmented mp4
+///
+/// The length of the output is not known up front, so the response has no
+/// `Content-Length` and ranges are not supported; players seek by requesting
+/// the stream again with `start` set. Only enabled with `LOCALTUBE_TRANSCODE`
+/// and limited to `LOCALTUBE_MAX_TRANSCODES` at once, as every transcode keeps
+/// a CPU core busy. `with_body` is false for `HEAD` requests, which get the
+/// same headers without starting ffmpeg.
+async fn serve_transcode(
+    ctx: &AppContext,
+    id: i32,
+    start: Option<f64>,
+    with_body: bool,
+) -> Result<Response> {
+    if !transcode::transcode_enabled() {
+        return Err(Error::CustomError(
+            StatusCode::FORBIDDEN,
+            ErrorDetail::new(
+                "Forbidden",
+                "Transcoding is disabled, set LOCALTUBE_TRANSCODE=true to enable it",
+            ),
+        ));
+    }
+    let (item, source) = load_item(ctx, id).await?;
+    let full_path = media_file_path(source.as_ref(), item.media_path.as_deref())?;
+    if !tokio::fs::metadata(&full_path)
+        .await
+        .is_ok_and(|metadata| metadata.is_file())
     {
         return Err(Error::NotFound);
     }
+    let body = if with_body {
+        let Some(permit) = transcode::try_claim() else {
+            return Err(Error::CustomError(
+                StatusCode::SERVICE_UNAVAILABLE,
+                ErrorDetail::new("Service Unavailable", "Too many transcodes are running"),
+            ));
+        };
+        let body = transcode::transcode_body(&full_path, start, permit).map_err(|err| {
+            warn!(media_id = id, error = %err, "Failed to start ffmpeg");
+            Error::string(&format!("Failed to start ffmpeg: {err}"))
+        })?;
+        TaskManager::global().record_stream_request(false);
+        body
+    } else {
+        // An empty body of unknown length, so no `Content-Length: 0` is added.
+        axum::body::Body::from_stream(futures_util::stream::empty::<std::io::Result<Vec<u8>>>())
+    };
+
+    let mut response = Response::new(body);
+    let headers = response.headers_mut();
+    headers.insert(
+        header::CONTENT_TYPE,
+        header::HeaderValue::from_static("video/mp4"),
+    );
+    headers.insert(
+        header::ACCEPT_RANGES,
+        header::HeaderValue::from_static("none"),
+    );
+    headers.insert(
+        header::CACHE_CONTROL,
+        header::HeaderValue::from_static("no-store"),
+    );
+    Ok(response)
+}
 
-    let full_path = crate::ytdlp::media_directory().join(&rel_path);
+/// HEAD /medias/{id}/stream - Same headers as the stream, without reading the file
+#[debug_handler]
+pub async fn stream_head(
+    Path(id): Path<i32>,
+    Query(params): Query<StreamParams>,
+    State(ctx): State<AppContext>,
+    headers: HeaderMap,
+) -> Result<Response> {
+    if params.transcode() {
+        return serve_transcode(&ctx, id, params.start, false).await;
+    }
+    serve_stream(&ctx, id, &headers, false).await
+}
+
+/// Builds the stream response, `with_body` is false for `HEAD` requests
+pub(crate) async fn serve_stream(
+    ctx: &AppContext,
+    id: i32,
+    headers: &HeaderMap,
+    with_body: bool,
+) -> Result<Response> {
+    let (item, source) = load_item(ctx, id).await?;
+    if item.media_path.is_none() {
+        if let Some(partial) = partial_download_path(&item, source.as_ref()).await {
+            return stream_partial(&partial, headers, with_body).await;
+        }
+    }
+    let full_path = media_file_path(source.as_ref(), item.media_path.as_deref())?;
     let metadata = tokio::fs::metadata(&full_path)
         .await
         .map_err(|_| Error::NotFound)?;
-    let mut file = tokio::fs::File::open(&full_path)
-        .await
-        .map_err(|_| Error::NotFound)?;
+    let validators = Validators::from_metadata(&metadata);
+    if validators.is_not_modified(headers) {
+        return Ok(validators.not_modified_response());
+    }
 
     let file_size = metadata.len();
-    let range_header = headers
-        .get(header::RANGE)
-        .and_then(|value| value.to_str().ok());
-    let is_bytes_range = range_header.is_some_and(|value| value.trim().starts_with("bytes="));
-    let is_multi_range = range_header
-        .filter(|_| is_bytes_range)
-        .is_some_and(is_multi_range_header);
-    let range = if is_multi_range || !is_bytes_range {
-        None
-    } else {
-        range_header.and_then(|value| parse_range_header(value, file_size))
-    };
-
-    if is_bytes_range && range.is_none() && !is_multi_range {
+    let Ok(range) = requested_range(headers, file_size) else {
         return Ok(range_not_satisfiable_response(file_size));
-    }
+    };
 
     let (start, end, status) = if let Some((start, end)) = range {
         (start, end, StatusCode::PARTIAL_CONTENT)
@@ -218,19 +491,27 @@ pub async fn stream(
         (0, file_size - 1, StatusCode::OK)
     };
 
-    if start > 0 {
-        file.seek(SeekFrom::Start(start))
-            .await
-            .map_err(|_| Error::NotFound)?;
-    }
-
     let remaining = if file_size == 0 {
         0
     } else {
         end.saturating_sub(start).saturating_add(1)
     };
 
-    let mut response = Response::new(stream_body(file, remaining));
+    let body = if with_body {
+        let mut file = tokio::fs::File::open(&full_path)
+            .await
+            .map_err(|_| Error::NotFound)?;
+        if start > 0 {
+            file.seek(SeekFrom::Start(start))
+                .await
+                .map_err(|_| Error::NotFound)?;
+        }
+        TaskManager::global().record_stream_request(status == StatusCode::PARTIAL_CONTENT);
+        stream_body(file, remaining)
+    } else {
+        axum::body::Body::empty()
+    };
+    let mut response = Response::new(body);
     let headers = response.headers_mut();
     headers.insert(
         header::CONTENT_TYPE,
@@ -250,38 +531,357 @@ pub async fn stream(
             headers.insert(header::CONTENT_RANGE, value);
         }
     }
+    validators.apply(headers);
     *response.status_mut() = status;
 
     Ok(response)
 }
 
-#[debug_handler]
-pub async fn redownload(Path(id): Path<i32>, State(ctx): State<AppContext>) -> Result<Redirect> {
-    let (item, _) = load_item(&ctx, id).await?;
+/// Returns the file of a download that is still running, if yt-dlp started writing it
+async fn partial_download_path(
+    item: &Model,
+    source: Option<&crate::models::_entities::sources::Model>,
+) -> Option<std::path::PathBuf> {
+    if !crate::workers::fetch_media::media_downloads().contains(&item.id) {
+        return None;
+    }
+    let source = source?;
+    let source_dir = source
+        .media_root()
+        .join(crate::ytdlp::source_dir_name(source)?);
+    crate::ytdlp::find_partial_download(&source_dir, item.video_id.as_deref()?).await
+}
 
-    // Remove existing media files from filesystem
-    item.remove_media_files()?;
+/// Streams the bytes of a growing file that were written so far
+///
+/// The total length is unknown, so ranges are capped at the current size,
+/// reported as `*` and the body is sent without a `Content-Length`.
+async fn stream_partial(
+    path: &std::path::Path,
+    headers: &HeaderMap,
+    with_body: bool,
+) -> Result<Response> {
+    let flushed = tokio::fs::metadata(path)
+        .await
+        .map_err(|_| Error::NotFound)?
+        .len();
+    let Ok(range) = requested_range(headers, flushed) else {
+        return Ok(range_not_satisfiable_response(flushed));
+    };
+    let (start, end) = range.unwrap_or((0, flushed.saturating_sub(1)));
+    let remaining = if flushed == 0 { 0 } else { end - start + 1 };
 
-    // Update database to clear media_path
-    let media_update = ActiveModel {
-        id: Set(item.id),
-        media_path: Set(None),
-        ..Default::default()
+    let body = if with_body {
+        let mut file = tokio::fs::File::open(path)
+            .await
+            .map_err(|_| Error::NotFound)?;
+        if start > 0 {
+            file.seek(SeekFrom::Start(start))
+                .await
+                .map_err(|_| Error::NotFound)?;
+        }
+        TaskManager::global().record_stream_request(range.is_some());
+        stream_body(file, remaining)
+    } else {
+        axum::body::Body::empty()
     };
-    Entity::update(media_update).exec(&ctx.db).await?;
+    let mut response = Response::new(body);
+    let headers = response.headers_mut();
+    headers.insert(
+        header::CONTENT_TYPE,
+        header::HeaderValue::from_static(content_type_for(&path.with_extension(""))),
+    );
+    headers.insert(
+        header::ACCEPT_RANGES,
+        header::HeaderValue::from_static("bytes"),
+    );
+    headers.insert(
+        header::CACHE_CONTROL,
+        header::HeaderValue::from_static("no-store"),
+    );
+    if range.is_some() {
+        if let Ok(value) = header::HeaderValue::from_str(&format!("bytes {start}-{end}/*")) {
+            headers.insert(header::CONTENT_RANGE, value);
+        }
+        *response.status_mut() = StatusCode::PARTIAL_CONTENT;
+    }
+    Ok(response)
+}
+
+/// GET /medias/{id}/thumbnail - Serves the thumbnail sidecar written next to the media file
+#[debug_handler]
+pub async fn thumbnail(
+    Path(id): Path<i32>,
+    State(ctx): State<AppContext>,
+    headers: HeaderMap,
+) -> Result<Response> {
+    let (item, source) = load_item(&ctx, id).await?;
+    let media_path = media_file_path(source.as_ref(), item.media_path.as_deref())?;
+
+    for ext in crate::ytdlp::THUMBNAIL_EXTENSIONS {
+        let path = media_path.with_extension(ext);
+        let Ok(metadata) = tokio::fs::metadata(&path).await else {
+            continue;
+        };
+        let validators = Validators::from_metadata(&metadata);
+        if validators.is_not_modified(&headers) {
+            return Ok(validators.not_modified_response());
+        }
+
+        let content = tokio::fs::read(&path).await.map_err(|_| Error::NotFound)?;
+        let mut response = Response::new(axum::body::Body::from(content));
+        let headers = response.headers_mut();
+        headers.insert(
+            header::CONTENT_TYPE,
+            header::HeaderValue::from_static(content_type_for(&path)),
+        );
+        validators.apply(headers);
+        return Ok(response);
+    }
+
+    Err(Error::NotFound)
+}
+
+/// GET /medias/{id}/subtitles/{lang}.vtt - Serves a WebVTT subtitle sidecar for browser playback
+#[debug_handler]
+pub async fn subtitles(
+    Path((id, file)): Path<(i32, String)>,
+    State(ctx): State<AppContext>,
+    headers: HeaderMap,
+) -> Result<Response> {
+    let (item, source) = load_item(&ctx, id).await?;
+    let media_path = media_file_path(source.as_ref(), item.media_path.as_deref())?;
+    let path = file
+        .strip_suffix(".vtt")
+        .and_then(|lang| crate::ytdlp::subtitle_path(&media_path, lang))
+        .ok_or(Error::NotFound)?;
+    serve_sidecar(&path, &headers).await
+}
+
+/// Serves a small file written next to the download, 404 when it does not exist
+async fn serve_sidecar(path: &std::path::Path, headers: &HeaderMap) -> Result<Response> {
+    let metadata = tokio::fs::metadata(path)
+        .await
+        .map_err(|_| Error::NotFound)?;
+    let validators = Validators::from_metadata(&metadata);
+    if validators.is_not_modified(headers) {
+        return Ok(validators.not_modified_response());
+    }
+
+    let content = tokio::fs::read(path).await.map_err(|_| Error::NotFound)?;
+    let mut response = Response::new(axum::body::Body::from(content));
+    let headers = response.headers_mut();
+    headers.insert(
+        header::CONTENT_TYPE,
+        header::HeaderValue::from_static(content_type_for(path)),
+    );
+    validators.apply(headers);
+    Ok(response)
+}
+
+/// GET /medias/{id}/info.json - Serves the yt-dlp info json written next to the download
+#[debug_handler]
+pub async fn info_json(
+    Path(id): Path<i32>,
+    State(ctx): State<AppContext>,
+    headers: HeaderMap,
+) -> Result<Response> {
+    let (item, source) = load_item(&ctx, id).await?;
+    let path =
+        media_file_path(source.as_ref(), item.media_path.as_deref())?.with_extension("info.json");
+    serve_sidecar(&path, &headers).await
+}
 
-    // Queue new download job
-    FetchMediaWorker::perform_later(&ctx, FetchMediaWorkerArgs { media_id: item.id }).await?;
+#[debug_handler]
+pub async fn redownload(Path(id): Path<i32>, State(ctx): State<AppContext>) -> Result<Redirect> {
+    let (item, source) = load_item(&ctx, id).await?;
+    FetchMediaWorker::redownload(&ctx, &item, &media_root_of(source.as_ref())).await?;
 
     // Redirect back to media list (303 See Other forces GET method)
     Ok(Redirect::to("/medias"))
 }
 
+/// DELETE /medias/{id} - Removes the media and its files, into the trash when it is enabled
+#[debug_handler]
+pub async fn remove(Path(id): Path<i32>, State(ctx): State<AppContext>) -> Result<Response> {
+    let (item, source) = load_item(&ctx, id).await?;
+    if item.trashed_at.is_some() {
+        return Err(Error::BadRequest(
+            "Media is already in the trash".to_string(),
+        ));
+    }
+    info!(media_id = item.id, "Removing media");
+    trash::discard_media(
+        &ctx.db,
+        item,
+        &media_root_of(source.as_ref()),
+        chrono::Utc::now(),
+    )
+    .await?;
+    format::empty()
+}
+
+/// POST /medias/{id}/restore - Puts a trashed media and its files back
+#[debug_handler]
+pub async fn restore(Path(id): Path<i32>, State(ctx): State<AppContext>) -> Result<Redirect> {
+    let (item, source) = load_item(&ctx, id).await?;
+    trash::restore_media(&ctx.db, &item, &media_root_of(source.as_ref())).await?;
+    info!(media_id = item.id, "Restored media from the trash");
+    Ok(Redirect::to(&format!("/medias/{id}")))
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct FailedParams {
+    /// Only medias of this source
+    pub source_id: Option<i32>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct RetryReport {
+    pub requeued: usize,
+    pub failed: usize,
+}
+
+/// Medias without a downloaded file that are neither downloading nor waiting to go live
+///
+/// Backlog of start-from-now sources is indexed without being downloaded and left out too.
+async fn failed_medias(ctx: &AppContext, params: &FailedParams) -> Result<Vec<Model>> {
+    let now = chrono::Utc::now();
+    // Duplicates and medias over the source limits are skipped on purpose, not failures.
+    let mut query = Entity::find()
+        .filter(Column::MediaPath.is_null())
+        .filter(Column::DuplicateOf.is_null())
+        .filter(Column::SkipReason.is_null())
+        .filter(Column::TrashedAt.is_null())
+        .filter(
+            sea_orm::Condition::any()
+                .add(Column::AvailableAt.is_null())
+                .add(Column::AvailableAt.lte(now)),
+        );
+    if let Some(source_id) = params.source_id {
+        query = query.filter(Column::SourceId.eq(source_id));
+    }
+    let items = query
+        .order_by(Column::Id, Order::Asc)
+        .find_also_related(crate::models::_entities::sources::Entity)
+        .all(&ctx.db)
+        .await?;
+    Ok(items
+        .into_iter()
+        .filter(|(item, source)| {
+            let backlog = source.as_ref().is_some_and(|source| {
+                item.get_metadata()
+                    .is_some_and(|metadata| source.is_backlog(metadata.timestamp))
+            });
+            !backlog && !media_downloads().contains(&item.id)
+        })
+        .map(|(item, _)| item)
+        .collect())
+}
+
+/// GET /medias/failed - Lists medias whose download has not succeeded, optionally of one source
+#[debug_handler]
+pub async fn failed(
+    Query(params): Query<FailedParams>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    format::json(failed_medias(&ctx, &params).await?)
+}
+
+/// POST /medias/failed/retry - Queues the download of every failed media again
+#[debug_handler]
+pub async fn retry_failed(
+    Query(params): Query<FailedParams>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    let items = failed_medias(&ctx, &params).await?;
+    info!(source_id = ?params.source_id, "Retrying {} failed medias", items.len());
+    let mut report = RetryReport::default();
+    for item in items {
+        match FetchMediaWorker::perform_later(&ctx, FetchMediaWorkerArgs { media_id: item.id })
+            .await
+        {
+            Ok(()) => report.requeued += 1,
+            Err(err) => {
+                warn!(media_id = item.id, error = %err, "Failed to queue download");
+                report.failed += 1;
+            }
+        }
+    }
+    format::json(report)
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct VerifyParams {
+    /// Clear the media path and queue a new download when the file is corrupt
+    #[serde(default)]
+    pub requeue: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct VerifyResponse {
+    #[serde(flatten)]
+    report: crate::ytdlp::VerifyReport,
+    requeued: bool,
+}
+
+/// POST /medias/{id}/verify - Decodes the downloaded file and reports whether it is playable
+#[debug_handler]
+pub async fn verify(
+    Path(id): Path<i32>,
+    Query(params): Query<VerifyParams>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    let (item, source) = load_item(&ctx, id).await?;
+    let full_path = media_file_path(source.as_ref(), item.media_path.as_deref())?;
+    if !tokio::fs::try_exists(&full_path).await.unwrap_or(false) {
+        return Err(Error::NotFound);
+    }
+    let report = crate::ytdlp::verify_media_file(&full_path, crate::ytdlp::VERIFY_TIMEOUT).await?;
+
+    let requeued = !report.playable && params.requeue;
+    if requeued {
+        warn!(media_id = item.id, error = ?report.error, "Media file is corrupt, downloading it again");
+        item.remove_media_files(&media_root_of(source.as_ref()))?;
+        ActiveModel {
+            id: Set(item.id),
+            media_path: Set(None),
+            ..Default::default()
+        }
+        .update(&ctx.db)
+        .await?;
+        FetchMediaWorker::perform_later(&ctx, FetchMediaWorkerArgs { media_id: item.id }).await?;
+    }
+    format::json(VerifyResponse { report, requeued })
+}
+
+/// GET /medias/{id}/log - Returns the raw yt-dlp output of the last download attempt
+#[debug_handler]
+pub async fn log(Path(id): Path<i32>, State(ctx): State<AppContext>) -> Result<Response> {
+    let (item, _) = load_item(&ctx, id).await?;
+    let Some(log) = crate::download_log::read(item.id).await else {
+        return Err(Error::NotFound);
+    };
+    format::render()
+        .header(header::CONTENT_TYPE, "text/plain; charset=utf-8")
+        .text(&log)
+}
+
 pub fn routes() -> Routes {
     Routes::new()
         .prefix("medias/")
         .add("/", get(list))
-        .add("{id}", get(show))
-        .add("{id}/stream", get(stream))
+        .add("list.json", get(list_json))
+        .add("failed", get(failed))
+        .add("failed/retry", post(retry_failed))
+        .add("{id}", get(show).delete(remove))
+        .add("{id}/stream", get(stream).head(stream_head))
+        .add("{id}/share", get(share))
+        .add("{id}/thumbnail", get(thumbnail))
+        .add("{id}/subtitles/{file}", get(subtitles))
+        .add("{id}/info.json", get(info_json))
+        .add("{id}/log", get(log))
         .add("{id}/redownload", post(redownload))
+        .add("{id}/verify", post(verify))
+        .add("{id}/restore", post(restore))
 }
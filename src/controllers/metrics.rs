@@ -5,7 +5,10 @@
 use axum::debug_handler;
 use loco_rs::prelude::*;
 
-use crate::job_tracking::manager::TaskManager;
+use crate::job_tracking::{
+    history::{MetricsHistory, SAMPLE_INTERVAL},
+    manager::TaskManager,
+};
 
 /// GET /metrics/ - Returns current task metrics in JSON format
 #[debug_handler]
@@ -13,6 +16,18 @@ pub async fn list(State(_ctx): State<AppContext>) -> Result<Response> {
     format::json(TaskManager::global().get_metrics())
 }
 
+/// GET /metrics/timeseries.json - Returns the sampled task counters of the last day
+#[debug_handler]
+pub async fn timeseries(State(_ctx): State<AppContext>) -> Result<Response> {
+    format::json(serde_json::json!({
+        "interval_secs": SAMPLE_INTERVAL.as_secs(),
+        "samples": MetricsHistory::global().samples(),
+    }))
+}
+
 pub fn routes() -> Routes {
-    Routes::new().prefix("metrics/").add("/", get(list))
+    Routes::new()
+        .prefix("metrics/")
+        .add("/", get(list))
+        .add("timeseries.json", get(timeseries))
 }
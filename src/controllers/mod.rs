@@ -2,6 +2,10 @@ pub mod auth;
 
 pub mod source;
 
+pub mod listing;
 pub mod media;
 pub mod metrics;
+pub mod monitoring;
+pub mod settings;
+pub mod share;
 pub mod status;
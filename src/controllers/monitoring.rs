@@ -0,0 +1,29 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::unused_async)]
+
+use axum::{debug_handler, http::StatusCode};
+use loco_rs::{
+    controller::monitoring::{self, Health},
+    prelude::*,
+};
+
+use crate::services::deps;
+
+/// GET /_readiness - Loco's readiness check, `503` until yt-dlp and ffmpeg are installed
+#[debug_handler]
+pub async fn readiness(State(ctx): State<AppContext>) -> Result<Response> {
+    if !deps::is_ready() {
+        return format::render()
+            .status(StatusCode::SERVICE_UNAVAILABLE)
+            .json(Health { ok: false });
+    }
+    monitoring::readiness(State(ctx)).await
+}
+
+/// Replaces the default monitoring routes so readiness includes the dependency install
+pub fn routes() -> Routes {
+    Routes::new()
+        .add("/_readiness", get(readiness))
+        .add("/_ping", get(monitoring::ping))
+        .add("/_health", get(monitoring::health))
+}
@@ -0,0 +1,40 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::unused_async)]
+
+use axum::debug_handler;
+use loco_rs::prelude::*;
+
+use crate::{models::settings::GlobalSettings, views};
+
+#[debug_handler]
+pub async fn show(State(ctx): State<AppContext>) -> Result<Response> {
+    format::json(GlobalSettings::load(&ctx.db).await?)
+}
+
+#[debug_handler]
+pub async fn edit(
+    ViewEngine(v): ViewEngine<TeraView>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    let settings = GlobalSettings::load(&ctx.db).await?;
+    views::settings::edit(&v, &settings)
+}
+
+#[debug_handler]
+pub async fn update(
+    State(ctx): State<AppContext>,
+    Json(params): Json<GlobalSettings>,
+) -> Result<Response> {
+    let settings = params.normalize().map_err(Error::BadRequest)?;
+    settings.store(&ctx.db).await?;
+    settings.apply();
+    format::json(settings)
+}
+
+pub fn routes() -> Routes {
+    Routes::new()
+        .prefix("settings/")
+        .add("/", get(show))
+        .add("/", put(update))
+        .add("edit", get(edit))
+}
@@ -0,0 +1,58 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::unused_async)]
+use axum::{
+    debug_handler,
+    http::{HeaderMap, StatusCode},
+};
+use loco_rs::{controller::ErrorDetail, prelude::*};
+use tracing::info;
+
+use crate::{controllers::media::serve_stream, services::share};
+
+/// Returns the media `token` opens, rejecting missing, expired or forged tokens
+fn authorize(token: &str) -> Result<i32> {
+    let forbidden = |message: &str| {
+        Error::CustomError(
+            StatusCode::FORBIDDEN,
+            ErrorDetail::new("Forbidden", message),
+        )
+    };
+    let secret = share::share_secret()
+        .ok_or_else(|| forbidden("Sharing is disabled, set LOCALTUBE_SHARE_SECRET to enable it"))?;
+    share::verify(&secret, token, chrono::Utc::now()).map_err(|err| {
+        info!(error = %err, "Rejected share token");
+        forbidden(&err.to_string())
+    })
+}
+
+/// GET /share/{token} - Streams the media of a share link
+///
+/// This is the only route a share link needs, so it is what gets exposed
+/// when LocalTube stays behind a proxy. Ranges work like on the regular
+/// stream.
+#[debug_handler]
+pub async fn stream(
+    Path(token): Path<String>,
+    State(ctx): State<AppContext>,
+    headers: HeaderMap,
+) -> Result<Response> {
+    let id = authorize(&token)?;
+    serve_stream(&ctx, id, &headers, true).await
+}
+
+/// HEAD /share/{token} - Same headers as the shared stream, without reading the file
+#[debug_handler]
+pub async fn stream_head(
+    Path(token): Path<String>,
+    State(ctx): State<AppContext>,
+    headers: HeaderMap,
+) -> Result<Response> {
+    let id = authorize(&token)?;
+    serve_stream(&ctx, id, &headers, false).await
+}
+
+pub fn routes() -> Routes {
+    Routes::new()
+        .prefix("share/")
+        .add("{token}", get(stream).head(stream_head))
+}
@@ -1,15 +1,25 @@
 #![allow(clippy::missing_errors_doc)]
 #![allow(clippy::unnecessary_struct_initialization)]
 #![allow(clippy::unused_async)]
-use axum::debug_handler;
-use loco_rs::prelude::*;
-use sea_orm::{sea_query::Order, QueryOrder};
+use axum::{debug_handler, extract::Query, http::StatusCode};
+use loco_rs::{controller::ErrorDetail, prelude::*};
+use sea_orm::{
+    sea_query::{Expr, Func, LikeExpr},
+    Condition, PaginatorTrait, Select,
+};
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    models::_entities::sources::{ActiveModel, Column, Entity, Model},
+    controllers::listing::{ListParams, PageInfo},
+    job_tracking::manager::TaskManager,
+    models::{
+        _entities::sources::{ActiveModel, Column, Entity, Model},
+        sources::{indexed_media_counts, IndexState, SourceStats},
+    },
+    services::{refresh_runs::RefreshRuns, trash},
     views,
     workers::fetch_source_info::{FetchSourceInfoWorker, FetchSourceInfoWorkerArgs},
+    ytdlp::{self, ClientOptions, ThumbnailMode, MAX_HEIGHT_RANGE},
 };
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -18,10 +28,193 @@ pub struct Params {
     pub fetch_last_days: i32,
     pub sponsorblock: String,
     pub refresh_frequency: i32,
+    #[serde(default)]
+    pub max_items: Option<i32>,
+    #[serde(default)]
+    pub download_weight: Option<i32>,
+    #[serde(default)]
+    pub min_age_hours: Option<i32>,
+    #[serde(default)]
+    pub max_height: Option<i32>,
+    #[serde(default)]
+    pub age_limit: Option<i32>,
+    #[serde(default)]
+    pub max_duration_minutes: Option<i32>,
+    #[serde(default)]
+    pub max_filesize_mb: Option<i32>,
+    #[serde(default)]
+    pub concurrent_fragments: Option<i32>,
     pub list_tab: Option<String>,
+    #[serde(default)]
+    pub user_agent: Option<String>,
+    #[serde(default)]
+    pub extractor_args: Option<String>,
+    #[serde(default)]
+    pub webhook_url: Option<String>,
+    #[serde(default)]
+    pub sponsorblock_api: Option<String>,
+    /// Folder the source downloads into instead of the global media directory
+    ///
+    /// Cannot change while the source has downloads, their stored paths are
+    /// relative to it. Empty source folders are only pruned and files only
+    /// reconciled under the global media directory, not under a source's own
+    /// root.
+    #[serde(default)]
+    pub media_root: Option<String>,
+    // Unchecked checkboxes are left out of the form submission entirely.
+    #[serde(default)]
+    pub download_archive: bool,
+    #[serde(default)]
+    pub write_description: bool,
+    #[serde(default)]
+    pub write_comments: bool,
+    #[serde(default)]
+    pub members_only: bool,
+    #[serde(default)]
+    pub write_subtitles: bool,
+    #[serde(default)]
+    pub skip_embed_metadata: bool,
+    #[serde(default)]
+    pub skip_embed_subs: bool,
+    #[serde(default)]
+    pub start_from_now: bool,
+    #[serde(default)]
+    pub download_paused: bool,
+    #[serde(default)]
+    pub aac_audio: bool,
+    #[serde(default)]
+    pub thumbnail: Option<ThumbnailMode>,
 }
 
+/// Longest refresh interval, a year in hours
+const MAX_REFRESH_FREQUENCY: i32 = 24 * 365;
+/// Longest fetch window, about a century
+const MAX_FETCH_LAST_DAYS: i32 = 36_500;
+
 impl Params {
+    /// Rejects values that would make the refresh schedule or fetch window meaningless
+    fn validate(&self) -> Result<()> {
+        let invalid = if !(1..=MAX_REFRESH_FREQUENCY).contains(&self.refresh_frequency) {
+            Some(format!(
+                "refresh_frequency must be between 1 and {MAX_REFRESH_FREQUENCY} hours"
+            ))
+        } else if !(0..=MAX_FETCH_LAST_DAYS).contains(&self.fetch_last_days) {
+            Some(format!(
+                "fetch_last_days must be between 0 and {MAX_FETCH_LAST_DAYS}"
+            ))
+        } else if self
+            .max_height
+            .is_some_and(|height| !MAX_HEIGHT_RANGE.contains(&height))
+        {
+            Some(format!(
+                "max_height must be between {} and {} pixels",
+                MAX_HEIGHT_RANGE.start(),
+                MAX_HEIGHT_RANGE.end()
+            ))
+        } else if self
+            .age_limit
+            .is_some_and(|years| !(0..=ytdlp::MAX_AGE_LIMIT).contains(&years))
+        {
+            Some(format!(
+                "age_limit must be between 0 and {} years",
+                ytdlp::MAX_AGE_LIMIT
+            ))
+        } else if self.concurrent_fragments.is_some_and(|fragments| {
+            u32::try_from(fragments).is_ok_and(|n| n > ytdlp::MAX_CONCURRENT_FRAGMENTS)
+        }) {
+            Some(format!(
+                "concurrent_fragments must be at most {}",
+                ytdlp::MAX_CONCURRENT_FRAGMENTS
+            ))
+        } else if self
+            .webhook_url
+            .as_deref()
+            .and_then(normalize_override)
+            .is_some_and(|url| !url.starts_with("http://") && !url.starts_with("https://"))
+        {
+            Some("webhook_url must be an http or https URL".to_string())
+        } else if self
+            .sponsorblock_api
+            .as_deref()
+            .and_then(normalize_override)
+            .is_some_and(|url| ytdlp::parse_sponsorblock_api(&url).is_none())
+        {
+            Some("sponsorblock_api must be an http or https URL".to_string())
+        } else if self
+            .media_root
+            .as_deref()
+            .and_then(normalize_override)
+            .is_some_and(|root| !std::path::Path::new(&root).is_absolute())
+        {
+            Some("media_root must be an absolute path".to_string())
+        } else if self.members_only && ClientOptions::global().cookies.is_none() {
+            Some(
+                "members_only needs LOCALTUBE_YTDLP_COOKIES or LOCALTUBE_YTDLP_COOKIES_FROM_BROWSER"
+                    .to_string(),
+            )
+        } else {
+            None
+        };
+        invalid.map_or(Ok(()), |description| {
+            Err(Error::CustomError(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                ErrorDetail::new("Unprocessable Entity", &description),
+            ))
+        })
+    }
+
+    /// Rejects a media root LocalTube cannot create folders in
+    async fn validate_media_root(&self) -> Result<()> {
+        let Some(root) = self.media_root.as_deref().and_then(normalize_override) else {
+            return Ok(());
+        };
+        ytdlp::check_dir_writable(std::path::Path::new(&root))
+            .await
+            .map_err(|err| {
+                Error::CustomError(
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    ErrorDetail::new(
+                        "Unprocessable Entity",
+                        &format!("media_root {root} is not writable: {err}"),
+                    ),
+                )
+            })
+    }
+
+    /// Rejects moving a source that has downloads to another media root
+    ///
+    /// The downloads stay where they are and their paths are relative to the
+    /// old root, so the next refresh would download everything again.
+    async fn validate_media_root_change(
+        &self,
+        db: &DatabaseConnection,
+        model: &Model,
+    ) -> Result<()> {
+        let Some(root) = &self.media_root else {
+            return Ok(());
+        };
+        if normalize_override(root) == model.media_root {
+            return Ok(());
+        }
+        let downloaded = crate::models::_entities::medias::Entity::find()
+            .filter(crate::models::_entities::medias::Column::SourceId.eq(model.id))
+            .filter(crate::models::_entities::medias::Column::MediaPath.is_not_null())
+            .count(db)
+            .await?;
+        if downloaded == 0 {
+            return Ok(());
+        }
+        Err(Error::CustomError(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            ErrorDetail::new(
+                "Unprocessable Entity",
+                &format!(
+                    "media_root cannot change while the source has {downloaded} downloaded medias"
+                ),
+            ),
+        ))
+    }
+
     fn update(&self, item: &mut ActiveModel) {
         if let Some(url) = &self.url {
             item.url = Set(url.clone());
@@ -29,9 +222,63 @@ impl Params {
         item.fetch_last_days = Set(self.fetch_last_days);
         item.sponsorblock = Set(self.sponsorblock.clone());
         item.refresh_frequency = Set(self.refresh_frequency);
+        item.download_archive = Set(self.download_archive);
+        item.write_description = Set(self.write_description);
+        item.write_comments = Set(self.write_comments);
+        item.members_only = Set(self.members_only);
+        item.write_subtitles = Set(self.write_subtitles);
+        item.skip_embed_metadata = Set(self.skip_embed_metadata);
+        item.skip_embed_subs = Set(self.skip_embed_subs);
+        item.download_paused = Set(self.download_paused);
+        item.aac_audio = Set(self.aac_audio);
+        if !self.start_from_now {
+            item.download_baseline = Set(None);
+        } else if matches!(item.start_from_now, sea_orm::ActiveValue::Unchanged(false)) {
+            // Enabled on an existing source, everything published so far is backlog.
+            item.download_baseline = Set(Some(chrono::Utc::now()));
+        }
+        item.start_from_now = Set(self.start_from_now);
+        item.max_items = Set(self.max_items.filter(|n| *n > 0));
+        item.download_weight = Set(self.download_weight.filter(|n| *n > 0));
+        item.min_age_hours = Set(self.min_age_hours.filter(|n| *n > 0));
+        item.max_height = Set(self.max_height);
+        item.age_limit = Set(self.age_limit);
+        item.max_duration_minutes = Set(self.max_duration_minutes.filter(|n| *n > 0));
+        item.max_filesize_mb = Set(self.max_filesize_mb.filter(|n| *n > 0));
+        item.concurrent_fragments = Set(self.concurrent_fragments.filter(|n| *n > 0));
+        if let Some(thumbnail) = self.thumbnail {
+            item.thumbnail = Set(Some(thumbnail.as_str().to_string()));
+        }
+        // Absent fields keep the stored override, blank ones fall back to the global setting.
+        if let Some(user_agent) = &self.user_agent {
+            item.user_agent = Set(normalize_override(user_agent));
+        }
+        if let Some(extractor_args) = &self.extractor_args {
+            item.extractor_args = Set(normalize_override(extractor_args));
+        }
+        if let Some(media_root) = &self.media_root {
+            item.media_root = Set(normalize_override(media_root));
+        }
+        if let Some(webhook_url) = &self.webhook_url {
+            item.webhook_url = Set(normalize_override(webhook_url));
+        }
+        if let Some(sponsorblock_api) = &self.sponsorblock_api {
+            item.sponsorblock_api = Set(ytdlp::parse_sponsorblock_api(sponsorblock_api));
+        }
     }
 }
 
+#[derive(Debug, Default, Deserialize)]
+pub struct ReindexParams {
+    #[serde(default)]
+    pub full: bool,
+}
+
+fn normalize_override(value: &str) -> Option<String> {
+    let trimmed = value.trim();
+    (!trimmed.is_empty()).then(|| trimmed.to_string())
+}
+
 fn normalize_list_tab(value: &str) -> Option<String> {
     let trimmed = value.trim();
     if trimmed.is_empty() || trimmed.eq_ignore_ascii_case("auto") {
@@ -46,16 +293,87 @@ async fn load_item(ctx: &AppContext, id: i32) -> Result<Model> {
     item.ok_or_else(|| Error::NotFound)
 }
 
+/// Escapes the LIKE wildcards in `text` with a backslash
+fn escape_like(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+/// Sources matching `params`, `q` is searched in the url and the uploader
+fn list_query(params: &ListParams) -> Select<Entity> {
+    let mut query = Entity::find();
+    if let Some(q) = params.search() {
+        let pattern =
+            || LikeExpr::new(format!("%{}%", escape_like(&q.to_lowercase()))).escape('\\');
+        // `->>` extracts the uploader as text in both SQLite and Postgres.
+        let uploader = Expr::cust(r#""sources"."metadata" ->> 'uploader'"#);
+        query = query.filter(
+            Condition::any()
+                .add(Expr::expr(Func::lower(Expr::col(Column::Url))).like(pattern()))
+                .add(Expr::expr(Func::lower(uploader)).like(pattern())),
+        );
+    }
+    params.apply_to(query, Column::Id, Column::CreatedAt, Column::UpdatedAt)
+}
+
+/// Loads the requested page of the sources matching `params`
+async fn list_page(ctx: &AppContext, params: &ListParams) -> Result<(Vec<Model>, PageInfo)> {
+    let paginator = list_query(params).paginate(&ctx.db, params.per_page());
+    let total = paginator.num_items().await?;
+    let items = paginator.fetch_page(params.page() - 1).await?;
+    Ok((
+        items,
+        PageInfo::new(params.page(), params.per_page(), total),
+    ))
+}
+
 #[debug_handler]
 pub async fn list(
     ViewEngine(v): ViewEngine<TeraView>,
+    Query(params): Query<ListParams>,
     State(ctx): State<AppContext>,
 ) -> Result<Response> {
-    let item = Entity::find()
-        .order_by(Column::Id, Order::Desc)
-        .all(&ctx.db)
-        .await?;
-    views::source::list(&v, &item)
+    let (items, page) = list_page(&ctx, &params).await?;
+    views::source::list(
+        &v,
+        &items,
+        &indexed_media_counts(&ctx.db).await?,
+        &page,
+        params.search(),
+    )
+}
+
+/// Entry of `GET /sources/list.json`
+#[derive(Debug, Serialize)]
+struct ListItem {
+    #[serde(flatten)]
+    item: Model,
+    index_state: IndexState,
+}
+
+/// GET /sources/list.json - Lists the sources with their timestamps
+///
+/// All matching sources are listed unless a `page` is asked for.
+#[debug_handler]
+pub async fn list_json(
+    Query(params): Query<ListParams>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    let indexed = indexed_media_counts(&ctx.db).await?;
+    let sources = if params.page.is_some() {
+        list_page(&ctx, &params).await?.0
+    } else {
+        list_query(&params).all(&ctx.db).await?
+    };
+    let items: Vec<ListItem> = sources
+        .into_iter()
+        .map(|item| {
+            let index_state = item.index_state(indexed.get(&item.id).copied().unwrap_or_default());
+            ListItem { item, index_state }
+        })
+        .collect();
+    format::json(items)
 }
 
 #[debug_handler]
@@ -73,6 +391,9 @@ pub async fn update(
     Json(params): Json<Params>,
 ) -> Result<Response> {
     let model = load_item(&ctx, id).await?;
+    params.validate()?;
+    params.validate_media_root().await?;
+    params.validate_media_root_change(&ctx.db, &model).await?;
     let mut item = model.clone().into_active_model();
     params.update(&mut item);
     let url_changed = params.url.as_ref().is_some_and(|url| url != &model.url);
@@ -106,13 +427,76 @@ pub async fn update(
         }
     }
     let item = item.update(&ctx.db).await?;
-    FetchSourceInfoWorker::perform_later(&ctx, FetchSourceInfoWorkerArgs { source_id: item.id })
-        .await?;
+    if item.download_limits_changed(&model) {
+        // Medias skipped under the old limits may fit the new ones.
+        crate::models::_entities::medias::Model::clear_skip_reasons(&ctx.db, item.id).await?;
+    }
+    // A refresh still listing with the old settings would race the new one.
+    RefreshRuns::global().cancel(item.id);
+    // Changed settings such as the tab or window may bring in items older than the checkpoint.
+    FetchSourceInfoWorker::perform_later(
+        &ctx,
+        FetchSourceInfoWorkerArgs {
+            source_id: item.id,
+            full_reindex: item.needs_full_reindex(&model),
+        },
+    )
+    .await?;
     format::html(
         "<div class=\"text-sm text-green-600 dark:text-green-400\">Saved. Refresh queued.</div>",
     )
 }
 
+/// Queues a refresh of the source, `full` relists it from scratch
+#[debug_handler]
+pub async fn reindex(
+    Path(id): Path<i32>,
+    Query(params): Query<ReindexParams>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    let item = load_item(&ctx, id).await?;
+    if params.full {
+        item.reset_index(&ctx.db).await?;
+    }
+    FetchSourceInfoWorker::schedule_refresh(&ctx, item.id, params.full).await?;
+    format::html(if params.full {
+        "<div class=\"text-sm text-green-600 dark:text-green-400\">Full reindex queued.</div>"
+    } else {
+        "<div class=\"text-sm text-green-600 dark:text-green-400\">Refresh queued.</div>"
+    })
+}
+
+/// Moves the queued downloads of the source to the front of the download queue
+#[debug_handler]
+pub async fn prioritize(Path(id): Path<i32>, State(ctx): State<AppContext>) -> Result<Response> {
+    let item = load_item(&ctx, id).await?;
+    let moved = TaskManager::global().prioritize_source_downloads(item.id);
+    format::html(&if moved == 0 {
+        "<div class=\"text-sm text-gray-600 dark:text-gray-300\">No downloads of this source are queued.</div>".to_string()
+    } else {
+        format!("<div class=\"text-sm text-green-600 dark:text-green-400\">Moved {moved} queued downloads to the front.</div>")
+    })
+}
+
+/// Switches the source to the URL its moved channel was found at
+#[debug_handler]
+pub async fn adopt_suggested_url(
+    Path(id): Path<i32>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    let item = load_item(&ctx, id).await?;
+    let Some(item) = item.adopt_suggested_url(&ctx.db).await? else {
+        return format::html(
+            "<div class=\"text-sm text-gray-600 dark:text-gray-300\">No new URL is suggested for this source.</div>",
+        );
+    };
+    RefreshRuns::global().cancel(item.id);
+    FetchSourceInfoWorker::schedule_refresh(&ctx, item.id, true).await?;
+    format::html(
+        "<div class=\"text-sm text-green-600 dark:text-green-400\">Switched to the new URL. Refresh queued.</div>",
+    )
+}
+
 #[debug_handler]
 pub async fn edit(
     Path(id): Path<i32>,
@@ -130,7 +514,26 @@ pub async fn show(
     State(ctx): State<AppContext>,
 ) -> Result<Response> {
     let item = load_item(&ctx, id).await?;
-    views::source::show(&v, &item)
+    let stats = item.stats(&ctx.db).await?;
+    views::source::show(&v, &item, &stats)
+}
+
+#[debug_handler]
+pub async fn stats(Path(id): Path<i32>, State(ctx): State<AppContext>) -> Result<Response> {
+    let item = load_item(&ctx, id).await?;
+    let stats = item.stats(&ctx.db).await?;
+    format::json(StatsResponse {
+        index_state: item.index_state(stats.indexed),
+        stats,
+    })
+}
+
+/// Body of `GET /sources/{id}/stats.json`
+#[derive(Debug, Serialize)]
+struct StatsResponse {
+    #[serde(flatten)]
+    stats: SourceStats,
+    index_state: IndexState,
 }
 
 #[debug_handler]
@@ -139,19 +542,29 @@ pub async fn add(
     State(ctx): State<AppContext>,
     Json(params): Json<Params>,
 ) -> Result<Response> {
+    params.validate()?;
+    params.validate_media_root().await?;
     let mut item = ActiveModel {
         ..Default::default()
     };
     params.update(&mut item);
     let item = item.insert(&ctx.db).await?;
-    FetchSourceInfoWorker::perform_later(&ctx, FetchSourceInfoWorkerArgs { source_id: item.id })
-        .await?;
-    views::source::show(&v, &item)
+    FetchSourceInfoWorker::perform_later(
+        &ctx,
+        FetchSourceInfoWorkerArgs {
+            source_id: item.id,
+            full_reindex: false,
+        },
+    )
+    .await?;
+    views::source::show(&v, &item, &SourceStats::default())
 }
 
 #[debug_handler]
 pub async fn remove(Path(id): Path<i32>, State(ctx): State<AppContext>) -> Result<Response> {
-    load_item(&ctx, id).await?.delete(&ctx.db).await?;
+    let item = load_item(&ctx, id).await?;
+    trash::discard_source_trash(&ctx.db, &item).await?;
+    item.delete(&ctx.db).await?;
     format::empty()
 }
 
@@ -161,8 +574,13 @@ pub fn routes() -> Routes {
         .add("/", get(list))
         .add("/", post(add))
         .add("new", get(new))
+        .add("list.json", get(list_json))
         .add("{id}", get(show))
         .add("{id}/edit", get(edit))
+        .add("{id}/stats.json", get(stats))
+        .add("{id}/reindex", post(reindex))
+        .add("{id}/prioritize", post(prioritize))
+        .add("{id}/adopt_suggested_url", post(adopt_suggested_url))
         .add("{id}", delete(remove))
         .add("{id}", put(update))
         .add("{id}", post(update))
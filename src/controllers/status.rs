@@ -1,26 +1,98 @@
 #![allow(clippy::missing_errors_doc)]
 #![allow(clippy::unused_async)]
 
-use axum::debug_handler;
-use loco_rs::prelude::*;
+use axum::{debug_handler, extract::Query, http::StatusCode};
+use loco_rs::{controller::ErrorDetail, prelude::*};
+use sea_orm::PaginatorTrait;
+use serde::{Deserialize, Serialize};
 use tracing::{error, info};
 
-use crate::{job_tracking::manager::TaskManager, views};
+use crate::{
+    gluetun::controller::GluetunRestartOutcome,
+    job_tracking::{manager::TaskManager, task::TaskType},
+    models::{_entities::medias, medias::Medias, sources::Sources},
+    services::{deps, disk, reconcile},
+    views, ytdlp,
+};
+
+#[derive(Debug, Default, Deserialize)]
+pub struct RestartParams {
+    /// Waits for the restart to finish and reports its outcome
+    #[serde(default)]
+    pub wait: bool,
+}
+
+/// Result of a manual VPN restart request
+#[derive(Debug, Serialize)]
+pub struct RestartResult {
+    /// `success`, `warning` or `error`, as shown on the status page
+    pub kind: &'static str,
+    pub message: String,
+    /// Outcome reported by Gluetun, only set when waiting for the restart
+    pub outcome: Option<GluetunRestartOutcome>,
+    pub error: Option<String>,
+    #[serde(skip)]
+    status: StatusCode,
+}
+
+impl RestartResult {
+    fn new(status: StatusCode, kind: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+            outcome: None,
+            error: None,
+            status,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ReconcileParams {
+    #[serde(default)]
+    pub adopt: bool,
+}
+
+/// Gluetun state in [`StatusSummary`]
+#[derive(Debug, Serialize)]
+pub struct GluetunSummary {
+    pub enabled: bool,
+    /// Most recent restart of any task type
+    pub last_restart_seconds_ago: Option<u64>,
+    pub last_restart_outcome: Option<String>,
+}
+
+/// Compact system state for dashboard widgets
+#[derive(Debug, Serialize)]
+pub struct StatusSummary {
+    pub sources: u64,
+    pub medias: u64,
+    pub downloaded: u64,
+    pub active_downloads: u64,
+    pub queued_downloads: u64,
+    pub gluetun: GluetunSummary,
+    /// Free bytes on the media directory filesystem
+    pub disk_free: Option<u64>,
+    /// yt-dlp version reported by the last dependency install
+    pub ytdlp_version: Option<String>,
+}
 
 #[debug_handler]
 pub async fn show(ViewEngine(v): ViewEngine<TeraView>) -> Result<Response> {
-    let metrics = TaskManager::global().get_metrics();
-    views::status::show(&v, &metrics)
+    let task_manager = TaskManager::global();
+    let metrics = task_manager.get_metrics();
+    let gluetun = crate::gluetun::supervisor::status(task_manager);
+    views::status::show(&v, &metrics, &gluetun, deps::last_install().as_ref())
 }
 
-#[debug_handler]
-pub async fn restart_gluetun(ViewEngine(v): ViewEngine<TeraView>) -> Result<Response> {
+/// Starts a manual VPN restart, with `wait` it only returns once the restart finished
+async fn manual_gluetun_restart(wait: bool) -> RestartResult {
     let task_manager = TaskManager::global();
 
     if !task_manager.gluetun_enabled() {
         info!("Manual Gluetun VPN restart requested but integration is disabled");
-        return views::status::restart_result(
-            &v,
+        return RestartResult::new(
+            StatusCode::SERVICE_UNAVAILABLE,
             "error",
             "Gluetun integration is disabled. Set LOCALTUBE_GLUETUN_CONTROL_ADDR to enable it.",
         );
@@ -28,8 +100,8 @@ pub async fn restart_gluetun(ViewEngine(v): ViewEngine<TeraView>) -> Result<Resp
 
     let Some(controller) = crate::gluetun::supervisor::controller() else {
         error!("Manual Gluetun VPN restart requested but controller is missing");
-        return views::status::restart_result(
-            &v,
+        return RestartResult::new(
+            StatusCode::SERVICE_UNAVAILABLE,
             "error",
             "Gluetun controller is not available. Check server logs.",
         );
@@ -37,29 +109,173 @@ pub async fn restart_gluetun(ViewEngine(v): ViewEngine<TeraView>) -> Result<Resp
 
     if !task_manager.begin_gluetun_restart(None) {
         info!("Manual Gluetun VPN restart requested but a restart is already in progress");
-        return views::status::restart_result(&v, "warning", "VPN restart is already in progress.");
+        return RestartResult::new(
+            StatusCode::CONFLICT,
+            "warning",
+            "VPN restart is already in progress.",
+        );
     }
 
-    info!("Manual Gluetun VPN restart accepted");
+    info!(wait, "Manual Gluetun VPN restart accepted");
     let task_manager = task_manager.clone();
-    tokio::spawn(async move {
+    let restart = tokio::spawn(async move {
         let outcome = controller.restart().await;
         match &outcome {
             Ok(result) => info!("Manual Gluetun VPN restart succeeded: {result}"),
             Err(err) => error!("Manual Gluetun VPN restart failed: {err}"),
         }
         task_manager.finish_gluetun_restart(None, &outcome);
+        outcome
     });
 
-    views::status::restart_result(
-        &v,
-        "success",
-        "VPN restart triggered. Check server logs for progress.",
-    )
+    if !wait {
+        return RestartResult::new(
+            StatusCode::OK,
+            "success",
+            "VPN restart triggered. Check server logs for progress.",
+        );
+    }
+    // Still spawned, so a dropped request does not leave the restart half done.
+    match restart.await {
+        Ok(Ok(outcome)) => RestartResult {
+            outcome: Some(outcome),
+            ..RestartResult::new(StatusCode::OK, "success", "VPN restarted.")
+        },
+        Ok(Err(err)) => RestartResult {
+            error: Some(err.to_string()),
+            ..RestartResult::new(
+                StatusCode::BAD_GATEWAY,
+                "error",
+                format!("VPN restart failed: {err}"),
+            )
+        },
+        Err(err) => RestartResult {
+            error: Some(err.to_string()),
+            ..RestartResult::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "error",
+                "VPN restart task failed. Check server logs.",
+            )
+        },
+    }
+}
+
+#[debug_handler]
+pub async fn restart_gluetun(
+    ViewEngine(v): ViewEngine<TeraView>,
+    Query(params): Query<RestartParams>,
+) -> Result<Response> {
+    let result = manual_gluetun_restart(params.wait).await;
+    views::status::restart_result(&v, result.kind, &result.message)
+}
+
+/// POST /status/gluetun/restart.json - Same as the HTML variant, the status code reflects the result
+#[debug_handler]
+pub async fn restart_gluetun_json(Query(params): Query<RestartParams>) -> Result<Response> {
+    let result = manual_gluetun_restart(params.wait).await;
+    format::render().status(result.status).json(result)
+}
+
+/// Build and dependency versions served by `GET /version`
+#[derive(Debug, Serialize)]
+pub struct VersionInfo {
+    pub version: &'static str,
+    /// Commit the binary was built from, set through `LOCALTUBE_GIT_COMMIT` at build time
+    pub git_commit: Option<&'static str>,
+    pub ytdlp_version: Option<String>,
+    pub ffmpeg_version: Option<String>,
+}
+
+/// GET /version - Returns the LocalTube version and the versions of yt-dlp and ffmpeg
+#[debug_handler]
+pub async fn version() -> Result<Response> {
+    let (ytdlp_version, ffmpeg_version) =
+        tokio::join!(ytdlp::yt_dlp_version(), ytdlp::ffmpeg_version());
+    format::json(VersionInfo {
+        version: env!("CARGO_PKG_VERSION"),
+        git_commit: option_env!("LOCALTUBE_GIT_COMMIT").filter(|commit| !commit.is_empty()),
+        ytdlp_version: ytdlp_version.ok(),
+        ffmpeg_version: ffmpeg_version.ok(),
+    })
+}
+
+/// GET /status/summary.json - Counts, download activity, VPN, disk and yt-dlp state in one payload
+#[debug_handler]
+pub async fn summary(State(ctx): State<AppContext>) -> Result<Response> {
+    let metrics = TaskManager::global().get_metrics();
+    let downloads = metrics.tasks.get(&TaskType::DownloadVideo);
+    let last_restart = metrics
+        .tasks
+        .values()
+        .filter(|task| task.last_restart_seconds_ago.is_some())
+        .min_by_key(|task| task.last_restart_seconds_ago);
+
+    format::json(StatusSummary {
+        sources: Sources::find().count(&ctx.db).await?,
+        medias: Medias::find().count(&ctx.db).await?,
+        downloaded: Medias::find()
+            .filter(medias::Column::MediaPath.is_not_null())
+            .count(&ctx.db)
+            .await?,
+        active_downloads: downloads.map_or(0, |task| task.active_count),
+        queued_downloads: downloads.map_or(0, |task| task.queued_count),
+        gluetun: GluetunSummary {
+            enabled: metrics.gluetun_enabled,
+            last_restart_seconds_ago: last_restart.and_then(|task| task.last_restart_seconds_ago),
+            last_restart_outcome: last_restart.and_then(|task| task.last_restart_outcome.clone()),
+        },
+        disk_free: disk::free_space(ytdlp::media_directory()),
+        ytdlp_version: deps::last_install().and_then(|record| record.version),
+    })
+}
+
+/// POST /status/reconcile - Re-scans the media directory and fixes stale media paths
+#[debug_handler]
+pub async fn reconcile(
+    State(ctx): State<AppContext>,
+    Query(params): Query<ReconcileParams>,
+) -> Result<Response> {
+    info!(adopt = params.adopt, "Media reconcile requested");
+    format::json(reconcile::reconcile_media(&ctx, params.adopt).await?)
+}
+
+/// POST /status/deps/reinstall - Installs yt-dlp and ffmpeg again and reports the outcome
+#[debug_handler]
+pub async fn reinstall_deps() -> Result<Response> {
+    info!("Dependency reinstall requested");
+    let Some(record) = deps::install().await else {
+        return Err(Error::CustomError(
+            StatusCode::CONFLICT,
+            ErrorDetail::new("Conflict", "A dependency install is already running"),
+        ));
+    };
+    let status = if record.success {
+        StatusCode::OK
+    } else {
+        StatusCode::INTERNAL_SERVER_ERROR
+    };
+    format::render().status(status).json(record)
+}
+
+/// GET /status/tasks/{id}.json - Full detail of a task, 404 once it was cleaned up
+#[debug_handler]
+pub async fn task_detail(Path(file): Path<String>) -> Result<Response> {
+    // The router only captures whole segments, so the extension is stripped here.
+    let id = file.strip_suffix(".json").ok_or(Error::NotFound)?;
+    let detail = TaskManager::global()
+        .task_detail(id)
+        .ok_or(Error::NotFound)?;
+    format::json(detail)
 }
 
 pub fn routes() -> Routes {
     Routes::new()
         .add("/status", get(show))
+        .add("/status/summary.json", get(summary))
+        .add("/status/tasks/{file}", get(task_detail))
         .add("/status/gluetun/restart", post(restart_gluetun))
+        .add("/status/gluetun/restart.json", post(restart_gluetun_json))
+        .add("/status/reconcile", post(reconcile))
+        .add("/status/deps/reinstall", post(reinstall_deps))
+        .add("/version", get(version))
 }
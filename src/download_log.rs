@@ -0,0 +1,151 @@
+use std::path::{Path, PathBuf};
+use std::process::Output;
+use std::sync::OnceLock;
+use tracing::warn;
+
+static LOG_DIRECTORY: OnceLock<PathBuf> = OnceLock::new();
+
+/// Returns the directory holding the per-media download logs
+#[must_use]
+pub fn log_directory() -> &'static PathBuf {
+    LOG_DIRECTORY.get_or_init(|| {
+        std::env::var("LOCALTUBE_DOWNLOAD_LOG_DIR")
+            .map_or_else(|_| PathBuf::from("logs/downloads"), PathBuf::from)
+    })
+}
+
+/// Returns the log file of the last download attempt for the media
+#[must_use]
+pub fn log_path(media_id: i32) -> PathBuf {
+    log_directory().join(format!("{media_id}.log"))
+}
+
+/// Options whose value may hold credentials and is left out of the log
+const SECRET_OPTIONS: [&str; 5] = [
+    "--cookies",
+    "--cookies-from-browser",
+    "--password",
+    "--video-password",
+    "--proxy",
+];
+
+fn shell_quote(arg: &str) -> String {
+    let plain = !arg.is_empty()
+        && arg
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"-_./:=,+@%".contains(&b));
+    if plain {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+/// Renders a command as a shell line that can be pasted to rerun it
+///
+/// Values of [`SECRET_OPTIONS`] are replaced, so the line has to be completed
+/// by hand when the download needs them.
+#[must_use]
+pub fn command_line(program: &Path, args: &[String]) -> String {
+    let mut parts = vec![shell_quote(&program.to_string_lossy())];
+    let mut redact_next = false;
+    for arg in args {
+        if redact_next {
+            parts.push("<redacted>".to_string());
+            redact_next = false;
+        } else if let Some((option, _)) = arg
+            .split_once('=')
+            .filter(|(option, _)| SECRET_OPTIONS.contains(option))
+        {
+            parts.push(format!("{option}=<redacted>"));
+        } else {
+            redact_next = SECRET_OPTIONS.contains(&arg.as_str());
+            parts.push(shell_quote(arg));
+        }
+    }
+    parts.join(" ")
+}
+
+fn render(url: &str, command: &str, output: &Output) -> String {
+    format!(
+        "command: {command}\nurl: {url}\nexit status: {}\n\n--- stdout ---\n{}\n--- stderr ---\n{}",
+        output.status,
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr),
+    )
+}
+
+/// Stores the command and raw yt-dlp output of a download attempt, replacing the previous one
+///
+/// Failures are only logged; a missing log must never fail the download itself.
+pub async fn save(media_id: i32, url: &str, command: &str, output: &Output) {
+    let path = log_path(media_id);
+    if let Err(err) = tokio::fs::create_dir_all(log_directory()).await {
+        warn!(error = %err, "failed to create download log directory");
+        return;
+    }
+    if let Err(err) = tokio::fs::write(&path, render(url, command, output)).await {
+        warn!(error = %err, "failed to write download log {}", path.display());
+    }
+}
+
+/// Appends the full error of a failed download attempt to its log
+///
+/// Failures are only logged, like in [`save`].
+pub async fn append_error(media_id: i32, error: &str) {
+    use tokio::io::AsyncWriteExt;
+
+    let path = log_path(media_id);
+    if let Err(err) = tokio::fs::create_dir_all(log_directory()).await {
+        warn!(error = %err, "failed to create download log directory");
+        return;
+    }
+    let written = async {
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .await?;
+        file.write_all(format!("\n--- error ---\n{error}\n").as_bytes())
+            .await
+    }
+    .await;
+    if let Err(err) = written {
+        warn!(error = %err, "failed to write download log {}", path.display());
+    }
+}
+
+/// Reads the log of the last download attempt, if any
+pub async fn read(media_id: i32) -> Option<String> {
+    tokio::fs::read_to_string(log_path(media_id)).await.ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::command_line;
+    use std::path::Path;
+
+    #[test]
+    fn command_line_quotes_args_and_redacts_secrets() {
+        let args = [
+            "--cookies",
+            "/config/cookies.txt",
+            "--cookies-from-browser=firefox",
+            "--user-agent",
+            "Mozilla/5.0 (X11)",
+            "--remux-video=mkv",
+            "it's",
+            "https://example.com/watch?v=abc",
+        ]
+        .map(String::from);
+        let line = command_line(Path::new("libs/yt-dlp"), &args);
+        assert_eq!(
+            line,
+            "libs/yt-dlp --cookies <redacted> --cookies-from-browser=<redacted> \
+             --user-agent 'Mozilla/5.0 (X11)' --remux-video=mkv 'it'\\''s' \
+             'https://example.com/watch?v=abc'"
+        );
+        assert!(!line.contains("cookies.txt"));
+        assert!(!line.contains("firefox"));
+    }
+}
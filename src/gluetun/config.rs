@@ -35,6 +35,12 @@ impl GluetunConfig {
         }))
     }
 
+    /// Returns the configured control server address
+    #[must_use]
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
     pub(crate) fn status_url(&self) -> String {
         format!("{}/v1/vpn/status", self.base_url)
     }
@@ -1,7 +1,7 @@
 use async_trait::async_trait;
 use loco_rs::prelude::*;
 use reqwest::{Client, StatusCode};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::fmt;
 use tracing::{debug, info, warn};
 
@@ -20,7 +20,7 @@ struct VpnStatusChangeResponse {
     outcome: Option<String>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct GluetunRestartOutcome {
     pub stop_outcome: Option<String>,
     pub start_outcome: Option<String>,
@@ -51,9 +51,22 @@ pub enum GluetunError {
     PollTimeout,
 }
 
+impl GluetunError {
+    /// Returns whether the control server could not be reached at all
+    #[must_use]
+    pub const fn is_unreachable(&self) -> bool {
+        matches!(self, Self::Http(_))
+    }
+}
+
 #[async_trait]
 pub trait GluetunController: Send + Sync {
     async fn restart(&self) -> std::result::Result<GluetunRestartOutcome, GluetunError>;
+
+    /// Returns the control server address, if the controller talks to one
+    fn base_url(&self) -> Option<&str> {
+        None
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -178,6 +191,10 @@ impl GluetunController for HttpGluetunController {
             start_outcome,
         })
     }
+
+    fn base_url(&self) -> Option<&str> {
+        Some(self.config.base_url())
+    }
 }
 
 #[cfg(test)]
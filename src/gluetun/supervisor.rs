@@ -1,5 +1,6 @@
 use std::sync::{Arc, LazyLock, Mutex};
 
+use serde::Serialize;
 use tokio::sync::oneshot;
 use tracing::{error, info, warn};
 
@@ -53,6 +54,24 @@ pub fn controller() -> Option<Arc<dyn GluetunController>> {
         .map(|handle| Arc::clone(&handle.controller))
 }
 
+/// Integration state shown on the status page
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct GluetunStatus {
+    /// Control server address of the active controller
+    pub base_url: Option<String>,
+    /// Whether the controller answered the last restart, `None` until one ran
+    pub reachable: Option<bool>,
+}
+
+/// Returns the configured control address and last known reachability.
+#[must_use]
+pub fn status(task_manager: &TaskManager) -> GluetunStatus {
+    GluetunStatus {
+        base_url: controller().and_then(|controller| controller.base_url().map(str::to_owned)),
+        reachable: task_manager.gluetun_reachable(),
+    }
+}
+
 struct GluetunSupervisorHandle {
     shutdown: Option<oneshot::Sender<()>>,
     controller: Arc<dyn GluetunController>,
@@ -4,7 +4,11 @@ use loco_rs::{
     Error, Result,
 };
 
-use crate::ytdlp;
+use crate::{
+    models::settings::GlobalSettings,
+    services::{deps, retry::RetryDelays},
+    ytdlp,
+};
 
 pub struct DownloadDeps;
 
@@ -14,8 +18,15 @@ impl Initializer for DownloadDeps {
         "download-deps".to_string()
     }
 
-    async fn before_run(&self, _app_context: &AppContext) -> Result<()> {
-        ytdlp::download_deps().await.map_err(Error::msg)?;
-        Ok(())
+    async fn before_run(&self, app_context: &AppContext) -> Result<()> {
+        RetryDelays::init_global()?;
+        GlobalSettings::load(&app_context.db).await?.apply();
+        ytdlp::ensure_libs_dir_writable(ytdlp::libs_directory()).await?;
+        match deps::install().await {
+            Some(deps::InstallRecord {
+                error: Some(err), ..
+            }) => Err(Error::Message(err)),
+            _ => Ok(()),
+        }
     }
 }
@@ -6,9 +6,12 @@ use loco_rs::{
     task::Task,
     Result,
 };
-use tracing::error;
+use tracing::{error, info};
 
-use crate::tasks::refresh_indexes::RefreshIndexes;
+use crate::{
+    models::sources::normalize_stored_metadata, services::trash::empty_trash,
+    tasks::refresh_indexes::RefreshIndexes,
+};
 
 pub struct RefreshSources;
 
@@ -19,6 +22,13 @@ impl Initializer for RefreshSources {
     }
 
     async fn before_run(&self, ctx: &AppContext) -> Result<()> {
+        // Before the sweep, so sources read metadata written by older versions.
+        match normalize_stored_metadata(&ctx.db).await {
+            Ok(0) => {}
+            Ok(rewritten) => info!("Normalized the metadata of {rewritten} sources"),
+            Err(e) => error!("Failed to normalize source metadata: {:?}", e),
+        }
+
         if let Err(e) = (RefreshIndexes)
             .run(
                 ctx,
@@ -43,6 +53,12 @@ impl Initializer for RefreshSources {
                 {
                     error!("RefreshIndexes error: {:?}", e);
                 }
+
+                match empty_trash(&ctx.db, chrono::Utc::now()).await {
+                    Ok(0) => {}
+                    Ok(deleted) => info!("Emptied {deleted} medias from the trash"),
+                    Err(e) => error!("Failed to empty the trash: {:?}", e),
+                }
             }
         });
 
@@ -5,7 +5,11 @@ use loco_rs::{
     Result,
 };
 
-use crate::job_tracking::manager::{start_cleanup_task, TaskManager};
+use crate::job_tracking::{
+    history::start_sampling_task,
+    manager::{start_cleanup_task, TaskManager},
+};
+use crate::sse::sse_handler;
 use crate::ws::ws_handler;
 
 async fn health_check() -> impl IntoResponse {
@@ -25,9 +29,11 @@ impl Initializer for WebSocketStatusInitializer {
     async fn after_routes(&self, router: AxumRouter, _ctx: &AppContext) -> Result<AxumRouter> {
         // Start the cleanup task now that the Tokio runtime is fully initialized
         start_cleanup_task(TaskManager::global().clone());
+        start_sampling_task(TaskManager::global().clone());
 
         let router = router.route("/ws/status", get(ws_handler));
         let router = router.route("/ws/health", get(health_check));
+        let router = router.route("/status/events", get(sse_handler));
 
         Ok(router)
     }
@@ -0,0 +1,100 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{LazyLock, Mutex},
+    time::Duration,
+};
+use tracing::info;
+
+use crate::job_tracking::{manager::TaskManager, metrics::AllMetrics, task::TaskType};
+
+/// How often the task metrics are sampled
+pub const SAMPLE_INTERVAL: Duration = Duration::from_secs(60);
+/// Samples kept, a day at [`SAMPLE_INTERVAL`]
+pub const HISTORY_CAPACITY: usize = 24 * 60;
+
+static METRICS_HISTORY: LazyLock<MetricsHistory> =
+    LazyLock::new(|| MetricsHistory::new(HISTORY_CAPACITY));
+
+/// Cumulative task counters at the time of a sample
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct TaskCounts {
+    pub success_count: u64,
+    pub failure_count: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MetricsSample {
+    pub at: DateTime<Utc>,
+    pub tasks: HashMap<TaskType, TaskCounts>,
+}
+
+/// Rolling in-memory series of task metrics, the oldest sample is dropped once full
+#[derive(Debug)]
+pub struct MetricsHistory {
+    capacity: usize,
+    samples: Mutex<VecDeque<MetricsSample>>,
+}
+
+impl MetricsHistory {
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            samples: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    #[must_use]
+    pub fn global() -> &'static Self {
+        &METRICS_HISTORY
+    }
+
+    /// Appends the counters of `metrics` as the sample taken `at`
+    ///
+    /// # Panics
+    ///
+    /// Panics if the samples mutex is poisoned.
+    pub fn record(&self, metrics: &AllMetrics, at: DateTime<Utc>) {
+        let tasks = metrics
+            .tasks
+            .iter()
+            .map(|(task_type, data)| {
+                (
+                    task_type.clone(),
+                    TaskCounts {
+                        success_count: data.success_count,
+                        failure_count: data.failure_count,
+                    },
+                )
+            })
+            .collect();
+        let mut samples = self.samples.lock().unwrap();
+        while samples.len() >= self.capacity {
+            samples.pop_front();
+        }
+        samples.push_back(MetricsSample { at, tasks });
+    }
+
+    /// Returns the retained samples, oldest first
+    ///
+    /// # Panics
+    ///
+    /// Panics if the samples mutex is poisoned.
+    #[must_use]
+    pub fn samples(&self) -> Vec<MetricsSample> {
+        self.samples.lock().unwrap().iter().cloned().collect()
+    }
+}
+
+pub fn start_sampling_task(task_manager: TaskManager) {
+    info!("Starting task metrics sampling background process");
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SAMPLE_INTERVAL);
+        loop {
+            interval.tick().await;
+            MetricsHistory::global().record(&task_manager.get_metrics(), Utc::now());
+        }
+    });
+}
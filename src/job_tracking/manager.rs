@@ -1,20 +1,81 @@
+use futures_util::stream::{self, Stream};
 use std::{
     collections::HashMap,
     sync::{
-        atomic::{AtomicBool, Ordering},
-        Arc, Mutex, RwLock,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex, OnceLock, RwLock,
     },
     time::{Duration, Instant},
 };
-use tokio::sync::broadcast;
+use tokio::sync::{broadcast, oneshot, Semaphore};
 use tracing::info;
 
 use crate::gluetun::controller::{GluetunError, GluetunRestartOutcome};
 use crate::job_tracking::{
-    metrics::{AllMetrics, TaskMetricData, TaskMetrics},
-    task::{QueuedTask, SerializableTaskStatus, Task, TaskState, TaskStatus, TaskType, TaskUpdate},
+    metrics::{AllMetrics, StreamMetricData, TaskMetricData, TaskMetrics},
+    task::{
+        QueuedTask, SerializableTaskStatus, SlotLines, SlotPermit, Task, TaskDetail, TaskState,
+        TaskStatus, TaskType, TaskUpdate,
+    },
 };
 
+/// Updates a broadcast channel buffers for a slow subscriber before it lags
+pub const DEFAULT_BROADCAST_CAPACITY: usize = 100;
+
+static TASK_BROADCAST_CAPACITY: OnceLock<usize> = OnceLock::new();
+static METRICS_BROADCAST_CAPACITY: OnceLock<usize> = OnceLock::new();
+static TASK_SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+fn capacity_from_env(name: &str) -> usize {
+    std::env::var(name)
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .filter(|n| *n > 0)
+        .unwrap_or(DEFAULT_BROADCAST_CAPACITY)
+}
+
+/// Returns the capacity of the task update channel, `LOCALTUBE_TASK_BROADCAST_CAPACITY`
+#[must_use]
+pub fn task_broadcast_capacity() -> usize {
+    *TASK_BROADCAST_CAPACITY.get_or_init(|| capacity_from_env("LOCALTUBE_TASK_BROADCAST_CAPACITY"))
+}
+
+/// Returns the capacity of the metrics channel, `LOCALTUBE_METRICS_BROADCAST_CAPACITY`
+#[must_use]
+pub fn metrics_broadcast_capacity() -> usize {
+    *METRICS_BROADCAST_CAPACITY
+        .get_or_init(|| capacity_from_env("LOCALTUBE_METRICS_BROADCAST_CAPACITY"))
+}
+
+/// Streams the updates received on `rx`
+///
+/// Lagged receivers skip the missed updates, every update carries the full
+/// state anyway. The stream ends once the sender is gone.
+pub fn broadcast_updates<T: Clone + Send + 'static>(
+    rx: broadcast::Receiver<T>,
+) -> impl Stream<Item = T> {
+    stream::unfold(rx, |mut rx| async move {
+        loop {
+            match rx.recv().await {
+                Ok(update) => return Some((update, rx)),
+                Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    })
+}
+
+/// Task updates within this window after a broadcast are coalesced into one snapshot
+const UPDATE_COALESCE_WINDOW: Duration = Duration::from_millis(100);
+
+#[derive(Default)]
+struct UpdateThrottle {
+    /// A snapshot went out less than a window ago
+    open: bool,
+    /// Tasks changed since that snapshot
+    dirty: bool,
+}
+
 // Global task manager instance without automatic cleanup task.
 static TASK_MANAGER: std::sync::LazyLock<TaskManager> = std::sync::LazyLock::new(|| {
     let manager = TaskManager::new();
@@ -22,6 +83,27 @@ static TASK_MANAGER: std::sync::LazyLock<TaskManager> = std::sync::LazyLock::new
     manager
 });
 
+/// Orders queued tasks, the highest priority first and the oldest among equals
+fn queue_order(task: &TaskStatus) -> (std::cmp::Reverse<u64>, u64) {
+    (std::cmp::Reverse(task.priority), task.sequence)
+}
+
+/// Places of the queued downloads in line, 1 for the next one to start
+fn queue_positions(tasks: &HashMap<String, TaskStatus>) -> HashMap<String, usize> {
+    let mut queued: Vec<&TaskStatus> = tasks
+        .values()
+        .filter(|task| {
+            task.task_type == TaskType::DownloadVideo && matches!(task.state, TaskState::Queued)
+        })
+        .collect();
+    queued.sort_by_key(|task| queue_order(task));
+    queued
+        .into_iter()
+        .enumerate()
+        .map(|(index, task)| (task.id.clone(), index + 1))
+        .collect()
+}
+
 #[derive(Clone)]
 pub struct TaskManager {
     pub tasks: Arc<Mutex<HashMap<String, TaskStatus>>>,
@@ -30,6 +112,11 @@ pub struct TaskManager {
     metrics_tx: broadcast::Sender<AllMetrics>,
     gluetun_enabled: Arc<AtomicBool>,
     gluetun_restart_in_progress: Arc<AtomicBool>,
+    gluetun_reachable: Arc<Mutex<Option<bool>>>,
+    stream_metrics: Arc<StreamMetricData>,
+    update_throttle: Arc<Mutex<UpdateThrottle>>,
+    /// Started tasks waiting for a semaphore
+    pub(crate) slot_lines: Arc<SlotLines>,
 }
 
 impl std::fmt::Debug for TaskManager {
@@ -56,8 +143,18 @@ impl Default for TaskManager {
 impl TaskManager {
     #[must_use]
     pub fn new() -> Self {
-        let (tx, _) = broadcast::channel(100);
-        let (metrics_tx, _) = broadcast::channel(100);
+        Self::with_capacities(task_broadcast_capacity(), metrics_broadcast_capacity())
+    }
+
+    /// Creates a manager whose task and metrics channels buffer the given number of updates
+    ///
+    /// # Panics
+    ///
+    /// Panics if a capacity is zero.
+    #[must_use]
+    pub fn with_capacities(task_capacity: usize, metrics_capacity: usize) -> Self {
+        let (tx, _) = broadcast::channel(task_capacity);
+        let (metrics_tx, _) = broadcast::channel(metrics_capacity);
         let mut metrics = HashMap::new();
         for task_type in &[TaskType::RefreshIndex, TaskType::DownloadVideo] {
             metrics.insert(task_type.clone(), TaskMetricData::default());
@@ -69,6 +166,10 @@ impl TaskManager {
             metrics_tx,
             gluetun_enabled: Arc::new(AtomicBool::new(false)),
             gluetun_restart_in_progress: Arc::new(AtomicBool::new(false)),
+            gluetun_reachable: Arc::new(Mutex::new(None)),
+            stream_metrics: Arc::new(StreamMetricData::default()),
+            update_throttle: Arc::new(Mutex::new(UpdateThrottle::default())),
+            slot_lines: Arc::new(SlotLines::default()),
         }
     }
 
@@ -82,15 +183,37 @@ impl TaskManager {
     /// Panics if the task registry mutex is poisoned.
     #[must_use]
     pub fn add_task(&self, task_type: TaskType, title: String) -> QueuedTask {
+        self.add_media_task(task_type, title, None, None)
+    }
+
+    /// Adds a task working on the given source or media
+    ///
+    /// # Panics
+    ///
+    /// Panics if the task registry mutex is poisoned.
+    #[must_use]
+    pub fn add_media_task(
+        &self,
+        task_type: TaskType,
+        title: String,
+        source_id: Option<i32>,
+        media_id: Option<i32>,
+    ) -> QueuedTask {
         let id = uuid::Uuid::new_v4().to_string();
         let task = TaskStatus {
             id: id.clone(),
             task_type,
             title,
             created_at: Instant::now(),
+            sequence: TASK_SEQUENCE.fetch_add(1, Ordering::Relaxed),
             state: TaskState::Queued,
             completed_at: None,
             status: None,
+            speed: None,
+            source_id,
+            media_id,
+            priority: 0,
+            waiting_on: None,
         };
         {
             let mut tasks = self.tasks.lock().unwrap();
@@ -128,6 +251,94 @@ impl TaskManager {
         self.broadcast_update();
     }
 
+    /// Records the current transfer speed of a task, `None` while unknown
+    ///
+    /// # Panics
+    ///
+    /// Panics if the task registry mutex is poisoned.
+    pub fn update_task_speed(&self, id: &str, speed: Option<u64>) {
+        {
+            let mut tasks = self.tasks.lock().unwrap();
+            if let Some(task) = tasks.get_mut(id) {
+                if task.speed == speed {
+                    return;
+                }
+                task.speed = speed;
+            }
+        }
+        self.broadcast_update();
+    }
+
+    /// Flags a task that is blocked on its concurrency semaphore, cleared by [`Self::mark_task_started`]
+    ///
+    /// # Panics
+    ///
+    /// Panics if the task registry mutex is poisoned.
+    pub fn mark_task_waiting_for_slot(&self, id: &str) {
+        {
+            let mut tasks = self.tasks.lock().unwrap();
+            if let Some(task) = tasks.get_mut(id) {
+                task.status = Some(task.task_type.slot_wait_status().to_string());
+            }
+        }
+        self.broadcast_update();
+    }
+
+    /// Puts a started task in line for `permits` permits of `sem`, received once it is its turn
+    pub(crate) fn join_slot_line(
+        &self,
+        id: &str,
+        sem: &Arc<Semaphore>,
+        permits: u32,
+    ) -> oneshot::Receiver<SlotPermit> {
+        let order = {
+            let mut tasks = self.tasks.lock().unwrap();
+            tasks.get_mut(id).map_or((0, u64::MAX), |task| {
+                task.waiting_on = Some(SlotLines::key(sem));
+                (task.priority, task.sequence)
+            })
+        };
+        self.slot_lines.join(id, order, sem, permits)
+    }
+
+    /// Hands permits added to `sem`, e.g. by a concurrency change, to the tasks waiting for it
+    pub fn slots_added(&self, sem: &Arc<Semaphore>) {
+        self.slot_lines.wake(sem);
+    }
+
+    /// Moves the queued downloads of the source ahead of every other queued task
+    ///
+    /// Returns the number of downloads that moved. Prioritizing another source
+    /// later puts that one in front.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the task registry mutex is poisoned.
+    pub fn prioritize_source_downloads(&self, source_id: i32) -> usize {
+        let prioritized: HashMap<String, u64> = {
+            let mut tasks = self.tasks.lock().unwrap();
+            let priority = tasks.values().map(|task| task.priority).max().unwrap_or(0) + 1;
+            tasks
+                .values_mut()
+                .filter(|task| {
+                    task.task_type == TaskType::DownloadVideo
+                        && task.source_id == Some(source_id)
+                        && matches!(task.state, TaskState::Queued)
+                        && task.completed_at.is_none()
+                })
+                .map(|task| {
+                    task.priority = priority;
+                    (task.id.clone(), priority)
+                })
+                .collect()
+        };
+        if !prioritized.is_empty() {
+            self.slot_lines.reprioritize(&prioritized);
+            self.broadcast_update();
+        }
+        prioritized.len()
+    }
+
     /// # Panics
     ///
     /// Panics if the task registry mutex is poisoned.
@@ -136,6 +347,10 @@ impl TaskManager {
             let mut tasks = self.tasks.lock().unwrap();
             if let Some(task) = tasks.get_mut(id) {
                 task.state = TaskState::InProgress;
+                task.waiting_on = None;
+                if task.status.as_deref() == Some(task.task_type.slot_wait_status()) {
+                    task.status = None;
+                }
             }
         }
         self.broadcast_update();
@@ -159,18 +374,24 @@ impl TaskManager {
     ///
     /// Panics if the task registry mutex is poisoned.
     pub fn remove_task(&self, id: &str) {
+        let mut left_line = None;
         let (task_type, final_state) = {
             let mut tasks = self.tasks.lock().unwrap();
             if let Some(task) = tasks.get_mut(id) {
                 if task.completed_at.is_none() {
                     task.completed_at = Some(Instant::now());
                 }
+                left_line = task.waiting_on.take();
                 Some((task.task_type.clone(), task.state.clone()))
             } else {
                 None
             }
         }
         .map_or((None, None), |(tt, fs)| (Some(tt), Some(fs)));
+        // A task dropped while waiting gives up its place in line.
+        if let Some(line) = left_line {
+            self.slot_lines.dispatch(line);
+        }
 
         let now = Instant::now();
 
@@ -244,9 +465,27 @@ impl TaskManager {
 
     /// # Panics
     ///
-    /// Panics if the metrics map lock is poisoned.
+    /// Panics if the task registry mutex or the metrics map lock is poisoned.
     #[must_use]
     pub fn get_metrics(&self) -> AllMetrics {
+        let mut admission: HashMap<TaskType, (u64, u64)> = HashMap::new();
+        let mut download_speed = 0;
+        {
+            let tasks = self.tasks.lock().unwrap();
+            for task in tasks.values().filter(|t| t.completed_at.is_none()) {
+                let counts = admission.entry(task.task_type.clone()).or_default();
+                match task.state {
+                    TaskState::Queued => counts.0 += 1,
+                    TaskState::InProgress => {
+                        counts.1 += 1;
+                        if task.task_type == TaskType::DownloadVideo {
+                            download_speed += task.speed.unwrap_or(0);
+                        }
+                    }
+                    TaskState::Completed | TaskState::Failed(_) => {}
+                }
+            }
+        }
         let metrics = self.metrics.read().unwrap();
         let now = Instant::now();
         let tasks = metrics
@@ -269,12 +508,18 @@ impl TaskManager {
                     now.checked_duration_since(t)
                         .map(|duration| duration.as_secs())
                 });
+                let (queued_count, active_count) =
+                    admission.get(task_type).copied().unwrap_or_default();
                 (
                     task_type.clone(),
                     TaskMetrics {
+                        queued_count,
+                        active_count,
                         success_count: data.success,
                         failure_count: data.failure,
                         consecutive_failures: data.consecutive_failures,
+                        retry_count: data.retry,
+                        permanent_failure_count: data.permanent_failure,
                         last_success_seconds_ago,
                         last_failure_seconds_ago,
                         restart_count: data.restart.count,
@@ -290,9 +535,58 @@ impl TaskManager {
         AllMetrics {
             tasks,
             gluetun_enabled: self.gluetun_enabled_internal(),
+            stream: self.stream_metrics.snapshot(),
+            download_speed,
         }
     }
 
+    /// Counts a failed task of `task_type` scheduled to run again later
+    ///
+    /// # Panics
+    ///
+    /// Panics if the metrics map lock is poisoned.
+    pub fn record_retry(&self, task_type: TaskType) {
+        self.metrics
+            .write()
+            .unwrap()
+            .entry(task_type)
+            .or_default()
+            .retry += 1;
+        self.broadcast_metrics();
+    }
+
+    /// Counts a failed task of `task_type` that is not retried anymore
+    ///
+    /// # Panics
+    ///
+    /// Panics if the metrics map lock is poisoned.
+    pub fn record_permanent_failure(&self, task_type: TaskType) {
+        self.metrics
+            .write()
+            .unwrap()
+            .entry(task_type)
+            .or_default()
+            .permanent_failure += 1;
+        self.broadcast_metrics();
+    }
+
+    /// Counts a media stream request, split by whether it asked for a byte range
+    pub fn record_stream_request(&self, ranged: bool) {
+        let counter = if ranged {
+            &self.stream_metrics.range_requests
+        } else {
+            &self.stream_metrics.full_requests
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts media bytes actually handed to the client
+    pub fn record_stream_bytes(&self, bytes: u64) {
+        self.stream_metrics
+            .bytes_served
+            .fetch_add(bytes, Ordering::Relaxed);
+    }
+
     #[must_use]
     ///
     /// # Panics
@@ -311,6 +605,7 @@ impl TaskManager {
         if !enabled {
             self.gluetun_restart_in_progress
                 .store(false, Ordering::SeqCst);
+            *self.gluetun_reachable.lock().unwrap() = None;
             let mut metrics = self.metrics.write().unwrap();
             for task_type in [TaskType::DownloadVideo, TaskType::RefreshIndex] {
                 if let Some(data) = metrics.get_mut(&task_type) {
@@ -323,6 +618,18 @@ impl TaskManager {
         self.broadcast_metrics();
     }
 
+    /// Returns whether the Gluetun controller answered the last restart
+    ///
+    /// `None` until a restart ran since the integration was enabled.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the reachability mutex is poisoned.
+    #[must_use]
+    pub fn gluetun_reachable(&self) -> Option<bool> {
+        *self.gluetun_reachable.lock().unwrap()
+    }
+
     #[must_use]
     pub fn subscribe_metrics(&self) -> broadcast::Receiver<AllMetrics> {
         self.metrics_tx.subscribe()
@@ -349,18 +656,15 @@ impl TaskManager {
         let now = Instant::now();
         {
             let mut metrics = self.metrics.write().unwrap();
-            for task_type in [TaskType::DownloadVideo, TaskType::RefreshIndex] {
-                if let Some(data) = metrics.get_mut(&task_type) {
-                    data.restart.in_progress = true;
-                    data.restart.last_started = Some(now);
-                }
+            for data in metrics.values_mut() {
+                data.restart.in_progress = true;
             }
-
-            if let Some(task_type) = trigger_task {
-                if let Some(data) = metrics.get_mut(&task_type) {
-                    data.restart.last_error = None;
-                    data.restart.last_outcome = None;
-                }
+            for task_type in restart_attribution(trigger_task.as_ref()) {
+                let data = metrics.entry(task_type).or_default();
+                data.restart.in_progress = true;
+                data.restart.last_started = Some(now);
+                data.restart.last_error = None;
+                data.restart.last_outcome = None;
             }
         }
 
@@ -380,23 +684,20 @@ impl TaskManager {
         let now = Instant::now();
         {
             let mut metrics = self.metrics.write().unwrap();
-            let is_manual = trigger_task.is_none();
-            for task_type in [TaskType::DownloadVideo, TaskType::RefreshIndex] {
-                if let Some(data) = metrics.get_mut(&task_type) {
-                    data.restart.in_progress = false;
-                    data.restart.last_completed = Some(now);
-                    let should_count = is_manual || trigger_task.as_ref() == Some(&task_type);
-                    match outcome {
-                        Ok(result) => {
-                            if should_count {
-                                data.restart.count += 1;
-                            }
-                            data.restart.last_outcome = Some(result.to_string());
-                            data.restart.last_error = None;
-                        }
-                        Err(err) => {
-                            data.restart.last_error = Some(err.to_string());
-                        }
+            for data in metrics.values_mut() {
+                data.restart.in_progress = false;
+            }
+            for task_type in restart_attribution(trigger_task.as_ref()) {
+                let data = metrics.entry(task_type).or_default();
+                data.restart.last_completed = Some(now);
+                match outcome {
+                    Ok(result) => {
+                        data.restart.count += 1;
+                        data.restart.last_outcome = Some(result.to_string());
+                        data.restart.last_error = None;
+                    }
+                    Err(err) => {
+                        data.restart.last_error = Some(err.to_string());
                     }
                 }
             }
@@ -410,6 +711,11 @@ impl TaskManager {
             }
         }
 
+        *self.gluetun_reachable.lock().unwrap() = Some(
+            outcome
+                .as_ref()
+                .map_or_else(|err| !err.is_unreachable(), |_| true),
+        );
         self.gluetun_restart_in_progress
             .store(false, Ordering::SeqCst);
         self.broadcast_metrics();
@@ -424,42 +730,125 @@ impl TaskManager {
         let _ = self.metrics_tx.send(snapshot);
     }
 
+    /// Returns the current task list in the shape sent to status subscribers
+    ///
     /// # Panics
     ///
     /// Panics if the task registry mutex is poisoned.
+    #[must_use]
+    pub fn task_snapshot(&self) -> TaskUpdate {
+        let tasks = self.tasks.lock().unwrap();
+        let positions = queue_positions(&tasks);
+        let now = Instant::now();
+        let ago = |at: Instant| now.saturating_duration_since(at).as_secs_f64();
+        let task_list = tasks
+            .values()
+            .map(|task| SerializableTaskStatus {
+                id: task.id.clone(),
+                task_type: task.task_type.clone(),
+                title: task.title.clone(),
+                state: task.state.clone(),
+                status: task.status.clone(),
+                speed: task.speed,
+                queue_position: positions.get(&task.id).copied(),
+                source_id: task.source_id,
+                media_id: task.media_id,
+                created_seconds_ago: ago(task.created_at),
+                completed_seconds_ago: task.completed_at.map(ago),
+            })
+            .collect::<Vec<SerializableTaskStatus>>();
+        TaskUpdate { tasks: task_list }
+    }
+
+    /// Returns the detail of a task, `None` once it was cleaned up
+    ///
+    /// # Panics
+    ///
+    /// Panics if the task registry mutex is poisoned.
+    #[must_use]
+    pub fn task_detail(&self, id: &str) -> Option<TaskDetail> {
+        let tasks = self.tasks.lock().unwrap();
+        let task = tasks.get(id)?;
+        let position = queue_positions(&tasks).get(id).copied();
+        Some(TaskDetail::new(task, position, Instant::now()))
+    }
+
+    /// Sends the task list to status subscribers
+    ///
+    /// The first change is sent right away, further changes within
+    /// [`UPDATE_COALESCE_WINDOW`] go out as a single trailing snapshot so a
+    /// burst of task mutations cannot overflow the channel.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the task registry or throttle mutex is poisoned.
     pub fn broadcast_update(&self) {
-        let task_list = {
-            let tasks = self.tasks.lock().unwrap();
-            tasks
-                .values()
-                .map(|task| SerializableTaskStatus {
-                    id: task.id.clone(),
-                    task_type: task.task_type.clone(),
-                    title: task.title.clone(),
-                    state: task.state.clone(),
-                    status: task.status.clone(),
-                })
-                .collect::<Vec<SerializableTaskStatus>>()
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            let _ = self.tx.send(self.task_snapshot());
+            return;
         };
-
-        let _ = self.tx.send(TaskUpdate { tasks: task_list });
+        {
+            let mut throttle = self.update_throttle.lock().unwrap();
+            if throttle.open {
+                throttle.dirty = true;
+                return;
+            }
+            throttle.open = true;
+        }
+        let _ = self.tx.send(self.task_snapshot());
+
+        let manager = self.clone();
+        runtime.spawn(async move {
+            loop {
+                tokio::time::sleep(UPDATE_COALESCE_WINDOW).await;
+                {
+                    let mut throttle = manager.update_throttle.lock().unwrap();
+                    if !throttle.dirty {
+                        throttle.open = false;
+                        return;
+                    }
+                    throttle.dirty = false;
+                }
+                let _ = manager.tx.send(manager.task_snapshot());
+            }
+        });
     }
 }
 
+/// Task types a VPN restart is counted for
+///
+/// An automatic restart belongs to the task type whose failures triggered it,
+/// a manual one to every task type that can trigger a restart.
+fn restart_attribution(trigger_task: Option<&TaskType>) -> Vec<TaskType> {
+    trigger_task.map_or_else(
+        || vec![TaskType::DownloadVideo, TaskType::RefreshIndex],
+        |task_type| vec![task_type.clone()],
+    )
+}
+
 #[must_use]
+/// Registers the download of a media of the source
+///
 /// # Panics
 ///
 /// Panics if the task registry mutex is poisoned.
-pub fn register_download_task(title: String) -> QueuedTask {
-    TaskManager::global().add_task(TaskType::DownloadVideo, title)
+pub fn register_download_task(title: String, source_id: i32, media_id: i32) -> QueuedTask {
+    TaskManager::global().add_media_task(
+        TaskType::DownloadVideo,
+        title,
+        Some(source_id),
+        Some(media_id),
+    )
 }
 
 #[must_use]
+/// Registers the refresh of a source
+///
 /// # Panics
 ///
 /// Panics if the task registry mutex is poisoned.
-pub fn register_refresh_task(title: String) -> QueuedTask {
-    TaskManager::global().add_task(TaskType::RefreshIndex, title)
+pub fn register_refresh_task(title: String, source_id: i32) -> QueuedTask {
+    TaskManager::global().add_media_task(TaskType::RefreshIndex, title, Some(source_id), None)
 }
 
 pub fn start_cleanup_task(task_manager: TaskManager) {
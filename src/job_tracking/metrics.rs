@@ -1,6 +1,7 @@
 use serde::Serialize;
 use std::{
     collections::HashMap,
+    sync::atomic::{AtomicU64, Ordering},
     time::{Duration, Instant},
 };
 
@@ -24,16 +25,52 @@ pub(crate) struct TaskMetricData {
     pub(crate) success: u64,
     pub(crate) failure: u64,
     pub(crate) consecutive_failures: u64,
+    pub(crate) retry: u64,
+    pub(crate) permanent_failure: u64,
     pub(crate) last_success: Option<Instant>,
     pub(crate) last_failure: Option<Instant>,
     pub(crate) restart: RestartMetrics,
 }
 
+#[derive(Default)]
+pub(crate) struct StreamMetricData {
+    pub(crate) bytes_served: AtomicU64,
+    pub(crate) range_requests: AtomicU64,
+    pub(crate) full_requests: AtomicU64,
+}
+
+impl StreamMetricData {
+    pub(crate) fn snapshot(&self) -> StreamMetrics {
+        let range_request_count = self.range_requests.load(Ordering::Relaxed);
+        let full_request_count = self.full_requests.load(Ordering::Relaxed);
+        StreamMetrics {
+            bytes_served: self.bytes_served.load(Ordering::Relaxed),
+            request_count: range_request_count + full_request_count,
+            range_request_count,
+            full_request_count,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct StreamMetrics {
+    pub bytes_served: u64,
+    pub request_count: u64,
+    pub range_request_count: u64,
+    pub full_request_count: u64,
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct TaskMetrics {
+    pub queued_count: u64,
+    pub active_count: u64,
     pub success_count: u64,
     pub failure_count: u64,
     pub consecutive_failures: u64,
+    /// Failed tasks scheduled to run again later, postponed ones are not counted
+    pub retry_count: u64,
+    /// Failed tasks given up on, they are not retried
+    pub permanent_failure_count: u64,
     pub last_success_seconds_ago: Option<u64>,
     pub last_failure_seconds_ago: Option<u64>,
     pub restart_count: u64,
@@ -47,4 +84,7 @@ pub struct TaskMetrics {
 pub struct AllMetrics {
     pub tasks: HashMap<TaskType, TaskMetrics>,
     pub gluetun_enabled: bool,
+    pub stream: StreamMetrics,
+    /// Combined speed of the running downloads in bytes per second
+    pub download_speed: u64,
 }
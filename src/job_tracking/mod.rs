@@ -1,5 +1,6 @@
 //! APIs for tracking long-running jobs and exposing their state/metrics.
 
+pub mod history;
 pub mod manager;
 pub mod metrics;
 pub mod task;
@@ -1,6 +1,11 @@
 use serde::{Deserialize, Serialize};
-use std::{sync::Arc, time::Instant};
-use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::{BinaryHeap, HashMap},
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+use tokio::sync::{oneshot, OwnedSemaphorePermit, Semaphore};
 
 use crate::job_tracking::manager::TaskManager;
 
@@ -20,6 +25,15 @@ impl TaskType {
             TaskType::DownloadVideo => "download_video",
         }
     }
+
+    /// Status shown while a started task waits for a concurrency slot
+    #[must_use]
+    pub fn slot_wait_status(&self) -> &'static str {
+        match self {
+            TaskType::RefreshIndex => "Waiting for refresh slot…",
+            TaskType::DownloadVideo => "Waiting for download slot…",
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -36,9 +50,20 @@ pub struct TaskStatus {
     pub task_type: TaskType,
     pub title: String,
     pub created_at: Instant,
+    /// Order the task was added in, unlike `created_at` never equal for two tasks
+    pub sequence: u64,
     pub state: TaskState,
     pub completed_at: Option<Instant>,
     pub status: Option<String>,
+    /// Current download speed in bytes per second
+    pub speed: Option<u64>,
+    pub source_id: Option<i32>,
+    pub media_id: Option<i32>,
+    /// Queued tasks with a higher priority go first, equal ones in `sequence` order
+    pub priority: u64,
+    /// Semaphore the started task waits for, tasks waiting for the same one
+    /// get it in queue order
+    pub(crate) waiting_on: Option<usize>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -48,6 +73,67 @@ pub struct SerializableTaskStatus {
     pub title: String,
     pub state: TaskState,
     pub status: Option<String>,
+    #[serde(default)]
+    pub speed: Option<u64>,
+    /// 1-based place of a queued download among the queued downloads
+    #[serde(default)]
+    pub queue_position: Option<usize>,
+    #[serde(default)]
+    pub source_id: Option<i32>,
+    #[serde(default)]
+    pub media_id: Option<i32>,
+    /// Seconds since the task was added, as of the snapshot
+    #[serde(default)]
+    pub created_seconds_ago: f64,
+    /// Seconds since the task completed or failed, `None` while it runs
+    #[serde(default)]
+    pub completed_seconds_ago: Option<f64>,
+}
+
+/// Everything known about a task, instants as seconds relative to now
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskDetail {
+    pub id: TaskId,
+    pub task_type: TaskType,
+    pub title: String,
+    pub state: TaskState,
+    /// Progress message of the task, e.g. the video being processed
+    pub status: Option<String>,
+    pub speed: Option<u64>,
+    pub source_id: Option<i32>,
+    pub media_id: Option<i32>,
+    pub sequence: u64,
+    pub queue_position: Option<usize>,
+    pub created_seconds_ago: f64,
+    pub completed_seconds_ago: Option<f64>,
+    /// Time from creation until completion, or until now for unfinished tasks
+    pub duration_secs: f64,
+}
+
+impl TaskDetail {
+    #[must_use]
+    pub fn new(task: &TaskStatus, queue_position: Option<usize>, now: Instant) -> Self {
+        let ago = |at: Instant| now.saturating_duration_since(at).as_secs_f64();
+        Self {
+            id: task.id.clone(),
+            task_type: task.task_type.clone(),
+            title: task.title.clone(),
+            state: task.state.clone(),
+            status: task.status.clone(),
+            speed: task.speed,
+            source_id: task.source_id,
+            media_id: task.media_id,
+            sequence: task.sequence,
+            queue_position,
+            created_seconds_ago: ago(task.created_at),
+            completed_seconds_ago: task.completed_at.map(ago),
+            duration_secs: task
+                .completed_at
+                .unwrap_or(now)
+                .saturating_duration_since(task.created_at)
+                .as_secs_f64(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -63,7 +149,227 @@ pub struct QueuedTask {
 #[derive(Debug)]
 pub struct ActiveTask {
     pub(crate) inner: Task,
-    pub(crate) _permit: OwnedSemaphorePermit,
+    pub(crate) _permit: SlotPermit,
+}
+
+/// Permits of a started task, handed to the next task in line once released
+#[derive(Debug)]
+pub(crate) struct SlotPermit {
+    permit: Option<OwnedSemaphorePermit>,
+    lines: Arc<SlotLines>,
+    line: usize,
+}
+
+impl Drop for SlotPermit {
+    fn drop(&mut self) {
+        if let Some(permit) = self.permit.take() {
+            drop(permit);
+            self.lines.dispatch(self.line);
+        }
+    }
+}
+
+/// Task waiting in a slot line until its permits are free
+#[derive(Debug)]
+struct SlotWaiter {
+    priority: u64,
+    sequence: u64,
+    id: TaskId,
+    permits: u32,
+    grant: oneshot::Sender<SlotPermit>,
+}
+
+impl SlotWaiter {
+    /// The highest priority first and the oldest among equals, like the queue order
+    fn key(&self) -> (u64, Reverse<u64>) {
+        (self.priority, Reverse(self.sequence))
+    }
+}
+
+impl PartialEq for SlotWaiter {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for SlotWaiter {}
+
+impl PartialOrd for SlotWaiter {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for SlotWaiter {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+/// Tasks waiting for one semaphore, the next one to start on top
+#[derive(Debug)]
+struct SlotLine {
+    sem: Arc<Semaphore>,
+    waiters: BinaryHeap<SlotWaiter>,
+    /// A watcher waits on the semaphore for the permits of the front task
+    watching: bool,
+}
+
+/// Lines of the tasks waiting for a semaphore, keyed by the semaphore
+///
+/// Released permits go straight to the task at the front of the line, the
+/// others are not woken up.
+#[derive(Debug, Default)]
+pub(crate) struct SlotLines {
+    lines: Mutex<HashMap<usize, SlotLine>>,
+}
+
+impl SlotLines {
+    /// Returns the key of the line waiting for `sem`
+    pub(crate) fn key(sem: &Arc<Semaphore>) -> usize {
+        Arc::as_ptr(sem) as usize
+    }
+
+    /// Puts task `id` in line for `permits` permits of `sem`, they are sent once it is its turn
+    pub(crate) fn join(
+        self: &Arc<Self>,
+        id: &str,
+        (priority, sequence): (u64, u64),
+        sem: &Arc<Semaphore>,
+        permits: u32,
+    ) -> oneshot::Receiver<SlotPermit> {
+        let line = Self::key(sem);
+        let (grant, granted) = oneshot::channel();
+        self.lines
+            .lock()
+            .unwrap()
+            .entry(line)
+            .or_insert_with(|| SlotLine {
+                sem: Arc::clone(sem),
+                waiters: BinaryHeap::new(),
+                watching: false,
+            })
+            .waiters
+            .push(SlotWaiter {
+                priority,
+                sequence,
+                id: id.to_string(),
+                permits,
+                grant,
+            });
+        self.dispatch(line);
+        granted
+    }
+
+    /// Hands the free permits to the tasks at the front of the line
+    ///
+    /// Stops at the first task they are not enough for, so a heavy task is not
+    /// overtaken by the lighter ones behind it, and leaves a watcher waiting on
+    /// the semaphore for its permits.
+    pub(crate) fn dispatch(self: &Arc<Self>, line: usize) {
+        let mut lines = self.lines.lock().unwrap();
+        let Some(slot_line) = lines.get_mut(&line) else {
+            return;
+        };
+        while let Some(next) = slot_line.waiters.peek() {
+            // Tasks dropped while waiting gave up their place.
+            if next.grant.is_closed() {
+                slot_line.waiters.pop();
+                continue;
+            }
+            let Ok(permit) = Arc::clone(&slot_line.sem).try_acquire_many_owned(next.permits) else {
+                if !slot_line.watching {
+                    if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+                        slot_line.watching = true;
+                        runtime.spawn(Arc::clone(self).watch(
+                            line,
+                            Arc::clone(&slot_line.sem),
+                            next.permits,
+                        ));
+                    }
+                }
+                break;
+            };
+            if let Some(next) = slot_line.waiters.pop() {
+                self.grant(next, permit, line);
+            }
+        }
+        if slot_line.waiters.is_empty() && !slot_line.watching {
+            lines.remove(&line);
+        }
+    }
+
+    /// Waits for `permits` permits of `sem` and hands them to the front of the line
+    ///
+    /// The semaphore queues its waiters, so permits released by any holder,
+    /// not only by tasks of the line, reach the next task without polling.
+    async fn watch(self: Arc<Self>, line: usize, sem: Arc<Semaphore>, permits: u32) {
+        let permit = sem.acquire_many_owned(permits).await;
+        {
+            let mut lines = self.lines.lock().unwrap();
+            if let Some(slot_line) = lines.get_mut(&line) {
+                slot_line.watching = false;
+                while slot_line
+                    .waiters
+                    .peek()
+                    .is_some_and(|next| next.grant.is_closed())
+                {
+                    slot_line.waiters.pop();
+                }
+                // The front task changed while watching when it needs other permits.
+                if let Ok(permit) = permit {
+                    if slot_line
+                        .waiters
+                        .peek()
+                        .is_some_and(|next| next.permits == permits)
+                    {
+                        if let Some(next) = slot_line.waiters.pop() {
+                            self.grant(next, permit, line);
+                        }
+                    }
+                }
+            }
+        }
+        self.dispatch(line);
+    }
+
+    /// Sends `permit` to `waiter`, the permit goes back to the semaphore if it stopped waiting
+    fn grant(self: &Arc<Self>, waiter: SlotWaiter, permit: OwnedSemaphorePermit, line: usize) {
+        let slot = SlotPermit {
+            permit: Some(permit),
+            lines: Arc::clone(self),
+            line,
+        };
+        if let Err(mut slot) = waiter.grant.send(slot) {
+            // Released without dispatching, the caller goes on with the next task.
+            drop(slot.permit.take());
+        }
+    }
+
+    /// Hands permits added to `sem` to the tasks waiting for it
+    pub(crate) fn wake(self: &Arc<Self>, sem: &Arc<Semaphore>) {
+        self.dispatch(Self::key(sem));
+    }
+
+    /// Moves the waiting tasks up after their priority was raised
+    pub(crate) fn reprioritize(self: &Arc<Self>, priorities: &HashMap<TaskId, u64>) {
+        let keys: Vec<usize> = {
+            let mut lines = self.lines.lock().unwrap();
+            for slot_line in lines.values_mut() {
+                let mut waiters = std::mem::take(&mut slot_line.waiters).into_vec();
+                for waiter in &mut waiters {
+                    if let Some(priority) = priorities.get(&waiter.id) {
+                        waiter.priority = *priority;
+                    }
+                }
+                slot_line.waiters = BinaryHeap::from(waiters);
+            }
+            lines.keys().copied().collect()
+        };
+        for line in keys {
+            self.dispatch(line);
+        }
+    }
 }
 
 /// RAII Task handle - automatically completes the task when dropped.
@@ -97,6 +403,10 @@ impl Task {
         self.manager.update_task_status(&self.id, status);
     }
 
+    pub fn update_speed(&self, speed: Option<u64>) {
+        self.manager.update_task_speed(&self.id, speed);
+    }
+
     pub fn mark_started(&self) {
         self.manager.mark_task_started(&self.id);
     }
@@ -152,13 +462,38 @@ impl QueuedTask {
     ///
     /// Panics if the semaphore acquisition fails unexpectedly.
     pub async fn start(self, sem: Arc<Semaphore>) -> ActiveTask {
-        let permit = sem.acquire_owned().await.unwrap();
+        self.start_weighted(sem, 1).await
+    }
 
-        self.inner.manager.mark_task_started(&self.inner.id);
+    /// Transition to active state holding `permits` permits of the semaphore,
+    /// so a heavy task counts as several regular ones.
+    ///
+    /// Tasks waiting for the same semaphore get it in queue order, so a task
+    /// whose priority was raised overtakes the ones queued before it.
+    /// `permits` must not exceed the semaphore capacity or the task never starts.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the slot line drops the task without handing it the permits.
+    pub async fn start_weighted(self, sem: Arc<Semaphore>, permits: u32) -> ActiveTask {
+        let manager = &self.inner.manager;
+        let id = &self.inner.id;
+        let mut granted = manager.join_slot_line(id, &sem, permits);
+        let permit = if let Ok(permit) = granted.try_recv() {
+            permit
+        } else {
+            // Tells a full semaphore apart from a job that was not picked up yet.
+            manager.mark_task_waiting_for_slot(id);
+            granted
+                .await
+                .expect("slot line keeps a waiting task until it gets its permits")
+        };
+
+        manager.mark_task_started(id);
 
         ActiveTask {
-            inner: self.inner,
             _permit: permit,
+            inner: self.inner,
         }
     }
 }
@@ -173,6 +508,10 @@ impl ActiveTask {
         self.inner.update_status(status);
     }
 
+    pub fn update_speed(&self, speed: Option<u64>) {
+        self.inner.update_speed(speed);
+    }
+
     pub fn complete(self) {
         self.inner.complete();
     }
@@ -1,13 +1,16 @@
 pub mod app;
 pub mod controllers;
+pub mod download_log;
 pub mod gluetun;
 pub mod initializers;
 pub mod job_tracking;
 pub mod mailers;
 pub mod models;
 pub mod services;
+pub mod sse;
 pub mod tasks;
 pub mod views;
+pub mod webhook;
 pub mod workers;
 pub mod ws;
 pub mod ytdlp;
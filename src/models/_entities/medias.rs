@@ -14,6 +14,15 @@ pub struct Model {
     pub source_id: i32,
     pub metadata: Option<Json>,
     pub media_path: Option<String>,
+    pub video_id: Option<String>,
+    pub duration: Option<i64>,
+    pub file_size: Option<i64>,
+    pub downloaded_format: Option<String>,
+    pub downloaded_height: Option<i32>,
+    pub available_at: Option<DateTimeUtc>,
+    pub duplicate_of: Option<i32>,
+    pub skip_reason: Option<String>,
+    pub trashed_at: Option<DateTimeUtc>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
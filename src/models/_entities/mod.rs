@@ -3,5 +3,6 @@
 pub mod prelude;
 
 pub mod medias;
+pub mod settings;
 pub mod sources;
 pub mod users;
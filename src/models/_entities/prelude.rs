@@ -1,5 +1,6 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.1.14
 
 pub use super::medias::Entity as Medias;
+pub use super::settings::Entity as Settings;
 pub use super::sources::Entity as Sources;
 pub use super::users::Entity as Users;
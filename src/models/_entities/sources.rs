@@ -17,6 +17,39 @@ pub struct Model {
     pub sponsorblock: String,
     pub metadata: Option<Json>,
     pub last_scheduled_refresh: Option<DateTimeUtc>,
+    pub user_agent: Option<String>,
+    pub extractor_args: Option<String>,
+    pub download_archive: bool,
+    pub max_items: Option<i32>,
+    pub thumbnail: Option<String>,
+    pub download_weight: Option<i32>,
+    pub last_video_at: Option<DateTimeUtc>,
+    pub index_checkpoint: Option<DateTimeUtc>,
+    pub write_description: bool,
+    pub write_comments: bool,
+    pub webhook_url: Option<String>,
+    pub max_height: Option<i32>,
+    pub last_download_at: Option<DateTimeUtc>,
+    pub members_only: bool,
+    pub start_from_now: bool,
+    pub download_baseline: Option<DateTimeUtc>,
+    pub refresh_failures: i32,
+    pub refresh_backoff_until: Option<DateTimeUtc>,
+    pub write_subtitles: bool,
+    pub min_age_hours: Option<i32>,
+    pub media_root: Option<String>,
+    pub skip_embed_metadata: bool,
+    pub skip_embed_subs: bool,
+    pub max_duration_minutes: Option<i32>,
+    pub max_filesize_mb: Option<i32>,
+    pub concurrent_fragments: Option<i32>,
+    pub download_paused: bool,
+    pub last_refresh_duration_secs: Option<i32>,
+    pub aac_audio: bool,
+    pub last_refresh_error: Option<String>,
+    pub suggested_url: Option<String>,
+    pub age_limit: Option<i32>,
+    pub sponsorblock_api: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
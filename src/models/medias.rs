@@ -1,50 +1,233 @@
 use crate::ytdlp::VideoMetadata;
 use loco_rs::prelude::*;
+use sea_orm::Condition;
 use serde::{Deserialize, Serialize};
 
-use super::_entities::medias::{ActiveModel, Entity};
+use super::_entities::medias::{ActiveModel, Column, Entity, Model};
 pub type Medias = Entity;
 
+#[async_trait::async_trait]
 impl ActiveModelBehavior for ActiveModel {
     // extend activemodel below (keep comment for generators)
+    async fn before_save<C>(mut self, _db: &C, insert: bool) -> Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        if !insert && !self.updated_at.is_set() {
+            self.updated_at = sea_orm::ActiveValue::Set(chrono::Utc::now().into());
+        }
+        Ok(self)
+    }
 }
 
-impl super::_entities::medias::Model {
-    /// Returns the parsed metadata of the media
+impl Model {
+    /// Finds the media of a source by its extractor video id
+    ///
+    /// Rows indexed before `video_id` was tracked are matched by their exact url instead.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database query fails
+    pub async fn find_by_video_id(
+        db: &DatabaseConnection,
+        source_id: i32,
+        video_id: &str,
+        url: &str,
+    ) -> Result<Option<Self>, DbErr> {
+        Medias::find()
+            .filter(
+                Condition::all().add(Column::SourceId.eq(source_id)).add(
+                    Condition::any().add(Column::VideoId.eq(video_id)).add(
+                        Condition::all()
+                            .add(Column::VideoId.is_null())
+                            .add(Column::Url.eq(url)),
+                    ),
+                ),
+            )
+            .one(db)
+            .await
+    }
+
+    /// Finds the medias of `source` that still need a download but were not in `listed`
+    ///
+    /// These are medias published since `cutoff` that were never downloaded or
+    /// whose file is gone. An incremental refresh only lists the newest items,
+    /// so older failed downloads or deleted files are recovered through this.
+    /// Trashed, skipped, linked duplicate and backlog medias are left out.
+    ///
+    /// A media is indexed after it was published, so only rows created since
+    /// `cutoff` are loaded, and the files are only checked for those.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database query fails
+    pub async fn find_unlisted_downloads(
+        db: &DatabaseConnection,
+        source: &super::_entities::sources::Model,
+        listed: &std::collections::HashSet<i32>,
+        cutoff: i64,
+    ) -> Result<Vec<Self>, DbErr> {
+        // Naive like the stored `CURRENT_TIMESTAMP`, so the text comparison of sqlite holds.
+        let Some(cutoff_at) = chrono::DateTime::from_timestamp(cutoff, 0).map(|at| at.naive_utc())
+        else {
+            return Ok(Vec::new());
+        };
+        let in_window = |media: &Self| {
+            !listed.contains(&media.id)
+                && media.get_metadata().is_some_and(|metadata| {
+                    metadata.timestamp >= cutoff && !source.is_backlog(metadata.timestamp)
+                })
+        };
+        let candidates = || {
+            Medias::find()
+                .filter(Column::SourceId.eq(source.id))
+                .filter(Column::CreatedAt.gte(cutoff_at))
+                .filter(Column::TrashedAt.is_null())
+                .filter(Column::SkipReason.is_null())
+                .filter(Column::DuplicateOf.is_null())
+        };
+        let mut medias: Vec<Self> = candidates()
+            .filter(Column::MediaPath.is_null())
+            .all(db)
+            .await?
+            .into_iter()
+            .filter(in_window)
+            .collect();
+        let media_root = source.media_root();
+        medias.extend(
+            candidates()
+                .filter(Column::MediaPath.is_not_null())
+                .all(db)
+                .await?
+                .into_iter()
+                .filter(|media| in_window(media) && media.file_is_missing(&media_root)),
+        );
+        Ok(medias)
+    }
+
+    /// Clears the skip reason of every media of `source_id` so they are downloaded again
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database update fails
+    pub async fn clear_skip_reasons(db: &DatabaseConnection, source_id: i32) -> Result<u64, DbErr> {
+        let result = Medias::update_many()
+            .col_expr(
+                Column::SkipReason,
+                sea_orm::sea_query::Expr::value(Option::<String>::None),
+            )
+            .filter(Column::SourceId.eq(source_id))
+            .filter(Column::SkipReason.is_not_null())
+            .exec(db)
+            .await?;
+        Ok(result.rows_affected)
+    }
+
+    /// Returns whether the media was downloaded but its file is gone from `media_root`
     ///
-    /// # Panics
+    /// A trashed media keeps its path while its files are in the trash, so it
+    /// is never missing and never downloaded again.
+    #[must_use]
+    pub fn file_is_missing(&self, media_root: &std::path::Path) -> bool {
+        self.trashed_at.is_none()
+            && self
+                .media_path
+                .as_deref()
+                .is_some_and(|path| !media_root.join(path).exists())
+    }
+
+    /// Returns how long the download has to wait for a premiere or stream to become available
+    #[must_use]
+    pub fn deferred_for(&self, now: chrono::DateTime<chrono::Utc>) -> Option<std::time::Duration> {
+        self.available_at
+            .and_then(|at| (at - now).to_std().ok())
+            .filter(|delay| !delay.is_zero())
+    }
+
+    /// Returns the parsed metadata of the media
     ///
-    /// Panics if the metadata field is None or contains invalid JSON
+    /// Returns `None` if the metadata field is empty or contains invalid JSON
     #[must_use]
     pub fn get_metadata(&self) -> Option<MediaMetadata> {
-        serde_json::from_value(self.metadata.clone().unwrap()).ok()
+        self.metadata
+            .clone()
+            .and_then(|metadata| serde_json::from_value(metadata).ok())
+    }
+
+    /// Returns the existing files of the media under `media_root`
+    ///
+    /// `media_root` is the media root of the source the paths are relative to.
+    /// These are the main media file, the corresponding .info.json file and any
+    /// thumbnail, subtitle or description sidecar.
+    #[must_use]
+    pub fn media_files(&self, media_root: &std::path::Path) -> Vec<std::path::PathBuf> {
+        let Some(path) = &self.media_path else {
+            return Vec::new();
+        };
+        let base_path = media_root.join(path);
+        let info_path = base_path.with_extension("info.json");
+        let description_path = base_path.with_extension(crate::ytdlp::DESCRIPTION_EXTENSION);
+        let thumbnail_paths =
+            crate::ytdlp::THUMBNAIL_EXTENSIONS.map(|ext| base_path.with_extension(ext));
+        let subtitle_paths: Vec<_> = crate::ytdlp::subtitle_languages(&base_path)
+            .iter()
+            .filter_map(|lang| crate::ytdlp::subtitle_path(&base_path, lang))
+            .collect();
+
+        thumbnail_paths
+            .iter()
+            .chain(&subtitle_paths)
+            .chain([&description_path, &info_path, &base_path])
+            .filter(|file_path| file_path.exists())
+            .cloned()
+            .collect()
     }
 
     /// Removes media files from the filesystem
     ///
-    /// This removes both the main media file and the corresponding .info.json file.
-    /// Files that don't exist are silently ignored (not an error).
+    /// Removes everything [`Self::media_files`] finds under `media_root`. Files
+    /// that don't exist are silently ignored (not an error).
     ///
     /// # Errors
     ///
     /// Returns an error if file removal fails due to permission issues or other filesystem errors.
     #[allow(clippy::result_large_err)]
-    pub fn remove_media_files(&self) -> Result<()> {
-        if let Some(path) = &self.media_path {
-            let base_path = crate::ytdlp::media_directory().join(path);
-            let info_path = base_path.with_extension("info.json");
+    pub fn remove_media_files(&self, media_root: &std::path::Path) -> Result<()> {
+        for file_path in self.media_files(media_root) {
+            std::fs::remove_file(&file_path).map_err(|e| {
+                Error::string(&format!(
+                    "Failed to remove file {}: {}",
+                    file_path.display(),
+                    e
+                ))
+            })?;
+        }
+        Ok(())
+    }
 
-            for file_path in [&info_path, &base_path] {
-                if file_path.exists() {
-                    std::fs::remove_file(file_path).map_err(|e| {
-                        Error::string(&format!(
-                            "Failed to remove file {}: {}",
-                            file_path.display(),
-                            e
-                        ))
-                    })?;
-                }
+    /// Moves the files of the media from under `from` to the same place under `to`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a folder cannot be created or a file cannot be moved.
+    #[allow(clippy::result_large_err)]
+    pub fn move_media_files(&self, from: &std::path::Path, to: &std::path::Path) -> Result<()> {
+        for file_path in self.media_files(from) {
+            let Ok(relative) = file_path.strip_prefix(from) else {
+                continue;
+            };
+            let target = to.join(relative);
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent)?;
             }
+            std::fs::rename(&file_path, &target).map_err(|e| {
+                Error::string(&format!(
+                    "Failed to move file {} to {}: {}",
+                    file_path.display(),
+                    target.display(),
+                    e
+                ))
+            })?;
         }
         Ok(())
     }
@@ -58,6 +241,9 @@ pub struct MediaMetadata {
     pub extractor_key: String,
     pub original_url: String,
     pub timestamp: i64,
+    /// Minimum viewer age the video is rated for, unknown for medias indexed before it was stored
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub age_limit: Option<i32>,
 }
 
 impl From<VideoMetadata> for MediaMetadata {
@@ -69,6 +255,7 @@ impl From<VideoMetadata> for MediaMetadata {
             extractor_key: v.extractor_key,
             original_url: v.original_url,
             timestamp: v.timestamp,
+            age_limit: v.age_limit,
         }
     }
 }
@@ -1,4 +1,5 @@
 pub mod _entities;
 pub mod medias;
+pub mod settings;
 pub mod sources;
 pub mod users;
@@ -0,0 +1,227 @@
+use loco_rs::prelude::*;
+use sea_orm::sea_query::OnConflict;
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize};
+
+use super::_entities::settings::{ActiveModel, Column, Entity};
+pub type Settings = Entity;
+
+const CONCURRENCY: &str = "concurrency";
+const RATE_LIMIT: &str = "rate_limit";
+const SPONSORBLOCK_API: &str = "sponsorblock_api";
+const CONCURRENT_FRAGMENTS: &str = "concurrent_fragments";
+const TRASH_DAYS: &str = "trash_days";
+const DEDUPE: &str = "dedupe";
+const TRANSCODE: &str = "transcode";
+const NOTIFY_REMOVALS: &str = "notify_removals";
+
+/// Reads a flag sent as a boolean, or by the settings form as `"true"`, `"false"` or `""`
+fn flag<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<bool>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Flag {
+        Bool(bool),
+        Text(String),
+    }
+    match Option::<Flag>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(Flag::Bool(value)) => Ok(Some(value)),
+        Some(Flag::Text(text)) => match text.trim() {
+            "" => Ok(None),
+            "true" => Ok(Some(true)),
+            "false" => Ok(Some(false)),
+            other => Err(D::Error::custom(format!("'{other}' is not true or false"))),
+        },
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {
+    // extend activemodel below (keep comment for generators)
+}
+
+/// Global settings stored in the database, `None` falls back to the environment
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct GlobalSettings {
+    /// Number of concurrent downloads
+    #[serde(default)]
+    pub concurrency: Option<usize>,
+    /// yt-dlp download rate limit, e.g. `4M`
+    #[serde(default)]
+    pub rate_limit: Option<String>,
+    /// SponsorBlock API instance, e.g. a self-hosted mirror
+    #[serde(default)]
+    pub sponsorblock_api: Option<String>,
+    /// Fragments each download fetches at once
+    #[serde(default)]
+    pub concurrent_fragments: Option<u32>,
+    /// Days removed medias stay in the trash, 0 deletes them right away
+    #[serde(default)]
+    pub trash_days: Option<u32>,
+    /// Handling of videos shared between sources, `allow`, `skip` or `link`
+    #[serde(default)]
+    pub dedupe: Option<String>,
+    /// Transcoding of medias while streaming
+    #[serde(default, deserialize_with = "flag")]
+    pub transcode: Option<bool>,
+    /// Webhook events for medias removed by retention
+    #[serde(default, deserialize_with = "flag")]
+    pub notify_removals: Option<bool>,
+}
+
+impl GlobalSettings {
+    /// Checks the values and normalizes them for storage
+    ///
+    /// # Errors
+    ///
+    /// Returns a message describing the first invalid value
+    pub fn normalize(mut self) -> std::result::Result<Self, String> {
+        if let Some(concurrency) = self.concurrency {
+            if !(1..=crate::ytdlp::MAX_CONCURRENCY).contains(&concurrency) {
+                return Err(format!(
+                    "concurrency must be between 1 and {}",
+                    crate::ytdlp::MAX_CONCURRENCY
+                ));
+            }
+        }
+        if let Some(fragments) = self.concurrent_fragments {
+            if !(1..=crate::ytdlp::MAX_CONCURRENT_FRAGMENTS).contains(&fragments) {
+                return Err(format!(
+                    "concurrent_fragments must be between 1 and {}",
+                    crate::ytdlp::MAX_CONCURRENT_FRAGMENTS
+                ));
+            }
+        }
+        self.rate_limit = match self.rate_limit.as_deref().map(str::trim) {
+            None | Some("") => None,
+            Some(rate_limit) => {
+                Some(crate::ytdlp::parse_rate_limit(rate_limit).ok_or_else(|| {
+                    format!("rate_limit '{rate_limit}' is not a rate such as 500K or 4.2M")
+                })?)
+            }
+        };
+        self.sponsorblock_api = match self.sponsorblock_api.as_deref().map(str::trim) {
+            None | Some("") => None,
+            Some(url) => Some(
+                crate::ytdlp::parse_sponsorblock_api(url)
+                    .ok_or_else(|| format!("sponsorblock_api '{url}' is not an http(s) URL"))?,
+            ),
+        };
+        self.dedupe = match self.dedupe.as_deref().map(str::trim) {
+            None | Some("") => None,
+            Some(mode) => {
+                crate::services::dedupe::DedupeMode::parse(mode)
+                    .ok_or_else(|| format!("dedupe '{mode}' is not allow, skip or link"))?;
+                Some(mode.to_ascii_lowercase())
+            }
+        };
+        Ok(self)
+    }
+
+    /// Loads the stored settings, unknown or malformed rows are ignored
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database query fails
+    pub async fn load(db: &DatabaseConnection) -> Result<Self, DbErr> {
+        let values = Settings::find()
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|row| (row.key, row.value))
+            .collect::<serde_json::Map<_, _>>();
+        Ok(Self {
+            concurrency: values
+                .get(CONCURRENCY)
+                .and_then(|v| serde_json::from_value(v.clone()).ok()),
+            rate_limit: values
+                .get(RATE_LIMIT)
+                .and_then(|v| serde_json::from_value(v.clone()).ok()),
+            sponsorblock_api: values
+                .get(SPONSORBLOCK_API)
+                .and_then(|v| serde_json::from_value(v.clone()).ok()),
+            concurrent_fragments: values
+                .get(CONCURRENT_FRAGMENTS)
+                .and_then(|v| serde_json::from_value(v.clone()).ok()),
+            trash_days: values
+                .get(TRASH_DAYS)
+                .and_then(|v| serde_json::from_value(v.clone()).ok()),
+            dedupe: values
+                .get(DEDUPE)
+                .and_then(|v| serde_json::from_value(v.clone()).ok()),
+            transcode: values
+                .get(TRANSCODE)
+                .and_then(|v| serde_json::from_value(v.clone()).ok()),
+            notify_removals: values
+                .get(NOTIFY_REMOVALS)
+                .and_then(|v| serde_json::from_value(v.clone()).ok()),
+        })
+    }
+
+    /// Stores the settings, unset values are removed so the default applies
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database query fails
+    pub async fn store(&self, db: &DatabaseConnection) -> Result<(), DbErr> {
+        let values = [
+            (CONCURRENCY, self.concurrency.map(serde_json::Value::from)),
+            (
+                RATE_LIMIT,
+                self.rate_limit.clone().map(serde_json::Value::from),
+            ),
+            (
+                SPONSORBLOCK_API,
+                self.sponsorblock_api.clone().map(serde_json::Value::from),
+            ),
+            (
+                CONCURRENT_FRAGMENTS,
+                self.concurrent_fragments.map(serde_json::Value::from),
+            ),
+            (TRASH_DAYS, self.trash_days.map(serde_json::Value::from)),
+            (DEDUPE, self.dedupe.clone().map(serde_json::Value::from)),
+            (TRANSCODE, self.transcode.map(serde_json::Value::from)),
+            (
+                NOTIFY_REMOVALS,
+                self.notify_removals.map(serde_json::Value::from),
+            ),
+        ];
+        for (key, value) in values {
+            if let Some(value) = value {
+                let row = ActiveModel {
+                    key: Set(key.to_string()),
+                    value: Set(value),
+                    ..Default::default()
+                };
+                Settings::insert(row)
+                    .on_conflict(
+                        OnConflict::column(Column::Key)
+                            .update_columns([Column::Value, Column::UpdatedAt])
+                            .to_owned(),
+                    )
+                    .exec(db)
+                    .await?;
+            } else {
+                Settings::delete_many()
+                    .filter(Column::Key.eq(key))
+                    .exec(db)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Makes the settings effective for tasks started from now on
+    pub fn apply(&self) {
+        crate::ytdlp::set_concurrency_limit(self.concurrency);
+        crate::ytdlp::set_rate_limit(self.rate_limit.clone());
+        crate::ytdlp::set_sponsorblock_api(self.sponsorblock_api.clone());
+        crate::ytdlp::set_concurrent_fragments(self.concurrent_fragments);
+        crate::services::trash::set_trash_days(self.trash_days);
+        crate::services::dedupe::set_dedupe_mode(
+            self.dedupe
+                .as_deref()
+                .and_then(crate::services::dedupe::DedupeMode::parse),
+        );
+        crate::services::transcode::set_transcode_enabled(self.transcode);
+        crate::webhook::set_notify_removals(self.notify_removals);
+    }
+}
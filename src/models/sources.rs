@@ -1,23 +1,236 @@
-use super::_entities::sources::{ActiveModel, Entity};
-use sea_orm::entity::prelude::*;
+use super::_entities::{
+    medias,
+    sources::{ActiveModel, Entity},
+};
+use sea_orm::{
+    entity::prelude::*,
+    sea_query::{Alias, Func, SimpleExpr},
+    FromQueryResult, QuerySelect,
+};
 use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, path::PathBuf, sync::OnceLock};
 pub type Sources = Entity;
 
+static STALE_SOURCE_DAYS: OnceLock<i64> = OnceLock::new();
+static REFRESH_JITTER_MAX: OnceLock<chrono::Duration> = OnceLock::new();
+static FOLLOW_MOVED_CHANNELS: OnceLock<bool> = OnceLock::new();
+
+/// Backoff after the first failed refresh of a source
+pub const REFRESH_BACKOFF_BASE: chrono::Duration = chrono::Duration::minutes(15);
+/// Longest backoff, reached after repeated failed refreshes
+pub const REFRESH_BACKOFF_MAX: chrono::Duration = chrono::Duration::hours(24);
+
+/// Backoff after `failures` consecutive failed refreshes, doubling up to [`REFRESH_BACKOFF_MAX`]
+#[must_use]
+pub fn refresh_backoff(failures: i32) -> chrono::Duration {
+    let to_std = |duration: chrono::Duration| duration.to_std().unwrap_or_default();
+    let delay = crate::services::retry::backoff(
+        to_std(REFRESH_BACKOFF_BASE),
+        2,
+        to_std(REFRESH_BACKOFF_MAX),
+        u32::try_from(failures).unwrap_or(0),
+    );
+    chrono::Duration::from_std(delay).unwrap_or(REFRESH_BACKOFF_MAX)
+}
+
+/// Channel tabs a source URL can point at
+pub const CHANNEL_TAB_SUFFIXES: [&str; 4] = ["/videos", "/streams", "/shorts", "/playlists"];
+
+/// Returns the channel tab `path` ends with, e.g. `/videos`
+#[must_use]
+pub fn channel_tab_suffix(path: &str) -> Option<&'static str> {
+    CHANNEL_TAB_SUFFIXES
+        .into_iter()
+        .find(|suffix| path.ends_with(suffix))
+}
+
+/// Longest delay added to a refresh interval unless `LOCALTUBE_REFRESH_JITTER_MINUTES` says otherwise
+pub const DEFAULT_REFRESH_JITTER_MAX: chrono::Duration = chrono::Duration::minutes(30);
+
+/// Returns the longest delay added to a refresh interval
+///
+/// Configured in minutes through `LOCALTUBE_REFRESH_JITTER_MINUTES`, 0
+/// refreshes sources right at their frequency.
+#[must_use]
+pub fn refresh_jitter_max() -> chrono::Duration {
+    *REFRESH_JITTER_MAX.get_or_init(|| {
+        std::env::var("LOCALTUBE_REFRESH_JITTER_MINUTES")
+            .ok()
+            .and_then(|v| v.trim().parse::<u32>().ok())
+            .map_or(DEFAULT_REFRESH_JITTER_MAX, |minutes| {
+                chrono::Duration::minutes(i64::from(minutes))
+            })
+    })
+}
+
+/// Random delay in `[0, max]` added to a refresh interval
+///
+/// Drawn again on every evaluation, so sources sharing a frequency and added
+/// together drift apart instead of refreshing in the same sweep forever.
+#[must_use]
+pub fn refresh_jitter(max: chrono::Duration) -> chrono::Duration {
+    let max = max.num_seconds().max(0);
+    chrono::Duration::seconds(fastrand::i64(0..=max))
+}
+
+/// Returns whether `frequency_hours` plus `jitter` passed between `last` and `now`
+///
+/// The jitter only ever delays, a refresh is never due before its frequency.
+/// Always due when `last` is `None`.
+#[must_use]
+pub fn refresh_interval_elapsed(
+    last: Option<chrono::DateTime<chrono::Utc>>,
+    frequency_hours: i32,
+    jitter: chrono::Duration,
+    now: chrono::DateTime<chrono::Utc>,
+) -> bool {
+    let interval = chrono::Duration::hours(i64::from(frequency_hours)) + jitter.abs();
+    last.is_none_or(|last| now - last >= interval)
+}
+
+/// Returns after how many days without a new video a source counts as stale
+///
+/// Configured through `LOCALTUBE_STALE_SOURCE_DAYS`, 0 disables the check.
+#[must_use]
+pub fn stale_source_days() -> i64 {
+    *STALE_SOURCE_DAYS.get_or_init(|| {
+        std::env::var("LOCALTUBE_STALE_SOURCE_DAYS")
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .unwrap_or(30)
+    })
+}
+
+/// Returns whether a source whose channel moved switches to the suggested URL by itself
+///
+/// Off unless `LOCALTUBE_FOLLOW_MOVED_CHANNELS=true`, the suggestion is then
+/// only shown on the source page.
+#[must_use]
+pub fn follow_moved_channels() -> bool {
+    *FOLLOW_MOVED_CHANNELS.get_or_init(|| {
+        std::env::var("LOCALTUBE_FOLLOW_MOVED_CHANNELS")
+            .is_ok_and(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes"))
+    })
+}
+
+#[async_trait::async_trait]
 impl ActiveModelBehavior for ActiveModel {
     // extend activemodel below (keep comment for generators)
+    async fn before_save<C>(mut self, _db: &C, insert: bool) -> Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        if !insert && !self.updated_at.is_set() {
+            self.updated_at = sea_orm::ActiveValue::Set(chrono::Utc::now().into());
+        }
+        Ok(self)
+    }
+}
+
+/// Returns an unsaved source with the settings of a newly added one
+///
+/// Shared by the tests that build views and yt-dlp arguments without a
+/// database, they override the fields they exercise.
+#[must_use]
+pub fn sample_source() -> super::_entities::sources::Model {
+    let timestamp = chrono::DateTime::parse_from_rfc3339("2024-01-01T00:00:00+00:00")
+        .expect("sample timestamp should parse");
+    super::_entities::sources::Model {
+        created_at: timestamp,
+        updated_at: timestamp,
+        id: 1,
+        url: "https://example.com/channel".to_string(),
+        fetch_last_days: 7,
+        last_refreshed_at: None,
+        refresh_frequency: 24,
+        sponsorblock: String::new(),
+        metadata: None,
+        last_scheduled_refresh: None,
+        user_agent: None,
+        extractor_args: None,
+        download_archive: false,
+        max_items: None,
+        thumbnail: None,
+        download_weight: None,
+        last_video_at: None,
+        index_checkpoint: None,
+        write_description: false,
+        write_comments: false,
+        webhook_url: None,
+        max_height: None,
+        last_download_at: None,
+        members_only: false,
+        start_from_now: false,
+        download_baseline: None,
+        refresh_failures: 0,
+        refresh_backoff_until: None,
+        write_subtitles: false,
+        min_age_hours: None,
+        media_root: None,
+        skip_embed_metadata: false,
+        skip_embed_subs: false,
+        max_duration_minutes: None,
+        max_filesize_mb: None,
+        concurrent_fragments: None,
+        download_paused: false,
+        last_refresh_duration_secs: None,
+        aac_audio: false,
+        last_refresh_error: None,
+        suggested_url: None,
+        age_limit: None,
+        sponsorblock_api: None,
+    }
+}
+
+/// Returns the media root of `source`, the global media directory for medias without a source
+#[must_use]
+pub fn media_root_of(source: Option<&super::_entities::sources::Model>) -> PathBuf {
+    source.map_or_else(
+        || crate::ytdlp::media_directory().clone(),
+        super::_entities::sources::Model::media_root,
+    )
 }
 
 impl super::_entities::sources::Model {
-    /// Returns the parsed metadata of the source
+    /// Returns the directory the source folder is created in
     ///
-    /// # Panics
+    /// The source's own `media_root` when set, the global media directory
+    /// otherwise. Stored `media_path`s are relative to it.
+    #[must_use]
+    pub fn media_root(&self) -> PathBuf {
+        self.media_root
+            .as_deref()
+            .map_or_else(|| crate::ytdlp::media_directory().clone(), PathBuf::from)
+    }
+
+    /// Returns the parsed metadata of the source, `None` before the first refresh
     ///
-    /// Panics if the metadata field is None or contains invalid JSON
+    /// Metadata that cannot be parsed is treated as missing, the next refresh
+    /// writes it again.
     #[must_use]
     pub fn get_metadata(&self) -> Option<SourceMetadata> {
-        self.metadata
-            .clone()
-            .and_then(|m| serde_json::from_value(m).ok())
+        let metadata = self.metadata.clone()?;
+        serde_json::from_value(metadata)
+            .inspect_err(|err| {
+                tracing::warn!(source_id = self.id, error = %err, "Unreadable source metadata");
+            })
+            .ok()
+    }
+
+    /// Returns how thumbnails of this source are stored
+    #[must_use]
+    pub fn thumbnail_mode(&self) -> crate::ytdlp::ThumbnailMode {
+        crate::ytdlp::ThumbnailMode::from_setting(self.thumbnail.as_deref())
+    }
+
+    /// Returns what gets embedded into the downloads of this source
+    #[must_use]
+    pub fn embed_options(&self) -> crate::ytdlp::EmbedOptions {
+        crate::ytdlp::EmbedOptions {
+            metadata: !self.skip_embed_metadata,
+            subtitles: !self.skip_embed_subs,
+            thumbnail: self.thumbnail_mode(),
+        }
     }
 
     /// Returns the configured `SponsorBlock` categories for this source
@@ -26,10 +239,445 @@ impl super::_entities::sources::Model {
         SponsorBlockCategories::deserialize(&self.sponsorblock)
     }
 
-    /// Returns the list of enabled `SponsorBlock` categories
+    /// Returns whether the source has gone `threshold_days` without a new video at `now`
+    ///
+    /// A source that never produced a video counts from its creation, but
+    /// only once it was refreshed at least once.
+    #[must_use]
+    pub fn is_stale_at(&self, threshold_days: i64, now: chrono::DateTime<chrono::Utc>) -> bool {
+        if threshold_days <= 0 {
+            return false;
+        }
+        let newest = match self.last_video_at {
+            Some(last_video_at) => last_video_at,
+            None if self.last_refreshed_at.is_some() => self.created_at.to_utc(),
+            None => return false,
+        };
+        now - newest > chrono::Duration::days(threshold_days)
+    }
+
+    /// Returns whether the source is stale now, see [`Self::is_stale_at`]
+    #[must_use]
+    pub fn is_stale(&self) -> bool {
+        self.is_stale_at(stale_source_days(), chrono::Utc::now())
+    }
+
+    /// Returns the publish timestamp up to which videos count as backlog, `None` outside start-from-now mode
+    ///
+    /// Until the first refresh stored a baseline the creation of the source is the cutoff.
+    #[must_use]
+    pub fn download_cutoff(&self) -> Option<i64> {
+        if !self.start_from_now {
+            return None;
+        }
+        Some(
+            self.download_baseline
+                .unwrap_or_else(|| self.created_at.to_utc())
+                .timestamp(),
+        )
+    }
+
+    /// Returns whether a video published at `timestamp` is indexed without being downloaded
+    #[must_use]
+    pub fn is_backlog(&self, timestamp: i64) -> bool {
+        self.download_cutoff()
+            .is_some_and(|cutoff| timestamp <= cutoff)
+    }
+
+    /// Stores the newest video of the first start-from-now refresh as the download baseline
+    ///
+    /// The baseline is capped at the creation of the source, so videos published
+    /// after it was added stay newer than the baseline. Does nothing outside
+    /// start-from-now mode or once a baseline is stored.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database update fails
+    pub async fn record_download_baseline(
+        &self,
+        db: &DatabaseConnection,
+        newest_seen: Option<i64>,
+    ) -> Result<Self, DbErr> {
+        if !self.start_from_now || self.download_baseline.is_some() {
+            return Ok(self.clone());
+        }
+        let created_at = self.created_at.to_utc();
+        let baseline = newest_seen
+            .and_then(|timestamp| chrono::DateTime::from_timestamp(timestamp, 0))
+            .map_or(created_at, |newest| newest.min(created_at));
+        ActiveModel {
+            id: sea_orm::Set(self.id),
+            download_baseline: sea_orm::Set(Some(baseline)),
+            ..Default::default()
+        }
+        .update(db)
+        .await
+    }
+
+    /// Returns how long a video published at `timestamp` still has to age before it is downloaded
+    ///
+    /// `None` when the source has no minimum age, the video is old enough or
+    /// its publish time is unknown.
+    #[must_use]
+    pub fn min_age_remaining(
+        &self,
+        timestamp: i64,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Option<std::time::Duration> {
+        let hours = self.min_age_hours.filter(|hours| *hours > 0)?;
+        let published = chrono::DateTime::from_timestamp(timestamp, 0).filter(|_| timestamp > 0)?;
+        (published + chrono::Duration::hours(hours.into()) - now)
+            .to_std()
+            .ok()
+            .filter(|remaining| !remaining.is_zero())
+    }
+
+    /// Returns why a video lasting `duration` seconds is not downloaded, `None` when it fits
+    ///
+    /// Videos of unknown length, e.g. upcoming streams, are never skipped.
+    #[must_use]
+    pub fn max_duration_skip_reason(&self, duration: u64) -> Option<String> {
+        let minutes = self.max_duration_minutes.filter(|minutes| *minutes > 0)?;
+        let limit = u64::try_from(minutes).ok()? * 60;
+        (duration > limit).then(|| {
+            format!(
+                "Longer than the source maximum of {minutes} minutes ({} minutes)",
+                duration.div_ceil(60)
+            )
+        })
+    }
+
+    /// Returns why a video rated for `age_limit` years is not downloaded, `None` when it is allowed
+    ///
+    /// Videos without a known rating are left to yt-dlp and its `--age-limit`.
+    #[must_use]
+    pub fn age_limit_skip_reason(&self, age_limit: Option<i32>) -> Option<String> {
+        let limit = self.age_limit?;
+        let rated = age_limit.filter(|rated| *rated > limit)?;
+        Some(format!(
+            "{}: rated {rated}+, above the source age limit of {limit}",
+            crate::workers::errors::AGE_RESTRICTED
+        ))
+    }
+
+    /// Returns whether the periodic sweep schedules a refresh of the source at `now`
+    ///
+    /// Due without metadata or once `refresh_frequency` plus [`refresh_jitter`]
+    /// passed since the last refresh, unless a refresh was already scheduled
+    /// within that interval or a failure backoff is holding it off.
+    #[must_use]
+    pub fn is_refresh_due_at(&self, now: chrono::DateTime<chrono::Utc>) -> bool {
+        let jitter = refresh_jitter(refresh_jitter_max());
+        let elapsed = |last| refresh_interval_elapsed(last, self.refresh_frequency, jitter, now);
+        (self.get_metadata().is_none() || elapsed(self.last_refreshed_at))
+            && elapsed(self.last_scheduled_refresh)
+            && !self.is_refresh_backed_off_at(now)
+    }
+
+    /// Returns whether a failed refresh still holds off the next scheduled one at `now`
+    #[must_use]
+    pub fn is_refresh_backed_off_at(&self, now: chrono::DateTime<chrono::Utc>) -> bool {
+        self.refresh_backoff_until.is_some_and(|until| until > now)
+    }
+
+    /// Counts a refresh failed with `error` and pushes the next scheduled one out by [`refresh_backoff`]
+    ///
+    /// The error is kept for the source page, together with the URL the
+    /// channel moved to when [`Self::moved_channel_suggestion`] finds one.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database update fails
+    pub async fn record_refresh_failure(
+        &self,
+        db: &DatabaseConnection,
+        now: chrono::DateTime<chrono::Utc>,
+        error: &str,
+    ) -> Result<Self, DbErr> {
+        let failures = self.refresh_failures.saturating_add(1);
+        ActiveModel {
+            id: sea_orm::Set(self.id),
+            refresh_failures: sea_orm::Set(failures),
+            refresh_backoff_until: sea_orm::Set(Some(now + refresh_backoff(failures))),
+            last_refresh_error: sea_orm::Set(Some(error.to_string())),
+            suggested_url: sea_orm::Set(self.moved_channel_suggestion(error)),
+            ..Default::default()
+        }
+        .update(db)
+        .await
+    }
+
+    /// Returns the URL to switch to when `error` says the channel of the source is gone
+    ///
+    /// Handles can change, the canonical channel URL stored by the last
+    /// successful refresh keeps pointing at the same channel. A tab the source
+    /// URL points at, e.g. `/videos`, is kept. `None` when the source already
+    /// uses that URL.
+    #[must_use]
+    pub fn moved_channel_suggestion(&self, error: &str) -> Option<String> {
+        if !crate::workers::errors::is_channel_gone(error) {
+            return None;
+        }
+        let normalize = |url: &str| url.trim().trim_end_matches('/').to_ascii_lowercase();
+        let channel_url = self.get_metadata()?.channel_url?;
+        let channel_url = channel_url.trim().trim_end_matches('/');
+        let source_path = self.url.split(['?', '#']).next().unwrap_or(&self.url);
+        let suggested = match channel_tab_suffix(source_path.trim_end_matches('/')) {
+            Some(tab) if channel_tab_suffix(channel_url).is_none() => {
+                format!("{channel_url}{tab}")
+            }
+            _ => channel_url.to_string(),
+        };
+        Some(suggested).filter(|url| normalize(url) != normalize(&self.url))
+    }
+
+    /// Switches the source to its suggested URL, `None` when there is no suggestion
+    ///
+    /// The source keeps its id, so indexed and downloaded medias stay attached.
+    /// The list tab and counts of the old URL are forgotten like on any URL
+    /// change, and the failure backoff is cleared.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database update fails
+    pub async fn adopt_suggested_url(
+        &self,
+        db: &DatabaseConnection,
+    ) -> Result<Option<Self>, DbErr> {
+        let Some(url) = self.suggested_url.clone() else {
+            return Ok(None);
+        };
+        let metadata = self.get_metadata().map(|mut metadata| {
+            metadata.list_tab = None;
+            metadata.list_tabs = None;
+            metadata.items = 0;
+            metadata.list_order = None;
+            metadata.list_count = None;
+            metadata
+        });
+        let metadata = metadata
+            .map(serde_json::to_value)
+            .transpose()
+            .map_err(|err| DbErr::Custom(err.to_string()))?;
+        ActiveModel {
+            id: sea_orm::Set(self.id),
+            url: sea_orm::Set(url),
+            metadata: sea_orm::Set(metadata.or_else(|| self.metadata.clone())),
+            suggested_url: sea_orm::Set(None),
+            last_refresh_error: sea_orm::Set(None),
+            refresh_failures: sea_orm::Set(0),
+            refresh_backoff_until: sea_orm::Set(None),
+            ..Default::default()
+        }
+        .update(db)
+        .await
+        .map(Some)
+    }
+
+    /// Records a finished refresh that took `duration` and clears its failure backoff
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database update fails
+    pub async fn record_refresh_success(
+        &self,
+        db: &DatabaseConnection,
+        now: chrono::DateTime<chrono::Utc>,
+        duration: std::time::Duration,
+    ) -> Result<Self, DbErr> {
+        ActiveModel {
+            id: sea_orm::Set(self.id),
+            last_refreshed_at: sea_orm::Set(Some(now)),
+            last_refresh_duration_secs: sea_orm::Set(Some(
+                i32::try_from(duration.as_secs()).unwrap_or(i32::MAX),
+            )),
+            refresh_failures: sea_orm::Set(0),
+            refresh_backoff_until: sea_orm::Set(None),
+            last_refresh_error: sea_orm::Set(None),
+            suggested_url: sea_orm::Set(None),
+            ..Default::default()
+        }
+        .update(db)
+        .await
+    }
+
+    /// Returns the date the next refresh lists from, `None` means a full reindex
+    ///
+    /// Incremental refreshes only list items from the day of the checkpoint on;
+    /// the overlap is harmless as items are matched by video id.
+    #[must_use]
+    pub fn incremental_date_after(&self, full_reindex: bool) -> Option<chrono::NaiveDate> {
+        if full_reindex {
+            return None;
+        }
+        self.index_checkpoint
+            .map(|checkpoint| checkpoint.date_naive())
+    }
+
+    /// Returns whether the settings changed from `previous` may list items older than the checkpoint
+    ///
+    /// That is the url, the tab, the date window or a filter of the listed items.
+    #[must_use]
+    pub fn needs_full_reindex(&self, previous: &Self) -> bool {
+        let list_tab = |source: &Self| source.get_metadata().and_then(|m| m.list_tab);
+        self.url != previous.url
+            || list_tab(self) != list_tab(previous)
+            || self.fetch_last_days != previous.fetch_last_days
+            || self.max_items != previous.max_items
+            || self.start_from_now != previous.start_from_now
+            || self.members_only != previous.members_only
+            || self.extractor_args != previous.extractor_args
+            || self.max_duration_minutes != previous.max_duration_minutes
+            || self.max_filesize_mb != previous.max_filesize_mb
+            || self.age_limit != previous.age_limit
+    }
+
+    /// Returns whether the limits medias of the source are skipped by changed from `previous`
+    #[must_use]
+    pub fn download_limits_changed(&self, previous: &Self) -> bool {
+        self.max_duration_minutes != previous.max_duration_minutes
+            || self.max_filesize_mb != previous.max_filesize_mb
+            || self.age_limit != previous.age_limit
+    }
+
+    /// Forgets how far the source was indexed so the next refresh lists every item
+    ///
+    /// Media rows are kept, so downloaded files stay attached to their items.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database update fails
+    pub async fn reset_index(&self, db: &DatabaseConnection) -> Result<Self, DbErr> {
+        ActiveModel {
+            id: sea_orm::Set(self.id),
+            index_checkpoint: sea_orm::Set(None),
+            last_refreshed_at: sea_orm::Set(None),
+            ..Default::default()
+        }
+        .update(db)
+        .await
+    }
+
+    /// Stores the newest video timestamp of the source
+    ///
+    /// Takes the newest of `seen` (unix seconds reported by a refresh), the
+    /// stored value and the indexed media, so it survives retention removing
+    /// the media it was computed from.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database query or update fails
+    pub async fn refresh_last_video_at(
+        &self,
+        db: &DatabaseConnection,
+        seen: Option<i64>,
+    ) -> Result<Self, DbErr> {
+        let indexed = medias::Entity::find()
+            .filter(medias::Column::SourceId.eq(self.id))
+            .all(db)
+            .await?
+            .iter()
+            .filter_map(|media| media.get_metadata().map(|m| m.timestamp))
+            .max();
+        let newest = [seen, indexed]
+            .into_iter()
+            .flatten()
+            .filter_map(|timestamp| chrono::DateTime::from_timestamp(timestamp, 0))
+            .chain(self.last_video_at)
+            .max();
+        if newest == self.last_video_at {
+            return Ok(self.clone());
+        }
+        ActiveModel {
+            id: sea_orm::Set(self.id),
+            last_video_at: sea_orm::Set(newest),
+            ..Default::default()
+        }
+        .update(db)
+        .await
+    }
+
+    /// Records that a media of this source finished downloading `at`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database update fails
+    pub async fn record_download(
+        &self,
+        db: &DatabaseConnection,
+        at: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Self, DbErr> {
+        ActiveModel {
+            id: sea_orm::Set(self.id),
+            last_download_at: sea_orm::Set(Some(at)),
+            ..Default::default()
+        }
+        .update(db)
+        .await
+    }
+
+    /// Aggregates the media counts, watch time and disk usage of this source
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database query fails
+    pub async fn stats(&self, db: &DatabaseConnection) -> Result<SourceStats, DbErr> {
+        let downloaded = |column: medias::Column| -> SimpleExpr {
+            Expr::case(
+                Expr::col(medias::Column::MediaPath).is_not_null(),
+                Expr::col(column),
+            )
+            .finally(0)
+            .into()
+        };
+        let stats = medias::Entity::find()
+            .select_only()
+            .column_as(bigint(medias::Column::Id.count()), "indexed")
+            .column_as(bigint(medias::Column::MediaPath.count()), "downloaded")
+            .column_as(
+                bigint(sum(downloaded(medias::Column::Duration))),
+                "downloaded_duration",
+            )
+            .column_as(
+                bigint(sum(downloaded(medias::Column::FileSize))),
+                "disk_usage",
+            )
+            .column_as(
+                bigint(sum(Expr::case(
+                    Expr::col(medias::Column::SkipReason)
+                        .like(format!("{}%", crate::workers::errors::AGE_RESTRICTED)),
+                    1,
+                )
+                .finally(0))),
+                "age_restricted",
+            )
+            .filter(medias::Column::SourceId.eq(self.id))
+            .filter(medias::Column::TrashedAt.is_null())
+            .into_model::<SourceStats>()
+            .one(db)
+            .await?;
+        Ok(stats.unwrap_or_default())
+    }
+
+    /// Returns whether the source was indexed yet, given its `indexed` media count
+    #[must_use]
+    pub fn index_state(&self, indexed: i64) -> IndexState {
+        if self.last_refreshed_at.is_none() {
+            IndexState::Pending
+        } else if indexed == 0 {
+            IndexState::Empty
+        } else {
+            IndexState::Indexed
+        }
+    }
+
+    /// Returns the list of removed `SponsorBlock` categories
     #[must_use]
     pub fn get_sponsorblock_list(&self) -> Vec<&str> {
         self.sponsorblock
+            .split(SponsorBlockCategories::MARK_SEPARATOR)
+            .next()
+            .unwrap_or_default()
             .split(',')
             .filter(|s| !s.is_empty())
             .collect()
@@ -38,21 +686,158 @@ impl super::_entities::sources::Model {
 
 pub enum Relation {}
 
+fn sum(expr: impl Into<SimpleExpr>) -> SimpleExpr {
+    Func::coalesce([Func::sum(expr).into(), Expr::val(0).into()]).into()
+}
+
+// Postgres widens `SUM` of a bigint to numeric, cast so both backends decode as i64.
+fn bigint(expr: impl Into<SimpleExpr>) -> SimpleExpr {
+    Func::cast_as(expr, Alias::new("bigint")).into()
+}
+
+/// Counts the indexed medias of every source that has any
+///
+/// # Errors
+///
+/// Returns an error if the database query fails
+pub async fn indexed_media_counts(db: &DatabaseConnection) -> Result<HashMap<i32, i64>, DbErr> {
+    let counts: Vec<(i32, i64)> = medias::Entity::find()
+        .select_only()
+        .column(medias::Column::SourceId)
+        .column_as(bigint(medias::Column::Id.count()), "indexed")
+        .group_by(medias::Column::SourceId)
+        .into_tuple()
+        .all(db)
+        .await?;
+    Ok(counts.into_iter().collect())
+}
+
+/// Whether a source was indexed yet, and whether that found anything
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IndexState {
+    /// Never refreshed, the first index is still pending
+    Pending,
+    /// Refreshed without indexing any media
+    Empty,
+    Indexed,
+}
+
+/// Aggregated media numbers of a source
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, FromQueryResult)]
+pub struct SourceStats {
+    /// Media indexed for the source
+    pub indexed: i64,
+    /// Media with a downloaded file
+    pub downloaded: i64,
+    /// Total duration of the downloaded media in seconds
+    pub downloaded_duration: i64,
+    /// Size of the downloaded files in bytes
+    pub disk_usage: i64,
+    /// Media skipped because of their age restriction
+    pub age_restricted: i64,
+}
+
+/// Metadata a refresh stores on the source
+///
+/// Stored as JSON, so blobs written by older versions must keep parsing: every
+/// field but the uploader has a default, and optional values of an unknown
+/// shape read as `None` rather than failing the whole blob.
 #[derive(Debug, PartialEq, Eq, Clone, Deserialize, Serialize)]
 pub struct SourceMetadata {
     pub uploader: String,
+    #[serde(default)]
     pub items: u64,
+    #[serde(default = "unknown_provider")]
     pub source_provider: String,
-    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(
+        default,
+        deserialize_with = "lenient",
+        skip_serializing_if = "Option::is_none"
+    )]
     pub list_kind: Option<crate::ytdlp::SourceListKind>,
-    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(
+        default,
+        deserialize_with = "lenient",
+        skip_serializing_if = "Option::is_none"
+    )]
     pub list_count: Option<u64>,
-    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(
+        default,
+        deserialize_with = "lenient",
+        skip_serializing_if = "Option::is_none"
+    )]
     pub list_order: Option<crate::ytdlp::SourceListOrder>,
-    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(
+        default,
+        deserialize_with = "lenient",
+        skip_serializing_if = "Option::is_none"
+    )]
     pub list_tab: Option<String>,
-    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(
+        default,
+        deserialize_with = "lenient",
+        skip_serializing_if = "Option::is_none"
+    )]
     pub list_tabs: Option<Vec<crate::ytdlp::SourceListTabOption>>,
+    /// Canonical URL of the channel, suggested when the source URL stops working
+    #[serde(
+        default,
+        deserialize_with = "lenient",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub channel_url: Option<String>,
+}
+
+fn unknown_provider() -> String {
+    "unknown".to_string()
+}
+
+/// Reads an optional value, `None` when it does not have the expected shape
+fn lenient<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: serde::de::DeserializeOwned,
+{
+    let value = Option::<serde_json::Value>::deserialize(deserializer)?;
+    Ok(value.and_then(|value| serde_json::from_value(value).ok()))
+}
+
+/// Rewrites the stored metadata of every source in its current shape
+///
+/// Fills in defaults and drops values older versions wrote that no longer
+/// parse, so the stored JSON matches what [`Model::get_metadata`] reads.
+/// Metadata that cannot be read at all is left for the next refresh. Returns
+/// the number of sources that were rewritten.
+///
+/// # Errors
+///
+/// Returns an error if the sources cannot be loaded or updated
+pub async fn normalize_stored_metadata(db: &DatabaseConnection) -> Result<usize, DbErr> {
+    let mut rewritten = 0;
+    for source in Entity::find()
+        .filter(super::_entities::sources::Column::Metadata.is_not_null())
+        .all(db)
+        .await?
+    {
+        let (Some(stored), Some(metadata)) = (&source.metadata, source.get_metadata()) else {
+            continue;
+        };
+        let Ok(normalized) = serde_json::to_value(&metadata) else {
+            continue;
+        };
+        if &normalized != stored {
+            ActiveModel {
+                id: sea_orm::Set(source.id),
+                metadata: sea_orm::Set(Some(normalized)),
+                ..Default::default()
+            }
+            .update(db)
+            .await?;
+            rewritten += 1;
+        }
+    }
+    Ok(rewritten)
 }
 
 impl From<crate::ytdlp::VideoMetadata> for SourceMetadata {
@@ -66,14 +851,15 @@ impl From<crate::ytdlp::VideoMetadata> for SourceMetadata {
             list_order: None,
             list_tab: None,
             list_tabs: None,
+            channel_url: None,
         }
     }
 }
 
 // To fix the "too many bools" warning, we'll add allow attribute since this matches the SponsorBlock API
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 #[allow(clippy::struct_excessive_bools)]
-pub struct SponsorBlockCategories {
+pub struct SponsorBlockSet {
     pub sponsor: bool,
     pub intro: bool,
     pub outro: bool,
@@ -84,9 +870,9 @@ pub struct SponsorBlockCategories {
     pub music_offtopic: bool,
 }
 
-impl SponsorBlockCategories {
+impl SponsorBlockSet {
     /// Comma delimited string, containing list of categories
-    /// that are enabled for this source
+    /// that are in this set
     /// example: `sponsor,selfpromo`
     #[must_use]
     pub fn serialize(&self) -> String {
@@ -118,7 +904,7 @@ impl SponsorBlockCategories {
         categories.join(",")
     }
 
-    /// Creates new `SponsorBlockCategories` from a string representation
+    /// Creates new `SponsorBlockSet` from a comma delimited list, unknown categories are ignored
     #[must_use]
     pub fn deserialize(categories: &str) -> Self {
         let mut sponsor = false;
@@ -131,7 +917,7 @@ impl SponsorBlockCategories {
         let mut music_offtopic = false;
 
         for category in categories.split(',') {
-            match category {
+            match category.trim() {
                 "sponsor" => sponsor = true,
                 "intro" => intro = true,
                 "outro" => outro = true,
@@ -155,4 +941,64 @@ impl SponsorBlockCategories {
             music_offtopic,
         }
     }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// `SponsorBlock` categories cut out of the downloads and the ones only marked as chapters
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SponsorBlockCategories {
+    /// Passed to `--sponsorblock-remove`
+    pub remove: SponsorBlockSet,
+    /// Passed to `--sponsorblock-mark`
+    pub mark: SponsorBlockSet,
+}
+
+impl SponsorBlockCategories {
+    /// Separates the removed categories from the marked ones
+    pub const MARK_SEPARATOR: char = ';';
+
+    /// The removed categories, followed by the marked ones after a `;`
+    /// example: `sponsor,selfpromo;intro,outro`
+    ///
+    /// Without marked categories this is the plain comma list sources always
+    /// stored.
+    #[must_use]
+    pub fn serialize(&self) -> String {
+        let remove = self.remove.serialize();
+        if self.mark.is_empty() {
+            remove
+        } else {
+            format!("{remove}{}{}", Self::MARK_SEPARATOR, self.mark.serialize())
+        }
+    }
+
+    /// Creates new `SponsorBlockCategories` from a string representation
+    ///
+    /// A list without a `;` only removes, as stored before categories could be
+    /// marked. A category in both sets is removed.
+    #[must_use]
+    pub fn deserialize(categories: &str) -> Self {
+        let (remove, mark) = categories
+            .split_once(Self::MARK_SEPARATOR)
+            .unwrap_or((categories, ""));
+        let remove = SponsorBlockSet::deserialize(remove);
+        let mark = SponsorBlockSet::deserialize(mark);
+        Self {
+            remove,
+            mark: SponsorBlockSet {
+                sponsor: mark.sponsor && !remove.sponsor,
+                intro: mark.intro && !remove.intro,
+                outro: mark.outro && !remove.outro,
+                selfpromo: mark.selfpromo && !remove.selfpromo,
+                preview: mark.preview && !remove.preview,
+                filler: mark.filler && !remove.filler,
+                interaction: mark.interaction && !remove.interaction,
+                music_offtopic: mark.music_offtopic && !remove.music_offtopic,
+            },
+        }
+    }
 }
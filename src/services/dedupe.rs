@@ -0,0 +1,184 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{Mutex, OnceLock, PoisonError},
+};
+
+use loco_rs::prelude::*;
+use tracing::warn;
+
+use crate::{
+    models::_entities::{medias, sources},
+    ytdlp,
+};
+
+static DEFAULT_DEDUPE_MODE: OnceLock<DedupeMode> = OnceLock::new();
+static DEDUPE_MODE: Mutex<Option<DedupeMode>> = Mutex::new(None);
+
+/// What happens to a video already downloaded under another source
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DedupeMode {
+    /// Every source downloads its own copy
+    #[default]
+    Allow,
+    /// The video is not downloaded again, only the other copy exists
+    Skip,
+    /// The other copy is linked into the folder of the source
+    Link,
+}
+
+impl DedupeMode {
+    /// Parses `allow`, `skip` or `link`, `None` for anything else
+    #[must_use]
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "allow" => Some(Self::Allow),
+            "skip" => Some(Self::Skip),
+            "link" => Some(Self::Link),
+            _ => None,
+        }
+    }
+}
+
+/// Returns how videos shared between sources are handled, set through `LOCALTUBE_DEDUPE`
+#[must_use]
+pub fn dedupe_mode() -> DedupeMode {
+    DEDUPE_MODE
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .unwrap_or_else(|| {
+            *DEFAULT_DEDUPE_MODE.get_or_init(|| {
+                std::env::var("LOCALTUBE_DEDUPE").map_or(DedupeMode::default(), |v| {
+                    DedupeMode::parse(&v).unwrap_or_else(|| {
+                        warn!("Warning: LOCALTUBE_DEDUPE value '{v}' is not allow, skip or link");
+                        DedupeMode::default()
+                    })
+                })
+            })
+        })
+}
+
+/// Applies the dedupe setting, `None` falls back to `LOCALTUBE_DEDUPE`
+pub fn set_dedupe_mode(mode: Option<DedupeMode>) {
+    *DEDUPE_MODE.lock().unwrap_or_else(PoisonError::into_inner) = mode;
+}
+
+/// Finds the downloaded copy of the video of `media` under another source
+///
+/// Only real downloads count, a copy that is itself a duplicate is never the
+/// canonical one. Copies whose file is gone are ignored.
+///
+/// # Errors
+///
+/// Returns an error if the database query fails
+pub async fn find_canonical(
+    db: &DatabaseConnection,
+    media: &medias::Model,
+) -> Result<Option<(medias::Model, sources::Model)>> {
+    let Some(video_id) = &media.video_id else {
+        return Ok(None);
+    };
+    let candidates = medias::Entity::find()
+        .filter(medias::Column::VideoId.eq(video_id.as_str()))
+        .filter(medias::Column::SourceId.ne(media.source_id))
+        .filter(medias::Column::MediaPath.is_not_null())
+        .filter(medias::Column::DuplicateOf.is_null())
+        .find_also_related(sources::Entity)
+        .all(db)
+        .await?;
+    Ok(candidates
+        .into_iter()
+        .filter_map(|(candidate, source)| Some((candidate, source?)))
+        .find(|(candidate, source)| {
+            candidate
+                .media_path
+                .as_deref()
+                .is_some_and(|path| source.media_root().join(path).is_file())
+        }))
+}
+
+/// Unmarks the copies recorded as duplicates of `canonical_id`, returns how many
+///
+/// Called when the canonical media is removed. A skipped copy is then
+/// downloaded by the next refresh of its source instead of never again.
+///
+/// # Errors
+///
+/// Returns an error if the database update fails
+pub async fn release_duplicates(db: &DatabaseConnection, canonical_id: i32) -> Result<u64> {
+    let result = medias::Entity::update_many()
+        .col_expr(
+            medias::Column::DuplicateOf,
+            sea_orm::sea_query::Expr::value(Option::<i32>::None),
+        )
+        .filter(medias::Column::DuplicateOf.eq(canonical_id))
+        .exec(db)
+        .await?;
+    Ok(result.rows_affected)
+}
+
+/// Links `file` into `dir` under the same name, returns the created link
+///
+/// Prefers a hardlink so the copy survives the removal of the original, and
+/// falls back to a symlink across filesystems.
+///
+/// # Errors
+///
+/// Returns error if the folder cannot be created or neither link works.
+pub fn link_into(file: &Path, dir: &Path) -> std::io::Result<PathBuf> {
+    let name = file
+        .file_name()
+        .ok_or_else(|| std::io::Error::other(format!("{} has no file name", file.display())))?;
+    std::fs::create_dir_all(dir)?;
+    let link = dir.join(name);
+    if link.exists() {
+        return Ok(link);
+    }
+    if std::fs::hard_link(file, &link).is_err() {
+        std::os::unix::fs::symlink(std::path::absolute(file)?, &link)?;
+    }
+    Ok(link)
+}
+
+/// Links the downloaded file of `canonical` into the folder of `source`
+///
+/// Returns the `media_path` of the link relative to the media root of `source`.
+///
+/// # Errors
+///
+/// Returns error if the source has no folder name yet or linking fails.
+pub fn link_duplicate(
+    canonical: &medias::Model,
+    canonical_source: &sources::Model,
+    source: &sources::Model,
+) -> Result<String> {
+    let (Some(path), Some(dir)) = (&canonical.media_path, ytdlp::source_dir_name(source)) else {
+        return Err(Error::string("Cannot link a media without a source folder"));
+    };
+    let file = canonical_source.media_root().join(path);
+    let link = link_into(&file, &source.media_root().join(&dir))?;
+    // The info json keeps the metadata next to the copy, like a regular download.
+    let info = file.with_extension("info.json");
+    if info.is_file() {
+        if let Err(err) = link_into(&info, &source.media_root().join(&dir)) {
+            warn!(error = %err, "Failed to link {}", info.display());
+        }
+    }
+    let name = link
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    Ok(format!("{dir}/{name}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DedupeMode;
+
+    #[test]
+    fn dedupe_modes_parse() {
+        assert_eq!(DedupeMode::parse("allow"), Some(DedupeMode::Allow));
+        assert_eq!(DedupeMode::parse(" Skip "), Some(DedupeMode::Skip));
+        assert_eq!(DedupeMode::parse("LINK"), Some(DedupeMode::Link));
+        assert_eq!(DedupeMode::parse("copy"), None);
+    }
+}
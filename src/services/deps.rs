@@ -0,0 +1,160 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, LazyLock, Mutex, RwLock,
+    },
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::sync::watch;
+use tracing::{error, info, warn};
+
+use crate::ytdlp;
+
+/// Installs yt-dlp and ffmpeg, replaceable so tests do not hit the network
+#[async_trait]
+pub trait DepsInstaller: Send + Sync {
+    /// Installs the dependencies and returns the installed yt-dlp version, if it reports one
+    async fn install(&self) -> std::result::Result<Option<String>, String>;
+}
+
+/// Downloads the dependencies into the libs directory
+pub struct LibrariesInstaller;
+
+#[async_trait]
+impl DepsInstaller for LibrariesInstaller {
+    async fn install(&self) -> std::result::Result<Option<String>, String> {
+        ytdlp::download_deps().await.map_err(|e| e.to_string())?;
+        match ytdlp::yt_dlp_version().await {
+            Ok(version) => Ok(Some(version)),
+            Err(err) => {
+                warn!(error = %err, "Failed to read the installed yt-dlp version");
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// Outcome of the last dependency install
+#[derive(Debug, Clone, Serialize)]
+pub struct InstallRecord {
+    pub finished_at: DateTime<Utc>,
+    pub success: bool,
+    pub version: Option<String>,
+    pub error: Option<String>,
+}
+
+static INSTALLER: LazyLock<RwLock<Arc<dyn DepsInstaller>>> =
+    LazyLock::new(|| RwLock::new(Arc::new(LibrariesInstaller)));
+static INSTALLING: AtomicBool = AtomicBool::new(false);
+static LAST_INSTALL: Mutex<Option<InstallRecord>> = Mutex::new(None);
+static READY: LazyLock<watch::Sender<bool>> = LazyLock::new(|| watch::channel(false).0);
+
+/// Longest a worker waits for the dependencies before giving up on its job
+pub const READY_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Replaces the installer used by [`install`]
+///
+/// # Panics
+///
+/// Panics if the installer lock is poisoned.
+pub fn set_installer(installer: Arc<dyn DepsInstaller>) {
+    *INSTALLER.write().unwrap() = installer;
+}
+
+/// Forgets earlier installs, the dependencies are not ready until the next one succeeds
+///
+/// # Panics
+///
+/// Panics if the record mutex is poisoned.
+pub fn reset() {
+    *LAST_INSTALL.lock().unwrap() = None;
+    READY.send_replace(false);
+}
+
+/// Returns the outcome of the last install, `None` before the first one finished
+///
+/// # Panics
+///
+/// Panics if the record mutex is poisoned.
+#[must_use]
+pub fn last_install() -> Option<InstallRecord> {
+    LAST_INSTALL.lock().unwrap().clone()
+}
+
+/// Returns whether an install is running
+#[must_use]
+pub fn install_in_progress() -> bool {
+    INSTALLING.load(Ordering::SeqCst)
+}
+
+/// Returns whether an install succeeded since startup
+///
+/// A failed reinstall keeps the previously installed binaries, so it does not
+/// make the dependencies unready again, [`last_install`] reports it instead.
+#[must_use]
+pub fn is_ready() -> bool {
+    *READY.borrow()
+}
+
+/// Waits up to `timeout` for a successful install, returns whether the dependencies are ready
+pub async fn wait_until_ready(timeout: Duration) -> bool {
+    let mut ready = READY.subscribe();
+    tokio::time::timeout(timeout, ready.wait_for(|ready| *ready))
+        .await
+        .is_ok_and(|result| result.is_ok())
+}
+
+/// Clears the in-progress flag even if the install future is dropped
+struct InstallingGuard;
+
+impl Drop for InstallingGuard {
+    fn drop(&mut self) {
+        INSTALLING.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Installs the dependencies and records the outcome
+///
+/// Returns `None` without doing anything when another install is running.
+///
+/// # Panics
+///
+/// Panics if the installer lock or record mutex is poisoned.
+pub async fn install() -> Option<InstallRecord> {
+    if INSTALLING
+        .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+        .is_err()
+    {
+        return None;
+    }
+    let _guard = InstallingGuard;
+    let installer = Arc::clone(&INSTALLER.read().unwrap());
+    info!("Installing yt-dlp and ffmpeg");
+    let outcome = installer.install().await;
+    let record = InstallRecord {
+        finished_at: Utc::now(),
+        success: outcome.is_ok(),
+        version: outcome.as_ref().ok().cloned().flatten(),
+        error: outcome.err(),
+    };
+    if let Some(err) = &record.error {
+        if is_ready() {
+            error!("Dependency reinstall failed, keeping the installed version: {err}");
+        } else {
+            error!("Dependency install failed: {err}");
+        }
+    } else {
+        info!(version = ?record.version, "Installed yt-dlp and ffmpeg");
+    }
+    *LAST_INSTALL.lock().unwrap() = Some(record.clone());
+    READY.send_if_modified(|ready| {
+        let became_ready = record.success && !*ready;
+        *ready |= record.success;
+        became_ready
+    });
+    Some(record)
+}
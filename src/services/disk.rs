@@ -0,0 +1,10 @@
+use std::path::Path;
+
+/// Returns the bytes available to unprivileged users on the filesystem holding `path`
+///
+/// `None` when the filesystem cannot be queried, e.g. the path does not exist.
+#[must_use]
+pub fn free_space(path: &Path) -> Option<u64> {
+    let stats = rustix::fs::statvfs(path).ok()?;
+    Some(stats.f_bavail.saturating_mul(stats.f_frsize))
+}
@@ -0,0 +1,90 @@
+use std::{
+    collections::HashSet,
+    hash::Hash,
+    sync::{Arc, Mutex},
+};
+
+/// Set of keys with work currently in progress.
+///
+/// A key can be claimed by one caller at a time; the claim is released when
+/// the returned guard is dropped.
+pub struct InFlight<K> {
+    keys: Arc<Mutex<HashSet<K>>>,
+}
+
+impl<K: Eq + Hash + Clone> Default for InFlight<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Eq + Hash + Clone> InFlight<K> {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            keys: Arc::new(Mutex::new(HashSet::new())),
+        }
+    }
+
+    /// Claims the key, returning `None` if another caller holds it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key set mutex is poisoned.
+    #[must_use]
+    pub fn try_claim(&self, key: K) -> Option<InFlightGuard<K>> {
+        let mut keys = self.keys.lock().unwrap();
+        if !keys.insert(key.clone()) {
+            return None;
+        }
+        Some(InFlightGuard {
+            keys: Arc::clone(&self.keys),
+            key,
+        })
+    }
+
+    /// # Panics
+    ///
+    /// Panics if the key set mutex is poisoned.
+    #[must_use]
+    pub fn contains(&self, key: &K) -> bool {
+        self.keys.lock().unwrap().contains(key)
+    }
+}
+
+/// Releases the claimed key on drop.
+pub struct InFlightGuard<K: Eq + Hash> {
+    keys: Arc<Mutex<HashSet<K>>>,
+    key: K,
+}
+
+impl<K: Eq + Hash> Drop for InFlightGuard<K> {
+    fn drop(&mut self) {
+        if let Ok(mut keys) = self.keys.lock() {
+            keys.remove(&self.key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::InFlight;
+
+    #[test]
+    fn second_claim_fails_while_first_is_held() {
+        let in_flight = InFlight::new();
+        let first = in_flight.try_claim(1);
+        assert!(first.is_some());
+        assert!(in_flight.try_claim(1).is_none());
+        assert!(in_flight.try_claim(2).is_some());
+    }
+
+    #[test]
+    fn claim_is_released_on_drop() {
+        let in_flight = InFlight::new();
+        let first = in_flight.try_claim(1);
+        drop(first);
+        assert!(!in_flight.contains(&1));
+        assert!(in_flight.try_claim(1).is_some());
+    }
+}
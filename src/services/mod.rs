@@ -1 +1,14 @@
+pub mod dedupe;
+pub mod deps;
+pub mod disk;
+pub mod in_flight;
+pub mod prune;
+pub mod rate_limit;
+pub mod reconcile;
+pub mod refresh_runs;
+pub mod relocate;
+pub mod retention;
 pub mod retry;
+pub mod share;
+pub mod transcode;
+pub mod trash;
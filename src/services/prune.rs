@@ -0,0 +1,92 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+use loco_rs::prelude::*;
+use tracing::{info, warn};
+
+use crate::{models::sources::Sources, ytdlp};
+
+/// Removes empty directories directly under `media_dir` whose name is not in `keep`
+///
+/// Only the first level is considered, anything below belongs to a source
+/// directory and is never touched.
+fn remove_empty_dirs(media_dir: &Path, keep: &HashSet<String>) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(media_dir) else {
+        return Vec::new();
+    };
+    let mut removed = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if !path.is_dir() {
+            continue;
+        }
+        let kept = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .is_none_or(|name| keep.contains(name));
+        let empty = std::fs::read_dir(&path).is_ok_and(|mut dir| dir.next().is_none());
+        if kept || !empty {
+            continue;
+        }
+        // remove_dir refuses non-empty directories, so a file created meanwhile survives.
+        match std::fs::remove_dir(&path) {
+            Ok(()) => removed.push(path),
+            Err(err) => warn!(error = %err, "Failed to remove empty directory {}", path.display()),
+        }
+    }
+    removed
+}
+
+/// Removes empty source directories left behind under the media directory
+///
+/// Directories of existing sources are kept even when empty, a source with no
+/// video inside its window keeps downloading into the same place.
+///
+/// # Errors
+///
+/// Returns error if the sources cannot be loaded or the directory scan fails.
+pub async fn prune_empty_source_dirs(ctx: &AppContext) -> Result<usize> {
+    let keep: HashSet<String> = Sources::find()
+        .all(&ctx.db)
+        .await?
+        .iter()
+        .filter_map(ytdlp::source_dir_name)
+        .collect();
+    let media_dir = ytdlp::media_directory().clone();
+    let removed = tokio::task::spawn_blocking(move || remove_empty_dirs(&media_dir, &keep))
+        .await
+        .map_err(|e| Error::string(&format!("Failed to prune media directory: {e}")))?;
+    for path in &removed {
+        info!("Removed empty source directory {}", path.display());
+    }
+    Ok(removed.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::remove_empty_dirs;
+
+    #[test]
+    fn removes_only_empty_unknown_dirs() {
+        let root = std::env::temp_dir().join(format!("localtube_prune_{}", uuid::Uuid::new_v4()));
+        for dir in ["Gone", "Active", "Kept"] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        std::fs::write(root.join("Active").join("Video_[abc].mkv"), b"media").unwrap();
+        std::fs::write(root.join("loose.txt"), b"file").unwrap();
+        let keep = HashSet::from(["Kept".to_string()]);
+
+        let removed = remove_empty_dirs(&root, &keep);
+
+        assert_eq!(removed, vec![root.join("Gone")]);
+        assert!(!root.join("Gone").exists());
+        assert!(root.join("Active").exists());
+        assert!(root.join("Kept").exists());
+        assert!(root.join("loose.txt").exists());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
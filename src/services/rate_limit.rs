@@ -0,0 +1,228 @@
+use std::{
+    collections::HashMap,
+    sync::{LazyLock, Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+
+use crate::services::retry::RetryDelays;
+
+/// Backoff after the first rate limited download of a source
+pub const RATE_LIMIT_BASE_DELAY: Duration = Duration::from_secs(60 * 60);
+/// Longest backoff, reached after repeated rate limiting
+pub const RATE_LIMIT_MAX_DELAY: Duration = Duration::from_secs(24 * 60 * 60);
+/// Factor the backoff grows by with every consecutive rate limit
+pub const RATE_LIMIT_MULTIPLIER: u32 = 2;
+
+static RATE_LIMITS: LazyLock<RateLimitBackoff> =
+    LazyLock::new(|| RateLimitBackoff::with_delays(*RetryDelays::global()));
+static PAUSE_RATE_LIMITED_SOURCES: OnceLock<bool> = OnceLock::new();
+
+/// Returns whether other downloads of a rate limited source wait for its backoff
+///
+/// Enabled by default, `LOCALTUBE_PAUSE_RATE_LIMITED_SOURCES=false` only
+/// delays the retry of the failed media.
+#[must_use]
+pub fn pause_rate_limited_sources() -> bool {
+    *PAUSE_RATE_LIMITED_SOURCES.get_or_init(|| {
+        std::env::var("LOCALTUBE_PAUSE_RATE_LIMITED_SOURCES").map_or(true, |v| {
+            !matches!(v.trim().to_ascii_lowercase().as_str(), "0" | "false" | "no")
+        })
+    })
+}
+
+#[derive(Debug, Clone, Copy)]
+struct SourceBackoff {
+    strikes: u32,
+    until: Instant,
+}
+
+/// Consecutive rate limits per source and how long the source is backed off
+#[derive(Debug, Default)]
+pub struct RateLimitBackoff {
+    delays: RetryDelays,
+    sources: Mutex<HashMap<i32, SourceBackoff>>,
+}
+
+impl RateLimitBackoff {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn with_delays(delays: RetryDelays) -> Self {
+        Self {
+            delays,
+            ..Self::default()
+        }
+    }
+
+    #[must_use]
+    pub fn global() -> &'static Self {
+        &RATE_LIMITS
+    }
+
+    /// Counts a rate limited download of the source and returns its backoff
+    ///
+    /// Downloads running in the same burst hit the limit together, so a rate
+    /// limit inside the current backoff is no new strike and only gets the
+    /// rest of it. The backoff escalates once it has run out.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the backoff mutex is poisoned.
+    pub fn record(&self, source_id: i32, now: Instant) -> Duration {
+        let mut sources = self.sources.lock().unwrap();
+        let previous = sources.get(&source_id).copied();
+        if let Some(backoff) = previous.filter(|b| now < b.until) {
+            return backoff.until - now;
+        }
+        let strikes = previous.map_or(0, |b| b.strikes) + 1;
+        let delay = self.delays.rate_limit_backoff(strikes);
+        sources.insert(
+            source_id,
+            SourceBackoff {
+                strikes,
+                until: now + delay,
+            },
+        );
+        delay
+    }
+
+    /// Forgets the rate limits of the source after a successful download
+    ///
+    /// # Panics
+    ///
+    /// Panics if the backoff mutex is poisoned.
+    pub fn clear(&self, source_id: i32) {
+        self.sources.lock().unwrap().remove(&source_id);
+    }
+
+    /// Records a failed download of the source and returns its retry delay
+    ///
+    /// A rate limited failure is [`Self::record`]ed against the source and
+    /// retries after its backoff, anything else retries after `default`.
+    pub fn record_failure(
+        &self,
+        source_id: Option<i32>,
+        error: &str,
+        default: Duration,
+        now: Instant,
+    ) -> Duration {
+        match source_id {
+            Some(source_id) if crate::workers::errors::is_rate_limited(error) => {
+                self.record(source_id, now)
+            }
+            _ => default,
+        }
+    }
+
+    /// Returns how long the source is still backed off, `None` once it may download again
+    ///
+    /// # Panics
+    ///
+    /// Panics if the backoff mutex is poisoned.
+    #[must_use]
+    pub fn remaining(&self, source_id: i32, now: Instant) -> Option<Duration> {
+        self.sources
+            .lock()
+            .unwrap()
+            .get(&source_id)
+            .map(|b| b.until.saturating_duration_since(now))
+            .filter(|remaining| !remaining.is_zero())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RateLimitBackoff, RATE_LIMIT_BASE_DELAY, RATE_LIMIT_MAX_DELAY};
+    use crate::services::retry::RetryDelays;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn rate_limited_failure_selects_long_backoff() {
+        let backoff = RateLimitBackoff::new();
+        let now = Instant::now();
+        let default = Duration::from_secs(300);
+        let too_many = "ERROR: [youtube] abc: HTTP Error 429: Too Many Requests";
+
+        assert_eq!(
+            backoff.record_failure(Some(1), "ERROR: Video unavailable", default, now),
+            default
+        );
+        assert_eq!(backoff.remaining(1, now), None);
+        assert_eq!(
+            backoff.record_failure(Some(1), too_many, default, now),
+            RATE_LIMIT_BASE_DELAY
+        );
+        let expired = now + RATE_LIMIT_BASE_DELAY;
+        assert_eq!(
+            backoff.record_failure(Some(1), too_many, default, expired),
+            RATE_LIMIT_BASE_DELAY * 2
+        );
+        assert!(backoff.remaining(1, expired).is_some());
+        assert_eq!(
+            backoff.record_failure(None, too_many, default, now),
+            default
+        );
+    }
+
+    #[test]
+    fn failures_in_one_burst_are_a_single_strike() {
+        let backoff = RateLimitBackoff::new();
+        let now = Instant::now();
+        let default = Duration::from_secs(300);
+        let too_many = "ERROR: [youtube] abc: HTTP Error 429: Too Many Requests";
+
+        assert_eq!(
+            backoff.record_failure(Some(1), too_many, default, now),
+            RATE_LIMIT_BASE_DELAY
+        );
+        assert_eq!(
+            backoff.record_failure(Some(1), too_many, default, now),
+            RATE_LIMIT_BASE_DELAY
+        );
+        let later = now + Duration::from_secs(60);
+        assert_eq!(
+            backoff.record_failure(Some(1), too_many, default, later),
+            RATE_LIMIT_BASE_DELAY - Duration::from_secs(60),
+            "the rest of the current backoff"
+        );
+        assert_eq!(
+            backoff.record(1, now + RATE_LIMIT_BASE_DELAY),
+            RATE_LIMIT_BASE_DELAY * 2,
+            "one strike so far"
+        );
+    }
+
+    #[test]
+    fn delay_escalates_up_to_the_cap() {
+        let rate_limit_delay = |strikes| RetryDelays::default().rate_limit_backoff(strikes);
+        assert_eq!(rate_limit_delay(1), RATE_LIMIT_BASE_DELAY);
+        assert_eq!(rate_limit_delay(2), RATE_LIMIT_BASE_DELAY * 2);
+        assert_eq!(rate_limit_delay(4), RATE_LIMIT_BASE_DELAY * 8);
+        assert_eq!(rate_limit_delay(6), RATE_LIMIT_MAX_DELAY);
+        assert_eq!(rate_limit_delay(u32::MAX), RATE_LIMIT_MAX_DELAY);
+    }
+
+    #[test]
+    fn source_is_backed_off_until_cleared() {
+        let backoff = RateLimitBackoff::new();
+        let now = Instant::now();
+        assert_eq!(backoff.remaining(1, now), None);
+
+        assert_eq!(backoff.record(1, now), RATE_LIMIT_BASE_DELAY);
+        let now = now + RATE_LIMIT_BASE_DELAY;
+        assert_eq!(backoff.record(1, now), RATE_LIMIT_BASE_DELAY * 2);
+        assert_eq!(backoff.remaining(1, now), Some(RATE_LIMIT_BASE_DELAY * 2));
+        assert_eq!(backoff.remaining(2, now), None);
+        assert_eq!(
+            backoff.remaining(1, now + RATE_LIMIT_BASE_DELAY * 2 + Duration::from_secs(1)),
+            None
+        );
+
+        backoff.clear(1);
+        assert_eq!(backoff.remaining(1, now), None);
+        assert_eq!(backoff.record(1, now), RATE_LIMIT_BASE_DELAY);
+    }
+}
@@ -0,0 +1,195 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use loco_rs::prelude::*;
+use sea_orm::Set;
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::{
+    models::{
+        _entities::{medias, sources},
+        medias::Medias,
+        sources::Sources,
+    },
+    services::trash,
+    workers::fetch_media::{media_downloads, FetchMediaWorker, FetchMediaWorkerArgs},
+    ytdlp,
+};
+
+/// Counts reported by a media directory reconcile run
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ReconcileReport {
+    /// Media rows inspected
+    pub checked: usize,
+    /// Rows whose stored file was gone and got their path cleared
+    pub missing: usize,
+    /// Rows queued for download again
+    pub requeued: usize,
+    /// Rows linked to a file found on disk
+    pub adopted: usize,
+    /// Media files not referenced by any row after reconciling
+    pub orphans: usize,
+}
+
+fn collect_media_files(root: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut dirs = vec![root.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                // Trashed files belong to their trashed rows, not to the library.
+                if path.file_name().and_then(|n| n.to_str()) != Some(trash::TRASH_DIR) {
+                    dirs.push(path);
+                }
+            } else if path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(ytdlp::is_media_file_name)
+            {
+                files.push(path);
+            }
+        }
+    }
+    files
+}
+
+/// Walks the media directory and brings `media_path`s back in line with the files on disk.
+///
+/// Rows pointing at a missing file have their path cleared and are queued for
+/// download. With `adopt_orphans`, rows without a file are first linked to an
+/// unreferenced file carrying their video id, which covers files moved by hand.
+/// Medias of sources with their own media root and trashed medias are left alone.
+///
+/// # Errors
+///
+/// Returns error if the media directory cannot be scanned or the database
+/// cannot be read or updated.
+pub async fn reconcile_media(ctx: &AppContext, adopt_orphans: bool) -> Result<ReconcileReport> {
+    let media_dir = ytdlp::media_directory().clone();
+    let files = {
+        let media_dir = media_dir.clone();
+        tokio::task::spawn_blocking(move || collect_media_files(&media_dir))
+            .await
+            .map_err(|e| Error::string(&format!("Failed to scan media directory: {e}")))?
+    };
+    let mut unreferenced: HashSet<String> = files
+        .iter()
+        .filter_map(|path| ytdlp::relative_media_path(path, &media_dir).ok())
+        .collect();
+
+    let own_root_sources: HashSet<i32> = Sources::find()
+        .filter(sources::Column::MediaRoot.is_not_null())
+        .all(&ctx.db)
+        .await?
+        .iter()
+        .map(|source| source.id)
+        .collect();
+    let rows: Vec<_> = Medias::find()
+        .filter(medias::Column::TrashedAt.is_null())
+        .all(&ctx.db)
+        .await?
+        .into_iter()
+        .filter(|media| !own_root_sources.contains(&media.source_id))
+        .collect();
+    let mut report = ReconcileReport {
+        checked: rows.len(),
+        ..Default::default()
+    };
+
+    let mut missing_ids = HashSet::new();
+    let mut pathless = Vec::new();
+    for media in rows {
+        match &media.media_path {
+            Some(path) if media_dir.join(path).exists() => {
+                unreferenced.remove(path);
+            }
+            Some(path) => {
+                warn!(media_id = media.id, "Media file {} is missing", path);
+                medias::ActiveModel {
+                    id: Set(media.id),
+                    media_path: Set(None),
+                    ..Default::default()
+                }
+                .update(&ctx.db)
+                .await?;
+                report.missing += 1;
+                missing_ids.insert(media.id);
+                pathless.push(media);
+            }
+            None => pathless.push(media),
+        }
+    }
+
+    if adopt_orphans {
+        let mut by_video_id: HashMap<&str, &str> = HashMap::new();
+        for path in &unreferenced {
+            let Some(video_id) = Path::new(path)
+                .file_name()
+                .and_then(|n| n.to_str())
+                .and_then(ytdlp::video_id_from_file_name)
+            else {
+                continue;
+            };
+            let current = by_video_id.get(video_id).map(Path::new);
+            if ytdlp::prefer_media_file(current, Path::new(path)) {
+                by_video_id.insert(video_id, path);
+            }
+        }
+
+        let mut adopted_paths = Vec::new();
+        pathless.retain(|media| {
+            let found = media
+                .video_id
+                .as_deref()
+                .filter(|_| !media_downloads().contains(&media.id))
+                .and_then(|video_id| by_video_id.remove(video_id));
+            match found {
+                Some(path) => {
+                    adopted_paths.push((media.id, path.to_string()));
+                    false
+                }
+                None => true,
+            }
+        });
+
+        for (media_id, path) in adopted_paths {
+            info!(media_id, "Adopting media file {}", path);
+            let file_size = std::fs::metadata(media_dir.join(&path))
+                .ok()
+                .and_then(|m| i64::try_from(m.len()).ok());
+            medias::ActiveModel {
+                id: Set(media_id),
+                media_path: Set(Some(path.clone())),
+                file_size: Set(file_size),
+                ..Default::default()
+            }
+            .update(&ctx.db)
+            .await?;
+            unreferenced.remove(&path);
+            report.adopted += 1;
+        }
+    }
+
+    for media in pathless {
+        if !missing_ids.contains(&media.id) {
+            continue;
+        }
+        match FetchMediaWorker::perform_later(ctx, FetchMediaWorkerArgs { media_id: media.id })
+            .await
+        {
+            Ok(()) => report.requeued += 1,
+            Err(err) => warn!(media_id = media.id, error = %err, "Failed to queue download"),
+        }
+    }
+
+    report.orphans = unreferenced.len();
+    info!(?report, "Media directory reconciled");
+    Ok(report)
+}
@@ -0,0 +1,165 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, LazyLock, Mutex},
+};
+
+use tokio::sync::OwnedMutexGuard;
+use tokio_util::sync::CancellationToken;
+
+static REFRESH_RUNS: LazyLock<RefreshRuns> = LazyLock::new(RefreshRuns::new);
+
+#[derive(Clone)]
+struct Run {
+    generation: u64,
+    cancel: CancellationToken,
+}
+
+#[derive(Default)]
+struct Runs {
+    next_generation: u64,
+    running: HashMap<i32, Run>,
+    locks: HashMap<i32, Arc<tokio::sync::Mutex<()>>>,
+}
+
+/// Refreshes in progress per source, one runs at a time and can be cancelled
+///
+/// A refresh waits for the previous refresh of the same source to stop before
+/// it starts, so it always reads the settings as they are once it runs.
+#[derive(Default)]
+pub struct RefreshRuns {
+    runs: Arc<Mutex<Runs>>,
+}
+
+impl RefreshRuns {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn global() -> &'static Self {
+        &REFRESH_RUNS
+    }
+
+    /// Waits for the running refresh of the source to stop and claims the next run
+    ///
+    /// # Panics
+    ///
+    /// Panics if the runs mutex is poisoned.
+    pub async fn start(&self, source_id: i32) -> RefreshRun {
+        let lock = Arc::clone(
+            self.runs
+                .lock()
+                .unwrap()
+                .locks
+                .entry(source_id)
+                .or_default(),
+        );
+        let exclusive = lock.lock_owned().await;
+        let mut runs = self.runs.lock().unwrap();
+        runs.next_generation += 1;
+        let run = Run {
+            generation: runs.next_generation,
+            cancel: CancellationToken::new(),
+        };
+        runs.running.insert(source_id, run.clone());
+        RefreshRun {
+            runs: Arc::clone(&self.runs),
+            source_id,
+            run,
+            _exclusive: exclusive,
+        }
+    }
+
+    /// Cancels the running refresh of the source, returns whether one was running
+    ///
+    /// # Panics
+    ///
+    /// Panics if the runs mutex is poisoned.
+    pub fn cancel(&self, source_id: i32) -> bool {
+        self.runs
+            .lock()
+            .unwrap()
+            .running
+            .get(&source_id)
+            .inspect(|run| run.cancel.cancel())
+            .is_some()
+    }
+
+    /// # Panics
+    ///
+    /// Panics if the runs mutex is poisoned.
+    #[must_use]
+    pub fn is_running(&self, source_id: i32) -> bool {
+        self.runs.lock().unwrap().running.contains_key(&source_id)
+    }
+}
+
+/// Claimed refresh of a source, lets the next refresh start on drop.
+pub struct RefreshRun {
+    runs: Arc<Mutex<Runs>>,
+    source_id: i32,
+    run: Run,
+    _exclusive: OwnedMutexGuard<()>,
+}
+
+impl RefreshRun {
+    /// Token cancelled once the refresh was superseded
+    #[must_use]
+    pub fn token(&self) -> CancellationToken {
+        self.run.cancel.clone()
+    }
+
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        self.run.cancel.is_cancelled()
+    }
+}
+
+impl Drop for RefreshRun {
+    fn drop(&mut self) {
+        if let Ok(mut runs) = self.runs.lock() {
+            if runs
+                .running
+                .get(&self.source_id)
+                .is_some_and(|run| run.generation == self.run.generation)
+            {
+                runs.running.remove(&self.source_id);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::RefreshRuns;
+
+    #[tokio::test]
+    async fn next_run_waits_for_the_cancelled_one() {
+        let runs = RefreshRuns::new();
+        let first = runs.start(1).await;
+        assert!(runs.is_running(1));
+        assert!(!runs.cancel(2));
+
+        let other = tokio::time::timeout(Duration::from_secs(1), runs.start(2)).await;
+        assert!(other.is_ok(), "other sources are not blocked");
+
+        assert!(runs.cancel(1));
+        assert!(first.is_cancelled());
+        assert!(
+            tokio::time::timeout(Duration::from_millis(50), runs.start(1))
+                .await
+                .is_err(),
+            "the next run waits until the cancelled one stopped"
+        );
+
+        drop(first);
+        let second = runs.start(1).await;
+        assert!(!second.is_cancelled());
+        assert!(runs.is_running(1));
+        drop(second);
+        assert!(!runs.is_running(1));
+    }
+}
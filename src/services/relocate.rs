@@ -0,0 +1,105 @@
+use std::{path::Path, sync::OnceLock};
+
+use loco_rs::prelude::*;
+use sea_orm::Set;
+use tracing::{info, warn};
+
+use crate::{
+    models::{_entities::medias, medias::Medias},
+    services::trash::trash_root,
+};
+
+static MOVE_RENAMED_SOURCES: OnceLock<bool> = OnceLock::new();
+
+/// Returns whether a refresh moves the files of a source whose uploader was renamed
+///
+/// Enabled through `LOCALTUBE_MOVE_RENAMED_SOURCES=true`, otherwise the
+/// rename is only logged and new downloads go to the new folder.
+#[must_use]
+pub fn move_renamed_sources() -> bool {
+    *MOVE_RENAMED_SOURCES.get_or_init(|| {
+        std::env::var("LOCALTUBE_MOVE_RENAMED_SOURCES")
+            .is_ok_and(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes"))
+    })
+}
+
+/// Moves the files of a source from the folder `old_dir` under `media_dir` to `new_dir` and rewrites their `media_path`s
+///
+/// Sources of the same uploader share a folder, so only the files of this
+/// source's medias are moved and `old_dir` itself is kept. Medias are moved one
+/// at a time, a path is only rewritten once its files were moved and a failed
+/// update moves them back. Trashed medias move inside the trash. A media whose
+/// file already exists in `new_dir` is left where it is. Returns the number of
+/// moved medias.
+///
+/// # Errors
+///
+/// Returns error if a file cannot be moved or the database cannot be updated.
+pub async fn move_source_dir(
+    db: &DatabaseConnection,
+    media_dir: &Path,
+    source_id: i32,
+    old_dir: &str,
+    new_dir: &str,
+) -> Result<usize> {
+    if old_dir == new_dir {
+        return Ok(0);
+    }
+    let rows = Medias::find()
+        .filter(medias::Column::SourceId.eq(source_id))
+        .filter(medias::Column::MediaPath.is_not_null())
+        .all(db)
+        .await?;
+    let mut moved = 0;
+    for row in rows {
+        let Some(rest) = row
+            .media_path
+            .as_deref()
+            .and_then(|path| Path::new(path).strip_prefix(old_dir).ok())
+            .map(|rest| rest.to_string_lossy().to_string())
+        else {
+            continue;
+        };
+        let root = if row.trashed_at.is_some() {
+            trash_root(media_dir)
+        } else {
+            media_dir.to_path_buf()
+        };
+        let (old_path, new_path) = (root.join(old_dir), root.join(new_dir));
+        if tokio::fs::try_exists(new_path.join(&rest)).await? {
+            warn!(
+                "Not moving {}: {} already exists",
+                old_path.join(&rest).display(),
+                new_path.join(&rest).display()
+            );
+            continue;
+        }
+
+        // Relative to the folder, so the files keep their names under the new one.
+        let in_folder = medias::Model {
+            media_path: Some(rest.clone()),
+            ..row.clone()
+        };
+        in_folder.move_media_files(&old_path, &new_path)?;
+        let update = medias::ActiveModel {
+            id: Set(row.id),
+            media_path: Set(Some(
+                Path::new(new_dir).join(&rest).to_string_lossy().to_string(),
+            )),
+            ..Default::default()
+        }
+        .update(db)
+        .await;
+        if let Err(err) = update {
+            in_folder.move_media_files(&new_path, &old_path)?;
+            return Err(err.into());
+        }
+        moved += 1;
+    }
+    info!(
+        "Moved {moved} medias of source {source_id} from {} to {}",
+        media_dir.join(old_dir).display(),
+        media_dir.join(new_dir).display()
+    );
+    Ok(moved)
+}
@@ -0,0 +1,159 @@
+use loco_rs::prelude::*;
+use serde::Serialize;
+use tracing::info;
+
+use crate::{
+    models::{
+        _entities::{medias, sources},
+        medias::Medias,
+    },
+    services::trash,
+    webhook::{self, RemovalReason, WebhookEvent},
+};
+
+/// Medias removed by a retention pass
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct RetentionReport {
+    pub removed: usize,
+    /// Bytes of the removed medias, as far as their size is known
+    pub freed_bytes: u64,
+}
+
+impl RetentionReport {
+    pub fn add(&mut self, other: Self) {
+        self.removed += other.removed;
+        self.freed_bytes += other.freed_bytes;
+    }
+}
+
+/// Oldest publish timestamp a source keeps, `fetch_last_days` before `now`
+#[must_use]
+pub fn retention_cutoff(fetch_last_days: i32, now: chrono::DateTime<chrono::Utc>) -> i64 {
+    now.checked_sub_signed(chrono::Duration::days(i64::from(fetch_last_days)))
+        .unwrap_or(now)
+        .timestamp()
+}
+
+/// Returns whether a media published at `timestamp` fell out of the window ending at `cutoff`
+///
+/// A media published exactly at the cutoff is still kept.
+#[must_use]
+pub fn is_expired(timestamp: i64, cutoff: i64) -> bool {
+    timestamp < cutoff
+}
+
+/// Removes the downloaded medias of the source outside its retention limits
+///
+/// These are the medias published before its `fetch_last_days` window and,
+/// with `max_items`, all but the newest `max_items` downloaded medias.
+/// Deletes the files of each removed media together with its row, or moves
+/// them into the trash when [`trash::trash_days`] is set. Medias that
+/// were never downloaded are kept, they are simply not queued again. With
+/// [`webhook::notify_removals`] the removed medias are reported in the
+/// background once the pass is done, so a slow webhook cannot hold up the
+/// refresh. The files are deleted or trashed by then, the events report what
+/// was removed rather than give a chance to keep it.
+///
+/// # Errors
+///
+/// Returns an error if the medias cannot be loaded, a file cannot be removed
+/// or a row cannot be deleted.
+pub async fn apply_retention(
+    db: &DatabaseConnection,
+    source: &sources::Model,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<RetentionReport> {
+    let cutoff = retention_cutoff(source.fetch_last_days, now);
+    let medias = Medias::find()
+        .filter(medias::Column::SourceId.eq(source.id))
+        .filter(medias::Column::MediaPath.is_not_null())
+        .filter(medias::Column::TrashedAt.is_null())
+        .all(db)
+        .await?;
+
+    let mut medias: Vec<_> = medias
+        .into_iter()
+        .filter_map(|media| media.get_metadata().map(|metadata| (media, metadata)))
+        .collect();
+    // Newest first, so every media from `max_items` on is over the count limit.
+    medias.sort_by_key(|(_, metadata)| std::cmp::Reverse(metadata.timestamp));
+    let max_items = source
+        .max_items
+        .and_then(|n| usize::try_from(n).ok())
+        .filter(|n| *n > 0);
+
+    let uploader = source.get_metadata().map(|m| m.uploader);
+    let name = uploader.clone().unwrap_or_else(|| source.url.clone());
+    let mut report = RetentionReport::default();
+    let mut removals = Vec::new();
+    for (index, (media, metadata)) in medias.into_iter().enumerate() {
+        if is_expired(metadata.timestamp, cutoff) {
+            info!(
+                event = "media_removed",
+                source_id = source.id,
+                media_id = media.id,
+                reason = "retention",
+                "{name}: Removing old media {}",
+                metadata.title
+            );
+        } else if let Some(max_items) = max_items.filter(|max| index >= *max) {
+            info!(
+                event = "media_removed",
+                source_id = source.id,
+                media_id = media.id,
+                reason = "retention",
+                "{name}: Removing media {} over the limit of {max_items} videos",
+                metadata.title
+            );
+        } else {
+            continue;
+        }
+        if webhook::notify_removals() {
+            removals.push(WebhookEvent::MediaRemoved {
+                source_id: source.id,
+                media_id: media.id,
+                title: metadata.title.clone(),
+                url: metadata.original_url.clone(),
+                media_path: media.media_path.clone(),
+                uploader: uploader.clone(),
+                reason: RemovalReason::Retention,
+            });
+        }
+        let size = media
+            .file_size
+            .and_then(|size| u64::try_from(size).ok())
+            .unwrap_or_default();
+        trash::discard_media(db, media, &source.media_root(), now).await?;
+        report.removed += 1;
+        report.freed_bytes += size;
+    }
+    if !removals.is_empty() {
+        let source = source.clone();
+        tokio::spawn(async move {
+            for event in &removals {
+                webhook::notify_source(&source, event).await;
+            }
+        });
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_expired, retention_cutoff};
+
+    #[test]
+    fn media_at_the_cutoff_is_kept() {
+        let cutoff = 1_700_000_000;
+        assert!(!is_expired(cutoff, cutoff));
+        assert!(!is_expired(cutoff + 1, cutoff));
+        assert!(is_expired(cutoff - 1, cutoff));
+    }
+
+    #[test]
+    fn cutoff_is_the_window_before_now() {
+        let now = chrono::DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        assert_eq!(retention_cutoff(7, now), 1_700_000_000 - 7 * 24 * 3600);
+        assert_eq!(retention_cutoff(0, now), 1_700_000_000);
+    }
+}
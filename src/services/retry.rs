@@ -1,8 +1,134 @@
-use std::{future::Future, time::Duration};
+use std::{future::Future, sync::OnceLock, time::Duration};
 
-use loco_rs::Result;
+use loco_rs::{Error, Result};
 use tokio::task::JoinHandle;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
+
+use crate::services::rate_limit::{
+    RATE_LIMIT_BASE_DELAY, RATE_LIMIT_MAX_DELAY, RATE_LIMIT_MULTIPLIER,
+};
+
+/// Delay before a failed download is retried
+pub const DEFAULT_RETRY_DELAY: Duration = Duration::from_secs(5 * 60);
+
+static RETRY_DELAYS: OnceLock<RetryDelays> = OnceLock::new();
+
+/// How long failed downloads wait before they are retried
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryDelays {
+    /// Delay before retrying an ordinary failure
+    pub delay: Duration,
+    /// Backoff after the first rate limited download of a source
+    pub rate_limit_delay: Duration,
+    /// Longest rate limit backoff
+    pub max_delay: Duration,
+    /// Factor the rate limit backoff grows by with every consecutive rate limit
+    pub multiplier: u32,
+}
+
+impl Default for RetryDelays {
+    fn default() -> Self {
+        Self {
+            delay: DEFAULT_RETRY_DELAY,
+            rate_limit_delay: RATE_LIMIT_BASE_DELAY,
+            max_delay: RATE_LIMIT_MAX_DELAY,
+            multiplier: RATE_LIMIT_MULTIPLIER,
+        }
+    }
+}
+
+impl RetryDelays {
+    /// Reads the delays from the variables returned by `lookup`, unset ones keep their default
+    ///
+    /// `LOCALTUBE_RETRY_DELAY_SECS`, `LOCALTUBE_RATE_LIMIT_DELAY_SECS`,
+    /// `LOCALTUBE_RATE_LIMIT_MAX_DELAY_SECS` and `LOCALTUBE_RATE_LIMIT_MULTIPLIER`.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if a value is not a positive integer or the cap is below the rate limit delay
+    pub fn parse(lookup: impl Fn(&str) -> Option<String>) -> std::result::Result<Self, String> {
+        let positive = |name: &str| -> std::result::Result<Option<u64>, String> {
+            let Some(value) = lookup(name).filter(|v| !v.trim().is_empty()) else {
+                return Ok(None);
+            };
+            match value.trim().parse::<u64>() {
+                Ok(n) if n > 0 => Ok(Some(n)),
+                _ => Err(format!("{name} must be a positive integer, got {value:?}")),
+            }
+        };
+        let defaults = Self::default();
+        let secs = |name: &str, default: Duration| {
+            Ok::<_, String>(positive(name)?.map_or(default, Duration::from_secs))
+        };
+        let delays = Self {
+            delay: secs("LOCALTUBE_RETRY_DELAY_SECS", defaults.delay)?,
+            rate_limit_delay: secs("LOCALTUBE_RATE_LIMIT_DELAY_SECS", defaults.rate_limit_delay)?,
+            max_delay: secs("LOCALTUBE_RATE_LIMIT_MAX_DELAY_SECS", defaults.max_delay)?,
+            multiplier: match positive("LOCALTUBE_RATE_LIMIT_MULTIPLIER")? {
+                Some(n) => u32::try_from(n)
+                    .map_err(|_| "LOCALTUBE_RATE_LIMIT_MULTIPLIER is too large".to_string())?,
+                None => defaults.multiplier,
+            },
+        };
+        if delays.max_delay < delays.rate_limit_delay {
+            return Err(
+                "LOCALTUBE_RATE_LIMIT_MAX_DELAY_SECS must not be below LOCALTUBE_RATE_LIMIT_DELAY_SECS"
+                    .to_string(),
+            );
+        }
+        Ok(delays)
+    }
+
+    /// Loads and validates the delays from the environment, called once on startup
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the environment holds invalid values
+    pub fn init_global() -> Result<&'static Self> {
+        let delays = Self::parse(|name| std::env::var(name).ok()).map_err(Error::Message)?;
+        Ok(RETRY_DELAYS.get_or_init(|| delays))
+    }
+
+    /// Returns the delays configured through the environment, see [`Self::parse`]
+    ///
+    /// Invalid values are rejected on startup by [`Self::init_global`];
+    /// if they are read here first, the defaults are used.
+    #[must_use]
+    pub fn global() -> &'static Self {
+        RETRY_DELAYS.get_or_init(|| {
+            Self::parse(|name| std::env::var(name).ok()).unwrap_or_else(|err| {
+                warn!("Ignoring retry delay settings: {}", err);
+                Self::default()
+            })
+        })
+    }
+
+    /// Backoff of the `strikes`-th consecutive rate limit, growing up to [`Self::max_delay`]
+    #[must_use]
+    pub fn rate_limit_backoff(&self, strikes: u32) -> Duration {
+        backoff(
+            self.rate_limit_delay,
+            self.multiplier,
+            self.max_delay,
+            strikes,
+        )
+    }
+}
+
+/// Delay after the `attempt`-th consecutive failure
+///
+/// Starts at `base` and grows by `multiplier` with every further attempt, up to `max`.
+#[must_use]
+pub fn backoff(base: Duration, multiplier: u32, max: Duration, attempt: u32) -> Duration {
+    let mut delay = base;
+    for _ in 1..attempt {
+        if delay >= max || multiplier <= 1 {
+            break;
+        }
+        delay = delay.saturating_mul(multiplier);
+    }
+    delay.min(max)
+}
 
 /// Utility for scheduling retry logic with a guard check before executing the action.
 pub struct RetryScheduler;
@@ -57,14 +183,67 @@ impl RetryScheduler {
 
 #[cfg(test)]
 mod tests {
-    use super::RetryScheduler;
+    use super::{RetryDelays, RetryScheduler};
     use std::{
+        collections::HashMap,
         sync::atomic::{AtomicUsize, Ordering},
         sync::Arc,
         time::Duration,
     };
     use tokio::time::sleep;
 
+    fn parse(vars: &[(&str, &str)]) -> Result<RetryDelays, String> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(name, value)| ((*name).to_string(), (*value).to_string()))
+            .collect();
+        RetryDelays::parse(|name| vars.get(name).cloned())
+    }
+
+    #[test]
+    fn retry_delays_default_without_env() {
+        assert_eq!(parse(&[]), Ok(RetryDelays::default()));
+        assert_eq!(
+            parse(&[("LOCALTUBE_RETRY_DELAY_SECS", " ")]),
+            Ok(RetryDelays::default())
+        );
+    }
+
+    #[test]
+    fn retry_delays_parse_env_values() {
+        let delays = parse(&[
+            ("LOCALTUBE_RETRY_DELAY_SECS", "60"),
+            ("LOCALTUBE_RATE_LIMIT_DELAY_SECS", "600"),
+            ("LOCALTUBE_RATE_LIMIT_MAX_DELAY_SECS", "5000"),
+            ("LOCALTUBE_RATE_LIMIT_MULTIPLIER", "3"),
+        ])
+        .unwrap();
+        assert_eq!(
+            delays,
+            RetryDelays {
+                delay: Duration::from_secs(60),
+                rate_limit_delay: Duration::from_secs(600),
+                max_delay: Duration::from_secs(5000),
+                multiplier: 3,
+            }
+        );
+        assert_eq!(delays.rate_limit_backoff(1), Duration::from_secs(600));
+        assert_eq!(delays.rate_limit_backoff(2), Duration::from_secs(1800));
+        assert_eq!(delays.rate_limit_backoff(3), Duration::from_secs(5000));
+        assert_eq!(
+            delays.rate_limit_backoff(u32::MAX),
+            Duration::from_secs(5000)
+        );
+    }
+
+    #[test]
+    fn retry_delays_reject_invalid_values() {
+        assert!(parse(&[("LOCALTUBE_RETRY_DELAY_SECS", "0")]).is_err());
+        assert!(parse(&[("LOCALTUBE_RETRY_DELAY_SECS", "5m")]).is_err());
+        assert!(parse(&[("LOCALTUBE_RATE_LIMIT_MULTIPLIER", "-2")]).is_err());
+        assert!(parse(&[("LOCALTUBE_RATE_LIMIT_MAX_DELAY_SECS", "60")]).is_err());
+    }
+
     #[tokio::test]
     async fn executes_action_when_check_passes() {
         let check_calls = Arc::new(AtomicUsize::new(0));
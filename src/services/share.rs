@@ -0,0 +1,130 @@
+use std::sync::{Mutex, PoisonError};
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Hours a share link stays valid unless asked otherwise
+pub const DEFAULT_SHARE_HOURS: u32 = 24;
+/// Longest a share link can be valid, 30 days
+pub const MAX_SHARE_HOURS: u32 = 30 * 24;
+
+/// Why a share token does not grant access
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum ShareTokenError {
+    #[error("share token is malformed")]
+    Malformed,
+    #[error("share token expired")]
+    Expired,
+    #[error("share token does not match the media")]
+    Invalid,
+}
+
+static SHARE_SECRET: Mutex<Option<String>> = Mutex::new(None);
+
+/// Returns the secret share tokens are signed with
+///
+/// Configured through `LOCALTUBE_SHARE_SECRET`. Without it sharing is
+/// disabled, no other secret is used since those may be public. Changing it
+/// invalidates every shared link.
+#[must_use]
+pub fn share_secret() -> Option<String> {
+    SHARE_SECRET
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+        .or_else(|| std::env::var("LOCALTUBE_SHARE_SECRET").ok())
+        .filter(|secret| !secret.trim().is_empty())
+}
+
+/// Overrides the share secret, `None` falls back to `LOCALTUBE_SHARE_SECRET`
+pub fn set_share_secret(secret: Option<String>) {
+    *SHARE_SECRET.lock().unwrap_or_else(PoisonError::into_inner) = secret;
+}
+
+fn mac(secret: &str, media_id: i32, expires: i64) -> HmacSha256 {
+    let mut mac =
+        HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(format!("{media_id}:{expires}").as_bytes());
+    mac
+}
+
+/// Signs access to the stream of `media_id` until the unix timestamp `expires`
+///
+/// The token is `<media id>.<expires>.<hex signature>`, the signature covers
+/// both the media id and the expiry so neither can be changed.
+#[must_use]
+pub fn sign(secret: &str, media_id: i32, expires: i64) -> String {
+    let signature = mac(secret, media_id, expires).finalize().into_bytes();
+    format!("{media_id}.{expires}.{}", hex::encode(signature))
+}
+
+/// Checks that `token` is valid at `now`, returns the media it opens
+///
+/// # Errors
+///
+/// Returns why the token does not grant access
+pub fn verify(
+    secret: &str,
+    token: &str,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<i32, ShareTokenError> {
+    let mut parts = token.splitn(3, '.');
+    let (Some(media_id), Some(expires), Some(signature)) =
+        (parts.next(), parts.next(), parts.next())
+    else {
+        return Err(ShareTokenError::Malformed);
+    };
+    let media_id: i32 = media_id.parse().map_err(|_| ShareTokenError::Malformed)?;
+    let expires: i64 = expires.parse().map_err(|_| ShareTokenError::Malformed)?;
+    let signature = hex::decode(signature).map_err(|_| ShareTokenError::Malformed)?;
+    mac(secret, media_id, expires)
+        .verify_slice(&signature)
+        .map_err(|_| ShareTokenError::Invalid)?;
+    if expires <= now.timestamp() {
+        return Err(ShareTokenError::Expired);
+    }
+    Ok(media_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sign, verify, ShareTokenError};
+
+    #[test]
+    fn tokens_only_open_their_media_until_expiry() {
+        let now = chrono::DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let token = sign("secret", 7, 1_700_003_600);
+        assert!(token.starts_with("7.1700003600."));
+        assert_eq!(verify("secret", &token, now), Ok(7));
+
+        // Pointing the token at another media breaks the signature.
+        let other_media = token.replacen("7.", "8.", 1);
+        assert_eq!(
+            verify("secret", &other_media, now),
+            Err(ShareTokenError::Invalid)
+        );
+        assert_eq!(verify("other", &token, now), Err(ShareTokenError::Invalid));
+        let later = chrono::DateTime::from_timestamp(1_700_003_600, 0).unwrap();
+        assert_eq!(
+            verify("secret", &token, later),
+            Err(ShareTokenError::Expired)
+        );
+
+        // Pushing the expiry out breaks the signature.
+        let extended = token.replacen("1700003600", "1800000000", 1);
+        assert_eq!(
+            verify("secret", &extended, now),
+            Err(ShareTokenError::Invalid)
+        );
+        assert_eq!(
+            verify("secret", "garbage", now),
+            Err(ShareTokenError::Malformed)
+        );
+        assert_eq!(
+            verify("secret", "7.1700003600.zz", now),
+            Err(ShareTokenError::Malformed)
+        );
+    }
+}
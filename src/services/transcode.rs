@@ -0,0 +1,182 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, LazyLock, Mutex, OnceLock, PoisonError},
+};
+
+use axum::body::Bytes;
+use futures_util::stream;
+use tokio::{
+    io::AsyncReadExt,
+    process::{Child, ChildStdout, Command},
+    sync::{OwnedSemaphorePermit, Semaphore},
+};
+
+use crate::{job_tracking::manager::TaskManager, ytdlp};
+
+/// Transcodes running at once unless `LOCALTUBE_MAX_TRANSCODES` says otherwise
+pub const DEFAULT_MAX_TRANSCODES: usize = 1;
+
+static TRANSCODE_DEFAULT: OnceLock<bool> = OnceLock::new();
+static TRANSCODE_ENABLED: Mutex<Option<bool>> = Mutex::new(None);
+static TRANSCODER: Mutex<Option<PathBuf>> = Mutex::new(None);
+static TRANSCODES: LazyLock<Arc<Semaphore>> = LazyLock::new(|| {
+    let max = std::env::var("LOCALTUBE_MAX_TRANSCODES")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .filter(|n| *n > 0)
+        .unwrap_or(DEFAULT_MAX_TRANSCODES);
+    Arc::new(Semaphore::new(max))
+});
+
+/// Returns whether medias may be transcoded while streaming
+///
+/// Transcoding keeps a CPU core busy for every viewer, so it is off unless
+/// `LOCALTUBE_TRANSCODE=true`.
+#[must_use]
+pub fn transcode_enabled() -> bool {
+    TRANSCODE_ENABLED
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .unwrap_or_else(|| {
+            *TRANSCODE_DEFAULT.get_or_init(|| {
+                std::env::var("LOCALTUBE_TRANSCODE").is_ok_and(|v| {
+                    matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes")
+                })
+            })
+        })
+}
+
+/// Applies the transcode setting, `None` falls back to `LOCALTUBE_TRANSCODE`
+pub fn set_transcode_enabled(enabled: Option<bool>) {
+    *TRANSCODE_ENABLED
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner) = enabled;
+}
+
+/// Replaces the ffmpeg executable used for transcoding, `None` restores [`ytdlp::ffmpeg_path`]
+pub fn set_transcoder(program: Option<PathBuf>) {
+    *TRANSCODER.lock().unwrap_or_else(PoisonError::into_inner) = program;
+}
+
+fn transcoder() -> PathBuf {
+    TRANSCODER
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+        .unwrap_or_else(ytdlp::ffmpeg_path)
+}
+
+/// Claims one of the transcode slots, `None` while all of them are in use
+#[must_use]
+pub fn try_claim() -> Option<OwnedSemaphorePermit> {
+    Arc::clone(&TRANSCODES).try_acquire_owned().ok()
+}
+
+/// Returns the ffmpeg arguments turning `input` into fragmented mp4 on stdout
+///
+/// The output is written as it is produced, so it has no index to seek in;
+/// `start` skips that many seconds of the input instead.
+#[must_use]
+pub fn transcode_args(input: &Path, start: Option<f64>) -> Vec<String> {
+    let mut args = ["-hide_banner", "-loglevel", "error", "-nostdin"]
+        .map(String::from)
+        .to_vec();
+    if let Some(start) = start.filter(|start| start.is_finite() && *start > 0.0) {
+        args.extend(["-ss".to_string(), format!("{start:.3}")]);
+    }
+    args.extend(["-i".to_string(), input.display().to_string()]);
+    args.extend(
+        [
+            "-map",
+            "0:v:0?",
+            "-map",
+            "0:a:0?",
+            "-c:v",
+            "libx264",
+            "-preset",
+            "veryfast",
+            "-pix_fmt",
+            "yuv420p",
+            "-c:a",
+            "aac",
+            "-movflags",
+            "frag_keyframe+empty_moov+default_base_moof",
+            "-f",
+            "mp4",
+            "pipe:1",
+        ]
+        .map(String::from),
+    );
+    args
+}
+
+/// Starts transcoding `input` and returns the output as a response body
+///
+/// ffmpeg is killed once the body is dropped, e.g. when the player
+/// disconnects, and `permit` is released with it.
+///
+/// # Errors
+///
+/// Returns error if ffmpeg cannot be started.
+pub fn transcode_body(
+    input: &Path,
+    start: Option<f64>,
+    permit: OwnedSemaphorePermit,
+) -> std::io::Result<axum::body::Body> {
+    let mut child = Command::new(transcoder())
+        .args(transcode_args(input, start))
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::null())
+        .kill_on_drop(true)
+        .spawn()?;
+    let stdout = child
+        .stdout
+        .take()
+        .ok_or_else(|| std::io::Error::other("ffmpeg has no stdout"))?;
+
+    type State = (Child, ChildStdout, OwnedSemaphorePermit);
+    let stream = stream::unfold::<State, _, _, _>(
+        (child, stdout, permit),
+        |(child, mut stdout, permit)| async move {
+            let mut buffer = vec![0u8; 64 * 1024];
+            match stdout.read(&mut buffer).await {
+                Ok(0) => None,
+                Ok(read) => {
+                    TaskManager::global().record_stream_bytes(read as u64);
+                    buffer.truncate(read);
+                    Some((
+                        Ok::<Bytes, std::io::Error>(Bytes::from(buffer)),
+                        (child, stdout, permit),
+                    ))
+                }
+                Err(err) => Some((Err(err), (child, stdout, permit))),
+            }
+        },
+    );
+    Ok(axum::body::Body::from_stream(stream))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::transcode_args;
+
+    #[test]
+    fn transcode_writes_fragmented_mp4_to_stdout() {
+        let args = transcode_args(Path::new("/media/a.mkv"), None);
+        assert!(!args.contains(&"-ss".to_string()));
+        let input = args.iter().position(|arg| arg == "-i").expect("input arg");
+        assert_eq!(args[input + 1], "/media/a.mkv");
+        assert_eq!(args.last().map(String::as_str), Some("pipe:1"));
+        assert!(args.contains(&"frag_keyframe+empty_moov+default_base_moof".to_string()));
+
+        let args = transcode_args(Path::new("/media/a.mkv"), Some(90.5));
+        let start = args.iter().position(|arg| arg == "-ss").expect("start arg");
+        assert_eq!(args[start + 1], "90.500");
+        let input = args.iter().position(|arg| arg == "-i").expect("input arg");
+        assert!(start < input, "seeking the input is fast");
+        assert!(!transcode_args(Path::new("a"), Some(-1.0)).contains(&"-ss".to_string()));
+    }
+}
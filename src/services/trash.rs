@@ -0,0 +1,161 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{Mutex, OnceLock, PoisonError},
+};
+
+use loco_rs::prelude::*;
+use tracing::{info, warn};
+
+use crate::models::_entities::{medias, sources};
+
+/// Folder under a media root the files of trashed medias are kept in
+pub const TRASH_DIR: &str = ".trash";
+
+static TRASH_DEFAULT: OnceLock<u32> = OnceLock::new();
+static TRASH_DAYS: Mutex<Option<u32>> = Mutex::new(None);
+
+/// Returns for how many days removed medias stay in the trash, 0 when there is no trash
+///
+/// Configured through `LOCALTUBE_TRASH_DAYS`. Without it removed medias are
+/// deleted right away.
+#[must_use]
+pub fn trash_days() -> u32 {
+    TRASH_DAYS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .unwrap_or_else(|| {
+            *TRASH_DEFAULT.get_or_init(|| {
+                std::env::var("LOCALTUBE_TRASH_DAYS")
+                    .ok()
+                    .and_then(|v| v.trim().parse().ok())
+                    .unwrap_or(0)
+            })
+        })
+}
+
+/// Applies the trash setting, `None` falls back to `LOCALTUBE_TRASH_DAYS`
+pub fn set_trash_days(days: Option<u32>) {
+    *TRASH_DAYS.lock().unwrap_or_else(PoisonError::into_inner) = days;
+}
+
+/// Returns the trash folder of `media_root`
+#[must_use]
+pub fn trash_root(media_root: &Path) -> PathBuf {
+    media_root.join(TRASH_DIR)
+}
+
+/// Removes a media, into the trash when [`trash_days`] is set
+///
+/// Trashed medias keep their row with `trashed_at` set, so the files can be
+/// put back with [`restore_media`]. Without a trash the files and the row are
+/// deleted. Copies recorded as its duplicates are released either way, so
+/// they get downloaded again.
+///
+/// # Errors
+///
+/// Returns an error if the files cannot be moved or removed or the row cannot
+/// be updated or deleted.
+pub async fn discard_media(
+    db: &DatabaseConnection,
+    media: medias::Model,
+    media_root: &Path,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<()> {
+    crate::services::dedupe::release_duplicates(db, media.id).await?;
+    if trash_days() == 0 {
+        media.remove_media_files(media_root)?;
+        media.delete(db).await?;
+        return Ok(());
+    }
+    media.move_media_files(media_root, &trash_root(media_root))?;
+    medias::ActiveModel {
+        id: Set(media.id),
+        trashed_at: Set(Some(now)),
+        ..Default::default()
+    }
+    .update(db)
+    .await?;
+    Ok(())
+}
+
+/// Moves the files of a trashed media back and takes it out of the trash
+///
+/// # Errors
+///
+/// Returns an error if the media is not in the trash, the files cannot be
+/// moved or the row cannot be updated.
+pub async fn restore_media(
+    db: &DatabaseConnection,
+    media: &medias::Model,
+    media_root: &Path,
+) -> Result<medias::Model> {
+    if media.trashed_at.is_none() {
+        return Err(Error::BadRequest("Media is not in the trash".to_string()));
+    }
+    media.move_media_files(&trash_root(media_root), media_root)?;
+    Ok(medias::ActiveModel {
+        id: Set(media.id),
+        trashed_at: Set(None),
+        ..Default::default()
+    }
+    .update(db)
+    .await?)
+}
+
+/// Removes the files of the trashed medias of `source`
+///
+/// Deleting a source cascades to its rows, so this runs first or
+/// [`empty_trash`] never sees the files again.
+///
+/// # Errors
+///
+/// Returns an error if the trashed medias cannot be loaded or their files
+/// cannot be removed.
+pub async fn discard_source_trash(db: &DatabaseConnection, source: &sources::Model) -> Result<()> {
+    let trashed = medias::Entity::find()
+        .filter(medias::Column::SourceId.eq(source.id))
+        .filter(medias::Column::TrashedAt.is_not_null())
+        .all(db)
+        .await?;
+    let trash_root = trash_root(&source.media_root());
+    for media in trashed {
+        media.remove_media_files(&trash_root)?;
+    }
+    Ok(())
+}
+
+/// Deletes the medias trashed more than [`trash_days`] before `now`, returns how many
+///
+/// A media whose files cannot be removed stays in the trash for the next run.
+///
+/// # Errors
+///
+/// Returns an error if the trashed medias cannot be loaded.
+pub async fn empty_trash(
+    db: &DatabaseConnection,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<usize> {
+    let days = trash_days();
+    if days == 0 {
+        return Ok(0);
+    }
+    let cutoff = now - chrono::Duration::days(i64::from(days));
+    let expired = medias::Entity::find()
+        .filter(medias::Column::TrashedAt.lte(cutoff))
+        .find_also_related(sources::Entity)
+        .all(db)
+        .await?;
+    let mut deleted = 0;
+    for (media, source) in expired {
+        let media_root = crate::models::sources::media_root_of(source.as_ref());
+        if let Err(err) = media.remove_media_files(&trash_root(&media_root)) {
+            warn!(media_id = media.id, error = %err, "Failed to empty trashed media");
+            continue;
+        }
+        let media_id = media.id;
+        media.delete(db).await?;
+        info!(media_id, "Deleted trashed media");
+        deleted += 1;
+    }
+    Ok(deleted)
+}
@@ -0,0 +1,66 @@
+use axum::response::{
+    sse::{Event, KeepAlive, Sse},
+    IntoResponse,
+};
+use futures_util::stream::{self, Stream, StreamExt};
+use serde::Serialize;
+use std::{convert::Infallible, time::Duration};
+use tracing::info;
+
+use crate::job_tracking::{
+    manager::{broadcast_updates, TaskManager},
+    metrics::AllMetrics,
+    task::TaskUpdate,
+};
+
+/// Payload carried by a single status event
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum StatusEvent {
+    Tasks(TaskUpdate),
+    Metrics(AllMetrics),
+}
+
+impl StatusEvent {
+    #[must_use]
+    pub fn event_name(&self) -> &'static str {
+        match self {
+            Self::Tasks(_) => "tasks",
+            Self::Metrics(_) => "metrics",
+        }
+    }
+}
+
+/// Subscribes to task and metrics updates of the given manager
+///
+/// The stream starts with the current task list and metrics, followed by
+/// every broadcast update.
+pub fn status_events(task_manager: &TaskManager) -> impl Stream<Item = StatusEvent> {
+    let updates = stream::select(
+        broadcast_updates(task_manager.tx.subscribe()).map(StatusEvent::Tasks),
+        broadcast_updates(task_manager.subscribe_metrics()).map(StatusEvent::Metrics),
+    );
+    let current = [
+        StatusEvent::Tasks(task_manager.task_snapshot()),
+        StatusEvent::Metrics(task_manager.get_metrics()),
+    ];
+
+    stream::iter(current).chain(updates)
+}
+
+/// Streams status updates as Server-Sent Events
+///
+/// Carries the same payloads as `/ws/status` for clients behind proxies
+/// that do not handle WebSockets.
+pub async fn sse_handler() -> impl IntoResponse {
+    info!("SSE connection established at /status/events");
+    let events = status_events(TaskManager::global()).filter_map(|event| async move {
+        Event::default()
+            .event(event.event_name())
+            .json_data(&event)
+            .ok()
+            .map(Ok::<_, Infallible>)
+    });
+
+    Sse::new(events).keep_alive(KeepAlive::new().interval(Duration::from_secs(5)))
+}
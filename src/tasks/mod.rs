@@ -1,3 +1,5 @@
 pub mod seed;
 
+pub mod prune;
+pub mod redownload_media;
 pub mod refresh_indexes;
@@ -0,0 +1,42 @@
+use loco_rs::prelude::*;
+
+use crate::services::{
+    prune::prune_empty_source_dirs,
+    retention::{apply_retention, RetentionReport},
+    trash::empty_trash,
+};
+
+pub struct Prune;
+#[async_trait]
+impl Task for Prune {
+    fn task(&self) -> TaskInfo {
+        TaskInfo {
+            name: "prune".to_string(),
+            detail:
+                "Remove the medias of all sources outside their retention window or video count"
+                    .to_string(),
+        }
+    }
+    #[allow(clippy::cast_precision_loss)]
+    async fn run(&self, ctx: &AppContext, _vars: &task::Vars) -> Result<()> {
+        let sources = crate::models::sources::Sources::find().all(&ctx.db).await?;
+        let now = chrono::Utc::now();
+
+        let mut report = RetentionReport::default();
+        for source in &sources {
+            report.add(apply_retention(&ctx.db, source, now).await?);
+        }
+        if report.removed > 0 {
+            prune_empty_source_dirs(ctx).await?;
+        }
+        let emptied = empty_trash(&ctx.db, now).await?;
+        // Precision loss is irrelevant for a human readable size.
+        let freed_mib = report.freed_bytes as f64 / (1024.0 * 1024.0);
+        tracing::info!(
+            "Removed {} medias of {} sources, freed {freed_mib:.1} MiB, emptied {emptied} from the trash",
+            report.removed,
+            sources.len(),
+        );
+        Ok(())
+    }
+}
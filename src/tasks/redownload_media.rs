@@ -0,0 +1,32 @@
+use loco_rs::prelude::*;
+
+use crate::{
+    models::{
+        _entities::{medias, sources},
+        sources::media_root_of,
+    },
+    workers::fetch_media::FetchMediaWorker,
+};
+
+pub struct RedownloadMedia;
+#[async_trait]
+impl Task for RedownloadMedia {
+    fn task(&self) -> TaskInfo {
+        TaskInfo {
+            name: "redownload_media".to_string(),
+            detail: "Remove the files of a media and download it again (media_id:<id>)".to_string(),
+        }
+    }
+    async fn run(&self, ctx: &AppContext, vars: &task::Vars) -> Result<()> {
+        let media_id: i32 = vars
+            .cli_arg("media_id")?
+            .parse()
+            .map_err(|_| Error::string("media_id must be a number"))?;
+        let (media, source) = medias::Entity::find_by_id(media_id)
+            .find_also_related(sources::Entity)
+            .one(&ctx.db)
+            .await?
+            .ok_or_else(|| Error::string(&format!("Media {media_id} not found")))?;
+        FetchMediaWorker::redownload(ctx, &media, &media_root_of(source.as_ref())).await
+    }
+}
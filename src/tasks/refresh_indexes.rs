@@ -1,6 +1,61 @@
+use std::future::Future;
+
+use futures_util::{stream, StreamExt};
 use loco_rs::prelude::*;
 
-use crate::workers::fetch_source_info::FetchSourceInfoWorker;
+use crate::{models::_entities::sources, workers::fetch_source_info::FetchSourceInfoWorker, ytdlp};
+
+/// Returns how many sources the sweep checks at once
+///
+/// Follows the refresh concurrency, but never holds more database connections
+/// than the pool has.
+#[must_use]
+pub fn sweep_concurrency(max_connections: u32) -> usize {
+    let pool = usize::try_from(max_connections).unwrap_or(usize::MAX);
+    ytdlp::refresh_concurrency_limit().min(pool).max(1)
+}
+
+/// Calls `schedule` for every source due at `now`, or every source with `force`
+///
+/// Up to `limit` sources are handled at once. Every source is handled even if
+/// scheduling another one failed, the first error is returned afterwards.
+///
+/// # Errors
+///
+/// Returns the first error of `schedule`
+pub async fn sweep_sources<F, Fut>(
+    sources: Vec<sources::Model>,
+    now: chrono::DateTime<chrono::Utc>,
+    force: bool,
+    limit: usize,
+    schedule: F,
+) -> Result<usize>
+where
+    F: Fn(sources::Model) -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    let results: Vec<Result<bool>> = stream::iter(sources)
+        .map(|source| {
+            let due = force || source.is_refresh_due_at(now);
+            let scheduled = due.then(|| schedule(source));
+            async move {
+                match scheduled {
+                    Some(scheduled) => scheduled.await.map(|()| true),
+                    None => Ok(false),
+                }
+            }
+        })
+        .buffer_unordered(limit.max(1))
+        .collect()
+        .await;
+    let mut scheduled = 0;
+    for result in results {
+        if result? {
+            scheduled += 1;
+        }
+    }
+    Ok(scheduled)
+}
 
 pub struct RefreshIndexes;
 #[async_trait]
@@ -13,28 +68,15 @@ impl Task for RefreshIndexes {
     }
     async fn run(&self, ctx: &AppContext, vars: &task::Vars) -> Result<()> {
         let sources = crate::models::sources::Sources::find().all(&ctx.db).await?;
-
-        for source in sources {
-            let jitter = source
-                .last_refreshed_at
-                .map_or(0, |last_refresh| (last_refresh.timestamp() % 1800) - 900);
-
-            let is_time_passed = |timestamp: Option<chrono::DateTime<chrono::Utc>>| -> bool {
-                timestamp.is_none_or(|d| {
-                    (chrono::Utc::now() - d).num_seconds()
-                        > i64::from(source.refresh_frequency * 3600) + jitter
-                })
-            };
-
-            let need_refresh = is_time_passed(source.last_refreshed_at);
-            let need_schedule = is_time_passed(source.last_scheduled_refresh);
-
-            if (source.get_metadata().is_none() || need_refresh) && need_schedule
-                || vars.cli_arg("force").is_ok()
-            {
-                FetchSourceInfoWorker::schedule_refresh(ctx, source.id).await?;
-            }
-        }
+        let full = vars.cli_arg("full").is_ok();
+        sweep_sources(
+            sources,
+            chrono::Utc::now(),
+            vars.cli_arg("force").is_ok(),
+            sweep_concurrency(ctx.config.database.max_connections),
+            |source| FetchSourceInfoWorker::schedule_refresh(ctx, source.id, full),
+        )
+        .await?;
         Ok(())
     }
 }
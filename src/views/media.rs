@@ -25,13 +25,15 @@ pub fn show(
     v: &impl ViewRenderer,
     item: &medias::Model,
     source: Option<&sources::Model>,
+    subtitles: &[String],
 ) -> Result<Response> {
     format::render().view(
         v,
         "media/show.html",
         data!({
             "item": item,
-            "source": source
+            "source": source,
+            "subtitles": subtitles
         }),
     )
 }
@@ -3,4 +3,5 @@ pub mod auth;
 pub mod source;
 
 pub mod media;
+pub mod settings;
 pub mod status;
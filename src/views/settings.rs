@@ -0,0 +1,22 @@
+use loco_rs::prelude::*;
+
+use crate::{models::settings::GlobalSettings, ytdlp};
+
+/// Render the settings form.
+///
+/// # Errors
+///
+/// When there is an issue with rendering the view.
+#[allow(clippy::result_large_err)]
+pub fn edit(v: &impl ViewRenderer, settings: &GlobalSettings) -> Result<Response> {
+    format::render().view(
+        v,
+        "settings/edit.html",
+        data!({
+            "settings": settings,
+            "default_concurrency": ytdlp::default_concurrency_limit(),
+            "max_concurrency": ytdlp::MAX_CONCURRENCY,
+            "max_concurrent_fragments": ytdlp::MAX_CONCURRENT_FRAGMENTS,
+        }),
+    )
+}
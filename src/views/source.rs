@@ -1,15 +1,71 @@
+use std::collections::HashMap;
+
 use loco_rs::prelude::*;
 
-use crate::models::_entities::sources;
+use crate::{
+    controllers::listing::PageInfo,
+    models::{
+        _entities::sources,
+        sources::{IndexState, SourceStats},
+    },
+};
+
+/// Describes how long ago `at` was, in hours up to two days and in days after
+#[must_use]
+pub fn time_ago(at: chrono::DateTime<chrono::Utc>, now: chrono::DateTime<chrono::Utc>) -> String {
+    let hours = (now - at).num_hours().max(0);
+    match hours {
+        0 => "less than an hour ago".to_string(),
+        1 => "1 hour ago".to_string(),
+        2..48 => format!("{hours} hours ago"),
+        _ => format!("{} days ago", hours / 24),
+    }
+}
 
-/// Render a list view of sources.
+/// Render a list view of sources, `indexed` holds the media count per source id.
+///
+/// `page` places the sources in the whole list and `q` is the searched text.
 ///
 /// # Errors
 ///
 /// When there is an issue with rendering the view.
 #[allow(clippy::result_large_err)]
-pub fn list(v: &impl ViewRenderer, items: &Vec<sources::Model>) -> Result<Response> {
-    format::render().view(v, "source/list.html", data!({"items": items}))
+pub fn list(
+    v: &impl ViewRenderer,
+    items: &Vec<sources::Model>,
+    indexed: &HashMap<i32, i64>,
+    page: &PageInfo,
+    q: Option<&str>,
+) -> Result<Response> {
+    let stale_ids: Vec<i32> = items
+        .iter()
+        .filter(|item| item.is_stale())
+        .map(|item| item.id)
+        .collect();
+    let now = chrono::Utc::now();
+    let last_download_ago: HashMap<String, String> = items
+        .iter()
+        .filter_map(|item| Some((item.id.to_string(), time_ago(item.last_download_at?, now))))
+        .collect();
+    let index_states: HashMap<String, IndexState> = items
+        .iter()
+        .map(|item| {
+            let count = indexed.get(&item.id).copied().unwrap_or_default();
+            (item.id.to_string(), item.index_state(count))
+        })
+        .collect();
+    format::render().view(
+        v,
+        "source/list.html",
+        data!({
+            "items": items,
+            "stale_ids": stale_ids,
+            "last_download_ago": last_download_ago,
+            "index_states": index_states,
+            "page": page,
+            "q": q,
+        }),
+    )
 }
 
 /// Render a single source view.
@@ -18,8 +74,17 @@ pub fn list(v: &impl ViewRenderer, items: &Vec<sources::Model>) -> Result<Respon
 ///
 /// When there is an issue with rendering the view.
 #[allow(clippy::result_large_err)]
-pub fn show(v: &impl ViewRenderer, item: &sources::Model) -> Result<Response> {
-    format::render().view(v, "source/show.html", data!({"item": item}))
+pub fn show(v: &impl ViewRenderer, item: &sources::Model, stats: &SourceStats) -> Result<Response> {
+    format::render().view(
+        v,
+        "source/show.html",
+        data!({
+            "item": item,
+            "stats": stats,
+            "stale": item.is_stale(),
+            "index_state": item.index_state(stats.indexed),
+        }),
+    )
 }
 
 /// Render a source create form.
@@ -1,6 +1,7 @@
 use loco_rs::prelude::*;
 use serde::Serialize;
 
+use crate::gluetun::supervisor::GluetunStatus;
 use crate::job_tracking::{
     metrics::{
         AllMetrics, TaskMetrics, MAX_CONSECUTIVE_FAILURES_BEFORE_RESTART,
@@ -8,6 +9,7 @@ use crate::job_tracking::{
     },
     task::TaskType,
 };
+use crate::services::deps::InstallRecord;
 
 #[derive(Serialize)]
 struct TaskEntry {
@@ -20,7 +22,12 @@ struct TaskEntry {
 /// # Errors
 ///
 /// Returns an error if rendering the status template fails.
-pub fn show(v: &impl ViewRenderer, metrics: &AllMetrics) -> Result<Response> {
+pub fn show(
+    v: &impl ViewRenderer,
+    metrics: &AllMetrics,
+    gluetun: &GluetunStatus,
+    deps: Option<&InstallRecord>,
+) -> Result<Response> {
     let mut tasks: Vec<TaskEntry> = metrics
         .tasks
         .iter()
@@ -40,10 +47,13 @@ pub fn show(v: &impl ViewRenderer, metrics: &AllMetrics) -> Result<Response> {
         "status/index.html",
         data!({
             "gluetun_enabled": metrics.gluetun_enabled,
+            "gluetun": gluetun,
             "gluetun_restart_failure_threshold": MAX_CONSECUTIVE_FAILURES_BEFORE_RESTART,
             "gluetun_restart_min_success_age_minutes": min_success_age_minutes,
             "tasks": tasks,
             "download_metrics": download_metrics,
+            "download_speed": metrics.download_speed,
+            "deps": deps,
         }),
     )
 }
@@ -0,0 +1,172 @@
+use std::{
+    sync::{Mutex, OnceLock, PoisonError},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tracing::{info, warn};
+
+static WEBHOOK_URL: OnceLock<Option<String>> = OnceLock::new();
+static DEFAULT_NOTIFY_REMOVALS: OnceLock<bool> = OnceLock::new();
+static NOTIFY_REMOVALS: Mutex<Option<bool>> = Mutex::new(None);
+
+/// Returns the URL configured through `LOCALTUBE_WEBHOOK_URL`, if any
+#[must_use]
+pub fn webhook_url() -> Option<&'static str> {
+    WEBHOOK_URL
+        .get_or_init(|| {
+            std::env::var("LOCALTUBE_WEBHOOK_URL")
+                .ok()
+                .map(|url| url.trim().to_string())
+                .filter(|url| !url.is_empty())
+        })
+        .as_deref()
+}
+
+/// Returns whether removed medias are reported to the webhook
+///
+/// Off unless `LOCALTUBE_WEBHOOK_MEDIA_REMOVED=true`, retention can remove
+/// many medias at once. The events only go out once the files are deleted or trashed.
+#[must_use]
+pub fn notify_removals() -> bool {
+    NOTIFY_REMOVALS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .unwrap_or_else(|| {
+            *DEFAULT_NOTIFY_REMOVALS.get_or_init(|| {
+                std::env::var("LOCALTUBE_WEBHOOK_MEDIA_REMOVED").is_ok_and(|v| {
+                    matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes")
+                })
+            })
+        })
+}
+
+/// Applies the removal events setting, `None` falls back to `LOCALTUBE_WEBHOOK_MEDIA_REMOVED`
+pub fn set_notify_removals(enabled: Option<bool>) {
+    *NOTIFY_REMOVALS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner) = enabled;
+}
+
+/// Why a media was removed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RemovalReason {
+    /// Published before the `fetch_last_days` window of its source
+    Retention,
+}
+
+/// Payload posted to the webhook, tagged by `event`
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum WebhookEvent {
+    /// A source refreshed fine but has not published anything for a while
+    SourceStale {
+        source_id: i32,
+        url: String,
+        uploader: Option<String>,
+        last_video_at: Option<DateTime<Utc>>,
+        stale_after_days: i64,
+    },
+    /// A media file finished downloading
+    MediaDownloaded {
+        source_id: i32,
+        media_id: i32,
+        title: String,
+        url: String,
+        media_path: String,
+    },
+    /// A downloaded media was removed
+    ///
+    /// Sent after the removal, the file is no longer at `media_path` and
+    /// cannot be copied in response.
+    MediaRemoved {
+        source_id: i32,
+        media_id: i32,
+        title: String,
+        url: String,
+        media_path: Option<String>,
+        uploader: Option<String>,
+        reason: RemovalReason,
+    },
+}
+
+/// Returns the webhook a source's events go to, its own URL wins over the global one
+#[must_use]
+pub fn url_for(source_url: Option<&str>) -> Option<&str> {
+    pick_url(source_url, webhook_url())
+}
+
+fn pick_url<'a>(source_url: Option<&'a str>, global: Option<&'a str>) -> Option<&'a str> {
+    source_url
+        .map(str::trim)
+        .filter(|url| !url.is_empty())
+        .or(global)
+}
+
+/// Posts `event` as JSON to `url`
+///
+/// Delivery failures are only logged, notifications are best effort.
+pub async fn send_to(url: &str, event: &WebhookEvent) {
+    let client = match reqwest::Client::builder()
+        .user_agent("localtube-webhook")
+        .timeout(Duration::from_secs(10))
+        .build()
+    {
+        Ok(client) => client,
+        Err(err) => {
+            warn!(error = %err, "Failed to build webhook client");
+            return;
+        }
+    };
+    match client
+        .post(url)
+        .json(event)
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+    {
+        Ok(_) => info!(?event, "Webhook delivered"),
+        Err(err) => warn!(error = %err, ?event, "Webhook delivery failed"),
+    }
+}
+
+/// Posts `event` to the global webhook when one is configured
+pub async fn notify(event: &WebhookEvent) {
+    if let Some(url) = webhook_url() {
+        send_to(url, event).await;
+    }
+}
+
+/// Posts an event of `source` to its webhook, falling back to the global one
+pub async fn notify_source(
+    source: &crate::models::_entities::sources::Model,
+    event: &WebhookEvent,
+) {
+    if let Some(url) = url_for(source.webhook_url.as_deref()) {
+        send_to(url, event).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GLOBAL: Option<&str> = Some("https://global.example.com/hook");
+
+    #[test]
+    fn source_url_overrides_global() {
+        assert_eq!(
+            pick_url(Some(" https://chat.example.com/hook "), GLOBAL),
+            Some("https://chat.example.com/hook")
+        );
+    }
+
+    #[test]
+    fn blank_source_url_falls_back_to_global() {
+        assert_eq!(pick_url(Some("  "), GLOBAL), GLOBAL);
+        assert_eq!(pick_url(None, GLOBAL), GLOBAL);
+        assert_eq!(pick_url(None, None), None);
+    }
+}
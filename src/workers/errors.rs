@@ -0,0 +1,208 @@
+use std::sync::OnceLock;
+
+/// Default length of the error snippet shown on a failed task, in characters
+pub const DEFAULT_ERROR_SNIPPET_CHARS: usize = 500;
+
+static ERROR_SNIPPET_CHARS: OnceLock<usize> = OnceLock::new();
+
+/// Returns the longest error snippet kept on a failed task
+///
+/// Configured through `LOCALTUBE_ERROR_SNIPPET_CHARS`.
+#[must_use]
+pub fn error_snippet_chars() -> usize {
+    *ERROR_SNIPPET_CHARS.get_or_init(|| {
+        std::env::var("LOCALTUBE_ERROR_SNIPPET_CHARS")
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .filter(|n| *n > 0)
+            .unwrap_or(DEFAULT_ERROR_SNIPPET_CHARS)
+    })
+}
+
+/// Picks the line of a possibly multi-line error that explains the failure
+///
+/// yt-dlp prints warnings and progress before the actual `ERROR:` line, so
+/// that line is preferred over the first one. The result is cut to
+/// `max_chars` characters.
+#[must_use]
+pub fn error_summary(text: &str, max_chars: usize) -> String {
+    let lines = || text.lines().map(str::trim).filter(|line| !line.is_empty());
+    let line = lines()
+        .find(|line| line.starts_with("ERROR:"))
+        .or_else(|| lines().next())
+        .unwrap_or("Unknown error");
+    match line.char_indices().nth(max_chars) {
+        Some((cut, _)) => format!("{}…", &line[..cut]),
+        None => line.to_string(),
+    }
+}
+
+/// Returns whether the error is the site rate limiting us, e.g. `HTTP Error 429: Too Many Requests`
+#[must_use]
+pub fn is_rate_limited(text: &str) -> bool {
+    text.contains("HTTP Error 429") || text.to_ascii_lowercase().contains("too many requests")
+}
+
+/// Returns whether the video is restricted to channel members, e.g.
+/// `This video is available to this channel's members on level: ...`
+#[must_use]
+pub fn is_members_only(text: &str) -> bool {
+    let text = text.to_ascii_lowercase();
+    (text.contains("available to") && text.contains("members"))
+        || text.contains("members-only content")
+}
+
+/// Returns whether the video is a premiere or stream that has not started yet
+#[must_use]
+pub fn is_upcoming(text: &str) -> bool {
+    let text = text.to_ascii_lowercase();
+    text.contains("this live event will begin")
+        || text.contains("premieres in")
+        || text.contains("premiere will begin")
+}
+
+/// Returns whether the listed channel is gone, e.g. after a handle change:
+/// `ERROR: [youtube:tab] @old: This channel does not exist.`
+///
+/// A plain HTTP 404 is not enough, YouTube answers some transient failures with it.
+#[must_use]
+pub fn is_channel_gone(text: &str) -> bool {
+    let text = text.to_ascii_lowercase();
+    text.contains("this channel does not exist") || text.contains("channel not found")
+}
+
+/// Start of the skip reason of a video left out because of its age restriction
+pub const AGE_RESTRICTED: &str = "Age-restricted";
+
+/// Returns whether the video needs an adult account or is above the `--age-limit`, e.g.
+/// `Sign in to confirm your age. This video may be inappropriate for some users.`
+#[must_use]
+pub fn is_age_restricted(text: &str) -> bool {
+    let text = text.to_ascii_lowercase();
+    text.contains("confirm your age")
+        || text.contains("because it is age restricted")
+        || text.contains("age-restricted")
+}
+
+/// Start of the error of a download left out because of the source maximum file size
+pub const MAX_FILESIZE_EXCEEDED: &str = "File is larger than max-filesize";
+
+/// Returns whether yt-dlp aborted the download for exceeding `--max-filesize`, e.g.
+/// `File is larger than max-filesize (1048576 bytes > 1024 bytes). Aborting.`
+#[must_use]
+pub fn is_over_max_filesize(text: &str) -> bool {
+    text.to_ascii_lowercase()
+        .contains("larger than max-filesize")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        error_summary, is_age_restricted, is_channel_gone, is_members_only, is_rate_limited,
+        is_upcoming,
+    };
+
+    #[test]
+    fn detects_age_restricted_videos() {
+        assert!(is_age_restricted(
+            "ERROR: [youtube] abc: Sign in to confirm your age. \
+             This video may be inappropriate for some users."
+        ));
+        assert!(is_age_restricted(
+            "[download] Skipping \"Title\" because it is age restricted"
+        ));
+        assert!(!is_age_restricted(
+            "ERROR: [youtube] abc: Video unavailable"
+        ));
+        assert!(!is_age_restricted(
+            "ERROR: [youtube] abc: Sign in to confirm you're not a bot"
+        ));
+    }
+
+    #[test]
+    fn detects_gone_channels() {
+        assert!(is_channel_gone(
+            "ERROR: [youtube:tab] @old: This channel does not exist."
+        ));
+        assert!(!is_channel_gone(
+            "ERROR: [youtube:tab] Unable to download API page: HTTP Error 404: Not Found"
+        ));
+        assert!(!is_channel_gone(
+            "ERROR: [youtube:tab] @old: HTTP Error 429: Too Many Requests"
+        ));
+    }
+
+    #[test]
+    fn detects_upcoming_videos() {
+        assert!(is_upcoming(
+            "ERROR: [youtube] abc: This live event will begin in 3 hours."
+        ));
+        assert!(is_upcoming("ERROR: [youtube] abc: Premieres in 25 minutes"));
+        assert!(!is_upcoming("ERROR: [youtube] abc: Video unavailable"));
+    }
+
+    #[test]
+    fn detects_members_only_videos() {
+        assert!(is_members_only(
+            "ERROR: [youtube] abc: This video is available to this channel's members on level: \
+             Supporter (or any higher level). Join this channel to get access to perks"
+        ));
+        assert!(is_members_only(
+            "ERROR: [youtube] abc: Join this channel to get access to members-only content like this video"
+        ));
+        assert!(!is_members_only("ERROR: [youtube] abc: Video unavailable"));
+        assert!(!is_members_only(
+            "ERROR: [youtube] abc: HTTP Error 429: Too Many Requests"
+        ));
+    }
+
+    #[test]
+    fn detects_rate_limiting() {
+        assert!(is_rate_limited(
+            "Download failed: ERROR: [youtube] abc: Unable to download webpage: \
+             HTTP Error 429: Too Many Requests (caused by <HTTPError 429>)"
+        ));
+        assert!(is_rate_limited("ERROR: too many requests, slow down"));
+        assert!(!is_rate_limited(
+            "ERROR: [youtube] abc: HTTP Error 403: Forbidden"
+        ));
+        assert!(!is_rate_limited("ERROR: [youtube] abc: Video unavailable"));
+    }
+
+    #[test]
+    fn prefers_ytdlp_error_line() {
+        let output = "WARNING: [youtube] abc: nsig extraction failed\n\
+                      [youtube] abc: Downloading webpage\n\
+                      ERROR: [youtube] abc: Sign in to confirm you're not a bot\n";
+        assert_eq!(
+            error_summary(output, 500),
+            "ERROR: [youtube] abc: Sign in to confirm you're not a bot"
+        );
+    }
+
+    #[test]
+    fn falls_back_to_first_non_empty_line() {
+        let output = "\n  \nEOF while parsing a value at line 1 column 0\ncaused by stdout\n";
+        assert_eq!(
+            error_summary(output, 500),
+            "EOF while parsing a value at line 1 column 0"
+        );
+        assert_eq!(error_summary("  \n", 500), "Unknown error");
+    }
+
+    #[test]
+    fn keeps_first_of_several_errors() {
+        let output = "ERROR: [youtube] abc: Video unavailable\n\
+                      ERROR: Postprocessing: Conversion failed!";
+        assert_eq!(
+            error_summary(output, 500),
+            "ERROR: [youtube] abc: Video unavailable"
+        );
+    }
+
+    #[test]
+    fn truncates_on_char_boundary() {
+        assert_eq!(error_summary("ERROR: zażółć gęślą", 10), "ERROR: zaż…");
+        assert_eq!(error_summary("ERROR: short", 12), "ERROR: short");
+    }
+}
@@ -1,13 +1,37 @@
-use std::time::Duration;
+use std::{sync::LazyLock, time::Duration};
 
 use loco_rs::prelude::*;
 use serde::{Deserialize, Serialize};
-use tracing::{error, info};
+use tracing::{debug, error, info, warn};
 
-use crate::job_tracking::{manager::register_download_task, task::ActiveTask};
-use crate::services::retry::RetryScheduler;
+use crate::job_tracking::{
+    manager::{register_download_task, TaskManager},
+    task::{ActiveTask, TaskType},
+};
+use crate::services::{
+    dedupe::{self, DedupeMode},
+    deps,
+    in_flight::InFlight,
+    rate_limit::{self, RateLimitBackoff},
+    retry::{RetryDelays, RetryScheduler},
+};
+use crate::webhook::{self, WebhookEvent};
+use crate::workers::errors;
 
-const RETRY_DELAY: Duration = Duration::from_secs(5 * 60);
+static MEDIA_DOWNLOADS: LazyLock<InFlight<i32>> = LazyLock::new(InFlight::new);
+static PENDING_RETRIES: LazyLock<InFlight<i32>> = LazyLock::new(InFlight::new);
+
+/// Returns the set of media ids with a download in progress
+#[must_use]
+pub fn media_downloads() -> &'static InFlight<i32> {
+    &MEDIA_DOWNLOADS
+}
+
+/// Returns the set of media ids waiting for a scheduled retry
+#[must_use]
+pub fn pending_retries() -> &'static InFlight<i32> {
+    &PENDING_RETRIES
+}
 
 pub struct FetchMediaWorker {
     pub ctx: AppContext,
@@ -18,6 +42,42 @@ pub struct FetchMediaWorkerArgs {
     pub media_id: i32,
 }
 
+impl FetchMediaWorker {
+    /// Removes the downloaded files of the media, clears its path and skip reason and queues a new download
+    ///
+    /// `media_root` is the media root of the source of the media. Trashed
+    /// medias have to be restored first, their files are not in `media_root`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the media is in the trash, the files cannot be
+    /// removed, the media record cannot be updated or enqueueing the job fails.
+    pub async fn redownload(
+        ctx: &AppContext,
+        media: &crate::models::_entities::medias::Model,
+        media_root: &std::path::Path,
+    ) -> Result<()> {
+        if media.trashed_at.is_some() {
+            return Err(Error::BadRequest(
+                "Media is in the trash, restore it first".to_string(),
+            ));
+        }
+        media.remove_media_files(media_root)?;
+
+        crate::models::_entities::medias::ActiveModel {
+            id: Set(media.id),
+            media_path: Set(None),
+            skip_reason: Set(None),
+            ..Default::default()
+        }
+        .update(&ctx.db)
+        .await?;
+
+        Self::perform_later(ctx, FetchMediaWorkerArgs { media_id: media.id }).await?;
+        Ok(())
+    }
+}
+
 #[async_trait]
 impl BackgroundWorker<FetchMediaWorkerArgs> for FetchMediaWorker {
     fn build(ctx: &AppContext) -> Self {
@@ -26,9 +86,20 @@ impl BackgroundWorker<FetchMediaWorkerArgs> for FetchMediaWorker {
     async fn perform(&self, args: FetchMediaWorkerArgs) -> Result<()> {
         // Store ActiveTask (not queued)
         let mut task: Option<ActiveTask> = None;
+        let mut source_id = None;
+
+        // Another worker already owns this media; it will set the path when done.
+        let Some(_claim) = media_downloads().try_claim(args.media_id) else {
+            info!(
+                media_id = args.media_id,
+                "Download already in progress, skipping"
+            );
+            return Ok(());
+        };
 
         // Try to execute the download operation
-        let result = async {
+        let result: Result<()> = async {
+            // Loaded after claiming so a download that just finished is observed.
             let media = crate::models::medias::Medias::find_by_id(args.media_id)
                 .one(&self.ctx.db)
                 .await
@@ -37,7 +108,22 @@ impl BackgroundWorker<FetchMediaWorkerArgs> for FetchMediaWorker {
                 return Ok(());
             }
             let media = media.unwrap();
-            if media.media_path.is_some() {
+            // Trashed medias were removed on purpose, they are not downloaded again. Skipped
+            // ones would only be skipped again until the source limits change.
+            if media.media_path.is_some()
+                || media.trashed_at.is_some()
+                || media.skip_reason.is_some()
+            {
+                return Ok(());
+            }
+            // Premieres and streams cannot be downloaded before they start.
+            if let Some(delay) = media.deferred_for(chrono::Utc::now()) {
+                info!(
+                    media_id = media.id,
+                    "Media is not available yet, postponing download by {}s",
+                    delay.as_secs()
+                );
+                schedule_media_retry(self.ctx.clone(), media.id, delay);
                 return Ok(());
             }
 
@@ -56,6 +142,69 @@ impl BackgroundWorker<FetchMediaWorkerArgs> for FetchMediaWorker {
                 return Ok(());
             }
             let source = source.unwrap();
+            source_id = Some(source.id);
+
+            // The next refresh queues the media again once downloads are resumed.
+            if source.download_paused {
+                info!(
+                    media_id = media.id,
+                    source_id = source.id,
+                    "Downloads of the source are paused, skipping {}",
+                    &metadata.title
+                );
+                return Ok(());
+            }
+
+            // Hours long stream recordings are rarely wanted and known before downloading,
+            // like a rating above the source age limit.
+            if let Some(reason) = source
+                .max_duration_skip_reason(metadata.duration)
+                .or_else(|| source.age_limit_skip_reason(metadata.age_limit))
+            {
+                info!(
+                    media_id = media.id,
+                    source_id = source.id,
+                    "Skipping {}: {reason}",
+                    &metadata.title
+                );
+                crate::models::_entities::medias::ActiveModel {
+                    id: Set(media.id),
+                    skip_reason: Set(Some(reason)),
+                    ..Default::default()
+                }
+                .update(&self.ctx.db)
+                .await?;
+                return Ok(());
+            }
+
+            // Fresh uploads are often still edited or taken down again.
+            if let Some(remaining) =
+                source.min_age_remaining(metadata.timestamp, chrono::Utc::now())
+            {
+                info!(
+                    media_id = media.id,
+                    source_id = source.id,
+                    "Media is younger than the source minimum age, postponing download by {}s",
+                    remaining.as_secs()
+                );
+                schedule_media_retry(self.ctx.clone(), media.id, remaining);
+                return Ok(());
+            }
+
+            if rate_limit::pause_rate_limited_sources() {
+                if let Some(remaining) =
+                    RateLimitBackoff::global().remaining(source.id, std::time::Instant::now())
+                {
+                    info!(
+                        media_id = media.id,
+                        source_id = source.id,
+                        "Source is rate limited, postponing download by {}s",
+                        remaining.as_secs()
+                    );
+                    schedule_media_retry(self.ctx.clone(), media.id, remaining);
+                    return Ok(());
+                }
+            }
 
             let source_metadata = source.get_metadata();
             if source_metadata.is_none() {
@@ -63,13 +212,59 @@ impl BackgroundWorker<FetchMediaWorkerArgs> for FetchMediaWorker {
             }
             let source_metadata = source_metadata.unwrap();
 
+            let mode = dedupe::dedupe_mode();
+            if mode != DedupeMode::Allow {
+                if let Some((canonical, canonical_source)) =
+                    dedupe::find_canonical(&self.ctx.db, &media).await?
+                {
+                    let media_path = match mode {
+                        DedupeMode::Link => Some(dedupe::link_duplicate(
+                            &canonical,
+                            &canonical_source,
+                            &source,
+                        )?),
+                        _ => None,
+                    };
+                    info!(
+                        media_id = media.id,
+                        canonical_id = canonical.id,
+                        "{}: {} is already downloaded by another source, {}",
+                        &source_metadata.source_provider,
+                        &metadata.title,
+                        if media_path.is_some() {
+                            "linked it"
+                        } else {
+                            "skipping it"
+                        }
+                    );
+                    crate::models::_entities::medias::ActiveModel {
+                        id: Set(media.id),
+                        file_size: Set(media_path.as_ref().and(canonical.file_size)),
+                        media_path: Set(media_path),
+                        duplicate_of: Set(Some(canonical.id)),
+                        ..Default::default()
+                    }
+                    .update(&self.ctx.db)
+                    .await?;
+                    return Ok(());
+                }
+            }
+
+            // Right after boot yt-dlp and ffmpeg may still be installing.
+            if !deps::wait_until_ready(deps::READY_TIMEOUT).await {
+                return Err(Error::string("yt-dlp and ffmpeg are not installed yet"));
+            }
+
             // Register task as Queued
-            let queued = register_download_task(metadata.title.clone());
+            let queued = register_download_task(metadata.title.clone(), source.id, media.id);
 
             // Acquire semaphore and transition to Active
             // This is where the task actually waits if semaphore is full!
             let active = queued
-                .start(crate::ytdlp::ytdtp_concurrency().clone())
+                .start_weighted(
+                    crate::ytdlp::ytdtp_concurrency().clone(),
+                    crate::ytdlp::download_permits(source.download_weight),
+                )
                 .await;
             active.update_status("Downloading...".to_string());
 
@@ -80,10 +275,34 @@ impl BackgroundWorker<FetchMediaWorkerArgs> for FetchMediaWorker {
                 &source_metadata.source_provider, &metadata.title,
             );
 
+            let archive_entry = media
+                .video_id
+                .clone()
+                .map(|video_id| crate::ytdlp::ArchiveEntry {
+                    extractor_key: metadata.extractor_key.clone(),
+                    video_id,
+                });
+            let report_speed = |speed| {
+                if let Some(task) = &task {
+                    task.update_speed(speed);
+                }
+            };
             // This is where errors are most likely to happen
-            let file_path = crate::ytdlp::download_media(&metadata.original_url, &source)
+            let download = crate::ytdlp::download_media(
+                &metadata.original_url,
+                &source,
+                media.id,
+                archive_entry.as_ref(),
+                &report_speed,
+            )
+            .await
+            .map_err(|e| Error::string(&format!("Download failed: {e}")))?;
+            let file_path = download.media_path.clone();
+            let file_size = tokio::fs::metadata(source.media_root().join(&file_path))
                 .await
-                .map_err(|e| Error::string(&format!("Download failed: {e}")))?;
+                .map(|m| m.len())
+                .ok()
+                .or(download.filesize);
 
             info!(
                 "{} Downloaded {} to {}",
@@ -92,34 +311,150 @@ impl BackgroundWorker<FetchMediaWorkerArgs> for FetchMediaWorker {
 
             let media_update = crate::models::_entities::medias::ActiveModel {
                 id: Set(media.id),
-                media_path: Set(Some(file_path)),
+                media_path: Set(Some(file_path.clone())),
+                file_size: Set(file_size.and_then(|size| i64::try_from(size).ok())),
+                downloaded_format: Set(download.format_id.clone()),
+                downloaded_height: Set(download.downloaded_height()),
+                skip_reason: Set(None),
                 ..Default::default()
             };
-            crate::models::medias::Medias::update(media_update)
-                .exec(&self.ctx.db)
+            media_update.update(&self.ctx.db).await?;
+            source
+                .record_download(&self.ctx.db, chrono::Utc::now())
                 .await?;
+            RateLimitBackoff::global().clear(source.id);
+
+            webhook::notify_source(
+                &source,
+                &WebhookEvent::MediaDownloaded {
+                    source_id: source.id,
+                    media_id: media.id,
+                    title: metadata.title.clone(),
+                    url: metadata.original_url.clone(),
+                    media_path: file_path,
+                },
+            )
+            .await;
 
             Ok(())
         }
         .await;
 
         // Handle errors if any - only mark failed if we still have the task
+        if let Some(e) = result
+            .as_ref()
+            .err()
+            .filter(|e| errors::is_upcoming(&e.to_string()))
+        {
+            // Not a failure: the task is dropped without counting towards the failure streak.
+            drop(task.take());
+            let available_at = chrono::Utc::now() + crate::ytdlp::LIVE_RECHECK_DELAY;
+            info!(
+                media_id = args.media_id,
+                "Media is not available yet, retrying at {available_at}: {e}"
+            );
+            crate::models::_entities::medias::ActiveModel {
+                id: Set(args.media_id),
+                available_at: Set(Some(available_at)),
+                ..Default::default()
+            }
+            .update(&self.ctx.db)
+            .await?;
+            schedule_media_retry(
+                self.ctx.clone(),
+                args.media_id,
+                crate::ytdlp::LIVE_RECHECK_DELAY,
+            );
+            return Ok(());
+        }
+        if let Some(e) = result
+            .as_ref()
+            .err()
+            .filter(|e| errors::is_over_max_filesize(&e.to_string()))
+        {
+            // Not a failure either, a retry would be aborted at the same size.
+            drop(task.take());
+            let reason = e.to_string();
+            let reason = reason.trim_start_matches("Download failed: ");
+            info!(media_id = args.media_id, "Skipping media: {reason}");
+            crate::models::_entities::medias::ActiveModel {
+                id: Set(args.media_id),
+                skip_reason: Set(Some(reason.to_string())),
+                ..Default::default()
+            }
+            .update(&self.ctx.db)
+            .await?;
+            return Ok(());
+        }
+        if let Some(e) = result
+            .as_ref()
+            .err()
+            .filter(|e| errors::is_age_restricted(&e.to_string()))
+        {
+            // Retrying changes nothing until cookies of an adult account are set up.
+            drop(task.take());
+            TaskManager::global().record_permanent_failure(TaskType::DownloadVideo);
+            let reason = format!(
+                "{}: {}",
+                errors::AGE_RESTRICTED,
+                errors::error_summary(&e.to_string(), errors::error_snippet_chars())
+            );
+            info!(media_id = args.media_id, "Skipping media: {reason}");
+            crate::download_log::append_error(args.media_id, &e.to_string()).await;
+            crate::models::_entities::medias::ActiveModel {
+                id: Set(args.media_id),
+                skip_reason: Set(Some(reason)),
+                ..Default::default()
+            }
+            .update(&self.ctx.db)
+            .await?;
+            return Ok(());
+        }
         if let Err(e) = &result {
             error!("Download failed: {}", e);
+            let members_only = errors::is_members_only(&e.to_string());
 
             // Report the error if we have a task
             if let Some(t) = task.take() {
+                let max_chars = errors::error_snippet_chars();
                 let error_msg = match e {
-                    Error::Message(msg) => msg.clone(),
+                    Error::Message(msg) => errors::error_summary(msg, max_chars),
                     _ => format!(
                         "Download failed: {}",
-                        e.to_string().split('\n').next().unwrap_or("Unknown error")
+                        errors::error_summary(&e.to_string(), max_chars)
                     ),
                 };
-                t.mark_failed(error_msg);
+                if members_only {
+                    t.mark_failed(format!(
+                        "Members-only video, check that the yt-dlp cookies belong to a channel member: {error_msg}"
+                    ));
+                } else {
+                    t.mark_failed(error_msg);
+                }
+            }
+            // The task only keeps a snippet, the log has the whole error.
+            crate::download_log::append_error(args.media_id, &e.to_string()).await;
+
+            // Retrying does not help until the cookies are fixed.
+            if members_only {
+                warn!(
+                    media_id = args.media_id,
+                    "Members-only video, not retrying until the cookies are fixed"
+                );
+                TaskManager::global().record_permanent_failure(TaskType::DownloadVideo);
+                return result;
             }
 
-            schedule_media_retry(self.ctx.clone(), args.media_id);
+            // Retrying a rate limited download right away only prolongs the ban.
+            let delay = RateLimitBackoff::global().record_failure(
+                source_id,
+                &e.to_string(),
+                RetryDelays::global().delay,
+                std::time::Instant::now(),
+            );
+            // Only failed attempts count, postponed downloads are not retries.
+            TaskManager::global().record_retry(TaskType::DownloadVideo);
+            schedule_media_retry(self.ctx.clone(), args.media_id, delay);
         } else {
             // On success, mark the task as complete for metrics
             if let Some(t) = task.take() {
@@ -132,15 +467,26 @@ impl BackgroundWorker<FetchMediaWorkerArgs> for FetchMediaWorker {
     }
 }
 
-fn schedule_media_retry(ctx: AppContext, media_id: i32) {
-    info!(media_id, "Rescheduling media download in 5 minutes");
+fn schedule_media_retry(ctx: AppContext, media_id: i32, delay: Duration) {
+    // Every refresh queues a waiting media again, the timer already scheduled covers it.
+    let Some(pending) = pending_retries().try_claim(media_id) else {
+        debug!(media_id, "Media download already rescheduled");
+        return;
+    };
+    info!(
+        media_id,
+        "Rescheduling media download in {} minutes",
+        delay.as_secs().div_ceil(60)
+    );
 
     let check_ctx = ctx.clone();
     let action_ctx = ctx;
 
     RetryScheduler::spawn_detached(
-        RETRY_DELAY,
+        delay,
         move || {
+            // Released once it fired, the download may be postponed again from here on.
+            drop(pending);
             let ctx = check_ctx.clone();
             async move {
                 let media = crate::models::medias::Medias::find_by_id(media_id)
@@ -1,12 +1,15 @@
 use loco_rs::prelude::*;
-use sea_orm::{Condition, Set};
+use sea_orm::Set;
 use serde::{Deserialize, Serialize};
 use tracing::{error, info, warn};
 
 use crate::{
     job_tracking::{manager::register_refresh_task, task::ActiveTask},
     models::medias::MediaMetadata,
-    workers::fetch_media::{FetchMediaWorker, FetchMediaWorkerArgs},
+    workers::{
+        errors,
+        fetch_media::{FetchMediaWorker, FetchMediaWorkerArgs},
+    },
     ytdlp::{self, ListProbeMode, MediaListOrder, SourceListKind, SourceListOrder},
 };
 use crate::{
@@ -14,8 +17,16 @@ use crate::{
         _entities::{
             medias::ActiveModel as MediaActiveModel, sources::ActiveModel as SourceActiveModel,
         },
-        sources::SourceMetadata,
+        sources::{channel_tab_suffix, follow_moved_channels, stale_source_days, SourceMetadata},
+    },
+    services::{
+        deps,
+        prune::prune_empty_source_dirs,
+        refresh_runs::RefreshRuns,
+        relocate::{move_renamed_sources, move_source_dir},
+        retention::{apply_retention, retention_cutoff},
     },
+    webhook::{self, WebhookEvent},
     ytdlp::{probe_list_metadata, probe_list_tabs, stream_media_list, SourceListTabOption},
 };
 
@@ -78,19 +89,13 @@ fn strip_query_fragment(value: &str) -> &str {
 }
 
 fn matches_known_tab_suffix(value: &str) -> bool {
-    value.ends_with("/videos")
-        || value.ends_with("/streams")
-        || value.ends_with("/shorts")
-        || value.ends_with("/playlists")
+    channel_tab_suffix(value).is_some()
 }
 
 fn strip_known_tab_suffix(path: &str) -> &str {
-    for suffix in ["/videos", "/streams", "/shorts", "/playlists"] {
-        if let Some(stripped) = path.strip_suffix(suffix) {
-            return stripped;
-        }
-    }
-    path
+    channel_tab_suffix(path)
+        .and_then(|suffix| path.strip_suffix(suffix))
+        .unwrap_or(path)
 }
 
 fn tab_matches_source(tab_url: &str, source_url: &str) -> bool {
@@ -120,6 +125,9 @@ pub struct FetchSourceInfoWorker {
 #[derive(Deserialize, Debug, Serialize)]
 pub struct FetchSourceInfoWorkerArgs {
     pub source_id: i32,
+    /// Lists the whole source instead of only what is newer than its checkpoint
+    #[serde(default)]
+    pub full_reindex: bool,
 }
 
 impl FetchSourceInfoWorker {
@@ -128,19 +136,28 @@ impl FetchSourceInfoWorker {
     /// # Errors
     ///
     /// Returns an error if updating the source record or enqueueing the job fails.
-    pub async fn schedule_refresh(ctx: &AppContext, source_id: i32) -> Result<()> {
+    pub async fn schedule_refresh(
+        ctx: &AppContext,
+        source_id: i32,
+        full_reindex: bool,
+    ) -> Result<()> {
         // Update the last_scheduled_refresh timestamp before scheduling the job
         let source_update = SourceActiveModel {
             id: Set(source_id),
             last_scheduled_refresh: Set(Some(chrono::Utc::now())),
             ..Default::default()
         };
-        crate::models::sources::Sources::update(source_update)
-            .exec(&ctx.db)
-            .await?;
+        source_update.update(&ctx.db).await?;
 
         // Now schedule the actual job
-        Self::perform_later(ctx, FetchSourceInfoWorkerArgs { source_id }).await?;
+        Self::perform_later(
+            ctx,
+            FetchSourceInfoWorkerArgs {
+                source_id,
+                full_reindex,
+            },
+        )
+        .await?;
         Ok(())
     }
 }
@@ -154,6 +171,9 @@ impl BackgroundWorker<FetchSourceInfoWorkerArgs> for FetchSourceInfoWorker {
     async fn perform(&self, args: FetchSourceInfoWorkerArgs) -> Result<()> {
         // Store ActiveTask (not queued)
         let mut task: Option<ActiveTask> = None;
+        // Loaded only after a superseded refresh of the source stopped, so this
+        // run sees the settings it was queued for.
+        let run = RefreshRuns::global().start(args.source_id).await;
 
         // Try to execute the source info fetching operation
         let result = async {
@@ -164,6 +184,9 @@ impl BackgroundWorker<FetchSourceInfoWorkerArgs> for FetchSourceInfoWorker {
 
             if let Some(source) = source {
                 info!("Fetching source info for {}", source.url);
+                if !deps::wait_until_ready(deps::READY_TIMEOUT).await {
+                    return Err(Error::string("yt-dlp and ffmpeg are not installed yet"));
+                }
 
                 // Register the task with the TaskManager
                 let task_title = format!(
@@ -174,19 +197,22 @@ impl BackgroundWorker<FetchSourceInfoWorkerArgs> for FetchSourceInfoWorker {
                 );
 
                 // Register task as Queued
-                let queued = register_refresh_task(task_title);
+                let queued = register_refresh_task(task_title, source.id);
 
                 // Acquire semaphore and transition to Active
                 // This is where the task actually waits if semaphore is full!
                 let active = queued
-                    .start(crate::ytdlp::ytdtp_concurrency().clone())
+                    .start(crate::ytdlp::refresh_concurrency().clone())
                     .await;
+                // Time spent waiting for a refresh slot is not the source being slow.
+                let started = std::time::Instant::now();
                 active.update_status("Fetching channel metadata...".to_string());
 
                 task = Some(active);
 
                 let existing_metadata = source.get_metadata();
-                let list_tabs = match probe_list_tabs(&source.url).await {
+                let client = ytdlp::ClientOptions::for_source(&source);
+                let list_tabs = match probe_list_tabs(&source.url, &client).await {
                     Ok(tabs) => tabs,
                     Err(err) => {
                         // Tab probing is best-effort; preserve prior tabs on transient failures.
@@ -244,7 +270,7 @@ impl BackgroundWorker<FetchSourceInfoWorkerArgs> for FetchSourceInfoWorker {
                 };
                 let probe_mode = probe_mode_for(existing_metadata.as_ref());
                 // Delegate list detection to yt-dlp so all providers stay supported.
-                let probe = probe_list_metadata(&effective_url, probe_mode)
+                let probe = probe_list_metadata(&effective_url, probe_mode, &client)
                     .await
                     .map_err(|e| Error::string(&format!("Failed to probe source metadata: {e}")))?;
                 let list_kind = Some(probe.list_kind);
@@ -270,10 +296,19 @@ impl BackgroundWorker<FetchSourceInfoWorkerArgs> for FetchSourceInfoWorker {
                     }
                 });
                 let order_known = list_order.is_some();
-                let uploader = probe
-                    .uploader
-                    .or_else(|| existing_metadata.as_ref().map(|m| m.uploader.clone()))
-                    .unwrap_or_else(|| source.url.clone());
+                let existing_uploader = existing_metadata
+                    .as_ref()
+                    .map(|m| m.uploader.clone())
+                    // The URL only stands in until a name is known.
+                    .filter(|uploader| uploader != &source.url);
+                // Playlists indexed before they were named after their title keep
+                // the name, their downloads are in the folder named after it.
+                let uploader = if probe.playlist {
+                    existing_uploader.or(probe.uploader)
+                } else {
+                    probe.uploader.or(existing_uploader)
+                }
+                .unwrap_or_else(|| source.url.clone());
                 let source_provider = probe
                     .source_provider
                     .or_else(|| {
@@ -282,6 +317,11 @@ impl BackgroundWorker<FetchSourceInfoWorkerArgs> for FetchSourceInfoWorker {
                             .map(|m| m.source_provider.clone())
                     })
                     .unwrap_or_else(|| "unknown".to_string());
+                if !ytdlp::extractor_allowed(ytdlp::allowed_extractors(), &source_provider) {
+                    return Err(Error::string(&format!(
+                        "Extractor {source_provider} is not in LOCALTUBE_ALLOWED_EXTRACTORS"
+                    )));
+                }
                 // For single videos, enforce a count of 1 to keep UI consistent.
                 let items = match list_kind {
                     Some(SourceListKind::Video) => 1,
@@ -306,6 +346,11 @@ impl BackgroundWorker<FetchSourceInfoWorkerArgs> for FetchSourceInfoWorker {
                     list_order,
                     list_tab,
                     list_tabs,
+                    channel_url: probe.channel_url.clone().or_else(|| {
+                        existing_metadata
+                            .as_ref()
+                            .and_then(|m| m.channel_url.clone())
+                    }),
                 };
 
                 let source_update = SourceActiveModel {
@@ -316,18 +361,19 @@ impl BackgroundWorker<FetchSourceInfoWorkerArgs> for FetchSourceInfoWorker {
                     )),
                     ..Default::default()
                 };
-                crate::models::sources::Sources::update(source_update)
-                    .exec(&self.ctx.db)
-                    .await?;
+                source_update.update(&self.ctx.db).await?;
+
+                if let Some(previous) = existing_metadata.as_ref() {
+                    relocate_renamed_source(&self.ctx.db, &source, previous, &source_metadata)
+                        .await;
+                }
 
                 if let Some(task) = &task {
                     task.update_status("Fetching video list...".to_string());
                 }
 
-                let fetch_before_timestamp = chrono::Utc::now()
-                    .checked_sub_signed(chrono::Duration::days(i64::from(source.fetch_last_days)))
-                    .unwrap()
-                    .timestamp();
+                let fetch_before_timestamp =
+                    retention_cutoff(source.fetch_last_days, chrono::Utc::now());
 
                 let should_stop_early = should_stop_early(
                     source_metadata.list_kind.as_ref(),
@@ -337,9 +383,36 @@ impl BackgroundWorker<FetchSourceInfoWorkerArgs> for FetchSourceInfoWorker {
                     source_metadata.list_kind.as_ref(),
                     source_metadata.list_order.as_ref(),
                 );
-                let mut media_stream = stream_media_list(&effective_url, stream_order).await;
+                // The date cutoff below still applies, so whichever limit is tighter wins.
+                let max_items = source
+                    .max_items
+                    .and_then(|n| u32::try_from(n).ok())
+                    .filter(|n| *n > 0);
+                let date_after = source
+                    .incremental_date_after(args.full_reindex)
+                    .map(|date| ytdlp::DateAfter {
+                        date,
+                        newest_first: should_stop_early && order_known,
+                    });
+                if let Some(date_after) = &date_after {
+                    info!(
+                        "{}: Incremental refresh from {}",
+                        source_metadata.uploader, date_after.date
+                    );
+                }
+                let mut media_stream = stream_media_list(
+                    &effective_url,
+                    stream_order,
+                    max_items,
+                    date_after,
+                    &client,
+                    run.token(),
+                )
+                .await;
                 let mut media_count = 0;
+                let mut listed = std::collections::HashSet::new();
                 let mut saw_newer_item = false;
+                let mut newest_seen: Option<i64> = None;
 
                 while let Some(item) = media_stream.recv().await {
                     let metadata = match item {
@@ -347,6 +420,17 @@ impl BackgroundWorker<FetchSourceInfoWorkerArgs> for FetchSourceInfoWorker {
                         Err(err) => return Err(err),
                     };
                     media_count += 1;
+                    // Playlists may mix in videos of other sites.
+                    if !ytdlp::extractor_allowed(
+                        ytdlp::allowed_extractors(),
+                        &metadata.extractor_key,
+                    ) {
+                        warn!(
+                            "{}: Skipping {} from disallowed extractor {}",
+                            source_metadata.uploader, metadata.title, metadata.extractor_key
+                        );
+                        continue;
+                    }
 
                     if let Some(task) = &task {
                         task.update_status(format!(
@@ -356,10 +440,12 @@ impl BackgroundWorker<FetchSourceInfoWorkerArgs> for FetchSourceInfoWorker {
                     }
 
                     let mut download_media_id = None;
+                    let backlog = source.is_backlog(metadata.timestamp);
                     info!(
                         "{}: Fetching media info for {}",
                         &source_metadata.uploader, &metadata.title
                     );
+                    newest_seen = newest_seen.max(Some(metadata.timestamp));
                     if metadata.timestamp >= fetch_before_timestamp {
                         saw_newer_item = true;
                     }
@@ -371,63 +457,71 @@ impl BackgroundWorker<FetchSourceInfoWorkerArgs> for FetchSourceInfoWorker {
                         break;
                     }
 
-                    // try to find existing media by url
-                    let media = crate::models::medias::Medias::find()
-                        .filter(
-                            Condition::all()
-                                .add(
-                                    crate::models::_entities::medias::Column::SourceId
-                                        .eq(source.id),
-                                )
-                                .add(
-                                    crate::models::_entities::medias::Column::Url
-                                        .contains(&metadata.original_url),
-                                ),
-                        )
-                        .one(&self.ctx.db)
-                        .await
-                        .map_err(Box::from)?;
+                    let media = crate::models::_entities::medias::Model::find_by_video_id(
+                        &self.ctx.db,
+                        source.id,
+                        &metadata.id,
+                        &metadata.original_url,
+                    )
+                    .await
+                    .map_err(Box::from)?;
 
+                    let video_id = metadata.id.clone();
+                    let available_at = metadata
+                        .available_at(chrono::Utc::now().timestamp())
+                        .and_then(|at| chrono::DateTime::from_timestamp(at, 0));
                     let media_metadata: MediaMetadata = metadata.into();
                     if let Some(media) = media {
-                        if media.media_path.is_none() {
+                        listed.insert(media.id);
+                        if media.media_path.is_none()
+                            && media.trashed_at.is_none()
+                            && media.skip_reason.is_none()
+                            && !backlog
+                        {
                             download_media_id = Some(media.id);
                         }
 
                         let mut media_update = MediaActiveModel {
                             id: Set(media.id),
+                            video_id: Set(Some(video_id)),
+                            duration: Set(i64::try_from(media_metadata.duration).ok()),
                             metadata: Set(Some(
                                 serde_json::to_value(media_metadata.clone()).map_err(Error::msg)?,
                             )),
+                            available_at: Set(available_at),
                             ..Default::default()
                         };
 
-                        if let Some(media_path) = &media.media_path {
-                            if !ytdlp::media_directory().join(media_path).exists() {
-                                warn!(
-                                    "{}: Media file not found for {} expected file in {}",
-                                    &source_metadata.uploader, &media_metadata.title, media_path
-                                );
-                                media_update.media_path = Set(None);
-                                download_media_id = Some(media.id);
-                            }
+                        if media.file_is_missing(&source.media_root()) {
+                            warn!(
+                                "{}: Media file not found for {} expected file in {}",
+                                &source_metadata.uploader,
+                                &media_metadata.title,
+                                media.media_path.as_deref().unwrap_or_default()
+                            );
+                            media_update.media_path = Set(None);
+                            download_media_id = Some(media.id);
                         }
-                        crate::models::medias::Medias::update(media_update)
-                            .exec(&self.ctx.db)
-                            .await?;
+                        media_update.update(&self.ctx.db).await?;
                     } else {
                         let media_insert = MediaActiveModel {
                             source_id: Set(source.id),
                             url: Set(media_metadata.original_url.clone()),
+                            video_id: Set(Some(video_id)),
+                            duration: Set(i64::try_from(media_metadata.duration).ok()),
                             metadata: Set(Some(
                                 serde_json::to_value(media_metadata).map_err(Error::msg)?,
                             )),
+                            available_at: Set(available_at),
                             ..Default::default()
                         };
                         let media = crate::models::medias::Medias::insert(media_insert)
                             .exec(&self.ctx.db)
                             .await?;
-                        download_media_id = Some(media.last_insert_id);
+                        listed.insert(media.last_insert_id);
+                        if !backlog {
+                            download_media_id = Some(media.last_insert_id);
+                        }
                     }
                     if let Some(media_id) = download_media_id {
                         FetchMediaWorker::perform_later(
@@ -438,42 +532,72 @@ impl BackgroundWorker<FetchSourceInfoWorkerArgs> for FetchSourceInfoWorker {
                     }
                 }
 
-                if let Some(task) = &task {
-                    task.update_status("Cleaning up old videos...".to_string());
+                if run.is_cancelled() {
+                    // A newer refresh with the changed settings takes over from here.
+                    info!("{}: Refresh cancelled", source_metadata.uploader);
+                    if let Some(task) = &task {
+                        task.update_status("Cancelled, settings changed".to_string());
+                    }
+                    return Ok(());
                 }
 
-                // select all media that were created after the fetch_before_timestamp
-                // this info is stored in metadata.timestamp, so we need to load all media for source in batches and check the timestamp
+                if date_after.is_some() {
+                    requeue_unlisted_medias(
+                        &self.ctx,
+                        &source,
+                        &listed,
+                        fetch_before_timestamp,
+                        &source_metadata.uploader,
+                    )
+                    .await?;
+                }
+
+                // Only stored once the list was fully processed, an interrupted run
+                // has to start over so it does not skip the items it never reached.
+                let checkpoint = newest_seen
+                    .and_then(|timestamp| chrono::DateTime::from_timestamp(timestamp, 0))
+                    .max(source.index_checkpoint);
+                if checkpoint != source.index_checkpoint {
+                    let source_update = SourceActiveModel {
+                        id: Set(source.id),
+                        index_checkpoint: Set(checkpoint),
+                        ..Default::default()
+                    };
+                    source_update.update(&self.ctx.db).await?;
+                }
 
-                let medias = crate::models::medias::Medias::find()
-                    .filter(crate::models::_entities::medias::Column::SourceId.eq(source.id))
-                    .all(&self.ctx.db)
+                source
+                    .record_download_baseline(&self.ctx.db, newest_seen)
                     .await?;
 
-                for media in medias {
-                    if let Some(metadata) = media.get_metadata() {
-                        if metadata.timestamp < fetch_before_timestamp && media.media_path.is_some()
-                        {
-                            info!(
-                                "{}: Removing old media {}",
-                                &source_metadata.uploader, &metadata.title
-                            );
-                            media.remove_media_files()?;
-                            media.delete(&self.ctx.db).await?;
-                        }
+                // Before retention, which may remove the newest media of a dormant source.
+                let refreshed = source
+                    .refresh_last_video_at(&self.ctx.db, newest_seen)
+                    .await?;
+                notify_if_became_stale(&source, &refreshed, &source_metadata).await;
+
+                if let Some(task) = &task {
+                    task.update_status("Cleaning up old videos...".to_string());
+                }
+
+                let retention =
+                    apply_retention(&self.ctx.db, &refreshed, chrono::Utc::now()).await?;
+                if retention.removed > 0 {
+                    if let Err(err) = prune_empty_source_dirs(&self.ctx).await {
+                        warn!("Failed to prune empty source directories: {}", err);
                     }
                 }
 
-                let source_update = SourceActiveModel {
-                    id: Set(source.id),
-                    last_refreshed_at: Set(Some(chrono::Utc::now())),
-                    ..Default::default()
-                };
-                crate::models::sources::Sources::update(source_update)
-                    .exec(&self.ctx.db)
+                let duration = started.elapsed();
+                source
+                    .record_refresh_success(&self.ctx.db, chrono::Utc::now(), duration)
                     .await?;
 
-                info!("{}: Finished source reindex", source_metadata.uploader);
+                info!(
+                    "{}: Finished source reindex in {:.1}s",
+                    source_metadata.uploader,
+                    duration.as_secs_f64()
+                );
             }
 
             Ok(())
@@ -484,15 +608,43 @@ impl BackgroundWorker<FetchSourceInfoWorkerArgs> for FetchSourceInfoWorker {
         if let Err(e) = &result {
             error!("Source refresh failed: {}", e);
 
+            let max_chars = errors::error_snippet_chars();
+            let error_msg = match e {
+                Error::Message(msg) => errors::error_summary(msg, max_chars),
+                _ => format!(
+                    "Source refresh failed: {}",
+                    errors::error_summary(&e.to_string(), max_chars)
+                ),
+            };
+
+            // Keeps a permanently broken source from failing on every sweep.
+            match crate::models::sources::Sources::find_by_id(args.source_id)
+                .one(&self.ctx.db)
+                .await
+            {
+                Ok(Some(source)) => {
+                    match source
+                        .record_refresh_failure(&self.ctx.db, chrono::Utc::now(), &error_msg)
+                        .await
+                    {
+                        Ok(source) => {
+                            info!(
+                                source_id = source.id,
+                                failures = source.refresh_failures,
+                                "Backing off source refresh until {:?}",
+                                source.refresh_backoff_until
+                            );
+                            follow_moved_channel(&self.ctx, &source).await;
+                        }
+                        Err(err) => warn!("Failed to record refresh backoff: {}", err),
+                    }
+                }
+                Ok(None) => {}
+                Err(err) => warn!("Failed to load source for refresh backoff: {}", err),
+            }
+
             // Report the error if we have a task
             if let Some(t) = task.take() {
-                let error_msg = match e {
-                    Error::Message(msg) => msg.clone(),
-                    _ => format!(
-                        "Source refresh failed: {}",
-                        e.to_string().split('\n').next().unwrap_or("Unknown error")
-                    ),
-                };
                 t.mark_failed(error_msg);
             }
         } else {
@@ -506,3 +658,128 @@ impl BackgroundWorker<FetchSourceInfoWorkerArgs> for FetchSourceInfoWorker {
         result
     }
 }
+
+/// Switches a source whose channel moved to the suggested URL when enabled
+///
+/// Without [`follow_moved_channels`] the suggestion waits on the source page.
+async fn follow_moved_channel(ctx: &AppContext, source: &crate::models::_entities::sources::Model) {
+    let Some(url) = source.suggested_url.as_deref() else {
+        return;
+    };
+    if !follow_moved_channels() {
+        info!(
+            source_id = source.id,
+            "Source channel seems to have moved to {url}"
+        );
+        return;
+    }
+    match source.adopt_suggested_url(&ctx.db).await {
+        Ok(Some(moved)) => {
+            info!(
+                source_id = moved.id,
+                "Following moved channel to {}", moved.url
+            );
+            if let Err(err) = FetchSourceInfoWorker::schedule_refresh(ctx, moved.id, true).await {
+                warn!("Failed to queue refresh of moved source: {}", err);
+            }
+        }
+        Ok(None) => {}
+        Err(err) => warn!("Failed to follow moved channel: {}", err),
+    }
+}
+
+/// Queues the medias an incremental refresh did not list that still need a download
+///
+/// Items older than the checkpoint are never listed again, so a lost retry or
+/// a deleted file would otherwise only be noticed by a full reindex.
+async fn requeue_unlisted_medias(
+    ctx: &AppContext,
+    source: &crate::models::_entities::sources::Model,
+    listed: &std::collections::HashSet<i32>,
+    cutoff: i64,
+    uploader: &str,
+) -> Result<()> {
+    let medias = crate::models::_entities::medias::Model::find_unlisted_downloads(
+        &ctx.db, source, listed, cutoff,
+    )
+    .await?;
+    for media in medias {
+        if media.media_path.is_some() {
+            warn!(
+                "{}: Media file not found for {} expected file in {}",
+                uploader,
+                media.url,
+                media.media_path.as_deref().unwrap_or_default()
+            );
+            MediaActiveModel {
+                id: Set(media.id),
+                media_path: Set(None),
+                ..Default::default()
+            }
+            .update(&ctx.db)
+            .await?;
+        }
+        FetchMediaWorker::perform_later(ctx, FetchMediaWorkerArgs { media_id: media.id }).await?;
+    }
+    Ok(())
+}
+
+/// Follows an uploader rename by moving the files of the source when enabled
+///
+/// Failures are logged, downloads then simply continue in the new folder.
+async fn relocate_renamed_source(
+    db: &DatabaseConnection,
+    source: &crate::models::_entities::sources::Model,
+    previous: &SourceMetadata,
+    current: &SourceMetadata,
+) {
+    let old_dir = ytdlp::sanitize_dir_name(&previous.uploader, source.id);
+    let new_dir = ytdlp::sanitize_dir_name(&current.uploader, source.id);
+    if old_dir == new_dir {
+        return;
+    }
+    if !move_renamed_sources() {
+        warn!(
+            "{}: Uploader renamed from '{}', new media goes to '{new_dir}' while existing files stay in '{old_dir}'; set LOCALTUBE_MOVE_RENAMED_SOURCES=true to move them",
+            current.uploader, previous.uploader
+        );
+        return;
+    }
+    if let Err(err) = move_source_dir(db, &source.media_root(), source.id, &old_dir, &new_dir).await
+    {
+        error!(
+            "{}: Failed to move media files from '{old_dir}' to '{new_dir}': {err}",
+            current.uploader
+        );
+    }
+}
+
+/// Fires the stale source webhook when this refresh made the source stale
+async fn notify_if_became_stale(
+    before: &crate::models::_entities::sources::Model,
+    after: &crate::models::_entities::sources::Model,
+    metadata: &SourceMetadata,
+) {
+    let stale_after_days = stale_source_days();
+    let was_stale = before
+        .last_refreshed_at
+        .is_some_and(|at| before.is_stale_at(stale_after_days, at));
+    if was_stale || !after.is_stale() {
+        return;
+    }
+    warn!(
+        "{}: No new videos for over {} days",
+        metadata.uploader, stale_after_days
+    );
+    webhook::notify_source(
+        after,
+        &WebhookEvent::SourceStale {
+            source_id: after.id,
+            url: after.url.clone(),
+            uploader: Some(metadata.uploader.clone()),
+            last_video_at: after.last_video_at,
+            stale_after_days,
+        },
+    )
+    .await;
+}
@@ -1,4 +1,5 @@
 pub mod downloader;
 
+pub mod errors;
 pub mod fetch_media;
 pub mod fetch_source_info;
@@ -1,46 +1,369 @@
 use crate::ytdlp_debug;
 use loco_rs::{Error, Result};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
-use std::sync::{Arc, OnceLock};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
 use tokio::sync::Semaphore;
-use tokio::{io::AsyncBufReadExt, process::Command};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt},
+    process::Command,
+};
 use tokio_process_terminate::TerminateExt;
-use tracing::{info, warn};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
 use yt_dlp::client::deps::Libraries;
 
-const LIBS_DIR: &str = "libs";
+const DEFAULT_LIBS_DIR: &str = "libs";
 const STREAM_ERROR_MESSAGE: &str = "yt-dlp stream failed; check logs for details";
+static DEFAULT_CONCURRENCY_LIMIT: OnceLock<usize> = OnceLock::new();
+static CONCURRENCY_LIMIT: Mutex<Option<usize>> = Mutex::new(None);
+static DEFAULT_RATE_LIMIT: OnceLock<Option<String>> = OnceLock::new();
+static SPONSORBLOCK_API: Mutex<Option<String>> = Mutex::new(None);
+static DEFAULT_SPONSORBLOCK_API: OnceLock<Option<String>> = OnceLock::new();
+static RATE_LIMIT: Mutex<Option<String>> = Mutex::new(None);
+static ALLOWED_EXTRACTORS: OnceLock<Option<Vec<String>>> = OnceLock::new();
+static CONCURRENT_FRAGMENTS: Mutex<Option<u32>> = Mutex::new(None);
+static DEFAULT_CONCURRENT_FRAGMENTS: OnceLock<u32> = OnceLock::new();
 static CONCURRENCY_SEMAPHORE: OnceLock<Arc<Semaphore>> = OnceLock::new();
+static REFRESH_CONCURRENCY_LIMIT: OnceLock<usize> = OnceLock::new();
+static REFRESH_CONCURRENCY_SEMAPHORE: OnceLock<Arc<Semaphore>> = OnceLock::new();
+static YT_DLP_PROGRAM: Mutex<Option<PathBuf>> = Mutex::new(None);
 
-pub fn ytdtp_concurrency() -> &'static Arc<Semaphore> {
-    const ENV_CONCURRENCY: &str = "LOCALTUBE_YTDLP_CONCURRENCY";
-    CONCURRENCY_SEMAPHORE.get_or_init(|| {
-        let concurrency = std::env::var(ENV_CONCURRENCY)
-            .ok()
-            .and_then(|v| {
-                v.parse::<usize>()
-                    .map_err(|e| {
-                        warn!(
-                            "Warning: {} value '{}' is invalid: {}",
-                            ENV_CONCURRENCY, v, e
-                        );
+pub const MAX_CONCURRENCY: usize = 8;
+
+fn parse_concurrency(env: &str, value: Option<&str>, default: usize) -> usize {
+    let concurrency = value
+        .and_then(|v| {
+            v.parse::<usize>()
+                .map_err(|e| {
+                    warn!("Warning: {} value '{}' is invalid: {}", env, v, e);
+                })
+                .ok()
+        })
+        .unwrap_or(default);
+
+    let limited_concurrency = concurrency.clamp(1, MAX_CONCURRENCY);
+    if limited_concurrency != concurrency {
+        warn!(
+            "Warning: {} value {} is outside allowed range (1-{}), using {}",
+            env, concurrency, MAX_CONCURRENCY, limited_concurrency
+        );
+    }
+    limited_concurrency
+}
+
+fn concurrency_from_env(env: &str, default: usize) -> usize {
+    parse_concurrency(env, std::env::var(env).ok().as_deref(), default)
+}
+
+/// Returns the download concurrency configured through `LOCALTUBE_YTDLP_CONCURRENCY`
+pub fn default_concurrency_limit() -> usize {
+    *DEFAULT_CONCURRENCY_LIMIT
+        .get_or_init(|| concurrency_from_env("LOCALTUBE_YTDLP_CONCURRENCY", 4))
+}
+
+/// Returns the number of permits of the download semaphore
+pub fn ytdtp_concurrency_limit() -> usize {
+    CONCURRENCY_LIMIT
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .unwrap_or_else(default_concurrency_limit)
+}
+
+/// Changes the download concurrency, `None` restores the environment default
+///
+/// Running downloads keep their permits, so a lower limit applies once enough
+/// of them finished.
+pub fn set_concurrency_limit(limit: Option<usize>) {
+    let semaphore = ytdtp_concurrency();
+    let limit = limit.map(|n| n.clamp(1, MAX_CONCURRENCY));
+    let (old, new) = {
+        let mut current = CONCURRENCY_LIMIT
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let old = current.unwrap_or_else(default_concurrency_limit);
+        *current = limit;
+        (old, limit.unwrap_or_else(default_concurrency_limit))
+    };
+    if new != old {
+        info!("yt-dlp concurrency: {}", new);
+    }
+    if new > old {
+        semaphore.add_permits(new - old);
+        crate::job_tracking::manager::TaskManager::global().slots_added(semaphore);
+    } else if new < old {
+        // Takes the released permits before they are handed to the waiting downloads.
+        let semaphore = semaphore.clone();
+        let surplus = u32::try_from(old - new).unwrap_or(u32::MAX);
+        tokio::spawn(async move {
+            if let Ok(permits) = semaphore.acquire_many_owned(surplus).await {
+                permits.forget();
+            }
+        });
+    }
+}
+
+/// Returns the yt-dlp download rate limit, e.g. `4M`
+pub fn rate_limit() -> Option<String> {
+    RATE_LIMIT
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+        .or_else(|| {
+            DEFAULT_RATE_LIMIT
+                .get_or_init(|| {
+                    env_value("LOCALTUBE_YTDLP_RATE_LIMIT").and_then(|v| {
+                        let limit = parse_rate_limit(&v);
+                        if limit.is_none() {
+                            warn!(
+                                "Warning: LOCALTUBE_YTDLP_RATE_LIMIT value '{}' is invalid",
+                                v
+                            );
+                        }
+                        limit
+                    })
+                })
+                .clone()
+        })
+}
+
+/// Changes the download rate limit, `None` restores the environment default
+pub fn set_rate_limit(limit: Option<String>) {
+    *RATE_LIMIT.lock().unwrap_or_else(PoisonError::into_inner) = limit;
+}
+
+/// Returns the SponsorBlock API instance, `None` uses the public one
+pub fn sponsorblock_api() -> Option<String> {
+    SPONSORBLOCK_API
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+        .or_else(|| {
+            DEFAULT_SPONSORBLOCK_API
+                .get_or_init(|| {
+                    env_value("LOCALTUBE_SPONSORBLOCK_API").and_then(|v| {
+                        let url = parse_sponsorblock_api(&v);
+                        if url.is_none() {
+                            warn!(
+                                "Warning: LOCALTUBE_SPONSORBLOCK_API value '{}' is not an http(s) URL",
+                                v
+                            );
+                        }
+                        url
                     })
-                    .ok()
+                })
+                .clone()
+        })
+}
+
+/// Changes the SponsorBlock API instance, `None` restores the environment default
+pub fn set_sponsorblock_api(url: Option<String>) {
+    *SPONSORBLOCK_API
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner) = url;
+}
+
+/// Most fragments of a DASH or HLS download fetched at once
+pub const MAX_CONCURRENT_FRAGMENTS: u32 = 16;
+
+/// Returns how many fragments a download fetches at once, e.g. `4`
+///
+/// Set through `LOCALTUBE_YTDLP_CONCURRENT_FRAGMENTS`, one fragment at a time
+/// like yt-dlp by default.
+pub fn concurrent_fragments() -> u32 {
+    CONCURRENT_FRAGMENTS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .unwrap_or_else(|| {
+            *DEFAULT_CONCURRENT_FRAGMENTS.get_or_init(|| {
+                env_value("LOCALTUBE_YTDLP_CONCURRENT_FRAGMENTS").map_or(1, |v| {
+                    v.trim().parse::<u32>().map_or_else(
+                        |e| {
+                            warn!(
+                                "Warning: LOCALTUBE_YTDLP_CONCURRENT_FRAGMENTS value '{}' is invalid: {}",
+                                v, e
+                            );
+                            1
+                        },
+                        |n| n.clamp(1, MAX_CONCURRENT_FRAGMENTS),
+                    )
+                })
             })
-            .unwrap_or(4);
+        })
+}
 
-        let limited_concurrency = concurrency.clamp(1, 8);
-        if limited_concurrency != concurrency {
-            warn!(
-                "Warning: {} value {} is outside allowed range (1-8), using {}",
-                ENV_CONCURRENCY, concurrency, limited_concurrency
-            );
-        }
+/// Changes the concurrent fragments of new downloads, `None` restores the environment default
+pub fn set_concurrent_fragments(fragments: Option<u32>) {
+    *CONCURRENT_FRAGMENTS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner) = fragments;
+}
+
+/// Returns the extractors sources may use, set through `LOCALTUBE_ALLOWED_EXTRACTORS`
+///
+/// `None` allows every extractor yt-dlp supports.
+pub fn allowed_extractors() -> Option<&'static [String]> {
+    ALLOWED_EXTRACTORS
+        .get_or_init(|| {
+            parse_allowed_extractors(env_value("LOCALTUBE_ALLOWED_EXTRACTORS").as_deref())
+        })
+        .as_deref()
+}
+
+/// Parses a comma separated extractor list, `None` if it names none
+#[must_use]
+pub fn parse_allowed_extractors(value: Option<&str>) -> Option<Vec<String>> {
+    let extractors: Vec<String> = value?
+        .split(',')
+        .map(|name| name.trim().to_ascii_lowercase())
+        .filter(|name| !name.is_empty())
+        .collect();
+    (!extractors.is_empty()).then_some(extractors)
+}
+
+/// Returns whether `allowed` lets through the extractor `extractor_key`
+///
+/// Matching ignores case and an entry also covers the extractors starting
+/// with it, so `youtube` allows channels listed by `YoutubeTab`.
+#[must_use]
+pub fn extractor_allowed(allowed: Option<&[String]>, extractor_key: &str) -> bool {
+    let key = extractor_key.to_ascii_lowercase();
+    allowed.is_none_or(|allowed| allowed.iter().any(|name| key.starts_with(name.as_str())))
+}
+
+/// Normalizes a SponsorBlock API base URL, `None` if it is not an http(s) URL
+#[must_use]
+pub fn parse_sponsorblock_api(value: &str) -> Option<String> {
+    let value = value.trim().trim_end_matches('/');
+    let host = value
+        .strip_prefix("https://")
+        .or_else(|| value.strip_prefix("http://"))?;
+    let valid = !host.is_empty()
+        && !host.starts_with('/')
+        && !value.chars().any(|c| c.is_whitespace() || c.is_control());
+    valid.then(|| value.to_string())
+}
+
+fn sponsorblock_api_args(source: Option<&str>, global: Option<&str>) -> Vec<String> {
+    source
+        .or(global)
+        .map(|url| vec!["--sponsorblock-api".to_string(), url.to_string()])
+        .unwrap_or_default()
+}
 
-        info!("yt-dlp concurrency: {}", limited_concurrency);
+/// Normalizes a yt-dlp rate such as `500K` or `4.2M`, `None` if it is not one
+#[must_use]
+pub fn parse_rate_limit(value: &str) -> Option<String> {
+    let value = value.trim();
+    let number = value
+        .strip_suffix(['K', 'M', 'G', 'k', 'm', 'g'])
+        .unwrap_or(value);
+    let digits = number
+        .splitn(2, '.')
+        .all(|part| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit()));
+    let positive = number.bytes().any(|b| b.is_ascii_digit() && b != b'0');
+    (digits && positive).then(|| value.to_uppercase())
+}
+
+/// Lowest and highest accepted resolution cap of a source, in pixels
+pub const MAX_HEIGHT_RANGE: std::ops::RangeInclusive<i32> = 144..=4320;
+
+/// Limits the picked formats to `max_height`, falling back to the best single file under it
+fn max_height_args(max_height: Option<i32>) -> Vec<String> {
+    max_height
+        .map(|n| {
+            vec![
+                "-f".to_string(),
+                format!("bv*[height<={n}]+ba/b[height<={n}]"),
+            ]
+        })
+        .unwrap_or_default()
+}
+
+/// Highest accepted age limit of a source, in years
+pub const MAX_AGE_LIMIT: i32 = 99;
+
+/// Skips videos rated above `age_limit` years instead of attempting them
+fn age_limit_args(age_limit: Option<i32>) -> Vec<String> {
+    age_limit
+        .map(|years| vec!["--age-limit".to_string(), years.to_string()])
+        .unwrap_or_default()
+}
+
+/// Makes yt-dlp abort downloads larger than `max_mb` MiB
+fn max_filesize_args(max_mb: Option<i32>) -> Vec<String> {
+    max_mb
+        .filter(|mb| *mb > 0)
+        .map(|mb| vec!["--max-filesize".to_string(), format!("{mb}M")])
+        .unwrap_or_default()
+}
+
+/// Fetches `source` fragments at once if the source sets them, `global` otherwise
+fn concurrent_fragments_args(source: Option<i32>, global: u32) -> Vec<String> {
+    let fragments = source
+        .and_then(|n| u32::try_from(n).ok())
+        .filter(|n| *n > 0)
+        .unwrap_or(global)
+        .clamp(1, MAX_CONCURRENT_FRAGMENTS);
+    if fragments == 1 {
+        return Vec::new();
+    }
+    vec!["--concurrent-fragments".to_string(), fragments.to_string()]
+}
+
+fn rate_limit_args(limit: Option<&str>) -> Vec<String> {
+    limit
+        .map(|limit| vec!["--limit-rate".to_string(), limit.to_string()])
+        .unwrap_or_default()
+}
 
-        Arc::new(Semaphore::new(limited_concurrency))
+/// Returns the semaphore limiting concurrent media downloads
+pub fn ytdtp_concurrency() -> &'static Arc<Semaphore> {
+    CONCURRENCY_SEMAPHORE.get_or_init(|| {
+        let concurrency = ytdtp_concurrency_limit();
+        info!("yt-dlp concurrency: {}", concurrency);
+        Arc::new(Semaphore::new(concurrency))
+    })
+}
+
+fn clamp_permits(weight: Option<i32>, limit: usize) -> u32 {
+    let limit = u32::try_from(limit).unwrap_or(u32::MAX).max(1);
+    let weight = weight
+        .and_then(|w| u32::try_from(w).ok())
+        .unwrap_or(1)
+        .max(1);
+    if weight > limit {
+        warn!(
+            "Warning: download weight {} exceeds the concurrency limit {}, using {}",
+            weight, limit, limit
+        );
+    }
+    weight.min(limit)
+}
+
+/// Returns how many download permits a source with the given weight takes
+///
+/// Weights are clamped to the concurrency limit so a heavy source still gets to run.
+#[must_use]
+pub fn download_permits(weight: Option<i32>) -> u32 {
+    clamp_permits(weight, ytdtp_concurrency_limit())
+}
+
+/// Returns the refresh concurrency configured through `LOCALTUBE_REFRESH_CONCURRENCY`
+pub fn refresh_concurrency_limit() -> usize {
+    *REFRESH_CONCURRENCY_LIMIT
+        .get_or_init(|| concurrency_from_env("LOCALTUBE_REFRESH_CONCURRENCY", 2))
+}
+
+/// Returns the semaphore limiting concurrent source refreshes
+///
+/// Kept apart from the download limit so a burst of refreshes after boot
+/// cannot starve downloads, and the other way around.
+pub fn refresh_concurrency() -> &'static Arc<Semaphore> {
+    REFRESH_CONCURRENCY_SEMAPHORE.get_or_init(|| {
+        let concurrency = refresh_concurrency_limit();
+        info!("refresh concurrency: {}", concurrency);
+        Arc::new(Semaphore::new(concurrency))
     })
 }
 
@@ -60,16 +383,279 @@ pub fn media_directory() -> &'static PathBuf {
     })
 }
 
+static CLIENT_OPTIONS: OnceLock<ClientOptions> = OnceLock::new();
+
+/// Where yt-dlp reads cookies from for authenticated extraction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Cookies {
+    /// Netscape formatted cookies file, passed as `--cookies`
+    File(String),
+    /// Browser (and optional profile) to read cookies from, passed as `--cookies-from-browser`
+    Browser(String),
+}
+
+impl Cookies {
+    /// Picks the cookie source from the cookies file and browser settings
+    ///
+    /// # Errors
+    ///
+    /// Returns error if both are set, yt-dlp only takes cookies from one place
+    pub fn from_settings(file: Option<&str>, browser: Option<&str>) -> Result<Option<Self>> {
+        match (non_empty(file), non_empty(browser)) {
+            (Some(_), Some(_)) => Err(Error::string(
+                "LOCALTUBE_YTDLP_COOKIES and LOCALTUBE_YTDLP_COOKIES_FROM_BROWSER are mutually exclusive",
+            )),
+            (Some(file), None) => Ok(Some(Self::File(file))),
+            (None, Some(browser)) => Ok(Some(Self::Browser(browser))),
+            (None, None) => Ok(None),
+        }
+    }
+
+    #[must_use]
+    pub fn args(&self) -> Vec<String> {
+        match self {
+            Self::File(file) => vec!["--cookies".to_string(), file.clone()],
+            Self::Browser(browser) => vec!["--cookies-from-browser".to_string(), browser.clone()],
+        }
+    }
+}
+
+/// Client identity overrides passed to every yt-dlp invocation
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClientOptions {
+    pub user_agent: Option<String>,
+    pub extractor_args: Option<String>,
+    pub cookies: Option<Cookies>,
+}
+
+fn non_empty(value: Option<&str>) -> Option<String> {
+    value
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(str::to_string)
+}
+
+fn env_value(name: &str) -> Option<String> {
+    non_empty(std::env::var(name).ok().as_deref())
+}
+
+impl ClientOptions {
+    /// Reads the options from `LOCALTUBE_YTDLP_USER_AGENT`,
+    /// `LOCALTUBE_YTDLP_EXTRACTOR_ARGS`, `LOCALTUBE_YTDLP_COOKIES` and
+    /// `LOCALTUBE_YTDLP_COOKIES_FROM_BROWSER`
+    ///
+    /// # Errors
+    ///
+    /// Returns error if both cookie settings are set
+    pub fn from_env() -> Result<Self> {
+        Ok(Self {
+            user_agent: env_value("LOCALTUBE_YTDLP_USER_AGENT"),
+            extractor_args: env_value("LOCALTUBE_YTDLP_EXTRACTOR_ARGS"),
+            cookies: Cookies::from_settings(
+                env_value("LOCALTUBE_YTDLP_COOKIES").as_deref(),
+                env_value("LOCALTUBE_YTDLP_COOKIES_FROM_BROWSER").as_deref(),
+            )?,
+        })
+    }
+
+    /// Loads and validates the global options, called once when the app boots
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the environment holds conflicting settings
+    pub fn init_global() -> Result<&'static Self> {
+        let options = Self::from_env()?;
+        Ok(CLIENT_OPTIONS.get_or_init(|| {
+            if options != Self::default() {
+                info!("yt-dlp client options: {:?}", options);
+            }
+            options
+        }))
+    }
+
+    /// Returns the options configured through the environment, see [`Self::from_env`]
+    ///
+    /// Conflicting cookie settings are rejected on boot by [`Self::init_global`];
+    /// if they are read here without booting, cookies are left out with an error logged.
+    #[must_use]
+    pub fn global() -> &'static Self {
+        CLIENT_OPTIONS.get_or_init(|| {
+            Self::from_env().unwrap_or_else(|err| {
+                error!("Ignoring yt-dlp cookies: {}", err);
+                Self {
+                    user_agent: env_value("LOCALTUBE_YTDLP_USER_AGENT"),
+                    extractor_args: env_value("LOCALTUBE_YTDLP_EXTRACTOR_ARGS"),
+                    cookies: None,
+                }
+            })
+        })
+    }
+
+    /// Returns the global options with the source overrides applied
+    #[must_use]
+    pub fn for_source(source: &crate::models::_entities::sources::Model) -> Self {
+        Self::global().with_overrides(
+            source.user_agent.as_deref(),
+            source.extractor_args.as_deref(),
+        )
+    }
+
+    #[must_use]
+    pub fn with_overrides(&self, user_agent: Option<&str>, extractor_args: Option<&str>) -> Self {
+        Self {
+            user_agent: non_empty(user_agent).or_else(|| self.user_agent.clone()),
+            extractor_args: non_empty(extractor_args).or_else(|| self.extractor_args.clone()),
+            cookies: self.cookies.clone(),
+        }
+    }
+
+    #[must_use]
+    pub fn args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if let Some(user_agent) = &self.user_agent {
+            args.push("--user-agent".to_string());
+            args.push(user_agent.clone());
+        }
+        if let Some(extractor_args) = &self.extractor_args {
+            args.push("--extractor-args".to_string());
+            args.push(extractor_args.clone());
+        }
+        if let Some(cookies) = &self.cookies {
+            args.extend(cookies.args());
+        }
+        args
+    }
+}
+
+static LIBS_DIRECTORY: OnceLock<PathBuf> = OnceLock::new();
+
+/// Resolves the libs directory from the `LOCALTUBE_LIBS_DIR` value, `libs` when unset or empty
+#[must_use]
+pub fn libs_dir_from(value: Option<&str>) -> PathBuf {
+    value
+        .map(str::trim)
+        .filter(|dir| !dir.is_empty())
+        .map_or_else(|| PathBuf::from(DEFAULT_LIBS_DIR), PathBuf::from)
+}
+
+/// Returns the directory yt-dlp and ffmpeg are installed into
+///
+/// Set through `LOCALTUBE_LIBS_DIR`, defaults to `libs` relative to the
+/// working directory.
+#[must_use]
+pub fn libs_directory() -> &'static PathBuf {
+    LIBS_DIRECTORY
+        .get_or_init(|| libs_dir_from(std::env::var("LOCALTUBE_LIBS_DIR").ok().as_deref()))
+}
+
+/// Returns the path to the yt-dlp executable in `libs_dir`
+#[must_use]
+pub fn yt_dlp_path_in(libs_dir: &Path) -> PathBuf {
+    libs_dir.join("yt-dlp")
+}
+
+/// Returns the path to the ffmpeg executable in `libs_dir`
+#[must_use]
+pub fn ffmpeg_path_in(libs_dir: &Path) -> PathBuf {
+    libs_dir.join("ffmpeg")
+}
+
 /// Returns the path to the yt-dlp executable
 #[must_use]
 pub fn yt_dlp_path() -> PathBuf {
-    PathBuf::from(LIBS_DIR).join("yt-dlp")
+    YT_DLP_PROGRAM
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+        .unwrap_or_else(|| yt_dlp_path_in(libs_directory()))
+}
+
+/// Replaces the yt-dlp executable that is run, `None` restores the one in [`libs_directory`]
+pub fn set_yt_dlp_program(program: Option<PathBuf>) {
+    *YT_DLP_PROGRAM
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner) = program;
 }
 
 /// Returns the path to the ffmpeg executable
 #[must_use]
 pub fn ffmpeg_path() -> PathBuf {
-    PathBuf::from(LIBS_DIR).join("ffmpeg")
+    ffmpeg_path_in(libs_directory())
+}
+
+/// Creates `dir` if needed and checks a file can be written into it
+///
+/// # Errors
+///
+/// Returns error if the directory cannot be created or written to
+pub async fn check_dir_writable(dir: &Path) -> std::io::Result<()> {
+    let probe = dir.join(".localtube-write-test");
+    tokio::fs::create_dir_all(dir).await?;
+    tokio::fs::write(&probe, b"").await?;
+    tokio::fs::remove_file(&probe).await
+}
+
+/// Checks that the libs directory can be created and written to
+///
+/// # Errors
+///
+/// Returns error naming `LOCALTUBE_LIBS_DIR` if the directory cannot be
+/// created or a file cannot be written into it
+pub async fn ensure_libs_dir_writable(libs_dir: &Path) -> Result<()> {
+    check_dir_writable(libs_dir).await.map_err(|err| {
+        Error::string(&format!(
+            "libs directory {} is not writable ({err}), set LOCALTUBE_LIBS_DIR to a writable directory",
+            libs_dir.display()
+        ))
+    })
+}
+
+/// What a download does when ffmpeg is not installed
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MissingFfmpeg {
+    /// Refuse to download so the problem is noticed
+    #[default]
+    Fail,
+    /// Download the file as served, without remuxing, embedding or `SponsorBlock` removal
+    Degrade,
+}
+
+impl MissingFfmpeg {
+    /// Parses `LOCALTUBE_MISSING_FFMPEG`, anything but `degrade` fails
+    #[must_use]
+    pub fn from_setting(value: Option<&str>) -> Self {
+        match value.map(str::trim) {
+            Some(v) if v.eq_ignore_ascii_case("degrade") => Self::Degrade,
+            _ => Self::Fail,
+        }
+    }
+}
+
+static MISSING_FFMPEG: OnceLock<MissingFfmpeg> = OnceLock::new();
+
+/// Returns the configured behavior for downloads without ffmpeg
+pub fn missing_ffmpeg() -> MissingFfmpeg {
+    *MISSING_FFMPEG.get_or_init(|| {
+        MissingFfmpeg::from_setting(std::env::var("LOCALTUBE_MISSING_FFMPEG").ok().as_deref())
+    })
+}
+
+/// Returns whether downloads may post-process with ffmpeg
+///
+/// # Errors
+///
+/// Returns an error explaining how to recover when ffmpeg is missing and the
+/// policy is [`MissingFfmpeg::Fail`]
+pub fn ffmpeg_postprocessing(available: bool, policy: MissingFfmpeg) -> Result<bool> {
+    match (available, policy) {
+        (true, _) => Ok(true),
+        (false, MissingFfmpeg::Degrade) => Ok(false),
+        (false, MissingFfmpeg::Fail) => Err(Error::string(&format!(
+            "ffmpeg is missing at {}, restart LocalTube to install it again or set \
+             LOCALTUBE_MISSING_FFMPEG=degrade to download without remuxing and embedding",
+            ffmpeg_path().display()
+        ))),
+    }
 }
 
 /// Downloads required dependencies
@@ -78,17 +664,131 @@ pub fn ffmpeg_path() -> PathBuf {
 ///
 /// Returns error if download or installation fails
 pub async fn download_deps() -> Result<(), yt_dlp::error::Error> {
-    let yt_dlp = yt_dlp_path();
+    let yt_dlp = yt_dlp_path_in(libs_directory());
     let ffmpeg = ffmpeg_path();
     let libraries = Libraries::new(yt_dlp, ffmpeg);
     libraries.install_dependencies().await?;
     Ok(())
 }
 
+/// Returns the version reported by the installed yt-dlp
+///
+/// # Errors
+///
+/// Returns error if yt-dlp cannot be run or exits with a failure
+pub async fn yt_dlp_version() -> Result<String> {
+    let output = Command::new(yt_dlp_path())
+        .arg("--version")
+        .output()
+        .await?;
+    if !output.status.success() {
+        return Err(Error::string(&format!(
+            "yt-dlp --version exited with {}",
+            output.status
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Extracts the version from the first line of `ffmpeg -version`
+#[must_use]
+pub fn parse_ffmpeg_version(output: &str) -> Option<String> {
+    output
+        .lines()
+        .next()?
+        .strip_prefix("ffmpeg version ")?
+        .split_whitespace()
+        .next()
+        .map(ToString::to_string)
+}
+
+/// Returns the version reported by the installed ffmpeg
+///
+/// # Errors
+///
+/// Returns error if ffmpeg cannot be run, exits with a failure or reports no version
+pub async fn ffmpeg_version() -> Result<String> {
+    let output = Command::new(ffmpeg_path()).arg("-version").output().await?;
+    if !output.status.success() {
+        return Err(Error::string(&format!(
+            "ffmpeg -version exited with {}",
+            output.status
+        )));
+    }
+    parse_ffmpeg_version(&String::from_utf8_lossy(&output.stdout))
+        .ok_or_else(|| Error::string("ffmpeg -version reported no version"))
+}
+
+/// Longest a media integrity check may decode before it is given up
+pub const VERIFY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10 * 60);
+
+/// Outcome of decoding a media file with ffmpeg
+#[derive(Debug, Clone, Serialize)]
+pub struct VerifyReport {
+    pub playable: bool,
+    /// First decoder error, `None` when the file is playable
+    pub error: Option<String>,
+}
+
+/// Decodes the whole file with ffmpeg to check the media is not corrupt
+///
+/// Any decoder output at the `error` level or a failed exit marks the file
+/// unplayable. ffmpeg is killed once `timeout` passes.
+///
+/// # Errors
+///
+/// Returns error if ffmpeg is not installed, cannot be run or times out
+pub async fn verify_media_file(path: &Path, timeout: std::time::Duration) -> Result<VerifyReport> {
+    let ffmpeg = ffmpeg_path();
+    if !ffmpeg.is_file() {
+        return Err(Error::string(&format!(
+            "ffmpeg is not installed at {}",
+            ffmpeg.display()
+        )));
+    }
+    let child = Command::new(&ffmpeg)
+        .args(["-nostdin", "-v", "error", "-i"])
+        .arg(path)
+        .args(["-f", "null", "-"])
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+    let output = tokio::time::timeout(timeout, child.wait_with_output())
+        .await
+        .map_err(|_| {
+            Error::string(&format!(
+                "Verifying {} timed out after {}s",
+                path.display(),
+                timeout.as_secs()
+            ))
+        })??;
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let playable = output.status.success() && stderr.trim().is_empty();
+    let error = (!playable).then(|| {
+        if stderr.trim().is_empty() {
+            format!("ffmpeg exited with {}", output.status)
+        } else {
+            crate::workers::errors::error_summary(
+                &stderr,
+                crate::workers::errors::error_snippet_chars(),
+            )
+        }
+    });
+    Ok(VerifyReport { playable, error })
+}
+
+/// How long an item that is live right now waits before its download is tried again
+pub const LIVE_RECHECK_DELAY: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
 #[derive(Deserialize, Serialize)]
 pub struct VideoMetadata {
+    pub id: String,
     pub title: String,
     pub description: Option<String>,
+    /// Upcoming premieres and streams have no duration yet
+    #[serde(default)]
     pub duration: u64,
     pub uploader: String,
     pub n_entries: Option<u64>,
@@ -96,6 +796,35 @@ pub struct VideoMetadata {
     pub original_url: String,
     pub timestamp: i64,
     pub filename: String,
+    /// `is_upcoming`, `is_live`, `post_live`, `was_live` or `not_live`
+    #[serde(default)]
+    pub live_status: Option<String>,
+    #[serde(default)]
+    pub is_live: Option<bool>,
+    /// Scheduled start of a premiere or stream
+    #[serde(default)]
+    pub release_timestamp: Option<i64>,
+    /// Minimum viewer age the video is rated for
+    #[serde(default)]
+    pub age_limit: Option<i32>,
+}
+
+impl VideoMetadata {
+    /// Returns when an item that cannot be downloaded yet should be tried, `None` if it can be now
+    ///
+    /// Upcoming premieres and streams wait for their scheduled start, streams
+    /// that are live or still being processed are checked again after
+    /// [`LIVE_RECHECK_DELAY`].
+    #[must_use]
+    pub fn available_at(&self, now: i64) -> Option<i64> {
+        let recheck = now.saturating_add_unsigned(LIVE_RECHECK_DELAY.as_secs());
+        match self.live_status.as_deref() {
+            Some("is_upcoming") => Some(self.release_timestamp.map_or(recheck, |at| at.max(now))),
+            Some("is_live" | "post_live") => Some(recheck),
+            _ if self.is_live == Some(true) => Some(recheck),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Deserialize, Serialize)]
@@ -118,39 +847,337 @@ pub struct SourceListTabOption {
     pub label: String,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
-pub enum MediaListOrder {
-    Original,
-    Reverse,
+/// How the video thumbnail is stored alongside a download
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ThumbnailMode {
+    /// Embedded into the media container
+    #[default]
+    Embed,
+    /// Written next to the media file as a jpg
+    Jpg,
+    /// Written next to the media file as a webp
+    Webp,
+    /// Not downloaded at all
+    None,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
-pub enum ListProbeMode {
-    Minimal,
-    OrderAware,
-}
+impl ThumbnailMode {
+    /// Parses the value stored on a source, unknown or missing values embed
+    #[must_use]
+    pub fn from_setting(value: Option<&str>) -> Self {
+        match value {
+            Some("jpg") => Self::Jpg,
+            Some("webp") => Self::Webp,
+            Some("none") => Self::None,
+            _ => Self::Embed,
+        }
+    }
 
-#[derive(Debug, Clone)]
-pub struct ListProbe {
-    pub list_kind: SourceListKind,
-    pub list_count: Option<u64>,
-    pub list_order: Option<SourceListOrder>,
-    pub uploader: Option<String>,
-    pub source_provider: Option<String>,
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Embed => "embed",
+            Self::Jpg => "jpg",
+            Self::Webp => "webp",
+            Self::None => "none",
+        }
+    }
 }
 
-#[derive(Deserialize)]
-struct ProbeOutput {
-    #[serde(rename = "_type")]
-    kind: Option<String>,
-    playlist_count: Option<u64>,
-    uploader: Option<String>,
-    extractor_key: Option<String>,
-    entries: Option<Vec<Option<ProbeEntry>>>,
+/// Extensions of the thumbnail sidecars yt-dlp may leave next to a media file
+pub const THUMBNAIL_EXTENSIONS: [&str; 3] = ["jpg", "webp", "png"];
+
+/// Extension of the description sidecar written by `--write-description`
+pub const DESCRIPTION_EXTENSION: &str = "description";
+
+/// Extension of the WebVTT subtitle sidecars, written as `<media>.<lang>.vtt`
+pub const SUBTITLE_EXTENSION: &str = "vtt";
+
+/// Info json size above which a download with comments is reported as oversized
+const LARGE_COMMENTS_BYTES: u64 = 50 * 1024 * 1024;
+
+/// Container every download is remuxed into
+const REMUX_CONTAINER: &str = "mkv";
+
+/// Extensions of the finished media files a download can leave behind
+const MEDIA_EXTENSIONS: [&str; 5] = ["mkv", "mp4", "webm", "m4a", "opus"];
+
+/// Returns the yt-dlp thumbnail arguments of a download
+///
+/// Downloads are remuxed into [`REMUX_CONTAINER`], mkv attaches the webp
+/// thumbnails most sites serve as they are.
+#[must_use]
+pub fn thumbnail_args(mode: ThumbnailMode) -> Vec<String> {
+    let args: &[&str] = match mode {
+        ThumbnailMode::Embed => &["--embed-thumbnail"],
+        ThumbnailMode::Jpg => &["--write-thumbnail", "--convert-thumbnails", "jpg"],
+        ThumbnailMode::Webp => &["--write-thumbnail", "--convert-thumbnails", "webp"],
+        ThumbnailMode::None => &[],
+    };
+    args.iter().map(ToString::to_string).collect()
 }
 
-#[derive(Deserialize)]
-struct ProbeEntry {
+/// Returns the yt-dlp arguments for the optional description and comments sidecars
+///
+/// Comments are stored in the `.info.json` already written for every download,
+/// yt-dlp fetches the top ones first.
+#[must_use]
+pub fn sidecar_args(description: bool, comments: bool) -> Vec<String> {
+    let mut args = Vec::new();
+    if description {
+        args.push("--write-description".to_string());
+    }
+    if comments {
+        args.push("--write-comments".to_string());
+    }
+    args
+}
+
+/// Returns the yt-dlp arguments writing WebVTT subtitle sidecars for browser playback
+///
+/// Converting other formats needs ffmpeg; without it only subtitles the site
+/// already serves as WebVTT end up as `.vtt` files.
+#[must_use]
+pub fn subtitle_args(write_subtitles: bool, postprocess: bool) -> Vec<String> {
+    if !write_subtitles {
+        return Vec::new();
+    }
+    let mut args = vec!["--write-subs", "--sub-format", "vtt/best"];
+    if postprocess {
+        args.extend(["--convert-subs", SUBTITLE_EXTENSION]);
+    }
+    args.into_iter().map(String::from).collect()
+}
+
+fn is_subtitle_language(lang: &str) -> bool {
+    !lang.is_empty()
+        && lang
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Returns the WebVTT sidecar of `media_path` in `lang`, `None` if `lang` is not a language tag
+#[must_use]
+pub fn subtitle_path(media_path: &Path, lang: &str) -> Option<PathBuf> {
+    if !is_subtitle_language(lang) {
+        return None;
+    }
+    let stem = media_path.file_stem()?.to_str()?;
+    Some(media_path.with_file_name(format!("{stem}.{lang}.{SUBTITLE_EXTENSION}")))
+}
+
+/// Lists the languages of the WebVTT sidecars next to `media_path`, sorted
+#[must_use]
+pub fn subtitle_languages(media_path: &Path) -> Vec<String> {
+    let (Some(dir), Some(stem)) = (
+        media_path.parent(),
+        media_path.file_stem().and_then(|s| s.to_str()),
+    ) else {
+        return Vec::new();
+    };
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let prefix = format!("{stem}.");
+    let suffix = format!(".{SUBTITLE_EXTENSION}");
+    let mut languages: Vec<String> = entries
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .filter_map(|name| {
+            let lang = name.strip_prefix(&prefix)?.strip_suffix(&suffix)?;
+            is_subtitle_language(lang).then(|| lang.to_string())
+        })
+        .collect();
+    languages.sort();
+    languages
+}
+
+async fn warn_on_large_comments(info_path: &Path) {
+    let Ok(metadata) = tokio::fs::metadata(info_path).await else {
+        return;
+    };
+    if metadata.len() > LARGE_COMMENTS_BYTES {
+        warn!(
+            "{} is {} MiB with comments included, consider disabling comments for this source",
+            info_path.display(),
+            metadata.len() / 1024 / 1024
+        );
+    }
+}
+
+/// What gets embedded into the remuxed container, each can fail on some videos
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EmbedOptions {
+    pub metadata: bool,
+    pub subtitles: bool,
+    pub thumbnail: ThumbnailMode,
+}
+
+impl Default for EmbedOptions {
+    fn default() -> Self {
+        Self {
+            metadata: true,
+            subtitles: true,
+            thumbnail: ThumbnailMode::default(),
+        }
+    }
+}
+
+/// Returns the download arguments that need ffmpeg to run
+///
+/// Marked `SponsorBlock` categories stay in the video and become chapters.
+fn postprocessing_args(
+    sponsorblock: &crate::models::sources::SponsorBlockCategories,
+    embed: EmbedOptions,
+) -> Vec<String> {
+    let remove = sponsorblock.remove.serialize();
+    let mut args = vec![format!(
+        "--sponsorblock-remove={}",
+        if remove.is_empty() { "-all" } else { &remove }
+    )];
+    if !sponsorblock.mark.is_empty() {
+        args.push(format!(
+            "--sponsorblock-mark={}",
+            sponsorblock.mark.serialize()
+        ));
+    }
+    args.push(format!("--remux-video={REMUX_CONTAINER}"));
+    if embed.metadata {
+        args.push("--embed-metadata".to_string());
+    }
+    if embed.subtitles {
+        args.push("--embed-subs".to_string());
+    }
+    args.extend(thumbnail_args(embed.thumbnail));
+    args
+}
+
+/// Bitrate of the audio when it is converted to AAC
+const AAC_BITRATE: &str = "192k";
+
+/// Returns the arguments converting the audio of a download to AAC
+///
+/// The video stream is still copied as is, only the audio is re-encoded when
+/// the formats are merged or remuxed into the container. Merging goes into
+/// the same container, a webm could not hold AAC audio.
+#[must_use]
+pub fn aac_audio_args(enabled: bool) -> Vec<String> {
+    if !enabled {
+        return Vec::new();
+    }
+    let output = format!("ffmpeg_o:-c:a aac -b:a {AAC_BITRATE}");
+    vec![
+        format!("--merge-output-format={REMUX_CONTAINER}"),
+        "--postprocessor-args".to_string(),
+        format!("Merger+{output}"),
+        "--postprocessor-args".to_string(),
+        format!("VideoRemuxer+{output}"),
+    ]
+}
+
+/// Lower upload date bound of an incremental list stream
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DateAfter {
+    pub date: chrono::NaiveDate,
+    /// The list is known to be newest first, so yt-dlp may stop at the first older item
+    pub newest_first: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MediaListOrder {
+    Original,
+    Reverse,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ListProbeMode {
+    Minimal,
+    OrderAware,
+}
+
+#[derive(Debug, Clone)]
+pub struct ListProbe {
+    pub list_kind: SourceListKind,
+    pub list_count: Option<u64>,
+    pub list_order: Option<SourceListOrder>,
+    pub uploader: Option<String>,
+    pub source_provider: Option<String>,
+    /// Canonical URL of the probed channel, it keeps working when the handle changes
+    pub channel_url: Option<String>,
+    /// The list is a curated playlist, `uploader` is then its title
+    pub playlist: bool,
+}
+
+#[derive(Deserialize)]
+struct ProbeOutput {
+    #[serde(rename = "_type")]
+    kind: Option<String>,
+    id: Option<String>,
+    title: Option<String>,
+    channel_id: Option<String>,
+    channel_url: Option<String>,
+    playlist_count: Option<u64>,
+    uploader: Option<String>,
+    extractor_key: Option<String>,
+    entries: Option<Vec<Option<ProbeEntry>>>,
+}
+
+/// What a source lists, decides which metadata names the source
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum RepresentativeKind {
+    Video,
+    /// Uploads of a channel or user, named after whoever uploaded the newest video
+    Channel,
+    /// Curated playlist, named after the playlist itself since its videos may
+    /// come from anyone
+    Playlist,
+}
+
+impl RepresentativeKind {
+    fn of(probe: &ProbeOutput, list_kind: &SourceListKind) -> Self {
+        match list_kind {
+            SourceListKind::Video => Self::Video,
+            // A channel tab carries the id of the channel, a playlist its own id.
+            SourceListKind::List => match (&probe.id, &probe.channel_id) {
+                (Some(id), Some(channel_id)) if id != channel_id => Self::Playlist,
+                _ => Self::Channel,
+            },
+        }
+    }
+}
+
+/// Picks the uploader and provider a source is named after
+///
+/// `probe` holds the list itself, `entries` the probed videos of it.
+fn representative_metadata(
+    probe: &ProbeOutput,
+    list_kind: &SourceListKind,
+    entries: &[ProbeEntry],
+) -> (Option<String>, Option<String>) {
+    let newest = entries
+        .iter()
+        .enumerate()
+        // Without dates the list position decides, which is newest first for channels.
+        .max_by_key(|(position, entry)| (entry_timestamp(entry), std::cmp::Reverse(*position)))
+        .map(|(_, entry)| entry);
+    let provider = newest
+        .and_then(|e| e.extractor_key.clone())
+        .or_else(|| probe.extractor_key.clone());
+    let uploader = match RepresentativeKind::of(probe, list_kind) {
+        RepresentativeKind::Video | RepresentativeKind::Channel => newest
+            .and_then(|e| e.uploader.clone())
+            .or_else(|| probe.uploader.clone()),
+        RepresentativeKind::Playlist => probe
+            .title
+            .clone()
+            .or_else(|| probe.uploader.clone())
+            .or_else(|| newest.and_then(|e| e.uploader.clone())),
+    };
+    (uploader, provider)
+}
+
+#[derive(Deserialize)]
+struct ProbeEntry {
     #[serde(rename = "_type")]
     kind: Option<String>,
     timestamp: Option<i64>,
@@ -167,49 +1194,22 @@ fn flatten_probe_entries(entries: Option<Vec<Option<ProbeEntry>>>) -> Vec<ProbeE
     entries.unwrap_or_default().into_iter().flatten().collect()
 }
 
-/// Downloads metadata for the last video from given URL
-///
-/// # Errors
-///
-/// Returns error if download fails or response parsing fails
-///
-/// # Note
-///
-/// This function does not acquire the concurrency semaphore. The caller
-/// must ensure proper concurrency control (typically via `ActiveTask`).
-pub async fn download_last_video_metadata(url: &str) -> Result<VideoMetadata> {
-    let output = Command::new(yt_dlp_path())
-        .arg("--dump-json")
-        .arg("-t")
-        .arg("sleep")
-        .arg("--max-downloads=1")
-        .arg("--simulate")
-        .arg(url)
-        .output()
-        .await?;
-
-    ytdlp_debug::log_ytdlp_json(
-        "download_last_video_metadata",
-        &output.stdout,
-        Some(url),
-        None,
-    )
-    .await;
-    let video_metadata: VideoMetadata = serde_json::from_slice(&output.stdout)?;
-    Ok(video_metadata)
-}
-
 /// Probes list metadata for the given URL.
 ///
 /// # Errors
 ///
 /// Returns error if yt-dlp fails or the response parsing fails.
-pub async fn probe_list_metadata(url: &str, mode: ListProbeMode) -> Result<ListProbe> {
+pub async fn probe_list_metadata(
+    url: &str,
+    mode: ListProbeMode,
+    client: &ClientOptions,
+) -> Result<ListProbe> {
     let item_spec = match mode {
         ListProbeMode::Minimal => "1:1",
         ListProbeMode::OrderAware => "1:2",
     };
     let output = Command::new(yt_dlp_path())
+        .args(client.args())
         .arg("--dump-single-json")
         .arg("-I")
         .arg(item_spec)
@@ -220,16 +1220,21 @@ pub async fn probe_list_metadata(url: &str, mode: ListProbeMode) -> Result<ListP
 
     // Use a tiny probe to avoid loading entire large lists just to detect order/count.
     ytdlp_debug::log_ytdlp_json("probe_list_metadata", &output.stdout, Some(url), None).await;
-    let probe: ProbeOutput = serde_json::from_slice(&output.stdout)?;
-    let ProbeOutput {
-        kind,
-        playlist_count,
-        uploader: probe_uploader,
-        extractor_key: probe_extractor_key,
-        entries,
-    } = probe;
-    let entries = flatten_probe_entries(entries);
-    let list_kind = match kind.as_deref() {
+    if !output.status.success() && output.stdout.is_empty() {
+        // The reason, e.g. a channel that no longer exists, is only on stderr.
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(Error::string(
+            if stderr.trim().is_empty() {
+                format!("yt-dlp exited with {}", output.status)
+            } else {
+                stderr.trim().to_string()
+            }
+            .as_str(),
+        ));
+    }
+    let mut probe: ProbeOutput = serde_json::from_slice(&output.stdout)?;
+    let entries = flatten_probe_entries(probe.entries.take());
+    let list_kind = match probe.kind.as_deref() {
         Some("video") => SourceListKind::Video,
         Some("playlist") => SourceListKind::List,
         _ => {
@@ -241,16 +1246,17 @@ pub async fn probe_list_metadata(url: &str, mode: ListProbeMode) -> Result<ListP
         }
     };
 
-    let list_count = playlist_count.or_else(|| entries.first().and_then(|e| e.playlist_count));
+    let list_count = probe
+        .playlist_count
+        .or_else(|| entries.first().and_then(|e| e.playlist_count));
 
-    let uploader = entries
-        .first()
-        .and_then(|e| e.uploader.clone())
-        .or(probe_uploader);
-    let source_provider = entries
-        .first()
-        .and_then(|e| e.extractor_key.clone())
-        .or(probe_extractor_key);
+    let (uploader, source_provider) = representative_metadata(&probe, &list_kind, &entries);
+    let kind = RepresentativeKind::of(&probe, &list_kind);
+    // A playlist reports the channel of its owner, which is not where it moved to.
+    let channel_url = probe
+        .channel_url
+        .clone()
+        .filter(|_| kind == RepresentativeKind::Channel);
 
     let list_order = match mode {
         ListProbeMode::OrderAware => detect_list_order(&entries),
@@ -263,6 +1269,8 @@ pub async fn probe_list_metadata(url: &str, mode: ListProbeMode) -> Result<ListP
         list_order,
         uploader,
         source_provider,
+        channel_url,
+        playlist: kind == RepresentativeKind::Playlist,
     })
 }
 
@@ -271,11 +1279,15 @@ pub async fn probe_list_metadata(url: &str, mode: ListProbeMode) -> Result<ListP
 /// # Errors
 ///
 /// Returns error if yt-dlp fails or the response parsing fails.
-pub async fn probe_list_tabs(url: &str) -> Result<Vec<SourceListTabOption>> {
+pub async fn probe_list_tabs(
+    url: &str,
+    client: &ClientOptions,
+) -> Result<Vec<SourceListTabOption>> {
     const TAB_PROBE_MAX: usize = 10;
     let run_probe = |flat: bool| async move {
         let mut cmd = Command::new(yt_dlp_path());
-        cmd.arg("--dump-single-json")
+        cmd.args(client.args())
+            .arg("--dump-single-json")
             .arg("-I")
             // Use a small cap to avoid scanning huge lists while still capturing all tabs.
             .arg(format!("1:{TAB_PROBE_MAX}"))
@@ -404,26 +1416,43 @@ fn parse_upload_date(value: &str) -> Option<i64> {
 ///
 /// # Note
 ///
+/// Cancelling `cancel` terminates yt-dlp and closes the channel without an
+/// error, also while the stream waits for the receiver to catch up.
+///
+/// # Note
+///
 /// This function does not acquire the concurrency semaphore. The caller
 /// must ensure proper concurrency control (typically via `ActiveTask`).
 pub async fn stream_media_list(
     url: &str,
     order: MediaListOrder,
+    max_items: Option<u32>,
+    date_after: Option<DateAfter>,
+    client: &ClientOptions,
+    cancel: CancellationToken,
+) -> tokio::sync::mpsc::Receiver<Result<VideoMetadata>> {
+    let mut cmd = Command::new(yt_dlp_path());
+    cmd.args(client.args())
+        .arg("--dump-json")
+        .arg("--simulate")
+        .arg("-t")
+        .arg("sleep")
+        .args(playlist_items_args(order, max_items))
+        .args(date_after_args(date_after))
+        .arg(url);
+    spawn_list_stream(cmd, url.to_string(), date_after.is_some(), cancel)
+}
+
+fn spawn_list_stream(
+    mut cmd: Command,
+    url: String,
+    allow_empty: bool,
+    cancel: CancellationToken,
 ) -> tokio::sync::mpsc::Receiver<Result<VideoMetadata>> {
     let (tx, rx) = tokio::sync::mpsc::channel(8);
-    let url = url.to_string();
     tokio::spawn(async move {
-        let mut cmd = Command::new(yt_dlp_path())
+        let mut cmd = cmd
             .process_group(0)
-            .arg("--dump-json")
-            .arg("--simulate")
-            .arg("-t")
-            .arg("sleep")
-            .args(match order {
-                MediaListOrder::Original => &[][..],
-                MediaListOrder::Reverse => &["-I", "::-1"][..],
-            })
-            .arg(&url)
             .stdin(std::process::Stdio::null())
             .stdout(std::process::Stdio::piped())
             .stderr(std::process::Stdio::piped())
@@ -441,6 +1470,13 @@ pub async fn stream_media_list(
 
         while !stdout_done || !stderr_done {
             tokio::select! {
+                () = cancel.cancelled() => {
+                    info!("Listing {} cancelled", url);
+                    if let Err(err) = cmd.terminate_wait().await {
+                        warn!(error = %err, "failed to terminate yt-dlp");
+                    }
+                    return;
+                }
                 line = stdout_lines.next_line(), if !stdout_done => {
                     match line {
                         Ok(Some(line)) => {
@@ -455,8 +1491,12 @@ pub async fn stream_media_list(
                                     continue;
                                 }
                             };
-                            if tx.send(Ok(video_metadata)).await.is_err() || tx.is_closed() {
-                                // Receiver was dropped, terminate the command
+                            let sent = tokio::select! {
+                                sent = tx.send(Ok(video_metadata)) => sent.is_ok() && !tx.is_closed(),
+                                () = cancel.cancelled() => false,
+                            };
+                            if !sent {
+                                // Receiver was dropped or the listing cancelled, terminate the command
                                 if let Err(err) = cmd.terminate_wait().await {
                                     warn!(error = %err, "failed to terminate yt-dlp");
                                 }
@@ -493,152 +1533,827 @@ pub async fn stream_media_list(
             }
         }
 
-        let exit_success = match cmd.wait().await {
-            Ok(status) => status.success(),
+        let exit_code = match cmd.wait().await {
+            Ok(status) => status.code(),
             Err(err) => {
                 warn!(error = %err, "failed to wait on yt-dlp");
-                false
+                None
             }
         };
 
-        if stream_should_fail(exit_success, items_emitted) {
+        if stream_should_fail(exit_code, items_emitted, allow_empty) {
             let _ = tx.send(Err(Error::string(STREAM_ERROR_MESSAGE))).await;
         }
     });
     rx
 }
 
-fn stream_should_fail(exit_success: bool, items_emitted: usize) -> bool {
-    !exit_success || items_emitted == 0
+fn date_after_args(date_after: Option<DateAfter>) -> Vec<String> {
+    let Some(date_after) = date_after else {
+        return Vec::new();
+    };
+    let date = date_after.date.format("%Y%m%d").to_string();
+    if date_after.newest_first {
+        // `--dateafter` is checked first and only rejects the older items, the
+        // whole list would still be walked without ever reaching the break.
+        vec![
+            "--break-match-filters".to_string(),
+            format!("upload_date>=?{date}"),
+        ]
+    } else {
+        vec!["--dateafter".to_string(), date]
+    }
 }
 
-/// Downloads media from given URL
-///
-/// # Errors
-///
-/// Returns error if download fails, source metadata is missing or invalid paths are encountered
+/// Builds the `-I` selection for the listing command.
 ///
-/// # Note
+/// `max_items` keeps only the newest entries: the head of the list in original
+/// order, or the tail walked backwards when the list is reversed.
+fn playlist_items_args(order: MediaListOrder, max_items: Option<u32>) -> Vec<String> {
+    let spec = match (order, max_items) {
+        (MediaListOrder::Original, None) => return Vec::new(),
+        (MediaListOrder::Reverse, None) => "::-1".to_string(),
+        (MediaListOrder::Original, Some(n)) => format!("1:{n}"),
+        (MediaListOrder::Reverse, Some(n)) => format!("-1:-{n}:-1"),
+    };
+    vec!["-I".to_string(), spec]
+}
+
+/// Exit code of yt-dlp stopping on `--break-match-filters` or `--max-downloads`
+const BREAK_EXIT_CODE: i32 = 101;
+
+/// Returns whether a listing that exited with `exit_code` failed
 ///
-/// This function does not acquire the concurrency semaphore. The caller
-/// must ensure proper concurrency control (typically via `ActiveTask`).
-pub async fn download_media(
-    url: &str,
-    source: &crate::models::_entities::sources::Model,
-) -> Result<String> {
-    let media_dir = media_directory();
-    let source_name = source
-        .get_metadata()
-        .map(|m| {
-            m.uploader
-                .chars()
-                .filter(|c| {
-                    c.is_alphanumeric()
-                        || matches!(c, '-' | '_' | ' ' | '.' | '(' | ')' | '[' | ']')
-                })
-                .collect::<String>()
-        })
-        .ok_or_else(|| Error::string("Missing source metadata"))?;
-    let source_dir = media_dir.join(source_name);
-    tokio::fs::create_dir_all(&source_dir).await?;
-    // we reserialize to ensure we have only valid input
-    let sponsorblock = source.get_sponsorblock_categories().serialize();
-    let output = Command::new(yt_dlp_path())
-        .arg("--dump-json")
-        .arg("-t")
-        .arg("sleep")
-        .arg("--restrict-filenames")
-        .arg("--write-info-json")
-        .arg(format!(
-            "--sponsorblock-remove={}",
-            if sponsorblock.is_empty() {
-                "-all"
-            } else {
-                &sponsorblock
-            }
-        ))
-        .arg(format!("--paths={}", source_dir.display()))
-        .arg("--max-downloads=1")
-        .arg("--no-simulate")
-        .arg("--remux-video=mkv")
-        .arg("--embed-metadata")
-        .arg("--embed-subs")
-        .arg("--embed-thumbnail")
-        .arg(url)
-        .output()
-        .await?;
+/// A break is a normal end of the list. An empty list is only expected when
+/// `allow_empty`, e.g. an incremental refresh without new videos.
+fn stream_should_fail(exit_code: Option<i32>, items_emitted: usize, allow_empty: bool) -> bool {
+    !matches!(exit_code, Some(0 | BREAK_EXIT_CODE)) || (items_emitted == 0 && !allow_empty)
+}
 
-    ytdlp_debug::log_ytdlp_json(
-        "download_media",
-        &output.stdout,
-        Some(url),
-        Some(&format!("source_id={}", source.id)),
-    )
-    .await;
-    let video_metadata: VideoMetadata = serde_json::from_slice(&output.stdout)?;
+const DOWNLOAD_ARCHIVE_FILE: &str = "download-archive.txt";
 
-    // yt-dlp do not report remuxed file path, we need to check if it exists
-    // check if video_metadata.filename with .mkv extension exists if not check if video_metadata.filename exists
-    // use existing file if it exists, error out if none exists
-    let video_path = PathBuf::from(&video_metadata.filename);
-    let video_path = if video_path.with_extension("mkv").exists() {
-        video_path.with_extension("mkv")
-    } else if video_path.exists() {
-        video_path
-    } else {
-        return Err(Error::string("Failed to download media"));
-    };
+/// Entry identifying a video in the yt-dlp download archive
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveEntry {
+    pub extractor_key: String,
+    pub video_id: String,
+}
 
-    Ok(PathBuf::from(&video_path)
-        .strip_prefix(media_dir)
-        .map_err(|_| Error::string("Invalid media path"))?
-        .to_string_lossy()
-        .to_string())
+impl ArchiveEntry {
+    /// Formats the entry the way yt-dlp writes it: lowercased extractor key and video id.
+    #[must_use]
+    pub fn line(&self) -> String {
+        format!("{} {}", self.extractor_key.to_lowercase(), self.video_id)
+    }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::{
-        detect_list_order, extract_list_tabs, flatten_probe_entries, stream_should_fail,
-        ProbeEntry, ProbeOutput, SourceListOrder, SourceListTabOption,
-    };
+/// Returns the download archive kept in the given source directory
+#[must_use]
+pub fn download_archive_path(source_dir: &Path) -> PathBuf {
+    source_dir.join(DOWNLOAD_ARCHIVE_FILE)
+}
 
-    fn entry(timestamp: Option<i64>, upload_date: Option<&str>) -> ProbeEntry {
-        ProbeEntry {
-            kind: None,
-            timestamp,
-            upload_date: upload_date.map(str::to_string),
-            uploader: None,
-            extractor_key: None,
-            playlist_count: None,
-            webpage_url: None,
-            url: None,
-            title: None,
-        }
-    }
+fn download_archive_args(archive_path: &Path) -> Vec<String> {
+    vec![
+        "--download-archive".to_string(),
+        archive_path.display().to_string(),
+    ]
+}
 
-    #[test]
-    fn stream_should_fail_when_exit_success_but_no_items() {
-        assert!(stream_should_fail(true, 0));
-    }
+async fn archive_contains(archive_path: &Path, entry: &ArchiveEntry) -> bool {
+    let line = entry.line();
+    tokio::fs::read_to_string(archive_path)
+        .await
+        .is_ok_and(|content| content.lines().any(|l| l.trim() == line))
+}
 
-    #[test]
-    fn stream_should_fail_when_exit_failure_even_with_items() {
-        assert!(stream_should_fail(false, 3));
-    }
+async fn remove_archive_entry(archive_path: &Path, entry: &ArchiveEntry) -> Result<()> {
+    let line = entry.line();
+    let content = tokio::fs::read_to_string(archive_path).await?;
+    let kept = content
+        .lines()
+        .filter(|l| l.trim() != line)
+        .map(|l| format!("{l}\n"))
+        .collect::<String>();
+    tokio::fs::write(archive_path, kept).await?;
+    Ok(())
+}
 
-    #[test]
-    fn stream_should_succeed_when_exit_success_and_items_present() {
-        assert!(!stream_should_fail(true, 2));
+/// Returns whether the file name looks like a finished media file rather than
+/// a yt-dlp sidecar, partial download or archive
+#[must_use]
+pub fn is_media_file_name(name: &str) -> bool {
+    let extension = Path::new(name).extension().and_then(|ext| ext.to_str());
+    let thumbnail = extension.is_some_and(|ext| THUMBNAIL_EXTENSIONS.contains(&ext));
+    let subtitle = extension == Some(SUBTITLE_EXTENSION);
+    !(name == DOWNLOAD_ARCHIVE_FILE
+        || thumbnail
+        || subtitle
+        || name.ends_with(".info.json")
+        || name.ends_with(".description")
+        || name.ends_with(".part")
+        || name.ends_with(".ytdl"))
+}
+
+/// Extracts the video id from a yt-dlp file name of the form `<title> [<id>].<ext>`
+#[must_use]
+pub fn video_id_from_file_name(name: &str) -> Option<&str> {
+    let end = name.rfind(']')?;
+    let start = name[..end].rfind('[')?;
+    Some(&name[start + 1..end]).filter(|id| !id.is_empty())
+}
+
+/// Returns whether `candidate` should replace `current` as the file for a video.
+///
+/// The remuxed mkv wins when both it and the original download are present.
+#[must_use]
+pub fn prefer_media_file(current: Option<&Path>, candidate: &Path) -> bool {
+    current.is_none() || candidate.extension().is_some_and(|ext| ext == "mkv")
+}
+
+/// Returns the name `path` would have without a ` (N)` copy suffix
+///
+/// Only names of the download template qualify, e.g. `Title_[id] (1).mkv`
+/// maps to `Title_[id].mkv`.
+fn unsuffixed_path(path: &Path) -> Option<PathBuf> {
+    let stem = path.file_stem()?.to_str()?;
+    let (base, suffix) = stem.rsplit_once(" (")?;
+    let copy = suffix.strip_suffix(')')?;
+    if !base.ends_with(']') || copy.is_empty() || !copy.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
     }
+    let name = match path.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) => format!("{base}.{ext}"),
+        None => base.to_string(),
+    };
+    Some(path.with_file_name(name))
+}
 
-    #[test]
-    fn detect_list_order_uses_timestamps() {
-        let entries = vec![entry(Some(100), None), entry(Some(200), None)];
-        assert_eq!(
-            detect_list_order(&entries),
-            Some(SourceListOrder::OldestFirst)
-        );
+/// Returns whether `suffix`, a file name past `<stem>.`, is a finished media file or sidecar
+///
+/// Format streams like `f137.mp4` and merge intermediates like `temp.mkv`
+/// are not: another download of the video may still be writing them.
+fn is_finished_download_suffix(suffix: &str) -> bool {
+    match suffix.rsplit_once('.') {
+        None => {
+            MEDIA_EXTENSIONS.contains(&suffix)
+                || THUMBNAIL_EXTENSIONS.contains(&suffix)
+                || suffix == DESCRIPTION_EXTENSION
+        }
+        Some((lang, ext)) => {
+            suffix == "info.json" || (ext == SUBTITLE_EXTENSION && !lang.contains('.'))
+        }
+    }
+}
+
+/// Moves a download written under a ` (N)` copy name over the canonical file
+///
+/// Two downloads of the same video racing each other would otherwise leave
+/// both files behind. The download that just finished wins: the files of the
+/// older download are removed, including a media file with another extension,
+/// and every sidecar of the copy moves along with it. Only finished media
+/// files and sidecars are touched, files still being written are left alone.
+async fn reconcile_suffixed_download(path: PathBuf) -> std::io::Result<PathBuf> {
+    let Some(canonical) = unsuffixed_path(&path) else {
+        return Ok(path);
+    };
+    let (Some(dir), Some(copy_stem), Some(canonical_stem)) = (
+        path.parent(),
+        path.file_stem()
+            .and_then(|s| s.to_str())
+            .map(|s| format!("{s}.")),
+        canonical
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .map(|s| format!("{s}.")),
+    ) else {
+        return Ok(path);
+    };
+    warn!(
+        "{} is a second copy of {}, keeping only the new download",
+        path.display(),
+        canonical.display()
+    );
+    let mut copies = Vec::new();
+    let mut entries = tokio::fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let Ok(name) = entry.file_name().into_string() else {
+            continue;
+        };
+        if let Some(rest) = name.strip_prefix(&copy_stem) {
+            if is_finished_download_suffix(rest) {
+                copies.push((entry.path(), dir.join(format!("{canonical_stem}{rest}"))));
+            }
+        } else if let Some(rest) = name.strip_prefix(&canonical_stem) {
+            if is_finished_download_suffix(rest) {
+                tokio::fs::remove_file(entry.path()).await?;
+            }
+        }
+    }
+    for (from, to) in copies {
+        tokio::fs::rename(from, to).await?;
+    }
+    Ok(canonical)
+}
+
+/// Finds a finished download for the video id in the source directory.
+async fn find_downloaded_file(source_dir: &Path, video_id: &str) -> Option<PathBuf> {
+    let mut entries = tokio::fs::read_dir(source_dir).await.ok()?;
+    let mut found = None;
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let path = entry.path();
+        let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        if video_id_from_file_name(name) != Some(video_id) || !is_media_file_name(name) {
+            continue;
+        }
+        if prefer_media_file(found.as_deref(), &path) {
+            found = Some(path);
+        }
+    }
+    found
+}
+
+/// Finds the file yt-dlp is still writing for the video id in the source directory
+///
+/// With split formats the largest part is taken, which is usually the video stream.
+pub async fn find_partial_download(source_dir: &Path, video_id: &str) -> Option<PathBuf> {
+    let mut entries = tokio::fs::read_dir(source_dir).await.ok()?;
+    let mut found: Option<(u64, PathBuf)> = None;
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let path = entry.path();
+        let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        let Some(stem) = name.strip_suffix(".part") else {
+            continue;
+        };
+        if video_id_from_file_name(stem) != Some(video_id) {
+            continue;
+        }
+        let len = entry.metadata().await.map_or(0, |m| m.len());
+        if found.as_ref().is_none_or(|(largest, _)| len > *largest) {
+            found = Some((len, path));
+        }
+    }
+    found.map(|(_, path)| path)
+}
+
+/// Returns the directory under the media directory that a source downloads into
+///
+/// Sources that were never indexed have no uploader yet and thus no directory.
+#[must_use]
+pub fn source_dir_name(source: &crate::models::_entities::sources::Model) -> Option<String> {
+    source
+        .get_metadata()
+        .map(|m| sanitize_dir_name(&m.uploader, source.id))
+}
+
+/// Longest directory name in bytes, leaves room under the usual 255 byte limit
+const MAX_DIR_NAME_BYTES: usize = 200;
+
+/// Turns an uploader name into a single safe path component
+///
+/// Keeps a conservative set of characters, drops trailing dots and spaces
+/// that some filesystems strip and caps the length. Names with nothing left,
+/// which covers `.` and `..` once trailing dots go, fall back to `source-<id>`.
+#[must_use]
+pub fn sanitize_dir_name(name: &str, source_id: i32) -> String {
+    let filtered = name
+        .chars()
+        .filter(|c| {
+            c.is_alphanumeric() || matches!(c, '-' | '_' | ' ' | '.' | '(' | ')' | '[' | ']')
+        })
+        .collect::<String>();
+    let mut end = filtered.len().min(MAX_DIR_NAME_BYTES);
+    while !filtered.is_char_boundary(end) {
+        end -= 1;
+    }
+    let sanitized = filtered[..end]
+        .trim_start_matches(' ')
+        .trim_end_matches(['.', ' ']);
+    if sanitized.is_empty() {
+        format!("source-{source_id}")
+    } else {
+        sanitized.to_string()
+    }
+}
+
+/// Converts a path under the media directory into the relative form stored in `media_path`
+///
+/// # Errors
+///
+/// Returns error if the path is not inside `media_dir`
+pub fn relative_media_path(path: &Path, media_dir: &Path) -> Result<String> {
+    Ok(path
+        .strip_prefix(media_dir)
+        .map_err(|_| Error::string("Invalid media path"))?
+        .to_string_lossy()
+        .to_string())
+}
+
+/// Outcome of a finished download
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct DownloadResult {
+    /// Path relative to the media directory
+    pub media_path: String,
+    pub format_id: Option<String>,
+    pub ext: Option<String>,
+    /// Reported size in bytes, falling back to yt-dlp's estimate
+    pub filesize: Option<u64>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// Whether the file on disk is the mkv remux rather than the original container
+    pub remuxed: bool,
+}
+
+#[derive(Deserialize)]
+struct DownloadOutput {
+    filename: String,
+    format_id: Option<String>,
+    ext: Option<String>,
+    filesize: Option<u64>,
+    filesize_approx: Option<u64>,
+    width: Option<u32>,
+    height: Option<u32>,
+}
+
+impl DownloadResult {
+    /// Height of the downloaded video in the shape stored on the media
+    #[must_use]
+    pub fn downloaded_height(&self) -> Option<i32> {
+        self.height.and_then(|height| i32::try_from(height).ok())
+    }
+}
+
+impl DownloadOutput {
+    /// Parses the last JSON object line of the download stdout
+    ///
+    /// stdout may carry more than the one object, e.g. noise from plugins or
+    /// a second object from a playlist entry, so every line is tried from the
+    /// end like [`stream_media_list`] does.
+    fn parse_last(stdout: &[u8]) -> serde_json::Result<Self> {
+        let text = String::from_utf8_lossy(stdout);
+        let found = text
+            .lines()
+            .rev()
+            .map(str::trim)
+            .filter(|line| line.starts_with('{'))
+            .find_map(|line| serde_json::from_str(line).ok());
+        match found {
+            Some(download) => Ok(download),
+            // Report why the output as a whole is not a download.
+            None => serde_json::from_slice(stdout),
+        }
+    }
+
+    fn into_result(self, media_path: String, remuxed: bool) -> DownloadResult {
+        DownloadResult {
+            media_path,
+            format_id: self.format_id,
+            ext: if remuxed {
+                Some("mkv".to_string())
+            } else {
+                self.ext
+            },
+            filesize: self.filesize.or(self.filesize_approx),
+            width: self.width,
+            height: self.height,
+            remuxed,
+        }
+    }
+}
+
+/// Returns whether yt-dlp left out the file because of `--max-filesize`
+///
+/// yt-dlp only mentions the limit on screen, which `--dump-json` silences, so
+/// a reported size over `max_mb` MiB counts as well.
+fn exceeded_max_filesize(download: &DownloadOutput, max_mb: i32, stderr: &[u8]) -> bool {
+    let limit = u64::try_from(max_mb).unwrap_or_default() * 1024 * 1024;
+    download
+        .filesize
+        .or(download.filesize_approx)
+        .is_some_and(|size| size > limit)
+        || crate::workers::errors::is_over_max_filesize(&String::from_utf8_lossy(stderr))
+}
+
+/// Rebuilds the result of an earlier download from its `.info.json` sidecar.
+async fn existing_download_result(path: &Path, media_dir: &Path) -> Result<DownloadResult> {
+    let media_path = relative_media_path(path, media_dir)?;
+    let info = tokio::fs::read(path.with_extension("info.json"))
+        .await
+        .ok()
+        .and_then(|bytes| serde_json::from_slice::<DownloadOutput>(&bytes).ok());
+    Ok(match info {
+        Some(info) => {
+            let remuxed = Path::new(&info.filename) != path;
+            info.into_result(media_path, remuxed)
+        }
+        None => DownloadResult {
+            media_path,
+            ..Default::default()
+        },
+    })
+}
+
+/// Prefix of the progress lines requested through [`PROGRESS_TEMPLATE`]
+const PROGRESS_PREFIX: &str = "[localtube-progress]";
+/// Progress line printed by yt-dlp while downloading, carries the speed in bytes per second
+const PROGRESS_TEMPLATE: &str = "download:[localtube-progress] %(progress.speed)s";
+
+/// Returns the download speed of a progress line in bytes per second
+///
+/// `None` means the line is not a progress line, `Some(None)` that yt-dlp
+/// does not know the speed yet.
+fn parse_progress_speed(line: &str) -> Option<Option<u64>> {
+    let speed = line.trim().strip_prefix(PROGRESS_PREFIX)?.trim();
+    Some(
+        speed
+            .parse::<f64>()
+            .ok()
+            .filter(|speed| speed.is_finite() && *speed >= 0.0)
+            .map(|speed| speed as u64),
+    )
+}
+
+/// Runs a yt-dlp download, passing the speed of every progress line to `on_speed`
+///
+/// Progress lines are left out of the returned stderr to keep the download log readable.
+async fn run_download(
+    program: &Path,
+    args: &[String],
+    on_speed: &(dyn Fn(Option<u64>) + Sync),
+) -> std::io::Result<std::process::Output> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+    let mut stdout = child.stdout.take().expect("Failed to get yt-dlp stdout");
+    let stderr = child.stderr.take().expect("Failed to get yt-dlp stderr");
+    let read_stdout = async {
+        let mut buf = Vec::new();
+        stdout.read_to_end(&mut buf).await.map(|_| buf)
+    };
+    let read_stderr = async {
+        let mut lines = tokio::io::BufReader::new(stderr).lines();
+        let mut kept = Vec::new();
+        while let Some(line) = lines.next_line().await? {
+            if let Some(speed) = parse_progress_speed(&line) {
+                on_speed(speed);
+            } else {
+                kept.extend_from_slice(line.as_bytes());
+                kept.push(b'\n');
+            }
+        }
+        Ok(kept)
+    };
+    let (stdout, stderr) = tokio::try_join!(read_stdout, read_stderr)?;
+    Ok(std::process::Output {
+        status: child.wait().await?,
+        stdout,
+        stderr,
+    })
+}
+
+/// Downloads media from given URL and returns only the stored path
+///
+/// # Errors
+///
+/// Returns error if the download fails, see [`download_media`]
+pub async fn download_media_path(
+    url: &str,
+    source: &crate::models::_entities::sources::Model,
+    media_id: i32,
+    archive_entry: Option<&ArchiveEntry>,
+) -> Result<String> {
+    Ok(
+        download_media(url, source, media_id, archive_entry, &|_| {})
+            .await?
+            .media_path,
+    )
+}
+
+/// Inputs of [`build_download_args`] that depend on the environment
+#[derive(Debug, Clone, Default)]
+pub struct DownloadArgsOptions {
+    pub client: ClientOptions,
+    /// Directory the download is written into
+    pub source_dir: PathBuf,
+    /// Download archive to record the video in, if the source keeps one
+    pub archive_path: Option<PathBuf>,
+    /// Whether ffmpeg is available to remux and embed
+    pub postprocess: bool,
+    pub rate_limit: Option<String>,
+    /// Fragments fetched at once unless the source sets its own
+    pub concurrent_fragments: u32,
+    /// SponsorBlock instance the segments are fetched from, the public one if unset
+    pub sponsorblock_api: Option<String>,
+}
+
+/// Returns the yt-dlp arguments downloading `url` for `source`
+#[must_use]
+pub fn build_download_args(
+    source: &crate::models::_entities::sources::Model,
+    url: &str,
+    opts: &DownloadArgsOptions,
+) -> Vec<String> {
+    let mut args = opts.client.args();
+    args.extend(
+        opts.archive_path
+            .as_deref()
+            .map(download_archive_args)
+            .unwrap_or_default(),
+    );
+    args.extend(
+        [
+            "--dump-json",
+            "-t",
+            "sleep",
+            "--restrict-filenames",
+            "--write-info-json",
+        ]
+        .map(String::from),
+    );
+    args.push(format!("--paths={}", opts.source_dir.display()));
+    args.push("--max-downloads=1".to_string());
+    args.push("--no-simulate".to_string());
+    // --dump-json implies quiet, which sends the forced progress lines to stderr.
+    args.extend(
+        [
+            "--newline",
+            "--progress",
+            "--progress-template",
+            PROGRESS_TEMPLATE,
+        ]
+        .map(String::from),
+    );
+    if opts.postprocess {
+        // we reserialize to ensure we have only valid input
+        args.extend(postprocessing_args(
+            &source.get_sponsorblock_categories(),
+            source.embed_options(),
+        ));
+        args.extend(sponsorblock_api_args(
+            source.sponsorblock_api.as_deref(),
+            opts.sponsorblock_api.as_deref(),
+        ));
+        args.extend(aac_audio_args(source.aac_audio));
+    }
+    args.extend(sidecar_args(
+        source.write_description,
+        source.write_comments,
+    ));
+    args.extend(subtitle_args(source.write_subtitles, opts.postprocess));
+    args.extend(max_height_args(source.max_height));
+    args.extend(age_limit_args(source.age_limit));
+    args.extend(max_filesize_args(source.max_filesize_mb));
+    args.extend(rate_limit_args(opts.rate_limit.as_deref()));
+    args.extend(concurrent_fragments_args(
+        source.concurrent_fragments,
+        opts.concurrent_fragments,
+    ));
+    args.push(url.to_string());
+    args
+}
+
+/// Downloads media from given URL
+///
+/// # Errors
+///
+/// Returns error if download fails, source metadata is missing or invalid paths are encountered
+///
+/// # Note
+///
+/// The raw yt-dlp output of every attempt is kept in the download log of
+/// `media_id`, see [`crate::download_log`]. The current speed in bytes per
+/// second is passed to `on_speed` while the download runs.
+///
+/// # Note
+///
+/// When the source keeps a download archive and `archive_entry` is known, an
+/// archived video whose file is still on disk is adopted instead of being
+/// downloaded; if the file is gone its archive entry is dropped so yt-dlp
+/// fetches it again.
+///
+/// # Note
+///
+/// This function does not acquire the concurrency semaphore. The caller
+/// must ensure proper concurrency control (typically via `ActiveTask`).
+pub async fn download_media(
+    url: &str,
+    source: &crate::models::_entities::sources::Model,
+    media_id: i32,
+    archive_entry: Option<&ArchiveEntry>,
+    on_speed: &(dyn Fn(Option<u64>) + Sync),
+) -> Result<DownloadResult> {
+    let media_dir = &source.media_root();
+    let source_name =
+        source_dir_name(source).ok_or_else(|| Error::string("Missing source metadata"))?;
+    let source_dir = media_dir.join(source_name);
+    tokio::fs::create_dir_all(&source_dir).await?;
+
+    // Without a video id an archived entry could not be reconciled and yt-dlp would skip forever.
+    let archive_path = archive_entry
+        .filter(|_| source.download_archive)
+        .map(|_| download_archive_path(&source_dir));
+    if let (Some(archive_path), Some(entry)) = (&archive_path, archive_entry) {
+        if archive_contains(archive_path, entry).await {
+            if let Some(existing) = find_downloaded_file(&source_dir, &entry.video_id).await {
+                info!(
+                    "{} is in the download archive, adopting {}",
+                    entry.line(),
+                    existing.display()
+                );
+                return existing_download_result(&existing, media_dir).await;
+            }
+            warn!(
+                "{} is in the download archive but its file is missing, downloading again",
+                entry.line()
+            );
+            remove_archive_entry(archive_path, entry).await?;
+        }
+    }
+
+    let postprocess = ffmpeg_postprocessing(ffmpeg_path().is_file(), missing_ffmpeg())?;
+    if !postprocess {
+        warn!(
+            "ffmpeg is missing, downloading {} without remuxing and embedding",
+            url
+        );
+    }
+    let args = build_download_args(
+        source,
+        url,
+        &DownloadArgsOptions {
+            client: ClientOptions::for_source(source),
+            source_dir,
+            archive_path,
+            postprocess,
+            rate_limit: rate_limit(),
+            concurrent_fragments: concurrent_fragments(),
+            sponsorblock_api: sponsorblock_api(),
+        },
+    );
+
+    let program = yt_dlp_path();
+    let output = run_download(&program, &args, on_speed).await?;
+    let command = crate::download_log::command_line(&program, &args);
+    crate::download_log::save(media_id, url, &command, &output).await;
+
+    ytdlp_debug::log_ytdlp_json(
+        "download_media",
+        &output.stdout,
+        Some(url),
+        Some(&format!("source_id={}", source.id)),
+    )
+    .await;
+    let download = DownloadOutput::parse_last(&output.stdout).map_err(|err| {
+        // Without a download the reason, e.g. an age restriction, is only on stderr.
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if !stderr.trim().is_empty() {
+            return Error::string(stderr.trim());
+        }
+        // --dump-json keeps yt-dlp quiet about videos it skips for --age-limit,
+        // a successful run without a download is the only sign of the skip.
+        match source.age_limit {
+            Some(limit) if output.status.success() => Error::string(&format!(
+                "Skipped because it is age restricted, nothing was downloaded with --age-limit {limit}"
+            )),
+            _ => Error::from(err),
+        }
+    })?;
+
+    // yt-dlp do not report remuxed file path, we need to check if it exists
+    // check if the filename with .mkv extension exists if not check if the filename exists
+    // use existing file if it exists, error out if none exists
+    let video_path = PathBuf::from(&download.filename);
+    let (video_path, remuxed) = if video_path.with_extension("mkv").exists() {
+        let remuxed = video_path.extension().is_none_or(|ext| ext != "mkv");
+        (video_path.with_extension("mkv"), remuxed)
+    } else if video_path.exists() {
+        (video_path, false)
+    } else if let Some(mb) = source
+        .max_filesize_mb
+        .filter(|mb| exceeded_max_filesize(&download, *mb, &output.stderr))
+    {
+        return Err(Error::string(&format!(
+            "{} of {mb} MiB",
+            crate::workers::errors::MAX_FILESIZE_EXCEEDED
+        )));
+    } else {
+        return Err(Error::string("Failed to download media"));
+    };
+    let video_path = reconcile_suffixed_download(video_path).await?;
+    if source.write_comments {
+        warn_on_large_comments(&video_path.with_extension("info.json")).await;
+    }
+
+    let media_path = relative_media_path(&video_path, media_dir)?;
+    Ok(download.into_result(media_path, remuxed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        age_limit_args, archive_contains, build_download_args, clamp_permits,
+        concurrent_fragments_args, date_after_args, detect_list_order, download_archive_args,
+        download_archive_path, extract_list_tabs, extractor_allowed, ffmpeg_path_in,
+        ffmpeg_postprocessing, find_downloaded_file, flatten_probe_entries, is_media_file_name,
+        libs_dir_from, max_filesize_args, max_height_args, parse_allowed_extractors,
+        parse_concurrency, parse_ffmpeg_version, parse_progress_speed, parse_rate_limit,
+        parse_sponsorblock_api, playlist_items_args, postprocessing_args, rate_limit_args,
+        reconcile_suffixed_download, remove_archive_entry, representative_metadata,
+        sanitize_dir_name, sidecar_args, spawn_list_stream, stream_should_fail, subtitle_args,
+        subtitle_languages, subtitle_path, thumbnail_args, unsuffixed_path,
+        video_id_from_file_name, yt_dlp_path_in, ArchiveEntry, ClientOptions, Cookies, DateAfter,
+        DownloadArgsOptions, DownloadOutput, DownloadResult, EmbedOptions, MediaListOrder,
+        MissingFfmpeg, ProbeEntry, ProbeOutput, SourceListKind, SourceListOrder,
+        SourceListTabOption, ThumbnailMode, VideoMetadata, LIVE_RECHECK_DELAY, MAX_DIR_NAME_BYTES,
+        PROGRESS_TEMPLATE,
+    };
+    use crate::models::sources::{sample_source, SponsorBlockCategories};
+    use std::path::{Path, PathBuf};
+
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            let path = std::env::temp_dir().join(format!("localtube-{}", uuid::Uuid::new_v4()));
+            std::fs::create_dir_all(&path).expect("temp dir should be created");
+            Self(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn download_options() -> DownloadArgsOptions {
+        DownloadArgsOptions {
+            source_dir: PathBuf::from("media/Channel"),
+            postprocess: true,
+            ..Default::default()
+        }
+    }
+
+    fn archive_entry() -> ArchiveEntry {
+        ArchiveEntry {
+            extractor_key: "Youtube".to_string(),
+            video_id: "abc123".to_string(),
+        }
+    }
+
+    fn entry(timestamp: Option<i64>, upload_date: Option<&str>) -> ProbeEntry {
+        ProbeEntry {
+            kind: None,
+            timestamp,
+            upload_date: upload_date.map(str::to_string),
+            uploader: None,
+            extractor_key: None,
+            playlist_count: None,
+            webpage_url: None,
+            url: None,
+            title: None,
+        }
+    }
+
+    #[test]
+    fn stream_should_fail_when_exit_success_but_no_items() {
+        assert!(stream_should_fail(Some(0), 0, false));
+    }
+
+    #[test]
+    fn stream_should_fail_when_exit_failure_even_with_items() {
+        assert!(stream_should_fail(Some(1), 3, false));
+        assert!(stream_should_fail(None, 3, false));
+    }
+
+    #[test]
+    fn stream_should_succeed_when_exit_success_and_items_present() {
+        assert!(!stream_should_fail(Some(0), 2, false));
+    }
+
+    #[test]
+    fn stream_should_succeed_on_break_and_allowed_empty_list() {
+        assert!(!stream_should_fail(Some(101), 2, false));
+        assert!(!stream_should_fail(Some(101), 0, true));
+        assert!(!stream_should_fail(Some(0), 0, true));
+        assert!(stream_should_fail(Some(1), 0, true));
+    }
+
+    #[test]
+    fn detect_list_order_uses_timestamps() {
+        let entries = vec![entry(Some(100), None), entry(Some(200), None)];
+        assert_eq!(
+            detect_list_order(&entries),
+            Some(SourceListOrder::OldestFirst)
+        );
     }
 
     #[test]
@@ -691,6 +2406,44 @@ mod tests {
         );
     }
 
+    fn representative(json: &str) -> (Option<String>, Option<String>) {
+        let mut probe: ProbeOutput = serde_json::from_str(json).expect("probe json");
+        let entries = flatten_probe_entries(probe.entries.take());
+        representative_metadata(&probe, &SourceListKind::List, &entries)
+    }
+
+    #[test]
+    fn channel_is_named_after_the_newest_upload() {
+        let channel = r#"{"_type":"playlist","id":"UC1","channel_id":"UC1","title":"Chan - Videos","uploader":"Chan","extractor_key":"YoutubeTab","entries":[
+            {"uploader":"Old Name","extractor_key":"Youtube","timestamp":100},
+            {"uploader":"New Name","extractor_key":"Youtube","timestamp":200}]}"#;
+        assert_eq!(
+            representative(channel),
+            (Some("New Name".to_string()), Some("Youtube".to_string()))
+        );
+
+        let undated =
+            r#"{"_type":"playlist","entries":[{"uploader":"First"},{"uploader":"Second"}]}"#;
+        assert_eq!(representative(undated).0.as_deref(), Some("First"));
+        assert_eq!(
+            representative(r#"{"_type":"playlist","uploader":"Chan","entries":[]}"#).0,
+            Some("Chan".to_string())
+        );
+    }
+
+    #[test]
+    fn playlist_is_named_after_itself() {
+        let playlist = r#"{"_type":"playlist","id":"PL1","channel_id":"UC1","title":"Best Talks","uploader":"Curator","extractor_key":"YoutubeTab","entries":[
+            {"uploader":"Speaker","extractor_key":"Youtube","timestamp":200}]}"#;
+        assert_eq!(
+            representative(playlist),
+            (Some("Best Talks".to_string()), Some("Youtube".to_string()))
+        );
+
+        let untitled = r#"{"_type":"playlist","id":"PL1","channel_id":"UC1","uploader":"Curator","entries":[{"uploader":"Speaker"}]}"#;
+        assert_eq!(representative(untitled).0.as_deref(), Some("Curator"));
+    }
+
     #[test]
     fn probe_output_skips_null_entries() {
         let json = r#"{"_type":"playlist","entries":[null,{"_type":"url","webpage_url":"https://example.com/videos","title":"Videos"}]}"#;
@@ -702,4 +2455,961 @@ mod tests {
             Some("https://example.com/videos")
         );
     }
+
+    #[test]
+    fn client_options_emit_no_args_by_default() {
+        assert!(ClientOptions::default().args().is_empty());
+    }
+
+    #[test]
+    fn client_options_emit_configured_args() {
+        let options = ClientOptions {
+            user_agent: Some("Mozilla/5.0".to_string()),
+            extractor_args: Some("youtube:player_client=web".to_string()),
+            cookies: None,
+        };
+        assert_eq!(
+            options.args(),
+            vec![
+                "--user-agent",
+                "Mozilla/5.0",
+                "--extractor-args",
+                "youtube:player_client=web",
+            ]
+        );
+    }
+
+    #[test]
+    fn client_options_source_overrides_win_over_global() {
+        let global = ClientOptions {
+            user_agent: Some("global-agent".to_string()),
+            extractor_args: Some("youtube:player_client=web".to_string()),
+            cookies: Some(Cookies::Browser("firefox".to_string())),
+        };
+        let merged = global.with_overrides(None, Some("youtube:player_client=tv"));
+        assert_eq!(merged.user_agent.as_deref(), Some("global-agent"));
+        assert_eq!(
+            merged.extractor_args.as_deref(),
+            Some("youtube:player_client=tv")
+        );
+
+        assert_eq!(merged.cookies, global.cookies);
+
+        let blank = global.with_overrides(Some("  "), Some(""));
+        assert_eq!(blank, global);
+    }
+
+    #[test]
+    fn client_options_pass_cookies_from_browser() {
+        let options = ClientOptions {
+            cookies: Cookies::from_settings(None, Some("chrome:Profile 1")).unwrap(),
+            ..Default::default()
+        };
+        assert_eq!(
+            options.args(),
+            vec!["--cookies-from-browser", "chrome:Profile 1"]
+        );
+
+        let options = ClientOptions {
+            cookies: Cookies::from_settings(Some("/config/cookies.txt"), Some(" ")).unwrap(),
+            ..Default::default()
+        };
+        assert_eq!(options.args(), vec!["--cookies", "/config/cookies.txt"]);
+    }
+
+    #[test]
+    fn cookies_file_and_browser_are_mutually_exclusive() {
+        let err = Cookies::from_settings(Some("/config/cookies.txt"), Some("firefox"))
+            .expect_err("both cookie sources should be rejected");
+        assert!(err.to_string().contains("mutually exclusive"));
+    }
+
+    #[test]
+    fn download_archive_lives_in_source_dir() {
+        let source_dir = Path::new("media/Some_Channel");
+        let archive_path = download_archive_path(source_dir);
+        assert_eq!(
+            archive_path,
+            PathBuf::from("media/Some_Channel/download-archive.txt")
+        );
+        assert_eq!(
+            download_archive_args(&archive_path),
+            vec![
+                "--download-archive",
+                "media/Some_Channel/download-archive.txt"
+            ]
+        );
+    }
+
+    #[test]
+    fn video_id_is_parsed_from_file_name() {
+        assert_eq!(
+            video_id_from_file_name("Title_[abc123].mkv"),
+            Some("abc123")
+        );
+        assert_eq!(
+            video_id_from_file_name("Some_[tag]_Title_[abc123].mkv"),
+            Some("abc123")
+        );
+        assert_eq!(video_id_from_file_name("Title.mkv"), None);
+        assert_eq!(video_id_from_file_name("Title_[].mkv"), None);
+    }
+
+    #[test]
+    fn archive_entry_matches_yt_dlp_format() {
+        assert_eq!(archive_entry().line(), "youtube abc123");
+    }
+
+    #[tokio::test]
+    async fn archived_file_is_found_for_reconcile() {
+        let dir = TempDir::new();
+        let archive_path = download_archive_path(&dir.0);
+        std::fs::write(&archive_path, "youtube other\nyoutube abc123\n").unwrap();
+        for name in [
+            "Title_[abc123].info.json",
+            "Title_[abc123].webm",
+            "Title_[abc123].mkv",
+            "Other_[other].mkv",
+        ] {
+            std::fs::write(dir.0.join(name), b"").unwrap();
+        }
+
+        assert!(archive_contains(&archive_path, &archive_entry()).await);
+        assert_eq!(
+            find_downloaded_file(&dir.0, "abc123").await,
+            Some(dir.0.join("Title_[abc123].mkv"))
+        );
+    }
+
+    #[tokio::test]
+    async fn missing_archived_file_drops_archive_entry() {
+        let dir = TempDir::new();
+        let archive_path = download_archive_path(&dir.0);
+        std::fs::write(&archive_path, "youtube other\nyoutube abc123\n").unwrap();
+        std::fs::write(dir.0.join("Title_[abc123].mkv.part"), b"").unwrap();
+
+        assert_eq!(find_downloaded_file(&dir.0, "abc123").await, None);
+        remove_archive_entry(&archive_path, &archive_entry())
+            .await
+            .unwrap();
+
+        assert!(!archive_contains(&archive_path, &archive_entry()).await);
+        assert_eq!(
+            std::fs::read_to_string(&archive_path).unwrap(),
+            "youtube other\n"
+        );
+    }
+
+    #[test]
+    fn rate_limits_are_validated() {
+        assert_eq!(parse_rate_limit(" 500k "), Some("500K".to_string()));
+        assert_eq!(parse_rate_limit("4.2M"), Some("4.2M".to_string()));
+        assert_eq!(parse_rate_limit("1048576"), Some("1048576".to_string()));
+        for invalid in [
+            "", "0", "0.0K", "fast", "1e3", "-5M", ".5M", "1.2.3M", "5MB",
+        ] {
+            assert_eq!(parse_rate_limit(invalid), None, "{invalid}");
+        }
+        assert!(rate_limit_args(None).is_empty());
+        assert_eq!(rate_limit_args(Some("2M")), vec!["--limit-rate", "2M"]);
+    }
+
+    #[test]
+    fn copy_suffix_is_only_stripped_from_template_names() {
+        assert_eq!(
+            unsuffixed_path(Path::new("media/C/Title_[abc] (1).mkv")),
+            Some(PathBuf::from("media/C/Title_[abc].mkv"))
+        );
+        assert_eq!(
+            unsuffixed_path(Path::new("media/C/Title_[abc] (12).webm")),
+            Some(PathBuf::from("media/C/Title_[abc].webm"))
+        );
+        assert_eq!(unsuffixed_path(Path::new("media/C/Title_[abc].mkv")), None);
+        assert_eq!(unsuffixed_path(Path::new("media/C/Part (1).mkv")), None);
+        assert_eq!(
+            unsuffixed_path(Path::new("media/C/Title_[abc] (x).mkv")),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn suffixed_download_replaces_the_canonical_file() {
+        let dir = TempDir::new();
+        let canonical = dir.0.join("Title_[abc].mkv");
+        let copy = dir.0.join("Title_[abc] (1).mkv");
+        let older = dir.0.join("Title_[abc].webm");
+        std::fs::write(&canonical, b"old").unwrap();
+        std::fs::write(&older, b"older").unwrap();
+        std::fs::write(dir.0.join("Title_[abc].info.json"), b"{}").unwrap();
+        std::fs::write(dir.0.join("Title_[abc].jpg"), b"old").unwrap();
+        std::fs::write(&copy, b"new").unwrap();
+        std::fs::write(dir.0.join("Title_[abc] (1).info.json"), b"{\"new\":1}").unwrap();
+        for sidecar in ["webp", "en.vtt", "description"] {
+            std::fs::write(dir.0.join(format!("Title_[abc] (1).{sidecar}")), b"new").unwrap();
+        }
+        std::fs::write(dir.0.join("Title_[abc] (10).mkv"), b"other").unwrap();
+        std::fs::write(dir.0.join("Title_[abc].f137.mp4.part"), b"").unwrap();
+        // Intermediates of another download of the same video.
+        for intermediate in ["f137.mp4", "f251.webm", "temp.mkv"] {
+            std::fs::write(dir.0.join(format!("Title_[abc].{intermediate}")), b"").unwrap();
+        }
+
+        let kept = reconcile_suffixed_download(copy.clone()).await.unwrap();
+        assert_eq!(kept, canonical);
+        assert_eq!(std::fs::read(&canonical).unwrap(), b"new");
+        assert!(!copy.exists(), "no duplicate is left behind");
+        assert_eq!(
+            std::fs::read(dir.0.join("Title_[abc].info.json")).unwrap(),
+            b"{\"new\":1}"
+        );
+        assert!(!dir.0.join("Title_[abc] (1).info.json").exists());
+        assert!(!older.exists(), "the older download is removed");
+        assert!(!dir.0.join("Title_[abc].jpg").exists());
+        for sidecar in ["webp", "en.vtt", "description"] {
+            assert_eq!(
+                std::fs::read(dir.0.join(format!("Title_[abc].{sidecar}"))).unwrap(),
+                b"new",
+                "{sidecar}"
+            );
+            assert!(!dir.0.join(format!("Title_[abc] (1).{sidecar}")).exists());
+        }
+        assert!(dir.0.join("Title_[abc] (10).mkv").exists());
+        assert!(dir.0.join("Title_[abc].f137.mp4.part").exists());
+        for intermediate in ["f137.mp4", "f251.webm", "temp.mkv"] {
+            assert!(
+                dir.0.join(format!("Title_[abc].{intermediate}")).exists(),
+                "{intermediate}"
+            );
+        }
+
+        // Regular downloads stay where they are.
+        assert_eq!(
+            reconcile_suffixed_download(canonical.clone())
+                .await
+                .unwrap(),
+            canonical
+        );
+    }
+
+    #[test]
+    fn max_filesize_aborts_large_downloads() {
+        assert!(max_filesize_args(None).is_empty());
+        assert!(max_filesize_args(Some(0)).is_empty());
+        assert_eq!(max_filesize_args(Some(500)), vec!["--max-filesize", "500M"]);
+
+        let source = crate::models::_entities::sources::Model {
+            max_filesize_mb: Some(2048),
+            ..sample_source()
+        };
+        let args = build_download_args(&source, "https://example.com/v", &download_options());
+        let limit = args
+            .iter()
+            .position(|arg| arg == "--max-filesize")
+            .expect("max filesize arg");
+        assert_eq!(args[limit + 1], "2048M");
+        assert!(!build_download_args(
+            &sample_source(),
+            "https://example.com/v",
+            &download_options()
+        )
+        .contains(&"--max-filesize".to_string()));
+    }
+
+    #[test]
+    fn age_limit_is_passed_when_set() {
+        assert!(age_limit_args(None).is_empty());
+        assert_eq!(age_limit_args(Some(17)), ["--age-limit", "17"]);
+
+        let mut source = sample_source();
+        source.age_limit = Some(0);
+        let args = build_download_args(&source, "https://example.com/v", &download_options());
+        let limit = args
+            .iter()
+            .position(|a| a == "--age-limit")
+            .expect("age limit arg");
+        assert_eq!(args[limit + 1], "0");
+    }
+
+    #[test]
+    fn max_height_caps_format_selection() {
+        assert!(max_height_args(None).is_empty());
+        assert_eq!(
+            max_height_args(Some(720)),
+            vec!["-f", "bv*[height<=720]+ba/b[height<=720]"]
+        );
+
+        let source = crate::models::_entities::sources::Model {
+            max_height: Some(1080),
+            ..sample_source()
+        };
+        let args = build_download_args(&source, "https://example.com/v", &download_options());
+        let format = args.iter().position(|arg| arg == "-f").expect("format arg");
+        assert_eq!(args[format + 1], "bv*[height<=1080]+ba/b[height<=1080]");
+        assert_eq!(
+            args.last().map(String::as_str),
+            Some("https://example.com/v")
+        );
+    }
+
+    #[test]
+    fn parse_concurrency_uses_default_and_clamps() {
+        assert_eq!(parse_concurrency("TEST", None, 2), 2);
+        assert_eq!(parse_concurrency("TEST", Some("3"), 2), 3);
+        assert_eq!(parse_concurrency("TEST", Some("abc"), 2), 2);
+        assert_eq!(parse_concurrency("TEST", Some("0"), 2), 1);
+        assert_eq!(parse_concurrency("TEST", Some("50"), 2), 8);
+    }
+
+    #[test]
+    fn date_after_args_break_instead_on_newest_first_lists() {
+        assert!(date_after_args(None).is_empty());
+        let date = chrono::NaiveDate::from_ymd_opt(2026, 3, 7).unwrap();
+        assert_eq!(
+            date_after_args(Some(DateAfter {
+                date,
+                newest_first: false,
+            })),
+            vec!["--dateafter", "20260307"]
+        );
+        assert_eq!(
+            date_after_args(Some(DateAfter {
+                date,
+                newest_first: true,
+            })),
+            vec!["--break-match-filters", "upload_date>=?20260307"]
+        );
+    }
+
+    #[test]
+    fn download_permits_are_clamped_to_limit() {
+        assert_eq!(clamp_permits(None, 4), 1);
+        assert_eq!(clamp_permits(Some(0), 4), 1);
+        assert_eq!(clamp_permits(Some(-3), 4), 1);
+        assert_eq!(clamp_permits(Some(2), 4), 2);
+        assert_eq!(clamp_permits(Some(9), 4), 4);
+    }
+
+    #[tokio::test]
+    async fn cancelled_list_stream_stops_and_reaps_process() {
+        let dir = TempDir::new();
+        let pid_file = dir.0.join("pid");
+        let line = r#"{"id":"a","title":"A","description":null,"duration":1,"uploader":"U","n_entries":null,"extractor_key":"Youtube","original_url":"https://example.com/a","timestamp":0,"filename":"a.mkv"}"#;
+        let mut cmd = tokio::process::Command::new("sh");
+        cmd.arg("-c").arg(format!(
+            "echo $$ > '{}'; while true; do echo '{line}'; sleep 0.05; done",
+            pid_file.display()
+        ));
+        let cancel = tokio_util::sync::CancellationToken::new();
+        let mut rx = spawn_list_stream(cmd, "test".to_string(), false, cancel.clone());
+
+        assert!(rx.recv().await.expect("first item").is_ok());
+        let pid = std::fs::read_to_string(&pid_file).unwrap();
+        cancel.cancel();
+
+        // Items buffered before the cancellation may still arrive, but nothing after.
+        let drained = tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            let mut count = 0;
+            while let Some(item) = rx.recv().await {
+                assert!(item.is_ok(), "cancellation must not report an error");
+                count += 1;
+            }
+            count
+        })
+        .await
+        .expect("stream should close after cancellation");
+        assert!(drained <= 8, "{drained} items after cancel");
+        assert!(
+            !Path::new(&format!("/proc/{}", pid.trim())).exists(),
+            "yt-dlp process should be reaped"
+        );
+    }
+
+    #[test]
+    fn playlist_items_args_follow_order_and_limit() {
+        assert!(playlist_items_args(MediaListOrder::Original, None).is_empty());
+        assert_eq!(
+            playlist_items_args(MediaListOrder::Reverse, None),
+            vec!["-I", "::-1"]
+        );
+        assert_eq!(
+            playlist_items_args(MediaListOrder::Original, Some(10)),
+            vec!["-I", "1:10"]
+        );
+        assert_eq!(
+            playlist_items_args(MediaListOrder::Reverse, Some(10)),
+            vec!["-I", "-1:-10:-1"]
+        );
+    }
+
+    #[test]
+    fn thumbnail_args_cover_modes() {
+        assert_eq!(
+            thumbnail_args(ThumbnailMode::Embed),
+            vec!["--embed-thumbnail"]
+        );
+        assert_eq!(
+            thumbnail_args(ThumbnailMode::Jpg),
+            vec!["--write-thumbnail", "--convert-thumbnails", "jpg"]
+        );
+        assert_eq!(
+            thumbnail_args(ThumbnailMode::Webp),
+            vec!["--write-thumbnail", "--convert-thumbnails", "webp"]
+        );
+        assert!(thumbnail_args(ThumbnailMode::None).is_empty());
+    }
+
+    #[test]
+    fn thumbnail_mode_defaults_to_embed() {
+        assert_eq!(ThumbnailMode::from_setting(None), ThumbnailMode::Embed);
+        assert_eq!(
+            ThumbnailMode::from_setting(Some("bogus")),
+            ThumbnailMode::Embed
+        );
+        for mode in [
+            ThumbnailMode::Embed,
+            ThumbnailMode::Jpg,
+            ThumbnailMode::Webp,
+            ThumbnailMode::None,
+        ] {
+            assert_eq!(ThumbnailMode::from_setting(Some(mode.as_str())), mode);
+        }
+    }
+
+    #[test]
+    fn sidecars_are_not_media_files() {
+        assert!(is_media_file_name("Title_[abc].mkv"));
+        assert!(!is_media_file_name("Title_[abc].jpg"));
+        assert!(!is_media_file_name("Title_[abc].webp"));
+        assert!(!is_media_file_name("Title_[abc].description"));
+        assert!(!is_media_file_name("Title_[abc].en.vtt"));
+    }
+
+    #[tokio::test]
+    async fn subtitles_are_not_adopted_as_the_download() {
+        let dir = TempDir::new();
+        for name in ["Title_[abc123].en.vtt", "Title_[abc123].webm"] {
+            std::fs::write(dir.0.join(name), b"").unwrap();
+        }
+        assert_eq!(
+            find_downloaded_file(&dir.0, "abc123").await,
+            Some(dir.0.join("Title_[abc123].webm"))
+        );
+    }
+
+    #[test]
+    fn missing_ffmpeg_fails_with_instructions_unless_degraded() {
+        assert_eq!(MissingFfmpeg::from_setting(None), MissingFfmpeg::Fail);
+        assert_eq!(
+            MissingFfmpeg::from_setting(Some(" Degrade ")),
+            MissingFfmpeg::Degrade
+        );
+        assert!(ffmpeg_postprocessing(true, MissingFfmpeg::Fail).unwrap());
+        assert!(!ffmpeg_postprocessing(false, MissingFfmpeg::Degrade).unwrap());
+
+        let err = ffmpeg_postprocessing(false, MissingFfmpeg::Fail)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("ffmpeg is missing at"), "{err}");
+        assert!(err.contains("LOCALTUBE_MISSING_FFMPEG=degrade"), "{err}");
+    }
+
+    #[test]
+    fn postprocessing_args_include_remux_and_embeds() {
+        let no_thumbnail = EmbedOptions {
+            thumbnail: ThumbnailMode::None,
+            ..Default::default()
+        };
+        assert_eq!(
+            postprocessing_args(&SponsorBlockCategories::default(), no_thumbnail),
+            vec![
+                "--sponsorblock-remove=-all",
+                "--remux-video=mkv",
+                "--embed-metadata",
+                "--embed-subs"
+            ]
+        );
+        assert_eq!(
+            postprocessing_args(
+                &SponsorBlockCategories::deserialize("sponsor"),
+                EmbedOptions::default()
+            )
+            .last(),
+            Some(&"--embed-thumbnail".to_string())
+        );
+    }
+
+    #[test]
+    fn aac_audio_is_converted_only_when_enabled_with_ffmpeg() {
+        let url = "https://example.com/v";
+        let mut source = sample_source();
+        let args = build_download_args(&source, url, &download_options());
+        assert!(!args.iter().any(|a| a == "--postprocessor-args"));
+
+        source.aac_audio = true;
+        let args = build_download_args(&source, url, &download_options());
+        assert!(args.contains(&"--remux-video=mkv".to_string()));
+        assert!(args.contains(&"--merge-output-format=mkv".to_string()));
+        let pp: Vec<&str> = args
+            .windows(2)
+            .filter(|w| w[0] == "--postprocessor-args")
+            .map(|w| w[1].as_str())
+            .collect();
+        assert_eq!(
+            pp,
+            [
+                "Merger+ffmpeg_o:-c:a aac -b:a 192k",
+                "VideoRemuxer+ffmpeg_o:-c:a aac -b:a 192k"
+            ]
+        );
+
+        let without_ffmpeg = DownloadArgsOptions {
+            postprocess: false,
+            ..download_options()
+        };
+        let args = build_download_args(&source, url, &without_ffmpeg);
+        assert!(!args.iter().any(|a| a == "--postprocessor-args"));
+        assert!(!args.iter().any(|a| a.starts_with("--merge-output-format")));
+    }
+
+    #[test]
+    fn embed_flags_follow_their_own_setting() {
+        let url = "https://example.com/v";
+        let embeds = |source: &crate::models::_entities::sources::Model| -> Vec<String> {
+            build_download_args(source, url, &download_options())
+                .into_iter()
+                .filter(|a| a.starts_with("--embed-"))
+                .collect()
+        };
+        let mut source = sample_source();
+        assert_eq!(
+            embeds(&source),
+            ["--embed-metadata", "--embed-subs", "--embed-thumbnail"]
+        );
+
+        source.skip_embed_metadata = true;
+        assert_eq!(embeds(&source), ["--embed-subs", "--embed-thumbnail"]);
+
+        source.skip_embed_metadata = false;
+        source.skip_embed_subs = true;
+        assert_eq!(embeds(&source), ["--embed-metadata", "--embed-thumbnail"]);
+
+        source.skip_embed_subs = false;
+        source.thumbnail = Some("none".to_string());
+        assert_eq!(embeds(&source), ["--embed-metadata", "--embed-subs"]);
+    }
+
+    #[test]
+    fn download_args_default_to_remux_and_embeds() {
+        let url = "https://example.com/watch?v=abc";
+        assert_eq!(
+            build_download_args(&sample_source(), url, &download_options()),
+            vec![
+                "--dump-json",
+                "-t",
+                "sleep",
+                "--restrict-filenames",
+                "--write-info-json",
+                "--paths=media/Channel",
+                "--max-downloads=1",
+                "--no-simulate",
+                "--newline",
+                "--progress",
+                "--progress-template",
+                PROGRESS_TEMPLATE,
+                "--sponsorblock-remove=-all",
+                "--remux-video=mkv",
+                "--embed-metadata",
+                "--embed-subs",
+                "--embed-thumbnail",
+                url,
+            ]
+        );
+    }
+
+    #[test]
+    fn sponsorblock_api_is_passed_when_configured() {
+        let default = build_download_args(
+            &sample_source(),
+            "https://example.com/v",
+            &download_options(),
+        );
+        assert!(!default.iter().any(|a| a == "--sponsorblock-api"));
+
+        let opts = DownloadArgsOptions {
+            sponsorblock_api: Some("https://sb.example.org".to_string()),
+            ..download_options()
+        };
+        let args = build_download_args(&sample_source(), "https://example.com/v", &opts);
+        let api = args
+            .iter()
+            .position(|a| a == "--sponsorblock-api")
+            .expect("sponsorblock api arg");
+        assert_eq!(args[api + 1], "https://sb.example.org");
+        assert!(args.iter().any(|a| a.starts_with("--sponsorblock-remove=")));
+
+        // The source's own instance wins over the global one.
+        let source = crate::models::_entities::sources::Model {
+            sponsorblock_api: Some("https://sb.mirror.example".to_string()),
+            ..sample_source()
+        };
+        let args = build_download_args(&source, "https://example.com/v", &opts);
+        let api = args
+            .iter()
+            .position(|a| a == "--sponsorblock-api")
+            .expect("sponsorblock api arg");
+        assert_eq!(args[api + 1], "https://sb.mirror.example");
+
+        // Without ffmpeg segments cannot be removed, so the instance is not queried.
+        let degraded = DownloadArgsOptions {
+            postprocess: false,
+            ..opts
+        };
+        let args = build_download_args(&sample_source(), "https://example.com/v", &degraded);
+        assert!(!args.iter().any(|a| a == "--sponsorblock-api"));
+    }
+
+    #[test]
+    fn concurrent_fragments_follow_source_then_global() {
+        assert!(concurrent_fragments_args(None, 1).is_empty());
+        assert!(concurrent_fragments_args(None, 0).is_empty());
+        assert_eq!(
+            concurrent_fragments_args(None, 4),
+            vec!["--concurrent-fragments", "4"]
+        );
+        assert_eq!(
+            concurrent_fragments_args(Some(8), 4),
+            vec!["--concurrent-fragments", "8"]
+        );
+        assert_eq!(
+            concurrent_fragments_args(Some(1_000), 1),
+            vec!["--concurrent-fragments", "16"]
+        );
+        assert!(concurrent_fragments_args(Some(1), 4).is_empty());
+
+        let source = crate::models::_entities::sources::Model {
+            concurrent_fragments: Some(6),
+            ..sample_source()
+        };
+        let args = build_download_args(&source, "https://example.com/v", &download_options());
+        let fragments = args
+            .iter()
+            .position(|arg| arg == "--concurrent-fragments")
+            .expect("concurrent fragments arg");
+        assert_eq!(args[fragments + 1], "6");
+        let opts = DownloadArgsOptions {
+            concurrent_fragments: 3,
+            ..download_options()
+        };
+        let args = build_download_args(&sample_source(), "https://example.com/v", &opts);
+        assert!(args
+            .windows(2)
+            .any(|pair| pair == ["--concurrent-fragments", "3"]));
+    }
+
+    #[test]
+    fn only_allowed_extractors_pass() {
+        assert_eq!(parse_allowed_extractors(None), None);
+        assert_eq!(parse_allowed_extractors(Some(" , ")), None);
+        let allowed = parse_allowed_extractors(Some("YouTube, vimeo"));
+        assert_eq!(
+            allowed.as_deref(),
+            Some(&["youtube".to_string(), "vimeo".to_string()][..])
+        );
+
+        for key in ["Youtube", "YoutubeTab", "Vimeo"] {
+            assert!(extractor_allowed(allowed.as_deref(), key), "{key}");
+        }
+        for key in ["Generic", "Twitch", "unknown"] {
+            assert!(!extractor_allowed(allowed.as_deref(), key), "{key}");
+        }
+        assert!(extractor_allowed(None, "Generic"));
+    }
+
+    #[test]
+    fn sponsorblock_api_urls_are_validated() {
+        assert_eq!(
+            parse_sponsorblock_api(" https://sb.example.org/ "),
+            Some("https://sb.example.org".to_string())
+        );
+        assert_eq!(
+            parse_sponsorblock_api("http://10.0.0.2:8080/api"),
+            Some("http://10.0.0.2:8080/api".to_string())
+        );
+        for invalid in [
+            "",
+            "sb.example.org",
+            "ftp://sb.example.org",
+            "https://",
+            "https://a b",
+        ] {
+            assert_eq!(parse_sponsorblock_api(invalid), None, "{invalid}");
+        }
+    }
+
+    #[test]
+    fn download_args_follow_source_and_options() {
+        let mut source = sample_source();
+        source.sponsorblock = "sponsor,intro".to_string();
+        source.write_description = true;
+        let opts = DownloadArgsOptions {
+            client: ClientOptions {
+                user_agent: Some("agent".to_string()),
+                ..Default::default()
+            },
+            archive_path: Some(PathBuf::from("media/Channel/.archive")),
+            rate_limit: Some("2M".to_string()),
+            ..download_options()
+        };
+        let args = build_download_args(&source, "https://example.com/v", &opts);
+        assert_eq!(&args[..2], ["--user-agent", "agent"]);
+        assert_eq!(
+            args.last().map(String::as_str),
+            Some("https://example.com/v")
+        );
+        for expected in ["--download-archive", "--write-description", "--limit-rate"] {
+            assert!(args.iter().any(|a| a == expected), "{expected}: {args:?}");
+        }
+        assert!(
+            args.iter()
+                .any(|a| a == "--sponsorblock-remove=sponsor,intro"),
+            "{args:?}"
+        );
+        assert!(!args.iter().any(|a| a.starts_with("--sponsorblock-mark")));
+
+        source.sponsorblock = "sponsor;intro,outro".to_string();
+        let marked = build_download_args(&source, "https://example.com/v", &opts);
+        assert!(marked.contains(&"--sponsorblock-remove=sponsor".to_string()));
+        assert!(marked.contains(&"--sponsorblock-mark=intro,outro".to_string()));
+
+        let degraded = DownloadArgsOptions {
+            postprocess: false,
+            ..download_options()
+        };
+        let args = build_download_args(&source, "https://example.com/v", &degraded);
+        assert!(!args
+            .iter()
+            .any(|a| a.starts_with("--remux-video") || a.starts_with("--embed-")));
+    }
+
+    #[test]
+    fn sidecar_args_are_opt_in() {
+        assert!(sidecar_args(false, false).is_empty());
+        assert_eq!(sidecar_args(true, false), vec!["--write-description"]);
+        assert_eq!(
+            sidecar_args(true, true),
+            vec!["--write-description", "--write-comments"]
+        );
+        assert_eq!(sidecar_args(false, true), vec!["--write-comments"]);
+    }
+
+    #[test]
+    fn libs_dir_override_changes_dependency_paths() {
+        let default = libs_dir_from(None);
+        assert_eq!(default, PathBuf::from("libs"));
+        assert_eq!(yt_dlp_path_in(&default), PathBuf::from("libs/yt-dlp"));
+        assert_eq!(libs_dir_from(Some("  ")), default);
+
+        let custom = libs_dir_from(Some("/var/lib/localtube/libs"));
+        assert_eq!(
+            yt_dlp_path_in(&custom),
+            PathBuf::from("/var/lib/localtube/libs/yt-dlp")
+        );
+        assert_eq!(
+            ffmpeg_path_in(&custom),
+            PathBuf::from("/var/lib/localtube/libs/ffmpeg")
+        );
+    }
+
+    #[test]
+    fn ffmpeg_version_is_read_from_the_banner() {
+        assert_eq!(
+            parse_ffmpeg_version(
+                "ffmpeg version 7.1-static https://johnvansickle.com/ffmpeg/  Copyright (c) 2000-2024\nbuilt with gcc 8"
+            ),
+            Some("7.1-static".to_string())
+        );
+        assert_eq!(parse_ffmpeg_version("Unknown option -version"), None);
+        assert_eq!(parse_ffmpeg_version(""), None);
+    }
+
+    #[test]
+    fn upcoming_items_are_available_at_their_start() {
+        let parse = |extra: &str| -> VideoMetadata {
+            serde_json::from_str(&format!(
+                r#"{{"id":"abc","title":"Title","description":null,"uploader":"Channel","n_entries":null,"extractor_key":"Youtube","original_url":"https://example.com/watch?v=abc","timestamp":1700000000,"filename":"Title_[abc].webm"{extra}}}"#
+            ))
+            .expect("video json")
+        };
+        let now = 1_700_000_000;
+        let recheck = now + i64::try_from(LIVE_RECHECK_DELAY.as_secs()).unwrap();
+
+        let upcoming = parse(r#","live_status":"is_upcoming","release_timestamp":1700007200"#);
+        assert_eq!(upcoming.duration, 0);
+        assert_eq!(upcoming.available_at(now), Some(1_700_007_200));
+        let overdue = parse(r#","live_status":"is_upcoming","release_timestamp":1690000000"#);
+        assert_eq!(overdue.available_at(now), Some(now));
+        assert_eq!(
+            parse(r#","live_status":"is_upcoming""#).available_at(now),
+            Some(recheck)
+        );
+        assert_eq!(
+            parse(r#","live_status":"is_live""#).available_at(now),
+            Some(recheck)
+        );
+        assert_eq!(parse(r#","is_live":true"#).available_at(now), Some(recheck));
+        assert_eq!(
+            parse(r#","duration":60,"live_status":"was_live""#).available_at(now),
+            None
+        );
+        assert_eq!(parse(r#","duration":60"#).available_at(now), None);
+    }
+
+    #[test]
+    fn subtitle_args_convert_to_vtt_with_ffmpeg() {
+        assert!(subtitle_args(false, true).is_empty());
+        assert_eq!(
+            subtitle_args(true, true),
+            vec![
+                "--write-subs",
+                "--sub-format",
+                "vtt/best",
+                "--convert-subs",
+                "vtt"
+            ]
+        );
+        assert_eq!(
+            subtitle_args(true, false),
+            vec!["--write-subs", "--sub-format", "vtt/best"]
+        );
+    }
+
+    #[test]
+    fn subtitle_sidecars_are_found_next_to_the_media() {
+        let dir = TempDir::new();
+        let media = dir.0.join("Title_[abc].mkv");
+        for name in [
+            "Title_[abc].mkv",
+            "Title_[abc].en.vtt",
+            "Title_[abc].pt-BR.vtt",
+            "Title_[abc].info.json",
+            "Other_[xyz].en.vtt",
+        ] {
+            std::fs::write(dir.0.join(name), b"").unwrap();
+        }
+
+        assert_eq!(subtitle_languages(&media), vec!["en", "pt-BR"]);
+        assert_eq!(
+            subtitle_path(&media, "en"),
+            Some(dir.0.join("Title_[abc].en.vtt"))
+        );
+        assert_eq!(subtitle_path(&media, "../en"), None);
+        assert_eq!(subtitle_path(&media, ""), None);
+    }
+
+    #[test]
+    fn download_output_parses_into_result() {
+        let json = r#"{"id":"abc123","title":"Title","filename":"media/Channel/Title_[abc123].webm","format_id":"303+251","ext":"webm","filesize":null,"filesize_approx":123456,"width":1920,"height":1080,"vcodec":"vp9"}"#;
+        let output: DownloadOutput = serde_json::from_str(json).expect("download json");
+        let result = output.into_result("Channel/Title_[abc123].mkv".to_string(), true);
+        assert_eq!(
+            result,
+            DownloadResult {
+                media_path: "Channel/Title_[abc123].mkv".to_string(),
+                format_id: Some("303+251".to_string()),
+                ext: Some("mkv".to_string()),
+                filesize: Some(123_456),
+                width: Some(1920),
+                height: Some(1080),
+                remuxed: true,
+            }
+        );
+    }
+
+    #[test]
+    fn download_output_parses_the_last_json_line() {
+        let stdout = concat!(
+            "[plugin] loaded\n",
+            r#"{"filename":"media/Channel/First_[a].webm","ext":"webm"}"#,
+            "\n\n",
+            r#"{"filename":"media/Channel/Second_[b].mp4","ext":"mp4","height":720}"#,
+            "\n",
+            "Deleting original file media/Channel/Second_[b].f140.m4a\n",
+        );
+        let output = DownloadOutput::parse_last(stdout.as_bytes()).expect("download json");
+        assert_eq!(output.filename, "media/Channel/Second_[b].mp4");
+        assert_eq!(output.height, Some(720));
+
+        let single = r#"{"filename":"media/Channel/Only_[c].mkv"}"#;
+        assert_eq!(
+            DownloadOutput::parse_last(single.as_bytes())
+                .expect("download json")
+                .filename,
+            "media/Channel/Only_[c].mkv"
+        );
+        assert!(DownloadOutput::parse_last(b"ERROR: Video unavailable\n").is_err());
+        assert!(DownloadOutput::parse_last(b"").is_err());
+    }
+
+    #[test]
+    fn download_output_tolerates_missing_format_fields() {
+        let json = r#"{"filename":"media/Channel/Audio_[xyz].m4a","ext":"m4a","filesize":2048}"#;
+        let output: DownloadOutput = serde_json::from_str(json).expect("download json");
+        let result = output.into_result("Channel/Audio_[xyz].m4a".to_string(), false);
+        assert_eq!(result.ext.as_deref(), Some("m4a"));
+        assert_eq!(result.filesize, Some(2048));
+        assert_eq!(result.height, None);
+        assert_eq!(result.downloaded_height(), None);
+        assert!(!result.remuxed);
+    }
+
+    #[test]
+    fn download_output_reports_downloaded_format() {
+        // Asked for up to 1080p, only a 720p stream was available.
+        let json = r#"{"filename":"media/Channel/Clip_[def].mp4","format_id":"136+140","format":"136 - 1280x720 (720p)+140 - audio only","ext":"mp4","width":1280,"height":720}"#;
+        let output: DownloadOutput = serde_json::from_str(json).expect("download json");
+        let result = output.into_result("Channel/Clip_[def].mp4".to_string(), false);
+        assert_eq!(result.format_id.as_deref(), Some("136+140"));
+        assert_eq!(result.downloaded_height(), Some(720));
+    }
+
+    #[test]
+    fn sanitize_dir_name_keeps_plain_names() {
+        assert_eq!(
+            sanitize_dir_name("Some Channel (Official)", 7),
+            "Some Channel (Official)"
+        );
+    }
+
+    #[test]
+    fn sanitize_dir_name_falls_back_to_source_id() {
+        assert_eq!(sanitize_dir_name("🎵🎶", 7), "source-7");
+        assert_eq!(sanitize_dir_name("", 7), "source-7");
+        assert_eq!(sanitize_dir_name(".", 7), "source-7");
+        assert_eq!(sanitize_dir_name("..", 7), "source-7");
+        assert_eq!(sanitize_dir_name("../", 7), "source-7");
+    }
+
+    #[test]
+    fn sanitize_dir_name_strips_separators_and_trailing_dots() {
+        assert_eq!(sanitize_dir_name("../../etc", 7), "....etc");
+        assert_eq!(sanitize_dir_name("Channel...", 7), "Channel");
+        assert_eq!(sanitize_dir_name(" Channel . ", 7), "Channel");
+    }
+
+    #[test]
+    fn sanitize_dir_name_caps_length_on_char_boundary() {
+        let name = sanitize_dir_name(&"é".repeat(150), 7);
+        assert!(name.len() <= MAX_DIR_NAME_BYTES);
+        assert_eq!(name, "é".repeat(100));
+    }
+
+    #[test]
+    fn parse_progress_speed_reads_template_lines() {
+        assert_eq!(
+            parse_progress_speed("[localtube-progress] 1536000.5"),
+            Some(Some(1_536_000))
+        );
+        assert_eq!(parse_progress_speed("[localtube-progress] NA"), Some(None));
+        assert_eq!(parse_progress_speed("[download] Destination: a.mkv"), None);
+    }
 }
@@ -215,3 +215,39 @@ async fn refresh_restart_does_not_reset_download_failure_streak() {
     .await;
     supervisor::deactivate(&manager);
 }
+
+#[tokio::test]
+async fn refresh_triggered_restart_is_attributed_to_refresh() {
+    let manager = TaskManager::new();
+    manager.set_gluetun_enabled(true);
+    let sem = Arc::new(tokio::sync::Semaphore::new(1));
+    manager
+        .add_task(TaskType::DownloadVideo, "download-ok".to_string())
+        .start(sem)
+        .await
+        .complete();
+
+    assert!(manager.begin_gluetun_restart(Some(TaskType::RefreshIndex)));
+    let snapshot = manager.get_metrics();
+    assert!(snapshot.tasks[&TaskType::DownloadVideo].restart_in_progress);
+    assert!(snapshot.tasks[&TaskType::RefreshIndex].restart_in_progress);
+
+    manager.finish_gluetun_restart(
+        Some(TaskType::RefreshIndex),
+        &Ok(GluetunRestartOutcome {
+            stop_outcome: Some("stopped".to_string()),
+            start_outcome: Some("running".to_string()),
+        }),
+    );
+
+    let snapshot = manager.get_metrics();
+    let refresh = &snapshot.tasks[&TaskType::RefreshIndex];
+    assert_eq!(refresh.restart_count, 1);
+    assert!(refresh.last_restart_outcome.is_some());
+    assert!(refresh.last_restart_seconds_ago.is_some());
+    let download = &snapshot.tasks[&TaskType::DownloadVideo];
+    assert_eq!(download.restart_count, 0);
+    assert_eq!(download.last_restart_outcome, None);
+    assert_eq!(download.last_restart_seconds_ago, None);
+    assert!(!download.restart_in_progress);
+}
@@ -0,0 +1,161 @@
+//! Fixtures shared by the integration test binaries
+//!
+//! Every binary declares the whole module and only uses some of it.
+#![allow(dead_code)]
+
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use axum::body;
+use localtube::{
+    models::{
+        _entities::{medias, sources},
+        medias::MediaMetadata,
+    },
+    ytdlp,
+};
+use loco_rs::prelude::*;
+use uuid::Uuid;
+
+/// Folder under the media directory, removed with everything in it when dropped
+pub struct TempMediaDir {
+    pub rel_dir: String,
+}
+
+impl TempMediaDir {
+    pub fn new() -> Self {
+        let rel_dir = format!("test_media_{}", Uuid::new_v4());
+        std::fs::create_dir_all(localtube::ytdlp::media_directory().join(&rel_dir))
+            .expect("media test directory should be created");
+        Self { rel_dir }
+    }
+
+    /// Returns the path of `name` in the folder, relative to the media directory
+    pub fn rel_path(&self, name: &str) -> String {
+        format!("{}/{name}", self.rel_dir)
+    }
+
+    /// Writes `name` into the folder and returns its relative path
+    pub fn create_file(&self, name: &str, content: &[u8]) -> String {
+        let rel_path = self.rel_path(name);
+        std::fs::write(localtube::ytdlp::media_directory().join(&rel_path), content)
+            .expect("media test file should be created");
+        rel_path
+    }
+}
+
+impl Drop for TempMediaDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(localtube::ytdlp::media_directory().join(&self.rel_dir));
+    }
+}
+
+/// Shell script standing in for yt-dlp until dropped
+pub struct FakeYtDlp {
+    /// Folder of the script, removed when dropped
+    pub dir: PathBuf,
+}
+
+impl FakeYtDlp {
+    /// Runs the `sh` body `script` built for the script folder instead of yt-dlp
+    pub fn install(script: impl FnOnce(&Path) -> String) -> Self {
+        let dir = std::env::temp_dir().join(format!("localtube-yt-dlp-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).expect("fake yt-dlp folder should be created");
+        let program = dir.join("yt-dlp");
+        std::fs::write(&program, format!("#!/bin/sh\n{}\n", script(&dir)))
+            .expect("fake yt-dlp should be written");
+        let mut permissions = std::fs::metadata(&program).unwrap().permissions();
+        std::os::unix::fs::PermissionsExt::set_mode(&mut permissions, 0o755);
+        std::fs::set_permissions(&program, permissions).expect("fake yt-dlp should be executable");
+        ytdlp::set_yt_dlp_program(Some(program));
+        Self { dir }
+    }
+}
+
+impl Drop for FakeYtDlp {
+    fn drop(&mut self) {
+        ytdlp::set_yt_dlp_program(None);
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Inserts a source with the settings of a newly added one
+pub async fn create_source(db: &DatabaseConnection) -> sources::Model {
+    sources::ActiveModel {
+        url: Set("https://example.com/channel".to_string()),
+        fetch_last_days: Set(7),
+        refresh_frequency: Set(24),
+        sponsorblock: Set(String::new()),
+        ..Default::default()
+    }
+    .insert(db)
+    .await
+    .expect("source should be inserted")
+}
+
+/// Inserts a downloaded media of `source` and writes its five byte file
+///
+/// `media_path` is relative to the media root of the source. The video is
+/// titled after `video_id` and was published at `timestamp`.
+pub async fn create_downloaded_media(
+    db: &DatabaseConnection,
+    source: &sources::Model,
+    media_path: &str,
+    video_id: &str,
+    timestamp: i64,
+) -> medias::Model {
+    let file = source.media_root().join(media_path);
+    std::fs::create_dir_all(file.parent().expect("media path should have a folder"))
+        .expect("media folder should be created");
+    std::fs::write(&file, b"media").expect("media file should be written");
+    let metadata = MediaMetadata {
+        title: video_id.to_string(),
+        description: None,
+        duration: 60,
+        extractor_key: "Youtube".to_string(),
+        original_url: format!("https://example.com/watch?v={video_id}"),
+        timestamp,
+        age_limit: None,
+    };
+    medias::ActiveModel {
+        source_id: Set(source.id),
+        url: Set(metadata.original_url.clone()),
+        video_id: Set(Some(video_id.to_string())),
+        metadata: Set(Some(serde_json::to_value(metadata).unwrap())),
+        media_path: Set(Some(media_path.to_string())),
+        file_size: Set(Some(5)),
+        ..Default::default()
+    }
+    .insert(db)
+    .await
+    .expect("media should be inserted")
+}
+
+/// Collects the JSON bodies posted to the returned webhook URL
+pub async fn webhook_receiver() -> (String, Arc<Mutex<Vec<serde_json::Value>>>) {
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let app = axum::Router::new().route(
+        "/hook",
+        axum::routing::post({
+            let received = Arc::clone(&received);
+            move |axum::Json(body): axum::Json<serde_json::Value>| async move {
+                received.lock().unwrap().push(body);
+            }
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await });
+    (url, received)
+}
+
+/// Returns the body of a rendered view
+pub fn render(response: Response) -> String {
+    let runtime = tokio::runtime::Runtime::new().expect("tokio runtime should be created");
+    let body_bytes = runtime
+        .block_on(body::to_bytes(response.into_body(), usize::MAX))
+        .expect("Converting response body into bytes should succeed");
+    String::from_utf8(body_bytes.to_vec()).expect("Body should be valid UTF-8")
+}
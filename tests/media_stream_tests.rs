@@ -1,5 +1,10 @@
 use axum::http::{header, StatusCode};
-use localtube::{app::App, models::_entities};
+use localtube::{
+    app::App,
+    job_tracking::manager::TaskManager,
+    models::_entities,
+    services::{share, transcode, trash},
+};
 use loco_rs::prelude::*;
 use sea_orm::{ActiveModelTrait, Set};
 use serial_test::serial;
@@ -131,6 +136,47 @@ async fn stream_honors_single_range_request() {
     .await;
 }
 
+#[tokio::test]
+#[serial]
+async fn stream_serves_ranges_of_a_large_file() {
+    request_with_create_db::<App, _, _>(|request, ctx| async move {
+        // Several chunks long, with bytes that differ between neighbouring chunks.
+        let content: Vec<u8> = (0..8 * 1024 * 1024 + 123)
+            .map(|i: u32| (i % 251) as u8)
+            .collect();
+        let temp = TempMediaFile::new(&content);
+        let media = create_media(&ctx, &temp.rel_path).await;
+
+        let (start, end) = (3 * 1024 * 1024 + 7, 5 * 1024 * 1024 + 70_001);
+        let response = request
+            .get(&format!("/medias/{}/stream", media.id))
+            .add_header(header::RANGE, format!("bytes={start}-{end}"))
+            .await;
+        assert_eq!(response.status_code(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(
+            response
+                .header(header::CONTENT_RANGE)
+                .to_str()
+                .expect("content range header should be valid"),
+            format!("bytes {start}-{end}/{}", content.len())
+        );
+        assert_eq!(response.as_bytes().as_ref(), &content[start..=end]);
+
+        let tail = request
+            .get(&format!("/medias/{}/stream", media.id))
+            .add_header(header::RANGE, "bytes=-1000")
+            .await;
+        assert_eq!(tail.status_code(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(tail.as_bytes().as_ref(), &content[content.len() - 1000..]);
+
+        let full = request.get(&format!("/medias/{}/stream", media.id)).await;
+        assert_eq!(full.status_code(), StatusCode::OK);
+        assert_eq!(full.as_bytes().len(), content.len());
+        assert!(full.as_bytes().as_ref() == content.as_slice());
+    })
+    .await;
+}
+
 #[tokio::test]
 #[serial]
 async fn stream_rejects_invalid_range_request() {
@@ -175,3 +221,625 @@ async fn stream_ignores_unsupported_range_unit() {
     })
     .await;
 }
+
+#[tokio::test]
+#[serial]
+async fn stream_range_request_updates_metrics() {
+    request_with_create_db::<App, _, _>(|request, ctx| async move {
+        let content = b"0123456789";
+        let temp = TempMediaFile::new(content);
+        let media = create_media(&ctx, &temp.rel_path).await;
+        let before = TaskManager::global().get_metrics().stream;
+
+        let response = request
+            .get(&format!("/medias/{}/stream", media.id))
+            .add_header(header::RANGE, "bytes=2-5")
+            .await;
+        assert_eq!(response.status_code(), StatusCode::PARTIAL_CONTENT);
+
+        let metrics = request.get("/metrics").await;
+        let after: serde_json::Value = metrics.json();
+        let stream = &after["stream"];
+        assert_eq!(stream["bytes_served"], before.bytes_served + 4);
+        assert_eq!(stream["request_count"], before.request_count + 1);
+        assert_eq!(
+            stream["range_request_count"],
+            before.range_request_count + 1
+        );
+        assert_eq!(stream["full_request_count"], before.full_request_count);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn stream_returns_not_modified_for_matching_etag() {
+    request_with_create_db::<App, _, _>(|request, ctx| async move {
+        let content = b"0123456789";
+        let temp = TempMediaFile::new(content);
+        let media = create_media(&ctx, &temp.rel_path).await;
+        let url = format!("/medias/{}/stream", media.id);
+
+        let response = request.get(&url).await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+        let etag = response
+            .header(header::ETAG)
+            .to_str()
+            .expect("etag header should be valid")
+            .to_string();
+        assert!(etag.starts_with('"') && etag.ends_with('"'));
+        let last_modified = response
+            .header(header::LAST_MODIFIED)
+            .to_str()
+            .expect("last modified header should be valid")
+            .to_string();
+
+        let response = request
+            .get(&url)
+            .add_header(header::IF_NONE_MATCH, etag.as_str())
+            .await;
+        assert_eq!(response.status_code(), StatusCode::NOT_MODIFIED);
+        assert!(response.as_bytes().is_empty());
+
+        let response = request
+            .get(&url)
+            .add_header(header::IF_MODIFIED_SINCE, last_modified.as_str())
+            .await;
+        assert_eq!(response.status_code(), StatusCode::NOT_MODIFIED);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn stream_serves_content_for_stale_etag() {
+    request_with_create_db::<App, _, _>(|request, ctx| async move {
+        let content = b"0123456789";
+        let temp = TempMediaFile::new(content);
+        let media = create_media(&ctx, &temp.rel_path).await;
+        let url = format!("/medias/{}/stream", media.id);
+
+        let response = request
+            .get(&url)
+            .add_header(header::IF_NONE_MATCH, "\"stale\"")
+            .await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+        assert_eq!(response.as_bytes().as_ref(), content);
+
+        let response = request
+            .get(&url)
+            .add_header(header::IF_NONE_MATCH, "\"stale\"")
+            .add_header(header::RANGE, "bytes=2-5")
+            .await;
+        assert_eq!(response.status_code(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.as_bytes().as_ref(), &content[2..=5]);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn thumbnail_honors_if_none_match() {
+    request_with_create_db::<App, _, _>(|request, ctx| async move {
+        let temp = TempMediaFile::new(b"0123456789");
+        let thumbnail_path = temp.full_path.with_extension("jpg");
+        std::fs::write(&thumbnail_path, b"jpeg").expect("thumbnail should be created");
+        let media = create_media(&ctx, &temp.rel_path).await;
+        let url = format!("/medias/{}/thumbnail", media.id);
+
+        let response = request.get(&url).await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+        assert_eq!(response.as_bytes().as_ref(), b"jpeg");
+        let etag = response
+            .header(header::ETAG)
+            .to_str()
+            .expect("etag header should be valid")
+            .to_string();
+
+        let response = request
+            .get(&url)
+            .add_header(header::IF_NONE_MATCH, etag.as_str())
+            .await;
+        assert_eq!(response.status_code(), StatusCode::NOT_MODIFIED);
+
+        let _ = std::fs::remove_file(&thumbnail_path);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn stream_serves_growing_file_of_running_download() {
+    request_with_create_db::<App, _, _>(|request, ctx| async move {
+        let uploader = format!("test_partial_{}", Uuid::new_v4());
+        let source = _entities::sources::ActiveModel {
+            url: Set("https://example.com/source".to_string()),
+            fetch_last_days: Set(7),
+            refresh_frequency: Set(24),
+            sponsorblock: Set("all".to_string()),
+            metadata: Set(Some(serde_json::json!({
+                "uploader": uploader,
+                "items": 1,
+                "source_provider": "Youtube",
+            }))),
+            ..Default::default()
+        }
+        .insert(&ctx.db)
+        .await
+        .expect("source should be inserted");
+        let media = _entities::medias::ActiveModel {
+            url: Set("https://example.com/video".to_string()),
+            source_id: Set(source.id),
+            video_id: Set(Some("abc".to_string())),
+            ..Default::default()
+        }
+        .insert(&ctx.db)
+        .await
+        .expect("media should be inserted");
+        let url = format!("/medias/{}/stream", media.id);
+
+        let dir = localtube::ytdlp::media_directory().join(&uploader);
+        std::fs::create_dir_all(&dir).expect("source directory should be created");
+        let part = dir.join("Episode_[abc].m4a.part");
+        std::fs::write(&part, b"01234").expect("partial file should be created");
+
+        // Nothing is served unless the download is actually running.
+        assert_eq!(request.get(&url).await.status_code(), StatusCode::NOT_FOUND);
+
+        let claim = localtube::workers::fetch_media::media_downloads()
+            .try_claim(media.id)
+            .expect("media should not be claimed");
+        let response = request.get(&url).await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+        assert!(response.maybe_header(header::CONTENT_LENGTH).is_none());
+        assert_eq!(response.header(header::ACCEPT_RANGES), "bytes");
+        assert_eq!(response.header(header::CONTENT_TYPE), "audio/mp4");
+        assert_eq!(response.as_bytes().as_ref(), b"01234");
+
+        // The file grew, ranges now reach the newly flushed bytes.
+        std::fs::write(&part, b"0123456789").expect("partial file should grow");
+        let response = request
+            .get(&url)
+            .add_header(header::RANGE, "bytes=3-")
+            .await;
+        assert_eq!(response.status_code(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.header(header::CONTENT_RANGE), "bytes 3-9/*");
+        assert_eq!(response.as_bytes().as_ref(), b"3456789");
+
+        let response = request
+            .get(&url)
+            .add_header(header::RANGE, "bytes=20-")
+            .await;
+        assert_eq!(response.status_code(), StatusCode::RANGE_NOT_SATISFIABLE);
+
+        drop(claim);
+        let _ = std::fs::remove_dir_all(&dir);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn stream_head_matches_get_headers_without_body() {
+    request_with_create_db::<App, _, _>(|request, ctx| async move {
+        let content = b"0123456789";
+        let temp = TempMediaFile::new(content);
+        let media = create_media(&ctx, &temp.rel_path).await;
+        let path = format!("/medias/{}/stream", media.id);
+
+        let get = request.get(&path).await;
+        let head = request.method(axum::http::Method::HEAD, &path).await;
+
+        assert_eq!(head.status_code(), StatusCode::OK);
+        for name in [
+            header::CONTENT_LENGTH,
+            header::ACCEPT_RANGES,
+            header::CONTENT_TYPE,
+            header::ETAG,
+        ] {
+            assert_eq!(head.header(&name), get.header(&name), "{name} should match");
+        }
+        assert_eq!(
+            head.header(header::CONTENT_LENGTH)
+                .to_str()
+                .expect("content length header should be valid"),
+            content.len().to_string()
+        );
+        assert!(head.as_bytes().is_empty());
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn subtitles_serves_seeded_vtt_sidecar() {
+    request_with_create_db::<App, _, _>(|request, ctx| async move {
+        let temp = TempMediaFile::new(b"0123456789");
+        let media = create_media(&ctx, &temp.rel_path).await;
+        let vtt_path = temp.full_path.with_extension("en.vtt");
+        let content = "WEBVTT\n\n00:00.000 --> 00:01.000\nHello\n";
+        std::fs::write(&vtt_path, content).expect("subtitle file should be created");
+
+        let response = request
+            .get(&format!("/medias/{}/subtitles/en.vtt", media.id))
+            .await;
+        let missing = request
+            .get(&format!("/medias/{}/subtitles/de.vtt", media.id))
+            .await;
+        let not_vtt = request
+            .get(&format!("/medias/{}/subtitles/en.srt", media.id))
+            .await;
+        let _ = std::fs::remove_file(&vtt_path);
+
+        assert_eq!(response.status_code(), StatusCode::OK);
+        assert_eq!(
+            response
+                .header(header::CONTENT_TYPE)
+                .to_str()
+                .expect("content type header should be valid"),
+            "text/vtt; charset=utf-8"
+        );
+        assert_eq!(response.text(), content);
+        assert_eq!(missing.status_code(), StatusCode::NOT_FOUND);
+        assert_eq!(not_vtt.status_code(), StatusCode::NOT_FOUND);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn transcode_streams_ffmpeg_output() {
+    request_with_create_db::<App, _, _>(|request, ctx| async move {
+        let temp = TempMediaFile::new(b"original mkv");
+        let media = create_media(&ctx, &temp.rel_path).await;
+        let url = format!("/medias/{}/stream?transcode=1", media.id);
+
+        let response = request.get(&url).await;
+        assert_eq!(
+            response.status_code(),
+            StatusCode::FORBIDDEN,
+            "off by default"
+        );
+        let head = request.method(axum::http::Method::HEAD, &url).await;
+        assert_eq!(head.status_code(), StatusCode::FORBIDDEN);
+
+        let ffmpeg = std::env::temp_dir().join(format!("localtube-ffmpeg-{}", Uuid::new_v4()));
+        std::fs::write(&ffmpeg, "#!/bin/sh\nprintf 'fragmented mp4'\n")
+            .expect("mock ffmpeg should be written");
+        let mut permissions = std::fs::metadata(&ffmpeg).unwrap().permissions();
+        std::os::unix::fs::PermissionsExt::set_mode(&mut permissions, 0o755);
+        std::fs::set_permissions(&ffmpeg, permissions).expect("mock ffmpeg should be executable");
+        transcode::set_transcoder(Some(ffmpeg.clone()));
+        request
+            .put("/settings")
+            .json(&serde_json::json!({"transcode": true}))
+            .await
+            .assert_status_ok();
+
+        let head = request.method(axum::http::Method::HEAD, &url).await;
+        let response = request.get(&url).await;
+        request
+            .put("/settings")
+            .json(&serde_json::json!({}))
+            .await
+            .assert_status_ok();
+        transcode::set_transcoder(None);
+        let _ = std::fs::remove_file(&ffmpeg);
+
+        assert_eq!(response.status_code(), StatusCode::OK);
+        assert_eq!(
+            response
+                .header(header::CONTENT_TYPE)
+                .to_str()
+                .expect("content type header should be valid"),
+            "video/mp4"
+        );
+        assert_eq!(
+            response
+                .header(header::ACCEPT_RANGES)
+                .to_str()
+                .expect("accept ranges header should be valid"),
+            "none"
+        );
+        assert!(response.maybe_header(header::CONTENT_LENGTH).is_none());
+        assert_eq!(response.as_bytes().as_ref(), b"fragmented mp4");
+
+        assert_eq!(head.status_code(), StatusCode::OK);
+        for name in [header::CONTENT_TYPE, header::ACCEPT_RANGES] {
+            assert_eq!(head.header(&name), response.header(&name), "{name}");
+        }
+        assert!(head.maybe_header(header::CONTENT_LENGTH).is_none());
+        assert!(head.as_bytes().is_empty());
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn share_link_streams_only_its_media_until_expiry() {
+    request_with_create_db::<App, _, _>(|request, ctx| async move {
+        share::set_share_secret(Some("test-share-secret".to_string()));
+        let temp = TempMediaFile::new(b"shared");
+        let media = create_media(&ctx, &temp.rel_path).await;
+        let other = create_media(&ctx, &temp.rel_path).await;
+
+        let response = request
+            .get(&format!("/medias/{}/share?hours=2", media.id))
+            .await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+        let link: serde_json::Value = response.json();
+        let url = link["url"].as_str().expect("share url");
+        let (_, token) = url.split_once("/share/").expect("share url");
+        let expires_at: chrono::DateTime<chrono::Utc> =
+            serde_json::from_value(link["expires_at"].clone()).expect("expiry");
+        let minutes = (expires_at - chrono::Utc::now()).num_minutes();
+        assert!((110..=120).contains(&minutes), "valid for two hours");
+
+        let response = request.get(&format!("/share/{token}")).await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+        assert_eq!(response.as_bytes().as_ref(), b"shared");
+        let response = request
+            .get(&format!("/share/{token}"))
+            .add_header(header::RANGE, "bytes=1-2")
+            .await;
+        assert_eq!(response.status_code(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.as_bytes().as_ref(), b"ha");
+
+        // The shared path opens nothing without a valid token.
+        let response = request.get(&format!("/share/{}", media.id)).await;
+        assert_eq!(response.status_code(), StatusCode::FORBIDDEN);
+        let other_media = token.replacen(&format!("{}.", media.id), &format!("{}.", other.id), 1);
+        let response = request.get(&format!("/share/{other_media}")).await;
+        assert_eq!(response.status_code(), StatusCode::FORBIDDEN);
+
+        let expired = share::sign(
+            "test-share-secret",
+            media.id,
+            chrono::Utc::now().timestamp() - 1,
+        );
+        let response = request.get(&format!("/share/{expired}")).await;
+        assert_eq!(response.status_code(), StatusCode::FORBIDDEN);
+
+        let tampered = format!("{token}0");
+        let response = request.get(&format!("/share/{tampered}")).await;
+        share::set_share_secret(None);
+        assert_eq!(response.status_code(), StatusCode::FORBIDDEN);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn sharing_is_disabled_without_a_share_secret() {
+    request_with_create_db::<App, _, _>(|request, ctx| async move {
+        let temp = TempMediaFile::new(b"shared");
+        let media = create_media(&ctx, &temp.rel_path).await;
+        assert_eq!(share::share_secret(), None);
+
+        let response = request.get(&format!("/medias/{}/share", media.id)).await;
+        assert_eq!(response.status_code(), StatusCode::FORBIDDEN);
+
+        // The JWT secret is committed to the config, tokens signed with it open nothing.
+        let jwt_secret = ctx
+            .config
+            .auth
+            .as_ref()
+            .and_then(|auth| auth.jwt.as_ref())
+            .map(|jwt| jwt.secret.clone())
+            .expect("test config has a JWT secret");
+        let forged = share::sign(&jwt_secret, media.id, chrono::Utc::now().timestamp() + 3600);
+        let response = request.get(&format!("/share/{forged}")).await;
+        assert_eq!(response.status_code(), StatusCode::FORBIDDEN);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn info_json_serves_seeded_sidecar() {
+    request_with_create_db::<App, _, _>(|request, ctx| async move {
+        let temp = TempMediaFile::new(b"0123456789");
+        let media = create_media(&ctx, &temp.rel_path).await;
+
+        let missing = request
+            .get(&format!("/medias/{}/info.json", media.id))
+            .await;
+        assert_eq!(missing.status_code(), StatusCode::NOT_FOUND);
+
+        let info_path = temp.full_path.with_extension("info.json");
+        let content = r#"{"id":"abc","title":"Video","formats":[]}"#;
+        std::fs::write(&info_path, content).expect("info json should be created");
+        let response = request
+            .get(&format!("/medias/{}/info.json", media.id))
+            .await;
+        let _ = std::fs::remove_file(&info_path);
+
+        assert_eq!(response.status_code(), StatusCode::OK);
+        assert_eq!(
+            response
+                .header(header::CONTENT_TYPE)
+                .to_str()
+                .expect("content type header should be valid"),
+            "application/json"
+        );
+        assert_eq!(response.text(), content);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn deleted_media_goes_to_the_trash_and_can_be_restored() {
+    request_with_create_db::<App, _, _>(|request, ctx| async move {
+        request
+            .put("/settings")
+            .json(&serde_json::json!({"trash_days": 7}))
+            .await
+            .assert_status_ok();
+        let temp = TempMediaFile::new(b"0123456789");
+        let media = create_media(&ctx, &temp.rel_path).await;
+        let trashed_path =
+            trash::trash_root(localtube::ytdlp::media_directory()).join(&temp.rel_path);
+
+        let response = request.delete(&format!("/medias/{}", media.id)).await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+        assert!(!temp.full_path.exists());
+        assert!(trashed_path.is_file(), "the file is kept in the trash");
+        let trashed = _entities::medias::Entity::find_by_id(media.id)
+            .one(&ctx.db)
+            .await
+            .expect("media should load")
+            .expect("the row is kept");
+        assert!(trashed.trashed_at.is_some());
+        let listed = request.get("/medias/list.json").await;
+        assert!(
+            !listed.text().contains(&temp.rel_path),
+            "trashed medias are hidden"
+        );
+        let response = request
+            .post(&format!("/medias/{}/redownload", media.id))
+            .await;
+        assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+        assert!(
+            trashed_path.is_file(),
+            "a trashed media is restored before downloading it again"
+        );
+
+        let response = request.post(&format!("/medias/{}/restore", media.id)).await;
+        assert_eq!(response.status_code(), StatusCode::SEE_OTHER);
+        assert!(temp.full_path.is_file(), "the file is back in place");
+        assert!(!trashed_path.exists());
+        let restored = _entities::medias::Entity::find_by_id(media.id)
+            .one(&ctx.db)
+            .await
+            .expect("media should load")
+            .expect("the row is kept");
+        assert_eq!(restored.trashed_at, None);
+        let again = request.post(&format!("/medias/{}/restore", media.id)).await;
+        assert_eq!(again.status_code(), StatusCode::BAD_REQUEST);
+
+        request
+            .put("/settings")
+            .json(&serde_json::json!({"trash_days": 0}))
+            .await
+            .assert_status_ok();
+        let response = request.delete(&format!("/medias/{}", media.id)).await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+        assert!(
+            !temp.full_path.exists(),
+            "without a trash the file is deleted"
+        );
+        assert!(!trashed_path.exists());
+        assert!(_entities::medias::Entity::find_by_id(media.id)
+            .one(&ctx.db)
+            .await
+            .expect("media should load")
+            .is_none());
+        request
+            .put("/settings")
+            .json(&serde_json::json!({}))
+            .await
+            .assert_status_ok();
+        let _ = std::fs::remove_dir_all(trash::trash_root(localtube::ytdlp::media_directory()));
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn deleting_a_source_removes_its_trashed_files() {
+    request_with_create_db::<App, _, _>(|request, ctx| async move {
+        request
+            .put("/settings")
+            .json(&serde_json::json!({"trash_days": 7}))
+            .await
+            .assert_status_ok();
+        let temp = TempMediaFile::new(b"0123456789");
+        let media = create_media(&ctx, &temp.rel_path).await;
+        let trashed_path =
+            trash::trash_root(localtube::ytdlp::media_directory()).join(&temp.rel_path);
+        let response = request.delete(&format!("/medias/{}", media.id)).await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+        assert!(trashed_path.is_file());
+
+        let response = request
+            .delete(&format!("/sources/{}", media.source_id))
+            .await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+        assert!(
+            !trashed_path.exists(),
+            "the trash is never emptied for rows that are gone"
+        );
+
+        request
+            .put("/settings")
+            .json(&serde_json::json!({}))
+            .await
+            .assert_status_ok();
+        let _ = std::fs::remove_dir_all(trash::trash_root(localtube::ytdlp::media_directory()));
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn trash_is_emptied_after_the_configured_days() {
+    request_with_create_db::<App, _, _>(|request, ctx| async move {
+        request
+            .put("/settings")
+            .json(&serde_json::json!({"trash_days": 7}))
+            .await
+            .assert_status_ok();
+        let media_dir = localtube::ytdlp::media_directory();
+        let old_file = TempMediaFile::new(b"old");
+        let new_file = TempMediaFile::new(b"new");
+        let old = create_media(&ctx, &old_file.rel_path).await;
+        let new = create_media(&ctx, &new_file.rel_path).await;
+        let now = chrono::Utc::now();
+        trash::discard_media(
+            &ctx.db,
+            old.clone(),
+            media_dir,
+            now - chrono::Duration::days(8),
+        )
+        .await
+        .expect("media should be trashed");
+        trash::discard_media(
+            &ctx.db,
+            new.clone(),
+            media_dir,
+            now - chrono::Duration::days(1),
+        )
+        .await
+        .expect("media should be trashed");
+
+        let deleted = trash::empty_trash(&ctx.db, now)
+            .await
+            .expect("trash should be emptied");
+        assert_eq!(deleted, 1);
+        let trash_root = trash::trash_root(media_dir);
+        assert!(!trash_root.join(&old_file.rel_path).exists());
+        assert!(_entities::medias::Entity::find_by_id(old.id)
+            .one(&ctx.db)
+            .await
+            .expect("media should load")
+            .is_none());
+        assert!(
+            trash_root.join(&new_file.rel_path).is_file(),
+            "recently trashed medias stay"
+        );
+        assert!(_entities::medias::Entity::find_by_id(new.id)
+            .one(&ctx.db)
+            .await
+            .expect("media should load")
+            .is_some());
+
+        request
+            .put("/settings")
+            .json(&serde_json::json!({}))
+            .await
+            .assert_status_ok();
+        let _ = std::fs::remove_dir_all(trash_root);
+    })
+    .await;
+}
@@ -0,0 +1,70 @@
+use axum::http::StatusCode;
+use localtube::{app::App, models::_entities};
+use loco_rs::prelude::*;
+use sea_orm::{ActiveModelTrait, Set};
+use serial_test::serial;
+use uuid::Uuid;
+
+mod helpers;
+use helpers::{create_source, TempMediaDir};
+
+async fn create_media(ctx: &AppContext, media_path: Option<String>) -> _entities::medias::Model {
+    let source = create_source(&ctx.db).await;
+    _entities::medias::ActiveModel {
+        url: Set(format!("https://example.com/watch?v={}", Uuid::new_v4())),
+        source_id: Set(source.id),
+        media_path: Set(media_path),
+        ..Default::default()
+    }
+    .insert(&ctx.db)
+    .await
+    .expect("media should be inserted")
+}
+
+#[tokio::test]
+#[serial]
+async fn verify_reports_truncated_file_as_unplayable() {
+    request_with_create_db::<App, _, _>(|request, ctx| async move {
+        let dir = TempMediaDir::new();
+        // An MP4 cut off inside its first box header.
+        let rel_path = dir.create_file("Broken_[broken].mp4", b"\x00\x00\x00\x20ftypisom\x00\x00");
+        let media = create_media(&ctx, Some(rel_path.clone())).await;
+
+        let response = request.post(&format!("/medias/{}/verify", media.id)).await;
+        response.assert_status_ok();
+        let report: serde_json::Value = response.json();
+        assert_eq!(report["playable"], false);
+        assert!(report["error"].is_string());
+        assert_eq!(report["requeued"], false);
+
+        // Without `requeue` the file and its path are left alone.
+        let media = _entities::medias::Entity::find_by_id(media.id)
+            .one(&ctx.db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(media.media_path, Some(rel_path.clone()));
+        assert!(localtube::ytdlp::media_directory().join(&rel_path).exists());
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn verify_returns_not_found_without_downloaded_file() {
+    request_with_create_db::<App, _, _>(|request, ctx| async move {
+        let pending = create_media(&ctx, None).await;
+        let response = request
+            .post(&format!("/medias/{}/verify", pending.id))
+            .await;
+        response.assert_status(StatusCode::NOT_FOUND);
+
+        let missing =
+            create_media(&ctx, Some(format!("missing_{}/Gone.mkv", Uuid::new_v4()))).await;
+        let response = request
+            .post(&format!("/medias/{}/verify", missing.id))
+            .await;
+        response.assert_status(StatusCode::NOT_FOUND);
+    })
+    .await;
+}
@@ -0,0 +1,51 @@
+use std::sync::Arc;
+
+use localtube::job_tracking::{history::MetricsHistory, manager::TaskManager, task::TaskType};
+use tokio::sync::Semaphore;
+
+#[tokio::test]
+async fn history_keeps_the_latest_samples_with_growing_counters() {
+    let manager = TaskManager::new();
+    let sem = Arc::new(Semaphore::new(1));
+    let history = MetricsHistory::new(3);
+    let start = chrono::Utc::now();
+
+    for idx in 0..5 {
+        let active = manager
+            .add_task(TaskType::DownloadVideo, format!("task-{idx}"))
+            .start(sem.clone())
+            .await;
+        if idx % 2 == 0 {
+            active.mark_failed("simulated failure".to_string());
+        } else {
+            active.complete();
+        }
+        history.record(
+            &manager.get_metrics(),
+            start + chrono::Duration::minutes(idx),
+        );
+    }
+
+    let samples = history.samples();
+    assert_eq!(
+        samples.len(),
+        3,
+        "history should be bounded by its capacity"
+    );
+    assert_eq!(samples[0].at, start + chrono::Duration::minutes(2));
+
+    let downloads = samples
+        .iter()
+        .map(|sample| sample.tasks[&TaskType::DownloadVideo])
+        .collect::<Vec<_>>();
+    for pair in downloads.windows(2) {
+        assert!(pair[0].success_count <= pair[1].success_count);
+        assert!(pair[0].failure_count <= pair[1].failure_count);
+        assert_eq!(
+            pair[0].success_count + pair[0].failure_count + 1,
+            pair[1].success_count + pair[1].failure_count
+        );
+    }
+    let last = downloads.last().unwrap();
+    assert_eq!((last.success_count, last.failure_count), (2, 3));
+}
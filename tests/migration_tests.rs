@@ -0,0 +1,48 @@
+use migration::{Migrator, MigratorTrait, SchemaManager};
+use sea_orm::{ConnectOptions, Database, DatabaseConnection};
+
+async fn empty_database() -> DatabaseConnection {
+    // Every connection to an in-memory SQLite database opens a new one.
+    let mut options = ConnectOptions::new("sqlite::memory:");
+    options.max_connections(1).sqlx_logging(false);
+    Database::connect(options)
+        .await
+        .expect("in-memory database should open")
+}
+
+#[tokio::test]
+async fn migrations_apply_from_empty_and_reverse() {
+    let db = empty_database().await;
+    let manager = SchemaManager::new(&db);
+
+    Migrator::up(&db, None)
+        .await
+        .expect("all migrations should apply to an empty database");
+    assert_eq!(
+        Migrator::get_applied_migrations(&db).await.unwrap().len(),
+        Migrator::migrations().len()
+    );
+    for table in ["users", "sources", "medias", "settings"] {
+        assert!(manager.has_table(table).await.unwrap(), "missing {table}");
+    }
+    assert!(manager.has_column("medias", "available_at").await.unwrap());
+    assert!(Migrator::get_pending_migrations(&db)
+        .await
+        .unwrap()
+        .is_empty());
+
+    Migrator::down(&db, None)
+        .await
+        .expect("all migrations should be reversible");
+    assert!(Migrator::get_applied_migrations(&db)
+        .await
+        .unwrap()
+        .is_empty());
+    for table in ["users", "sources", "medias", "settings"] {
+        assert!(!manager.has_table(table).await.unwrap(), "left {table}");
+    }
+
+    Migrator::up(&db, None)
+        .await
+        .expect("migrations should apply again after a full rollback");
+}
@@ -1,4 +1,6 @@
+mod helpers;
 mod models;
 mod requests;
+mod services;
 mod tasks;
 mod workers;
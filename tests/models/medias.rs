@@ -1,7 +1,13 @@
-use localtube::app::App;
-use loco_rs::testing::prelude::*;
+use localtube::{
+    app::App,
+    models::{_entities::medias, medias::Medias, settings::GlobalSettings},
+    services::trash,
+};
+use loco_rs::prelude::*;
 use serial_test::serial;
 
+use crate::helpers::create_source;
+
 macro_rules! configure_insta {
     ($($expr:expr),*) => {
         let mut settings = insta::Settings::clone_current();
@@ -29,3 +35,278 @@ async fn test_model() {
     // snapshot the result:
     // assert_debug_snapshot!(item);
 }
+
+#[tokio::test]
+#[serial]
+async fn find_by_video_id_returns_single_exact_match() {
+    let boot = boot_test::<App>().await.unwrap();
+    let db = &boot.app_context.db;
+    let source = create_source(db).await;
+
+    // Ids that are prefixes of one another used to collide under a `LIKE %url%` lookup.
+    let mut video_id = String::from("abc");
+    for n in 0..50 {
+        medias::ActiveModel {
+            source_id: Set(source.id),
+            url: Set(format!("https://example.com/watch?v={video_id}")),
+            video_id: Set(Some(video_id.clone())),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+        .expect("media should be inserted");
+        video_id.push(char::from(b'0' + (n % 10)));
+    }
+
+    let url = "https://example.com/watch?v=abc0";
+    let contains_matches = Medias::find()
+        .filter(medias::Column::SourceId.eq(source.id))
+        .filter(medias::Column::Url.contains(url))
+        .all(db)
+        .await
+        .unwrap();
+    assert!(contains_matches.len() > 1);
+
+    let media = medias::Model::find_by_video_id(db, source.id, "abc0", url)
+        .await
+        .unwrap()
+        .expect("media should be found");
+    assert_eq!(media.video_id.as_deref(), Some("abc0"));
+    assert_eq!(media.url, url);
+}
+
+#[tokio::test]
+#[serial]
+async fn find_by_video_id_falls_back_to_exact_url_for_legacy_rows() {
+    let boot = boot_test::<App>().await.unwrap();
+    let db = &boot.app_context.db;
+    let source = create_source(db).await;
+
+    for url in [
+        "https://example.com/watch?v=abc",
+        "https://example.com/watch?v=abc1",
+    ] {
+        medias::ActiveModel {
+            source_id: Set(source.id),
+            url: Set(url.to_string()),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+        .expect("media should be inserted");
+    }
+
+    let media =
+        medias::Model::find_by_video_id(db, source.id, "abc", "https://example.com/watch?v=abc")
+            .await
+            .unwrap()
+            .expect("legacy media should be found");
+    assert_eq!(media.url, "https://example.com/watch?v=abc");
+
+    let missing = medias::Model::find_by_video_id(
+        db,
+        source.id,
+        "abc12",
+        "https://example.com/watch?v=abc12",
+    )
+    .await
+    .unwrap();
+    assert!(missing.is_none());
+}
+
+#[tokio::test]
+#[serial]
+async fn video_id_is_unique_per_source() {
+    let boot = boot_test::<App>().await.unwrap();
+    let db = &boot.app_context.db;
+    let source = create_source(db).await;
+
+    let media = || medias::ActiveModel {
+        source_id: Set(source.id),
+        url: Set("https://example.com/watch?v=abc".to_string()),
+        video_id: Set(Some("abc".to_string())),
+        ..Default::default()
+    };
+    media().insert(db).await.expect("media should be inserted");
+    assert!(media().insert(db).await.is_err());
+}
+
+#[tokio::test]
+#[serial]
+async fn remove_media_files_removes_sidecars() {
+    let boot = boot_test::<App>().await.unwrap();
+    let db = &boot.app_context.db;
+    let source = create_source(db).await;
+
+    let dir_name = format!("test_sidecars_{}", uuid::Uuid::new_v4());
+    let dir = localtube::ytdlp::media_directory().join(&dir_name);
+    std::fs::create_dir_all(&dir).expect("media test directory should be created");
+    let files = [
+        "Title_[abc].mkv",
+        "Title_[abc].info.json",
+        "Title_[abc].description",
+        "Title_[abc].jpg",
+    ];
+    for file in files {
+        std::fs::write(dir.join(file), b"data").expect("media test file should be created");
+    }
+
+    let media = medias::ActiveModel {
+        source_id: Set(source.id),
+        url: Set("https://example.com/watch?v=abc".to_string()),
+        media_path: Set(Some(format!("{dir_name}/Title_[abc].mkv"))),
+        ..Default::default()
+    }
+    .insert(db)
+    .await
+    .expect("media should be inserted");
+
+    media
+        .remove_media_files(localtube::ytdlp::media_directory())
+        .expect("files should be removed");
+    for file in files {
+        assert!(!dir.join(file).exists(), "{file} should be removed");
+    }
+    std::fs::remove_dir(&dir).expect("media test directory should be empty");
+}
+
+#[tokio::test]
+#[serial]
+async fn trashed_media_file_is_not_missing() {
+    let boot = boot_test::<App>().await.unwrap();
+    let db = &boot.app_context.db;
+    let source = create_source(db).await;
+    let media_dir = localtube::ytdlp::media_directory();
+
+    let dir_name = format!("test_trashed_{}", uuid::Uuid::new_v4());
+    std::fs::create_dir_all(media_dir.join(&dir_name))
+        .expect("media test directory should be created");
+    let media_path = format!("{dir_name}/Title_[abc].mkv");
+    std::fs::write(media_dir.join(&media_path), b"data")
+        .expect("media test file should be created");
+    let media = medias::ActiveModel {
+        source_id: Set(source.id),
+        url: Set("https://example.com/watch?v=abc".to_string()),
+        media_path: Set(Some(media_path.clone())),
+        ..Default::default()
+    }
+    .insert(db)
+    .await
+    .expect("media should be inserted");
+    assert!(!media.file_is_missing(media_dir));
+
+    GlobalSettings {
+        trash_days: Some(7),
+        ..Default::default()
+    }
+    .apply();
+    trash::discard_media(db, media.clone(), media_dir, chrono::Utc::now())
+        .await
+        .expect("media should be trashed");
+    GlobalSettings::default().apply();
+    let trashed = Medias::find_by_id(media.id)
+        .one(db)
+        .await
+        .expect("media should load")
+        .expect("the row is kept");
+    assert!(!media_dir.join(&media_path).exists());
+    assert!(
+        media.file_is_missing(media_dir),
+        "an untrashed row would be downloaded again"
+    );
+    assert!(
+        !trashed.file_is_missing(media_dir),
+        "the refresh leaves trashed medias alone"
+    );
+
+    let _ = std::fs::remove_dir_all(media_dir.join(&dir_name));
+    let _ = std::fs::remove_dir_all(trash::trash_root(media_dir));
+}
+
+#[tokio::test]
+#[serial]
+async fn removing_the_canonical_media_releases_its_duplicates() {
+    let boot = boot_test::<App>().await.unwrap();
+    let db = &boot.app_context.db;
+    let canonical_source = create_source(db).await;
+    let duplicate_source = create_source(db).await;
+    let media_dir = localtube::ytdlp::media_directory();
+
+    let canonical = medias::ActiveModel {
+        source_id: Set(canonical_source.id),
+        url: Set("https://example.com/watch?v=shared".to_string()),
+        media_path: Set(Some(format!(
+            "test_canonical_{}/Title_[shared].mkv",
+            uuid::Uuid::new_v4()
+        ))),
+        ..Default::default()
+    }
+    .insert(db)
+    .await
+    .expect("canonical media should be inserted");
+    let skipped = medias::ActiveModel {
+        source_id: Set(duplicate_source.id),
+        url: Set("https://example.com/watch?v=shared".to_string()),
+        duplicate_of: Set(Some(canonical.id)),
+        ..Default::default()
+    }
+    .insert(db)
+    .await
+    .expect("duplicate media should be inserted");
+
+    trash::discard_media(db, canonical, media_dir, chrono::Utc::now())
+        .await
+        .expect("canonical media should be removed");
+
+    let released = Medias::find_by_id(skipped.id)
+        .one(db)
+        .await
+        .expect("media should load")
+        .expect("the duplicate is kept");
+    assert_eq!(released.duplicate_of, None, "the next refresh downloads it");
+}
+
+#[tokio::test]
+#[serial]
+async fn unlisted_pending_download_in_the_window_is_found() {
+    let boot = boot_test::<App>().await.unwrap();
+    let db = &boot.app_context.db;
+    let source = create_source(db).await;
+    let now = chrono::Utc::now().timestamp();
+    let insert = |video_id: &str, timestamp: i64| {
+        let metadata = localtube::models::medias::MediaMetadata {
+            title: video_id.to_string(),
+            description: None,
+            duration: 60,
+            extractor_key: "Youtube".to_string(),
+            original_url: format!("https://example.com/watch?v={video_id}"),
+            timestamp,
+            age_limit: None,
+        };
+        medias::ActiveModel {
+            source_id: Set(source.id),
+            url: Set(metadata.original_url.clone()),
+            video_id: Set(Some(video_id.to_string())),
+            metadata: Set(Some(serde_json::to_value(metadata).unwrap())),
+            ..Default::default()
+        }
+        .insert(db)
+    };
+    let pending = insert("pending", now - 60).await.unwrap();
+    let listed = insert("listed", now - 60).await.unwrap();
+    insert("expired", now - 10 * 86_400).await.unwrap();
+
+    let found = medias::Model::find_unlisted_downloads(
+        db,
+        &source,
+        &std::collections::HashSet::from([listed.id]),
+        now - 3600,
+    )
+    .await
+    .expect("query should succeed");
+
+    assert_eq!(
+        found.iter().map(|media| media.id).collect::<Vec<_>>(),
+        vec![pending.id]
+    );
+}
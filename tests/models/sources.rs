@@ -1,5 +1,17 @@
-use localtube::app::App;
-use loco_rs::testing::prelude::*;
+use localtube::{
+    app::App,
+    models::{
+        _entities::{medias, sources},
+        medias::MediaMetadata,
+        sources::{
+            normalize_stored_metadata, refresh_backoff, refresh_interval_elapsed, refresh_jitter,
+            refresh_jitter_max, SourceMetadata, SponsorBlockCategories, SponsorBlockSet,
+            REFRESH_BACKOFF_BASE, REFRESH_BACKOFF_MAX,
+        },
+    },
+};
+use loco_rs::prelude::*;
+use sea_orm::PaginatorTrait;
 use serial_test::serial;
 
 macro_rules! configure_insta {
@@ -29,3 +41,606 @@ async fn test_model() {
     // snapshot the result:
     // assert_debug_snapshot!(item);
 }
+
+async fn create_source(db: &DatabaseConnection) -> sources::Model {
+    sources::ActiveModel {
+        url: Set("https://example.com/channel".to_string()),
+        fetch_last_days: Set(7),
+        refresh_frequency: Set(24),
+        sponsorblock: Set(String::new()),
+        last_refreshed_at: Set(Some(chrono::Utc::now())),
+        ..Default::default()
+    }
+    .insert(db)
+    .await
+    .expect("source should be inserted")
+}
+
+async fn create_media(db: &DatabaseConnection, source_id: i32, video_id: &str, days_ago: i64) {
+    let metadata = MediaMetadata {
+        title: video_id.to_string(),
+        description: None,
+        duration: 1,
+        extractor_key: "Youtube".to_string(),
+        original_url: format!("https://example.com/watch?v={video_id}"),
+        timestamp: (chrono::Utc::now() - chrono::Duration::days(days_ago)).timestamp(),
+        age_limit: None,
+    };
+    medias::ActiveModel {
+        source_id: Set(source_id),
+        url: Set(metadata.original_url.clone()),
+        video_id: Set(Some(video_id.to_string())),
+        metadata: Set(Some(serde_json::to_value(metadata).unwrap())),
+        ..Default::default()
+    }
+    .insert(db)
+    .await
+    .expect("media should be inserted");
+}
+
+#[tokio::test]
+#[serial]
+async fn source_with_only_old_media_is_stale() {
+    let boot = boot_test::<App>().await.unwrap();
+    let db = &boot.app_context.db;
+
+    let dormant = create_source(db).await;
+    create_media(db, dormant.id, "old1", 90).await;
+    create_media(db, dormant.id, "old2", 45).await;
+    let dormant = dormant.refresh_last_video_at(db, None).await.unwrap();
+    let newest = dormant
+        .last_video_at
+        .expect("newest video should be stored");
+    assert_eq!(
+        (chrono::Utc::now() - newest).num_days(),
+        45,
+        "the newest media should win"
+    );
+    assert!(dormant.is_stale_at(30, chrono::Utc::now()));
+    assert!(!dormant.is_stale_at(0, chrono::Utc::now()));
+
+    let active = create_source(db).await;
+    create_media(db, active.id, "old", 90).await;
+    create_media(db, active.id, "new", 2).await;
+    let active = active.refresh_last_video_at(db, None).await.unwrap();
+    assert!(!active.is_stale_at(30, chrono::Utc::now()));
+}
+
+#[tokio::test]
+#[serial]
+async fn newest_video_survives_media_removal() {
+    let boot = boot_test::<App>().await.unwrap();
+    let db = &boot.app_context.db;
+
+    let source = create_source(db).await;
+    let recent = (chrono::Utc::now() - chrono::Duration::days(3)).timestamp();
+    let source = source
+        .refresh_last_video_at(db, Some(recent))
+        .await
+        .unwrap();
+    create_media(db, source.id, "older", 60).await;
+
+    let source = source.refresh_last_video_at(db, None).await.unwrap();
+    assert_eq!(source.last_video_at.map(|at| at.timestamp()), Some(recent));
+    assert!(!source.is_stale_at(30, chrono::Utc::now()));
+}
+
+#[tokio::test]
+#[serial]
+async fn incremental_refresh_starts_from_checkpoint_unless_full() {
+    let boot = boot_test::<App>().await.unwrap();
+    let db = &boot.app_context.db;
+
+    let source = create_source(db).await;
+    assert_eq!(source.incremental_date_after(false), None);
+
+    let checkpoint = chrono::DateTime::parse_from_rfc3339("2026-03-07T18:30:00Z")
+        .unwrap()
+        .with_timezone(&chrono::Utc);
+    let mut item = source.into_active_model();
+    item.index_checkpoint = Set(Some(checkpoint));
+    let source = item.update(db).await.unwrap();
+
+    assert_eq!(
+        source.incremental_date_after(false),
+        chrono::NaiveDate::from_ymd_opt(2026, 3, 7)
+    );
+    assert_eq!(source.incremental_date_after(true), None);
+}
+
+#[tokio::test]
+#[serial]
+async fn only_settings_widening_the_list_need_a_full_reindex() {
+    let boot = boot_test::<App>().await.unwrap();
+    let db = &boot.app_context.db;
+
+    let source = create_source(db).await;
+    let mut rescheduled = source.clone();
+    rescheduled.refresh_frequency = source.refresh_frequency + 1;
+    assert!(!rescheduled.needs_full_reindex(&source));
+
+    let mut widened = source.clone();
+    widened.fetch_last_days = source.fetch_last_days + 30;
+    assert!(widened.needs_full_reindex(&source));
+}
+
+#[tokio::test]
+#[serial]
+async fn reset_index_makes_the_next_refresh_full() {
+    let boot = boot_test::<App>().await.unwrap();
+    let db = &boot.app_context.db;
+
+    let source = create_source(db).await;
+    let mut item = source.into_active_model();
+    item.index_checkpoint = Set(Some(chrono::Utc::now()));
+    let source = item.update(db).await.unwrap();
+    create_media(db, source.id, "kept", 3).await;
+    assert!(source.incremental_date_after(false).is_some());
+
+    let source = source.reset_index(db).await.unwrap();
+    assert_eq!(source.index_checkpoint, None);
+    assert_eq!(source.last_refreshed_at, None);
+    assert_eq!(source.incremental_date_after(false), None);
+
+    let media = medias::Entity::find()
+        .filter(medias::Column::SourceId.eq(source.id))
+        .all(db)
+        .await
+        .unwrap();
+    assert_eq!(media.len(), 1, "media rows survive a reindex");
+}
+
+#[tokio::test]
+#[serial]
+async fn completed_download_updates_last_download_at() {
+    let boot = boot_test::<App>().await.unwrap();
+    let db = &boot.app_context.db;
+    let source = create_source(db).await;
+    assert_eq!(source.last_download_at, None);
+
+    let at = chrono::Utc::now();
+    source.record_download(db, at).await.unwrap();
+
+    let stored = sources::Entity::find_by_id(source.id)
+        .one(db)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        stored.last_download_at.map(|t| t.timestamp()),
+        Some(at.timestamp())
+    );
+    assert_eq!(stored.last_refreshed_at, source.last_refreshed_at);
+}
+
+#[tokio::test]
+#[serial]
+async fn start_from_now_indexes_backlog_without_downloading() {
+    let boot = boot_test::<App>().await.unwrap();
+    let db = &boot.app_context.db;
+    let source = create_source(db).await;
+    let backlog = (chrono::Utc::now() - chrono::Duration::days(3)).timestamp();
+    let published_later = (chrono::Utc::now() + chrono::Duration::minutes(5)).timestamp();
+    assert!(!source.is_backlog(backlog), "off by default");
+
+    let mut item = source.into_active_model();
+    item.start_from_now = Set(true);
+    let source = item.update(db).await.unwrap();
+    assert!(source.is_backlog(backlog));
+    assert!(!source.is_backlog(published_later));
+
+    let newest = (chrono::Utc::now() - chrono::Duration::days(1)).timestamp();
+    let source = source
+        .record_download_baseline(db, Some(newest))
+        .await
+        .unwrap();
+    assert_eq!(
+        source
+            .download_baseline
+            .map(|baseline| baseline.timestamp()),
+        Some(newest)
+    );
+    assert!(source.is_backlog(backlog));
+    assert!(source.is_backlog(newest));
+    assert!(!source.is_backlog(published_later));
+
+    // Later refreshes keep the first baseline.
+    let source = source
+        .record_download_baseline(db, Some(published_later))
+        .await
+        .unwrap();
+    assert_eq!(
+        source
+            .download_baseline
+            .map(|baseline| baseline.timestamp()),
+        Some(newest)
+    );
+}
+
+#[tokio::test]
+#[serial]
+async fn repeated_refresh_failures_increase_the_backoff() {
+    let boot = boot_test::<App>().await.unwrap();
+    let db = &boot.app_context.db;
+    let now = chrono::Utc::now();
+    let source = create_source(db).await;
+    assert!(!source.is_refresh_backed_off_at(now));
+
+    let mut backoffs = Vec::new();
+    let mut source = source;
+    for _ in 0..3 {
+        source = source
+            .record_refresh_failure(db, now, "ERROR: boom")
+            .await
+            .unwrap();
+        backoffs.push(source.refresh_backoff_until.unwrap() - now);
+    }
+    assert_eq!(source.refresh_failures, 3);
+    assert_eq!(
+        backoffs,
+        vec![
+            REFRESH_BACKOFF_BASE,
+            REFRESH_BACKOFF_BASE * 2,
+            REFRESH_BACKOFF_BASE * 4
+        ]
+    );
+    assert!(source.is_refresh_backed_off_at(now));
+    assert!(!source.is_refresh_backed_off_at(now + REFRESH_BACKOFF_BASE * 4));
+    assert_eq!(refresh_backoff(100), REFRESH_BACKOFF_MAX);
+}
+
+#[tokio::test]
+#[serial]
+async fn moved_channel_error_suggests_the_canonical_url() {
+    let boot = boot_test::<App>().await.unwrap();
+    let db = &boot.app_context.db;
+    let now = chrono::Utc::now();
+    let canonical = "https://www.youtube.com/channel/UC123";
+    let source = sources::ActiveModel {
+        url: Set("https://www.youtube.com/@old".to_string()),
+        fetch_last_days: Set(7),
+        refresh_frequency: Set(24),
+        sponsorblock: Set(String::new()),
+        metadata: Set(Some(serde_json::json!({
+            "uploader": "Channel",
+            "items": 12,
+            "source_provider": "YoutubeTab",
+            "list_tab": "https://www.youtube.com/@old/videos",
+            "channel_url": canonical,
+        }))),
+        ..Default::default()
+    }
+    .insert(db)
+    .await
+    .unwrap();
+    create_media(db, source.id, "a", 1).await;
+
+    let source = source
+        .record_refresh_failure(
+            db,
+            now,
+            "ERROR: [youtube:tab] @old: HTTP Error 429: Too Many Requests",
+        )
+        .await
+        .unwrap();
+    assert_eq!(source.suggested_url, None, "only a gone channel moved");
+    assert!(source.adopt_suggested_url(db).await.unwrap().is_none());
+
+    let error = "ERROR: [youtube:tab] @old: This channel does not exist.";
+    let source = source.record_refresh_failure(db, now, error).await.unwrap();
+    assert_eq!(source.last_refresh_error.as_deref(), Some(error));
+    assert_eq!(source.suggested_url.as_deref(), Some(canonical));
+
+    let moved = source.adopt_suggested_url(db).await.unwrap().unwrap();
+    assert_eq!(moved.id, source.id);
+    assert_eq!(moved.url, canonical);
+    assert_eq!(moved.suggested_url, None);
+    assert_eq!(moved.last_refresh_error, None);
+    assert_eq!(moved.refresh_failures, 0);
+    let metadata = moved.get_metadata().unwrap();
+    assert_eq!(metadata.list_tab, None);
+    assert_eq!(metadata.channel_url.as_deref(), Some(canonical));
+    let medias = medias::Entity::find()
+        .filter(medias::Column::SourceId.eq(moved.id))
+        .count(db)
+        .await
+        .unwrap();
+    assert_eq!(medias, 1, "the media history stays with the source");
+
+    // Already on the canonical URL there is nothing left to suggest.
+    let moved = moved.record_refresh_failure(db, now, error).await.unwrap();
+    assert_eq!(moved.suggested_url, None);
+}
+
+#[tokio::test]
+#[serial]
+async fn moved_channel_suggestion_keeps_the_tab() {
+    let boot = boot_test::<App>().await.unwrap();
+    let db = &boot.app_context.db;
+    let source = sources::ActiveModel {
+        url: Set("https://www.youtube.com/@old/streams/".to_string()),
+        fetch_last_days: Set(7),
+        refresh_frequency: Set(24),
+        sponsorblock: Set(String::new()),
+        metadata: Set(Some(serde_json::json!({
+            "uploader": "Channel",
+            "items": 12,
+            "source_provider": "YoutubeTab",
+            "channel_url": "https://www.youtube.com/channel/UC123",
+        }))),
+        ..Default::default()
+    }
+    .insert(db)
+    .await
+    .unwrap();
+
+    let gone = "ERROR: [youtube:tab] @old: This channel does not exist.";
+    assert_eq!(
+        source.moved_channel_suggestion(gone).as_deref(),
+        Some("https://www.youtube.com/channel/UC123/streams")
+    );
+    let not_found = "ERROR: [youtube:tab] Unable to download API page: HTTP Error 404: Not Found";
+    assert_eq!(
+        source.moved_channel_suggestion(not_found),
+        None,
+        "a plain 404 does not mean the channel moved"
+    );
+}
+
+#[tokio::test]
+#[serial]
+async fn finished_refresh_records_its_duration_and_clears_the_backoff() {
+    let boot = boot_test::<App>().await.unwrap();
+    let db = &boot.app_context.db;
+    let now = chrono::Utc::now();
+    let source = create_source(db).await;
+    assert_eq!(source.last_refresh_duration_secs, None);
+    let source = source
+        .record_refresh_failure(db, now, "ERROR: boom")
+        .await
+        .unwrap();
+
+    let started = std::time::Instant::now();
+    tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    let source = source
+        .record_refresh_success(db, now, started.elapsed())
+        .await
+        .unwrap();
+    assert_eq!(source.last_refresh_duration_secs, Some(0));
+    assert_eq!(source.refresh_failures, 0);
+    assert_eq!(source.refresh_backoff_until, None);
+    assert_eq!(source.last_refresh_error, None);
+    assert_eq!(
+        source.last_refreshed_at.map(|at| at.timestamp()),
+        Some(now.timestamp())
+    );
+
+    let source = source
+        .record_refresh_success(db, now, std::time::Duration::from_millis(95_400))
+        .await
+        .unwrap();
+    assert_eq!(source.last_refresh_duration_secs, Some(95));
+}
+
+#[test]
+fn old_metadata_blobs_still_parse() {
+    let oldest: SourceMetadata =
+        serde_json::from_value(serde_json::json!({ "uploader": "Channel" })).unwrap();
+    assert_eq!(oldest.uploader, "Channel");
+    assert_eq!(oldest.items, 0);
+    assert_eq!(oldest.source_provider, "unknown");
+    assert_eq!(oldest.list_kind, None);
+
+    let old: SourceMetadata = serde_json::from_value(serde_json::json!({
+        "uploader": "Channel",
+        "items": 12,
+        "source_provider": "Youtube",
+        "list_kind": "playlist",
+        "list_order": null,
+        "list_tabs": "videos",
+        "thumbnail": "https://example.com/a.jpg",
+    }))
+    .unwrap();
+    assert_eq!(old.items, 12);
+    assert_eq!(old.source_provider, "Youtube");
+    assert_eq!(old.list_kind, None, "unknown kinds read as unknown");
+    assert_eq!(old.list_order, None);
+    assert_eq!(old.list_tabs, None);
+}
+
+#[tokio::test]
+#[serial]
+async fn stored_metadata_is_rewritten_in_the_current_shape() {
+    let boot = boot_test::<App>().await.unwrap();
+    let db = &boot.app_context.db;
+    let source = create_source(db).await;
+    let old = sources::ActiveModel {
+        id: Set(source.id),
+        metadata: Set(Some(serde_json::json!({
+            "uploader": "Channel",
+            "items": 3,
+            "list_kind": "playlist",
+        }))),
+        ..Default::default()
+    }
+    .update(db)
+    .await
+    .unwrap();
+    let unreadable = sources::ActiveModel {
+        id: Set(create_source(db).await.id),
+        metadata: Set(Some(serde_json::json!({ "items": 3 }))),
+        ..Default::default()
+    }
+    .update(db)
+    .await
+    .unwrap();
+
+    assert_eq!(normalize_stored_metadata(db).await.unwrap(), 1);
+    let old = sources::Entity::find_by_id(old.id)
+        .one(db)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        old.metadata,
+        Some(serde_json::json!({
+            "uploader": "Channel",
+            "items": 3,
+            "source_provider": "unknown",
+        }))
+    );
+    let unreadable = sources::Entity::find_by_id(unreadable.id)
+        .one(db)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        unreadable.metadata,
+        Some(serde_json::json!({ "items": 3 })),
+        "left for the next refresh"
+    );
+    assert_eq!(normalize_stored_metadata(db).await.unwrap(), 0);
+}
+
+#[test]
+fn refresh_jitter_stays_within_its_bound() {
+    let max = chrono::Duration::minutes(30);
+    let jitters: Vec<_> = (0..200).map(|_| refresh_jitter(max)).collect();
+    for jitter in &jitters {
+        assert!(*jitter >= chrono::Duration::zero(), "{jitter}");
+        assert!(*jitter <= max, "{jitter}");
+    }
+    assert!(
+        jitters.iter().any(|jitter| *jitter != jitters[0]),
+        "drawn again every time"
+    );
+    assert_eq!(
+        refresh_jitter(chrono::Duration::zero()),
+        chrono::Duration::zero()
+    );
+    assert_eq!(
+        refresh_jitter(-max),
+        chrono::Duration::zero(),
+        "a negative bound never makes refreshes early"
+    );
+}
+
+#[test]
+fn scheduled_refresh_stays_between_interval_and_jitter() {
+    let last = chrono::DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+    let max = chrono::Duration::minutes(30);
+    let interval = chrono::Duration::hours(24);
+    for _ in 0..200 {
+        let jitter = refresh_jitter(max);
+        let due = |at| refresh_interval_elapsed(Some(last), 24, jitter, at);
+        assert!(!due(last + interval - chrono::Duration::seconds(1)));
+        assert!(due(last + interval + max));
+    }
+}
+
+#[test]
+fn refresh_is_never_due_before_its_frequency() {
+    let last = chrono::DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+    let jitter = chrono::Duration::minutes(10);
+    let due =
+        |after: chrono::Duration| refresh_interval_elapsed(Some(last), 24, jitter, last + after);
+
+    assert!(refresh_interval_elapsed(None, 24, jitter, last));
+    assert!(!due(chrono::Duration::hours(23)));
+    assert!(!due(chrono::Duration::hours(24)));
+    assert!(!due(
+        chrono::Duration::hours(24) + chrono::Duration::minutes(9)
+    ));
+    assert!(due(
+        chrono::Duration::hours(24) + chrono::Duration::minutes(10)
+    ));
+    assert!(due(chrono::Duration::hours(48)));
+    // A negative jitter never makes the refresh early.
+    assert!(!refresh_interval_elapsed(
+        Some(last),
+        24,
+        -jitter,
+        last + chrono::Duration::hours(24) - chrono::Duration::minutes(5)
+    ));
+}
+
+#[tokio::test]
+#[serial]
+async fn refresh_is_due_once_interval_and_jitter_passed() {
+    let boot = boot_test::<App>().await.unwrap();
+    let db = &boot.app_context.db;
+    let source = create_source(db).await;
+    let refreshed_at = source.last_refreshed_at.unwrap();
+    let source = sources::Model {
+        metadata: Some(serde_json::json!({
+            "uploader": "Channel",
+            "items": 1,
+            "source_provider": "youtube",
+        })),
+        last_scheduled_refresh: Some(refreshed_at),
+        ..source
+    };
+    let interval = chrono::Duration::hours(24);
+
+    assert!(!source.is_refresh_due_at(refreshed_at + chrono::Duration::hours(23)));
+    assert!(!source.is_refresh_due_at(refreshed_at + interval - chrono::Duration::seconds(1)));
+    assert!(source.is_refresh_due_at(refreshed_at + interval + refresh_jitter_max()));
+
+    // Without metadata it is scheduled again right away, once the last schedule is old enough.
+    let unindexed = sources::Model {
+        metadata: None,
+        last_scheduled_refresh: None,
+        ..source
+    };
+    assert!(unindexed.is_refresh_due_at(refreshed_at));
+}
+
+#[test]
+fn sponsorblock_remove_and_mark_sets_round_trip() {
+    let categories = SponsorBlockCategories {
+        remove: SponsorBlockSet {
+            sponsor: true,
+            selfpromo: true,
+            ..Default::default()
+        },
+        mark: SponsorBlockSet {
+            intro: true,
+            outro: true,
+            ..Default::default()
+        },
+    };
+    let serialized = categories.serialize();
+    assert_eq!(serialized, "sponsor,selfpromo;intro,outro");
+    assert_eq!(SponsorBlockCategories::deserialize(&serialized), categories);
+
+    let marked_only = SponsorBlockCategories {
+        mark: SponsorBlockSet {
+            filler: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    assert_eq!(marked_only.serialize(), ";filler");
+    assert_eq!(
+        SponsorBlockCategories::deserialize(&marked_only.serialize()),
+        marked_only
+    );
+    assert_eq!(SponsorBlockCategories::default().serialize(), "");
+}
+
+#[test]
+fn old_sponsorblock_list_only_removes() {
+    let categories = SponsorBlockCategories::deserialize("sponsor,intro");
+    assert!(categories.remove.sponsor && categories.remove.intro);
+    assert!(categories.mark.is_empty());
+    assert_eq!(categories.serialize(), "sponsor,intro");
+
+    let overlapping = SponsorBlockCategories::deserialize("sponsor;sponsor,outro,unknown");
+    assert!(overlapping.remove.sponsor);
+    assert!(
+        !overlapping.mark.sponsor,
+        "removed categories are not marked"
+    );
+    assert!(overlapping.mark.outro);
+    assert_eq!(overlapping.serialize(), "sponsor;outro");
+}
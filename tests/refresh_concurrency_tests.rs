@@ -0,0 +1,53 @@
+use localtube::{
+    job_tracking::{
+        manager::TaskManager,
+        task::{TaskState, TaskType},
+    },
+    ytdlp::{refresh_concurrency, ytdtp_concurrency},
+};
+use std::time::Duration;
+
+#[tokio::test]
+async fn test_refresh_cap_holds_under_many_queued_refreshes() {
+    let manager = TaskManager::new();
+    let cap = refresh_concurrency().available_permits();
+    assert!(cap >= 1);
+
+    let mut handles = Vec::new();
+    for n in 0..cap + 4 {
+        let queued = manager.add_task(TaskType::RefreshIndex, format!("Refresh {n}"));
+        handles.push(tokio::spawn(async move {
+            queued.start(refresh_concurrency().clone()).await
+        }));
+    }
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let in_progress = {
+        let tasks = manager.tasks.lock().unwrap();
+        tasks
+            .values()
+            .filter(|t| matches!(t.state, TaskState::InProgress))
+            .count()
+    };
+    assert_eq!(in_progress, cap, "refreshes should never exceed the cap");
+
+    let metrics = manager.get_metrics();
+    let refresh = &metrics.tasks[&TaskType::RefreshIndex];
+    assert_eq!(refresh.active_count, cap as u64);
+    assert_eq!(refresh.queued_count, 4);
+    assert_eq!(metrics.tasks[&TaskType::DownloadVideo].active_count, 0);
+
+    // Refreshes hold their own permits, downloads are not blocked.
+    let download = manager.add_task(TaskType::DownloadVideo, "Download".into());
+    let active_download = tokio::time::timeout(
+        Duration::from_millis(500),
+        download.start(ytdtp_concurrency().clone()),
+    )
+    .await
+    .expect("download should start while refreshes are saturated");
+    active_download.complete();
+
+    for handle in &handles {
+        handle.abort();
+    }
+}
@@ -0,0 +1,163 @@
+use localtube::{
+    app::App,
+    models::_entities::{medias, sources},
+};
+use loco_rs::prelude::*;
+use serial_test::serial;
+
+use crate::helpers::create_source;
+
+#[tokio::test]
+#[serial]
+async fn list_json_sorts_by_updated_at() {
+    request_with_create_db::<App, _, _>(|request, ctx| async move {
+        let source = sources::ActiveModel {
+            url: Set("https://example.com/channel".to_string()),
+            fetch_last_days: Set(7),
+            refresh_frequency: Set(24),
+            sponsorblock: Set(String::new()),
+            ..Default::default()
+        }
+        .insert(&ctx.db)
+        .await
+        .expect("source should be inserted");
+
+        let base = chrono::Utc::now() - chrono::Duration::hours(3);
+        let mut ids = Vec::new();
+        for (video_id, hours) in [("a", 1), ("b", 2), ("c", 0)] {
+            let media = medias::ActiveModel {
+                source_id: Set(source.id),
+                url: Set(format!("https://example.com/watch?v={video_id}")),
+                ..Default::default()
+            }
+            .insert(&ctx.db)
+            .await
+            .expect("media should be inserted");
+            medias::ActiveModel {
+                id: Set(media.id),
+                updated_at: Set((base + chrono::Duration::hours(hours)).into()),
+                ..Default::default()
+            }
+            .update(&ctx.db)
+            .await
+            .expect("media should be updated");
+            ids.push(media.id);
+        }
+
+        let response = request.get("/medias/list.json?sort=updated_at").await;
+        response.assert_status_ok();
+        let items: Vec<serde_json::Value> = response.json();
+        let listed: Vec<i64> = items.iter().map(|m| m["id"].as_i64().unwrap()).collect();
+        let expected: Vec<i64> = [ids[1], ids[0], ids[2]].map(i64::from).to_vec();
+        assert_eq!(listed, expected);
+        assert!(items[0]["created_at"].is_string());
+        assert!(items[0]["updated_at"].is_string());
+    })
+    .await;
+}
+
+async fn create_media(
+    ctx: &AppContext,
+    source_id: i32,
+    media_path: Option<&str>,
+    available_at: Option<chrono::DateTime<chrono::Utc>>,
+) -> i64 {
+    let media = medias::ActiveModel {
+        source_id: Set(source_id),
+        url: Set(format!(
+            "https://example.com/watch?v={}",
+            uuid::Uuid::new_v4()
+        )),
+        media_path: Set(media_path.map(ToString::to_string)),
+        available_at: Set(available_at),
+        ..Default::default()
+    }
+    .insert(&ctx.db)
+    .await
+    .expect("media should be inserted");
+    i64::from(media.id)
+}
+
+#[tokio::test]
+#[serial]
+async fn failed_medias_are_listed_and_requeued() {
+    request_with_create_db::<App, _, _>(|request, ctx| async move {
+        let source = create_source(&ctx.db).await;
+        let other = create_source(&ctx.db).await;
+        let failed = [
+            create_media(&ctx, source.id, None, None).await,
+            create_media(&ctx, source.id, None, Some(chrono::Utc::now())).await,
+        ];
+        create_media(&ctx, source.id, Some("Done_[done].mkv"), None).await;
+        let upcoming = Some(chrono::Utc::now() + chrono::Duration::hours(2));
+        create_media(&ctx, source.id, None, upcoming).await;
+        let foreign = create_media(&ctx, other.id, None, None).await;
+
+        let ids = |items: Vec<serde_json::Value>| -> Vec<i64> {
+            items.iter().map(|m| m["id"].as_i64().unwrap()).collect()
+        };
+        let response = request.get("/medias/failed").await;
+        response.assert_status_ok();
+        assert_eq!(ids(response.json()), vec![failed[0], failed[1], foreign]);
+
+        let response = request
+            .get(&format!("/medias/failed?source_id={}", source.id))
+            .await;
+        assert_eq!(ids(response.json()), failed.to_vec());
+
+        let response = request
+            .post(&format!("/medias/failed/retry?source_id={}", source.id))
+            .await;
+        response.assert_status_ok();
+        let report: serde_json::Value = response.json();
+        assert_eq!(report["requeued"], 2);
+        assert_eq!(report["failed"], 0);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn start_from_now_backlog_is_not_failed() {
+    request_with_create_db::<App, _, _>(|request, ctx| async move {
+        let source = create_source(&ctx.db).await;
+        let baseline = chrono::Utc::now() - chrono::Duration::days(1);
+        let source = sources::ActiveModel {
+            id: Set(source.id),
+            start_from_now: Set(true),
+            download_baseline: Set(Some(baseline)),
+            ..Default::default()
+        }
+        .update(&ctx.db)
+        .await
+        .expect("source should be updated");
+
+        let mut ids = Vec::new();
+        for published in [baseline - chrono::Duration::days(3), chrono::Utc::now()] {
+            let id = create_media(&ctx, source.id, None, None).await;
+            medias::ActiveModel {
+                id: Set(i32::try_from(id).unwrap()),
+                metadata: Set(Some(serde_json::json!({
+                    "title": "Video",
+                    "description": null,
+                    "duration": 60,
+                    "extractor_key": "Youtube",
+                    "original_url": "https://example.com/watch",
+                    "timestamp": published.timestamp(),
+                }))),
+                ..Default::default()
+            }
+            .update(&ctx.db)
+            .await
+            .expect("media should be updated");
+            ids.push(id);
+        }
+
+        let response = request.get("/medias/failed").await;
+        response.assert_status_ok();
+        let items: Vec<serde_json::Value> = response.json();
+        let listed: Vec<i64> = items.iter().map(|m| m["id"].as_i64().unwrap()).collect();
+        assert_eq!(listed, vec![ids[1]], "the backlog video is not a failure");
+    })
+    .await;
+}
@@ -1,2 +1,6 @@
 mod auth;
+mod media;
 mod prepare_data;
+mod settings;
+mod source;
+mod status;
@@ -0,0 +1,124 @@
+use localtube::{
+    app::App,
+    services::{dedupe, trash},
+    webhook, ytdlp,
+};
+use loco_rs::prelude::*;
+use serial_test::serial;
+
+fn unset_settings() -> serde_json::Value {
+    serde_json::json!({
+        "concurrency": null,
+        "rate_limit": null,
+        "sponsorblock_api": null,
+        "concurrent_fragments": null,
+        "trash_days": null,
+        "dedupe": null,
+        "transcode": null,
+        "notify_removals": null
+    })
+}
+
+#[tokio::test]
+#[serial]
+async fn settings_round_trip_through_api() {
+    request_with_create_db::<App, _, _>(|request, _ctx| async move {
+        let response = request.get("/settings").await;
+        response.assert_status_ok();
+        let settings: serde_json::Value = response.json();
+        assert_eq!(
+            settings,
+            unset_settings()
+        );
+
+        let response = request
+            .put("/settings")
+            .json(&serde_json::json!({
+                "concurrency": 2,
+                "rate_limit": " 750k ",
+                "sponsorblock_api": "https://sb.example.org/",
+                "concurrent_fragments": 4,
+                "trash_days": 7,
+                "dedupe": " Link ",
+                "transcode": "true",
+                "notify_removals": false
+            }))
+            .await;
+        response.assert_status_ok();
+
+        let settings: serde_json::Value = request.get("/settings").await.json();
+        assert_eq!(
+            settings,
+            serde_json::json!({
+                "concurrency": 2,
+                "rate_limit": "750K",
+                "sponsorblock_api": "https://sb.example.org",
+                "concurrent_fragments": 4,
+                "trash_days": 7,
+                "dedupe": "link",
+                "transcode": true,
+                "notify_removals": false
+            })
+        );
+        assert_eq!(ytdlp::ytdtp_concurrency_limit(), 2);
+        assert_eq!(ytdlp::rate_limit(), Some("750K".to_string()));
+        assert_eq!(ytdlp::concurrent_fragments(), 4);
+        assert_eq!(
+            ytdlp::sponsorblock_api(),
+            Some("https://sb.example.org".to_string())
+        );
+        assert_eq!(trash::trash_days(), 7);
+        assert_eq!(dedupe::dedupe_mode(), dedupe::DedupeMode::Link);
+        assert!(localtube::services::transcode::transcode_enabled());
+        assert!(!webhook::notify_removals());
+
+        // Clearing a value falls back to the environment default again.
+        let response = request
+            .put("/settings")
+            .json(
+                &serde_json::json!({"concurrency": null, "rate_limit": "", "sponsorblock_api": "", "concurrent_fragments": null, "dedupe": "", "transcode": ""}),
+            )
+            .await;
+        response.assert_status_ok();
+        let settings: serde_json::Value = request.get("/settings").await.json();
+        assert_eq!(
+            settings,
+            unset_settings()
+        );
+        assert_eq!(
+            ytdlp::ytdtp_concurrency_limit(),
+            ytdlp::default_concurrency_limit()
+        );
+        assert_eq!(ytdlp::concurrent_fragments(), 1);
+        assert_eq!(trash::trash_days(), 0);
+        assert_eq!(dedupe::dedupe_mode(), dedupe::DedupeMode::Allow);
+        assert!(!localtube::services::transcode::transcode_enabled());
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn invalid_settings_are_rejected() {
+    request_with_create_db::<App, _, _>(|request, _ctx| async move {
+        for body in [
+            serde_json::json!({"concurrency": 0}),
+            serde_json::json!({"rate_limit": "fast"}),
+            serde_json::json!({"sponsorblock_api": "sb.example.org"}),
+            serde_json::json!({"concurrent_fragments": 17}),
+            serde_json::json!({"dedupe": "merge"}),
+        ] {
+            let response = request.put("/settings").json(&body).await;
+            response.assert_status_bad_request();
+        }
+        // Unparseable flags fail before the handler runs.
+        let response = request
+            .put("/settings")
+            .json(&serde_json::json!({"transcode": "maybe"}))
+            .await;
+        assert!(response.status_code().is_client_error());
+        let settings: serde_json::Value = request.get("/settings").await.json();
+        assert_eq!(settings, unset_settings());
+    })
+    .await;
+}
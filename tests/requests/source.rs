@@ -0,0 +1,435 @@
+use localtube::{
+    app::App,
+    models::_entities::{medias, sources},
+    services::refresh_runs::RefreshRuns,
+};
+use loco_rs::prelude::*;
+use serial_test::serial;
+use std::time::Duration;
+
+use crate::helpers::create_source;
+
+async fn create_media(
+    ctx: &AppContext,
+    source_id: i32,
+    video_id: &str,
+    duration: i64,
+    download: Option<i64>,
+) {
+    medias::ActiveModel {
+        source_id: Set(source_id),
+        url: Set(format!("https://example.com/watch?v={video_id}")),
+        video_id: Set(Some(video_id.to_string())),
+        duration: Set(Some(duration)),
+        media_path: Set(download.map(|_| format!("Channel/{video_id}.mkv"))),
+        file_size: Set(download),
+        ..Default::default()
+    }
+    .insert(&ctx.db)
+    .await
+    .expect("media should be inserted");
+}
+
+#[tokio::test]
+#[serial]
+async fn stats_aggregate_source_medias() {
+    request_with_create_db::<App, _, _>(|request, ctx| async move {
+        let source = create_source(&ctx.db).await;
+        create_media(&ctx, source.id, "a", 600, Some(1_000)).await;
+        create_media(&ctx, source.id, "b", 3_000, Some(5_000_000_000)).await;
+        create_media(&ctx, source.id, "c", 120, None).await;
+        let other = create_source(&ctx.db).await;
+        create_media(&ctx, other.id, "d", 60, Some(10)).await;
+
+        let response = request
+            .get(&format!("/sources/{}/stats.json", source.id))
+            .await;
+        response.assert_status_ok();
+        let stats: serde_json::Value = response.json();
+        assert_eq!(stats["indexed"], 3);
+        assert_eq!(stats["downloaded"], 2);
+        assert_eq!(stats["downloaded_duration"], 3_600);
+        assert_eq!(stats["disk_usage"], 5_000_001_000_i64);
+
+        let response = request.get(&format!("/sources/{}", source.id)).await;
+        response.assert_status_ok();
+        let body = response.text();
+        assert!(body.contains("2 of 3 indexed"));
+        assert!(body.contains("1h 0m"));
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn age_restricted_skips_are_counted() {
+    request_with_create_db::<App, _, _>(|request, ctx| async move {
+        let source = create_source(&ctx.db).await;
+        for (video_id, reason) in [
+            (
+                "a",
+                "Age-restricted: ERROR: [youtube] a: Sign in to confirm your age.",
+            ),
+            (
+                "b",
+                "Longer than the source maximum of 10 minutes (12 minutes)",
+            ),
+        ] {
+            medias::ActiveModel {
+                source_id: Set(source.id),
+                url: Set(format!("https://example.com/watch?v={video_id}")),
+                skip_reason: Set(Some(reason.to_string())),
+                ..Default::default()
+            }
+            .insert(&ctx.db)
+            .await
+            .expect("media should be inserted");
+        }
+
+        let response = request
+            .get(&format!("/sources/{}/stats.json", source.id))
+            .await;
+        let stats: serde_json::Value = response.json();
+        assert_eq!(stats["age_restricted"], 1);
+
+        let body = request.get(&format!("/sources/{}", source.id)).await.text();
+        assert!(body.contains("Age-restricted:</span> 1 videos skipped"));
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn stats_of_empty_source_are_zero() {
+    request_with_create_db::<App, _, _>(|request, ctx| async move {
+        let source = create_source(&ctx.db).await;
+
+        let response = request
+            .get(&format!("/sources/{}/stats.json", source.id))
+            .await;
+        response.assert_status_ok();
+        let stats: serde_json::Value = response.json();
+        assert_eq!(stats["indexed"], 0);
+        assert_eq!(stats["disk_usage"], 0);
+        assert_eq!(stats["index_state"], "pending");
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn reindex_of_unknown_source_is_not_found() {
+    request_with_create_db::<App, _, _>(|request, _ctx| async move {
+        let response = request.post("/sources/9999/reindex?full=true").await;
+        response.assert_status_not_found();
+    })
+    .await;
+}
+
+fn source_params(fetch_last_days: i32, refresh_frequency: i32) -> serde_json::Value {
+    serde_json::json!({
+        "url": "https://example.com/invalid",
+        "fetch_last_days": fetch_last_days,
+        "sponsorblock": "",
+        "refresh_frequency": refresh_frequency,
+        "list_tab": null,
+    })
+}
+
+#[tokio::test]
+#[serial]
+async fn invalid_schedule_values_are_rejected() {
+    request_with_create_db::<App, _, _>(|request, ctx| async move {
+        let existing = create_source(&ctx.db).await;
+        for (fetch_last_days, refresh_frequency) in [(7, 0), (7, -1), (-1, 24), (7, 1_000_000)] {
+            let body = source_params(fetch_last_days, refresh_frequency);
+            let response = request.post("/sources").json(&body).await;
+            assert_eq!(
+                response.status_code(),
+                422,
+                "create with {fetch_last_days}/{refresh_frequency}"
+            );
+            let response = request
+                .put(&format!("/sources/{}", existing.id))
+                .json(&body)
+                .await;
+            assert_eq!(
+                response.status_code(),
+                422,
+                "update with {fetch_last_days}/{refresh_frequency}"
+            );
+        }
+
+        let sources = sources::Entity::find().all(&ctx.db).await.unwrap();
+        assert_eq!(sources, vec![existing], "nothing should be stored");
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn out_of_range_max_height_is_rejected() {
+    request_with_create_db::<App, _, _>(|request, ctx| async move {
+        let existing = create_source(&ctx.db).await;
+        for max_height in [0, 100, 10_000] {
+            let mut body = source_params(7, 24);
+            body["max_height"] = max_height.into();
+            let response = request
+                .put(&format!("/sources/{}", existing.id))
+                .json(&body)
+                .await;
+            assert_eq!(response.status_code(), 422, "max_height {max_height}");
+        }
+
+        let stored = sources::Entity::find_by_id(existing.id)
+            .one(&ctx.db)
+            .await
+            .unwrap();
+        assert_eq!(stored, Some(existing), "nothing should be stored");
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn list_json_sorts_by_updated_at() {
+    request_with_create_db::<App, _, _>(|request, ctx| async move {
+        let base = chrono::Utc::now() - chrono::Duration::hours(3);
+        let mut ids = Vec::new();
+        for hours in [2, 0, 1] {
+            let source = create_source(&ctx.db).await;
+            sources::ActiveModel {
+                id: Set(source.id),
+                updated_at: Set((base + chrono::Duration::hours(hours)).into()),
+                ..Default::default()
+            }
+            .update(&ctx.db)
+            .await
+            .expect("source should be updated");
+            ids.push(source.id);
+        }
+
+        let response = request.get("/sources/list.json?sort=updated_at").await;
+        response.assert_status_ok();
+        let items: Vec<serde_json::Value> = response.json();
+        let listed: Vec<i64> = items.iter().map(|s| s["id"].as_i64().unwrap()).collect();
+        let expected: Vec<i64> = [ids[0], ids[2], ids[1]].map(i64::from).to_vec();
+        assert_eq!(listed, expected);
+        assert!(items[0]["created_at"].is_string());
+        assert!(items[0]["updated_at"].is_string());
+
+        let since = (base + chrono::Duration::minutes(30)).to_rfc3339();
+        let response = request
+            .get("/sources/list.json")
+            .add_query_param("sort", "updated_at")
+            .add_query_param("order", "asc")
+            .add_query_param("updated_since", since)
+            .await;
+        response.assert_status_ok();
+        let items: Vec<serde_json::Value> = response.json();
+        let listed: Vec<i64> = items.iter().map(|s| s["id"].as_i64().unwrap()).collect();
+        assert_eq!(listed, vec![i64::from(ids[2]), i64::from(ids[0])]);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn list_is_paged_and_searched() {
+    request_with_create_db::<App, _, _>(|request, ctx| async move {
+        let mut ids = Vec::new();
+        for name in ["Alpha Tech", "Beta Cooking", "Gamma Tech"] {
+            let source = create_source(&ctx.db).await;
+            sources::ActiveModel {
+                id: Set(source.id),
+                url: Set(format!("https://example.com/{}", name.replace(' ', "_"))),
+                metadata: Set(Some(serde_json::json!({
+                    "uploader": name,
+                    "items": 0,
+                    "source_provider": "youtube",
+                }))),
+                ..Default::default()
+            }
+            .update(&ctx.db)
+            .await
+            .expect("source should be updated");
+            ids.push(i64::from(source.id));
+        }
+        let listed = |items: Vec<serde_json::Value>| -> Vec<i64> {
+            items.iter().map(|s| s["id"].as_i64().unwrap()).collect()
+        };
+
+        let response = request.get("/sources/list.json?page=1&per_page=2").await;
+        response.assert_status_ok();
+        assert_eq!(listed(response.json()), vec![ids[2], ids[1]]);
+        let response = request.get("/sources/list.json?page=2&per_page=2").await;
+        assert_eq!(listed(response.json()), vec![ids[0]]);
+
+        let response = request.get("/sources?page=2&per_page=2").await;
+        response.assert_status_ok();
+        let body = response.text();
+        assert!(body.contains("Page 2 of 2"), "{body}");
+        assert!(body.contains("Alpha Tech"));
+        assert!(!body.contains("Gamma Tech"));
+
+        let response = request
+            .get("/sources/list.json")
+            .add_query_param("q", "TECH")
+            .await;
+        assert_eq!(listed(response.json()), vec![ids[2], ids[0]]);
+        let response = request
+            .get("/sources/list.json")
+            .add_query_param("q", "beta_cooking")
+            .await;
+        assert_eq!(listed(response.json()), vec![ids[1]]);
+        // Neither the JSON keys nor wildcards in the query match every source.
+        for q in ["uploader", "items", "a%h", "p_a"] {
+            let response = request
+                .get("/sources/list.json")
+                .add_query_param("q", q)
+                .await;
+            assert!(listed(response.json()).is_empty(), "{q}");
+        }
+
+        let response = request.get("/sources?q=nothing").await;
+        assert!(response.text().contains("No sources match"));
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn members_only_without_cookies_is_rejected() {
+    request_with_create_db::<App, _, _>(|request, ctx| async move {
+        let existing = create_source(&ctx.db).await;
+        let mut body = source_params(7, 24);
+        body["members_only"] = true.into();
+        let response = request
+            .put(&format!("/sources/{}", existing.id))
+            .json(&body)
+            .await;
+        assert_eq!(response.status_code(), 422);
+        assert!(response.text().contains("members_only"));
+
+        let stored = sources::Entity::find_by_id(existing.id)
+            .one(&ctx.db)
+            .await
+            .unwrap();
+        assert_eq!(stored, Some(existing), "nothing should be stored");
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn relative_media_root_is_rejected() {
+    request_with_create_db::<App, _, _>(|request, _ctx| async move {
+        let mut params = source_params(7, 24);
+        params["media_root"] = serde_json::json!("media/elsewhere");
+        let response = request.post("/sources").json(&params).await;
+        assert_eq!(response.status_code(), 422);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn invalid_sponsorblock_api_is_rejected() {
+    request_with_create_db::<App, _, _>(|request, _ctx| async move {
+        let mut params = source_params(7, 24);
+        params["sponsorblock_api"] = serde_json::json!("sb.example.org");
+        let response = request.post("/sources").json(&params).await;
+        assert_eq!(response.status_code(), 422);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn media_root_cannot_change_with_downloads() {
+    request_with_create_db::<App, _, _>(|request, ctx| async move {
+        let source = create_source(&ctx.db).await;
+        let root = std::env::temp_dir().join(format!("localtube_root_{}", uuid::Uuid::new_v4()));
+        let mut params = source_params(7, 24);
+        params["media_root"] = serde_json::json!(root.display().to_string());
+
+        create_media(&ctx, source.id, "pending", 60, None).await;
+        // The queued refresh runs inline and fails without yt-dlp, the row is
+        // saved before it.
+        let response = request
+            .put(&format!("/sources/{}", source.id))
+            .json(&params)
+            .await;
+        assert_ne!(response.status_code(), 422, "nothing was downloaded yet");
+        let stored = sources::Entity::find_by_id(source.id)
+            .one(&ctx.db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored.media_root, Some(root.display().to_string()));
+
+        create_media(&ctx, source.id, "done", 60, Some(0)).await;
+        params["media_root"] = serde_json::json!("");
+        let response = request
+            .put(&format!("/sources/{}", source.id))
+            .json(&params)
+            .await;
+        assert_eq!(response.status_code(), 422);
+        let after = sources::Entity::find_by_id(source.id)
+            .one(&ctx.db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(after.media_root, stored.media_root);
+        let _ = std::fs::remove_dir_all(&root);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn editing_cancels_the_running_refresh() {
+    request_with_create_db::<App, _, _>(|request, ctx| async move {
+        let source = create_source(&ctx.db).await;
+        // Stands in for a refresh still listing the source with its old settings.
+        let running = RefreshRuns::global().start(source.id).await;
+        let stale = tokio::spawn(async move {
+            let token = running.token();
+            let cancelled = tokio::time::timeout(Duration::from_secs(10), token.cancelled())
+                .await
+                .is_ok();
+            drop(running);
+            cancelled
+        });
+
+        request
+            .put(&format!("/sources/{}", source.id))
+            .json(&source_params(30, 24))
+            .await;
+        assert!(
+            stale.await.unwrap(),
+            "the running refresh should be cancelled"
+        );
+        assert!(!RefreshRuns::global().is_running(source.id));
+
+        // The replacing refresh ran once against the new settings, failing on the stub yt-dlp.
+        let source = sources::Entity::find_by_id(source.id)
+            .one(&ctx.db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(source.fetch_last_days, 30);
+        assert_eq!(source.refresh_failures, 1);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn prioritize_of_unknown_source_is_not_found() {
+    request_with_create_db::<App, _, _>(|request, _ctx| async move {
+        let response = request.post("/sources/9999/prioritize").await;
+        response.assert_status_not_found();
+    })
+    .await;
+}
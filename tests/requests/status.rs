@@ -0,0 +1,342 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use async_trait::async_trait;
+use localtube::{
+    app::App,
+    gluetun::{
+        controller::{GluetunController, GluetunError, GluetunRestartOutcome},
+        supervisor,
+    },
+    job_tracking::manager::{register_download_task, TaskManager},
+    models::_entities::medias,
+    services::deps::{self, DepsInstaller},
+};
+use loco_rs::prelude::*;
+use serial_test::serial;
+use uuid::Uuid;
+
+use crate::helpers::{create_source, TempMediaDir};
+
+async fn create_media(
+    ctx: &AppContext,
+    video_id: &str,
+    media_path: Option<String>,
+) -> medias::Model {
+    let source = create_source(&ctx.db).await;
+    medias::ActiveModel {
+        source_id: Set(source.id),
+        url: Set(format!("https://example.com/watch?v={video_id}")),
+        video_id: Set(Some(video_id.to_string())),
+        media_path: Set(media_path),
+        ..Default::default()
+    }
+    .insert(&ctx.db)
+    .await
+    .expect("media should be inserted")
+}
+
+#[tokio::test]
+#[serial]
+async fn reconcile_clears_missing_media_path() {
+    request_with_create_db::<App, _, _>(|request, ctx| async move {
+        let dir = TempMediaDir::new();
+        let kept_path = dir.create_file("Kept_[kept].mkv", b"media");
+        let kept = create_media(&ctx, "kept", Some(kept_path.clone())).await;
+        let missing = create_media(&ctx, "gone", Some(dir.rel_path("Gone_[gone].mkv"))).await;
+
+        let response = request.post("/status/reconcile").await;
+        response.assert_status_ok();
+        let report: serde_json::Value = response.json();
+        assert_eq!(report["missing"], 1);
+        assert_eq!(report["requeued"], 1);
+        assert_eq!(report["adopted"], 0);
+
+        let missing = medias::Entity::find_by_id(missing.id)
+            .one(&ctx.db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(missing.media_path, None);
+        let kept = medias::Entity::find_by_id(kept.id)
+            .one(&ctx.db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(kept.media_path, Some(kept_path));
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn reconcile_adopts_moved_file_when_requested() {
+    request_with_create_db::<App, _, _>(|request, ctx| async move {
+        let dir = TempMediaDir::new();
+        dir.create_file("Moved_[moved].info.json", b"media");
+        let moved_path = dir.create_file("Moved_[moved].mkv", b"media");
+        let media = create_media(
+            &ctx,
+            "moved",
+            Some("Old_Channel/Moved_[moved].mkv".to_string()),
+        )
+        .await;
+
+        let response = request.post("/status/reconcile?adopt=true").await;
+        response.assert_status_ok();
+        let report: serde_json::Value = response.json();
+        assert_eq!(report["missing"], 1);
+        assert_eq!(report["adopted"], 1);
+        assert_eq!(report["requeued"], 0);
+
+        let media = medias::Entity::find_by_id(media.id)
+            .one(&ctx.db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(media.media_path, Some(moved_path));
+    })
+    .await;
+}
+
+struct MockInstaller {
+    calls: Arc<AtomicUsize>,
+    outcome: Result<Option<String>, String>,
+}
+
+#[async_trait]
+impl DepsInstaller for MockInstaller {
+    async fn install(&self) -> Result<Option<String>, String> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        self.outcome.clone()
+    }
+}
+
+fn mock_installer(outcome: Result<Option<String>, String>) -> Arc<AtomicUsize> {
+    let calls = Arc::new(AtomicUsize::new(0));
+    deps::set_installer(Arc::new(MockInstaller {
+        calls: Arc::clone(&calls),
+        outcome,
+    }));
+    calls
+}
+
+#[tokio::test]
+#[serial]
+async fn reinstall_deps_reports_new_version() {
+    request_with_create_db::<App, _, _>(|request, _ctx| async move {
+        let calls = mock_installer(Ok(Some("2099.01.01".to_string())));
+
+        let response = request.post("/status/deps/reinstall").await;
+        response.assert_status_ok();
+        let record: serde_json::Value = response.json();
+        assert_eq!(record["success"], true);
+        assert_eq!(record["version"], "2099.01.01");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(
+            deps::last_install().and_then(|r| r.version).as_deref(),
+            Some("2099.01.01")
+        );
+        assert!(!deps::install_in_progress());
+
+        let page = request.get("/status").await;
+        page.assert_status_ok();
+        assert!(page.text().contains("2099.01.01"));
+        deps::set_installer(Arc::new(deps::LibrariesInstaller));
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn reinstall_deps_reports_failure() {
+    request_with_create_db::<App, _, _>(|request, _ctx| async move {
+        let calls = mock_installer(Err("download failed".to_string()));
+
+        let response = request.post("/status/deps/reinstall").await;
+        response.assert_status(axum::http::StatusCode::INTERNAL_SERVER_ERROR);
+        let record: serde_json::Value = response.json();
+        assert_eq!(record["success"], false);
+        assert_eq!(record["error"], "download failed");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert!(!deps::install_in_progress());
+        // Boot runs the installer too, later tests must not inherit the failing mock.
+        deps::set_installer(Arc::new(deps::LibrariesInstaller));
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn summary_aggregates_system_state() {
+    request_with_create_db::<App, _, _>(|request, ctx| async move {
+        let dir = TempMediaDir::new();
+        let path = dir.create_file("Done_[done].mkv", b"media");
+        create_media(&ctx, "done", Some(path)).await;
+        create_media(&ctx, "pending", None).await;
+
+        let response = request.get("/status/summary.json").await;
+        response.assert_status_ok();
+        let summary: serde_json::Value = response.json();
+        assert_eq!(summary["sources"], 2);
+        assert_eq!(summary["medias"], 2);
+        assert_eq!(summary["downloaded"], 1);
+        assert!(summary["active_downloads"].is_u64());
+        assert!(summary["queued_downloads"].is_u64());
+        assert_eq!(summary["gluetun"]["enabled"], false);
+        assert!(summary["gluetun"]
+            .as_object()
+            .unwrap()
+            .contains_key("last_restart_seconds_ago"));
+        assert!(summary["disk_free"].is_u64());
+        assert!(summary.as_object().unwrap().contains_key("ytdlp_version"));
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn version_reports_the_crate_version() {
+    request_with_create_db::<App, _, _>(|request, _ctx| async move {
+        let response = request.get("/version").await;
+        response.assert_status_ok();
+        let version: serde_json::Value = response.json();
+        assert_eq!(version["version"], env!("CARGO_PKG_VERSION"));
+        let fields = version.as_object().unwrap();
+        for field in ["git_commit", "ytdlp_version", "ffmpeg_version"] {
+            assert!(fields.contains_key(field), "missing {field}");
+        }
+    })
+    .await;
+}
+
+struct MockGluetun {
+    fail: bool,
+}
+
+#[async_trait]
+impl GluetunController for MockGluetun {
+    async fn restart(&self) -> Result<GluetunRestartOutcome, GluetunError> {
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        if self.fail {
+            return Err(GluetunError::PollTimeout);
+        }
+        Ok(GluetunRestartOutcome {
+            stop_outcome: Some("stopped".to_string()),
+            start_outcome: Some("running".to_string()),
+        })
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn gluetun_restart_wait_returns_controller_outcome() {
+    request_with_create_db::<App, _, _>(|request, _ctx| async move {
+        let controller: Arc<dyn GluetunController> = Arc::new(MockGluetun { fail: false });
+        supervisor::activate(&controller, TaskManager::global());
+
+        let response = request.post("/status/gluetun/restart.json?wait=true").await;
+        response.assert_status_ok();
+        let result: serde_json::Value = response.json();
+        assert_eq!(result["kind"], "success");
+        assert_eq!(result["outcome"]["stop_outcome"], "stopped");
+        assert_eq!(result["outcome"]["start_outcome"], "running");
+        assert!(result["error"].is_null());
+
+        let response = request.post("/status/gluetun/restart?wait=true").await;
+        response.assert_status_ok();
+        assert!(response.text().contains("VPN restarted."));
+
+        let controller: Arc<dyn GluetunController> = Arc::new(MockGluetun { fail: true });
+        supervisor::activate(&controller, TaskManager::global());
+        let response = request.post("/status/gluetun/restart.json?wait=true").await;
+        assert_eq!(response.status_code(), 502);
+        let result: serde_json::Value = response.json();
+        assert_eq!(result["kind"], "error");
+        assert_eq!(
+            result["error"],
+            "gluetun did not report desired state after polling"
+        );
+
+        supervisor::deactivate(TaskManager::global());
+        let response = request.post("/status/gluetun/restart.json").await;
+        assert_eq!(response.status_code(), 503);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn readiness_waits_for_dependency_install() {
+    request_with_create_db::<App, _, _>(|request, _ctx| async move {
+        deps::reset();
+        mock_installer(Err("download failed".to_string()));
+        assert!(deps::install().await.is_some_and(|record| !record.success));
+        assert!(!deps::is_ready());
+
+        let response = request.get("/_readiness").await;
+        assert_eq!(response.status_code(), 503);
+        assert_eq!(response.json::<serde_json::Value>()["ok"], false);
+
+        mock_installer(Ok(Some("2099.01.01".to_string())));
+        assert!(deps::install().await.is_some_and(|record| record.success));
+        let response = request.get("/_readiness").await;
+        response.assert_status_ok();
+        assert_eq!(response.json::<serde_json::Value>()["ok"], true);
+
+        // A failed reinstall keeps the installed binaries, it is only reported.
+        mock_installer(Err("download failed".to_string()));
+        let response = request.post("/status/deps/reinstall").await;
+        assert_eq!(response.status_code(), 500);
+        assert!(deps::is_ready());
+        request.get("/_readiness").await.assert_status_ok();
+        assert_eq!(
+            deps::last_install().and_then(|record| record.error),
+            Some("download failed".to_string())
+        );
+        deps::set_installer(Arc::new(deps::LibrariesInstaller));
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn task_detail_reports_one_task() {
+    request_with_create_db::<App, _, _>(|request, _ctx| async move {
+        let title = format!("Detail {}", Uuid::new_v4());
+        let queued = register_download_task(title.clone(), 3, 7);
+        let id = queued.id().to_string();
+        TaskManager::global().update_task_status(&id, "Waiting".to_string());
+
+        let response = request.get(&format!("/status/tasks/{id}.json")).await;
+        response.assert_status_ok();
+        let detail: serde_json::Value = response.json();
+        assert_eq!(detail["id"], id.as_str());
+        assert_eq!(detail["title"], title.as_str());
+        assert_eq!(detail["task_type"], "DownloadVideo");
+        assert_eq!(detail["state"], "Queued");
+        assert_eq!(detail["status"], "Waiting");
+        assert_eq!(detail["source_id"], 3);
+        assert_eq!(detail["media_id"], 7);
+        assert!(detail["queue_position"].is_u64());
+        assert!(detail["created_seconds_ago"].as_f64().unwrap() >= 0.0);
+        assert!(detail["completed_seconds_ago"].is_null());
+        assert!(detail["duration_secs"].is_f64());
+
+        // Dropped tasks keep their detail until the cleanup removes them.
+        drop(queued);
+        let detail: serde_json::Value = request
+            .get(&format!("/status/tasks/{id}.json"))
+            .await
+            .json();
+        assert!(detail["completed_seconds_ago"].is_f64());
+
+        for unknown in [format!("{}.json", Uuid::new_v4()), id] {
+            let response = request.get(&format!("/status/tasks/{unknown}")).await;
+            response.assert_status_not_found();
+        }
+    })
+    .await;
+}
@@ -0,0 +1,32 @@
+use std::{sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use localtube::services::deps::{self, DepsInstaller};
+use serial_test::serial;
+
+struct SlowInstaller;
+
+#[async_trait]
+impl DepsInstaller for SlowInstaller {
+    async fn install(&self) -> Result<Option<String>, String> {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        Ok(Some("2099.01.01".to_string()))
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn concurrent_install_is_rejected() {
+    deps::set_installer(Arc::new(SlowInstaller));
+
+    let (first, second) = tokio::join!(deps::install(), async {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(deps::install_in_progress());
+        deps::install().await
+    });
+
+    assert!(first.is_some_and(|record| record.success));
+    assert!(second.is_none());
+    assert!(!deps::install_in_progress());
+    deps::set_installer(Arc::new(deps::LibrariesInstaller));
+}
@@ -0,0 +1,3 @@
+mod deps;
+mod relocate;
+mod retention;
@@ -0,0 +1,129 @@
+use localtube::{
+    app::App,
+    models::{_entities::medias, medias::Medias},
+    services::relocate::move_source_dir,
+};
+use loco_rs::prelude::*;
+use serial_test::serial;
+
+use crate::helpers::create_source;
+
+async fn create_media(db: &DatabaseConnection, source_id: i32, media_path: Option<String>) -> i32 {
+    medias::ActiveModel {
+        source_id: Set(source_id),
+        url: Set(format!(
+            "https://example.com/watch?v={}",
+            uuid::Uuid::new_v4()
+        )),
+        media_path: Set(media_path),
+        ..Default::default()
+    }
+    .insert(db)
+    .await
+    .expect("media should be inserted")
+    .id
+}
+
+#[tokio::test]
+#[serial]
+async fn renamed_source_files_move_with_their_media_paths() {
+    let boot = boot_test::<App>().await.unwrap();
+    let db = &boot.app_context.db;
+    let source = create_source(db).await;
+    let other = create_source(db).await;
+
+    let suffix = uuid::Uuid::new_v4();
+    let (old_dir, new_dir) = (format!("Old Name {suffix}"), format!("New Name {suffix}"));
+    let media_dir = localtube::ytdlp::media_directory();
+    std::fs::create_dir_all(media_dir.join(&old_dir)).expect("old folder should be created");
+    std::fs::write(media_dir.join(&old_dir).join("Video_[abc].mkv"), b"media")
+        .expect("media file should be created");
+    std::fs::write(
+        media_dir.join(&old_dir).join("Video_[abc].info.json"),
+        b"{}",
+    )
+    .expect("sidecar should be created");
+    std::fs::write(media_dir.join(&old_dir).join("Other_[xyz].mkv"), b"other")
+        .expect("media file of the other source should be created");
+
+    let moved = create_media(db, source.id, Some(format!("{old_dir}/Video_[abc].mkv"))).await;
+    let pending = create_media(db, source.id, None).await;
+    // Another source of the same uploader shares the folder and must not be touched.
+    let foreign = create_media(db, other.id, Some(format!("{old_dir}/Other_[xyz].mkv"))).await;
+
+    let updated = move_source_dir(db, media_dir, source.id, &old_dir, &new_dir)
+        .await
+        .expect("files should be moved");
+    assert_eq!(updated, 1);
+
+    assert!(media_dir.join(&new_dir).join("Video_[abc].mkv").exists());
+    assert!(media_dir
+        .join(&new_dir)
+        .join("Video_[abc].info.json")
+        .exists());
+    assert!(!media_dir.join(&old_dir).join("Video_[abc].mkv").exists());
+    assert!(media_dir.join(&old_dir).join("Other_[xyz].mkv").exists());
+    assert!(!media_dir.join(&new_dir).join("Other_[xyz].mkv").exists());
+
+    let path_of = |id| async move {
+        Medias::find_by_id(id)
+            .one(db)
+            .await
+            .unwrap()
+            .unwrap()
+            .media_path
+    };
+    assert_eq!(
+        path_of(moved).await,
+        Some(format!("{new_dir}/Video_[abc].mkv"))
+    );
+    assert_eq!(path_of(pending).await, None);
+    assert_eq!(
+        path_of(foreign).await,
+        Some(format!("{old_dir}/Other_[xyz].mkv"))
+    );
+
+    std::fs::remove_dir_all(media_dir.join(&old_dir)).expect("test folder should be removed");
+    std::fs::remove_dir_all(media_dir.join(&new_dir)).expect("test folder should be removed");
+}
+
+#[tokio::test]
+#[serial]
+async fn existing_target_file_is_not_overwritten() {
+    let boot = boot_test::<App>().await.unwrap();
+    let db = &boot.app_context.db;
+    let source = create_source(db).await;
+
+    let suffix = uuid::Uuid::new_v4();
+    let (old_dir, new_dir) = (format!("Old Name {suffix}"), format!("New Name {suffix}"));
+    let media_dir = localtube::ytdlp::media_directory();
+    std::fs::create_dir_all(media_dir.join(&old_dir)).expect("old folder should be created");
+    std::fs::create_dir_all(media_dir.join(&new_dir)).expect("new folder should be created");
+    std::fs::write(media_dir.join(&old_dir).join("Video_[abc].mkv"), b"old")
+        .expect("media file should be created");
+    std::fs::write(media_dir.join(&new_dir).join("Video_[abc].mkv"), b"new")
+        .expect("existing target should be created");
+    let media = create_media(db, source.id, Some(format!("{old_dir}/Video_[abc].mkv"))).await;
+
+    let updated = move_source_dir(db, media_dir, source.id, &old_dir, &new_dir)
+        .await
+        .expect("conflicts are skipped");
+    assert_eq!(updated, 0);
+    assert_eq!(
+        std::fs::read(media_dir.join(&new_dir).join("Video_[abc].mkv")).unwrap(),
+        b"new"
+    );
+    assert!(media_dir.join(&old_dir).join("Video_[abc].mkv").exists());
+    assert_eq!(
+        Medias::find_by_id(media)
+            .one(db)
+            .await
+            .unwrap()
+            .unwrap()
+            .media_path,
+        Some(format!("{old_dir}/Video_[abc].mkv"))
+    );
+
+    std::fs::remove_dir_all(media_dir.join(&old_dir)).expect("test folder should be removed");
+    std::fs::remove_dir_all(media_dir.join(&new_dir)).expect("test folder should be removed");
+}
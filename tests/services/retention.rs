@@ -0,0 +1,119 @@
+use localtube::{
+    app::App,
+    models::{
+        _entities::{medias, sources},
+        settings::GlobalSettings,
+    },
+    services::retention::{apply_retention, RetentionReport},
+};
+use loco_rs::prelude::*;
+use serial_test::serial;
+
+use crate::helpers::{create_downloaded_media, create_source, webhook_receiver, TempMediaDir};
+
+#[tokio::test]
+#[serial]
+async fn retention_keeps_medias_published_at_the_cutoff() {
+    let boot = boot_test::<App>().await.unwrap();
+    let db = &boot.app_context.db;
+    let source = create_source(db).await;
+    let dir = TempMediaDir::new();
+
+    let now = chrono::DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+    let cutoff = (now - chrono::Duration::days(7)).timestamp();
+    let at_cutoff = create_downloaded_media(db, &source, &dir.rel_path("at.mkv"), "at", cutoff)
+        .await
+        .id;
+    let before = create_downloaded_media(
+        db,
+        &source,
+        &dir.rel_path("before.mkv"),
+        "before",
+        cutoff - 1,
+    )
+    .await
+    .id;
+
+    let report = apply_retention(db, &source, now)
+        .await
+        .expect("retention should run");
+    assert_eq!(
+        report,
+        RetentionReport {
+            removed: 1,
+            freed_bytes: 5,
+        }
+    );
+    let exists = |id| async move {
+        medias::Entity::find_by_id(id)
+            .one(db)
+            .await
+            .unwrap()
+            .is_some()
+    };
+    assert!(exists(at_cutoff).await);
+    assert!(!exists(before).await);
+}
+
+#[tokio::test]
+#[serial]
+async fn retention_reports_removed_medias_when_enabled() {
+    let boot = boot_test::<App>().await.unwrap();
+    let db = &boot.app_context.db;
+    let (url, received) = webhook_receiver().await;
+    let dir = TempMediaDir::new();
+    let source = sources::ActiveModel {
+        url: Set("https://example.com/channel".to_string()),
+        fetch_last_days: Set(7),
+        refresh_frequency: Set(24),
+        sponsorblock: Set(String::new()),
+        webhook_url: Set(Some(url)),
+        ..Default::default()
+    }
+    .insert(db)
+    .await
+    .expect("source should be inserted");
+    let now = chrono::DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+    create_downloaded_media(db, &source, &dir.rel_path("old.mkv"), "old", 0).await;
+
+    GlobalSettings {
+        notify_removals: Some(false),
+        ..Default::default()
+    }
+    .apply();
+    let report = apply_retention(db, &source, now).await;
+    assert_eq!(report.expect("retention should run").removed, 1);
+    assert!(received.lock().unwrap().is_empty(), "opt-in only");
+
+    let removed = create_downloaded_media(db, &source, &dir.rel_path("removed.mkv"), "removed", 0)
+        .await
+        .id;
+    GlobalSettings {
+        notify_removals: Some(true),
+        ..Default::default()
+    }
+    .apply();
+    let report = apply_retention(db, &source, now).await;
+    GlobalSettings::default().apply();
+    assert_eq!(report.expect("retention should run").removed, 1);
+
+    // The removals are delivered in the background.
+    let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(5);
+    while received.lock().unwrap().is_empty() && tokio::time::Instant::now() < deadline {
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    let events = received.lock().unwrap().clone();
+    assert_eq!(
+        events,
+        vec![serde_json::json!({
+            "event": "media_removed",
+            "source_id": source.id,
+            "media_id": removed,
+            "title": "removed",
+            "url": "https://example.com/watch?v=removed",
+            "media_path": dir.rel_path("removed.mkv"),
+            "uploader": null,
+            "reason": "retention",
+        })]
+    );
+}
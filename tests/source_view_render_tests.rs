@@ -1,10 +1,20 @@
+use std::collections::HashMap;
+
 use chrono::DateTime;
 use localtube::{
+    controllers::listing::{PageInfo, DEFAULT_PER_PAGE},
     initializers::view_engine::build_test_tera_engine,
-    models::{_entities::sources, sources::SourceMetadata},
+    models::{
+        _entities::sources,
+        sources::{SourceMetadata, SourceStats},
+    },
     views,
     ytdlp::SourceListTabOption,
 };
+use loco_rs::prelude::*;
+
+mod helpers;
+use helpers::render;
 
 fn sample_timestamp() -> DateTime<chrono::FixedOffset> {
     DateTime::parse_from_rfc3339("2024-01-01T00:00:00Z").expect("timestamp should parse")
@@ -23,23 +33,20 @@ fn sample_metadata_with_unknown_tab_count() -> SourceMetadata {
             url: "https://example.com/tab".to_string(),
             label: "Videos".to_string(),
         }]),
+        channel_url: None,
     }
 }
 
+fn single_page() -> PageInfo {
+    PageInfo::new(1, DEFAULT_PER_PAGE, 1)
+}
+
 fn sample_source(metadata: Option<SourceMetadata>) -> sources::Model {
-    let timestamp = sample_timestamp();
     sources::Model {
-        created_at: timestamp,
-        updated_at: timestamp,
-        id: 1,
-        url: "https://example.com/channel".to_string(),
-        fetch_last_days: 7,
-        last_refreshed_at: None,
-        refresh_frequency: 24,
         sponsorblock: "sponsor".to_string(),
         metadata: metadata
             .map(|data| serde_json::to_value(data).expect("metadata should serialize")),
-        last_scheduled_refresh: None,
+        ..localtube::models::sources::sample_source()
     }
 }
 
@@ -49,7 +56,14 @@ fn renders_source_list_with_unknown_tab_count() {
     let source = sample_source(Some(sample_metadata_with_unknown_tab_count()));
     let sources = vec![source];
 
-    views::source::list(&view_engine, &sources).expect("Rendering source list view should succeed");
+    views::source::list(
+        &view_engine,
+        &sources,
+        &HashMap::new(),
+        &single_page(),
+        None,
+    )
+    .expect("Rendering source list view should succeed");
 }
 
 #[test]
@@ -57,5 +71,178 @@ fn renders_source_show_with_unknown_tab_count() {
     let view_engine = build_test_tera_engine().expect("TeraView build should succeed");
     let source = sample_source(Some(sample_metadata_with_unknown_tab_count()));
 
-    views::source::show(&view_engine, &source).expect("Rendering source show view should succeed");
+    views::source::show(&view_engine, &source, &SourceStats::default())
+        .expect("Rendering source show view should succeed");
+}
+
+#[test]
+fn renders_source_edit_with_client_options() {
+    let view_engine = build_test_tera_engine().expect("TeraView build should succeed");
+    let mut source = sample_source(Some(sample_metadata_with_unknown_tab_count()));
+    source.user_agent = Some("LocalTubeAgent".to_string());
+
+    let response = views::source::edit(&view_engine, &source)
+        .expect("Rendering source edit view should succeed")
+        .into_response();
+
+    let body = render(response);
+
+    assert!(body.contains(r#"name="user_agent" type="text" value="LocalTubeAgent""#));
+    assert!(body.contains(r#"name="extractor_args" type="text" value="""#));
+}
+
+#[test]
+fn renders_stale_flag_for_dormant_source() {
+    let view_engine = build_test_tera_engine().expect("TeraView build should succeed");
+    let mut source = sample_source(Some(sample_metadata_with_unknown_tab_count()));
+    source.last_refreshed_at = Some(chrono::Utc::now());
+    source.last_video_at = Some(sample_timestamp().to_utc());
+
+    let list = views::source::list(
+        &view_engine,
+        &vec![source.clone()],
+        &HashMap::new(),
+        &single_page(),
+        None,
+    )
+    .expect("Rendering source list view should succeed")
+    .into_response();
+    assert!(render(list).contains(">Stale</span>"));
+
+    let show = views::source::show(&view_engine, &source, &SourceStats::default())
+        .expect("Rendering source show view should succeed")
+        .into_response();
+    assert!(render(show).contains("no new videos since 2024-01-01"));
+}
+
+#[test]
+fn renders_pager_keeping_the_search() {
+    let view_engine = build_test_tera_engine().expect("TeraView build should succeed");
+    let response = views::source::list(
+        &view_engine,
+        &vec![sample_source(None)],
+        &HashMap::new(),
+        &PageInfo::new(2, 1, 3),
+        Some("tech talks"),
+    )
+    .expect("Rendering source list view should succeed")
+    .into_response();
+    let body = render(response);
+
+    assert!(body.contains("Page 2 of 3"));
+    assert!(body.contains("/sources?page=1&per_page=1&q=tech%20talks"));
+    assert!(body.contains("page=3"));
+    assert!(body.contains(r#"value="tech talks""#));
+
+    let response = views::source::list(
+        &view_engine,
+        &vec![sample_source(None)],
+        &HashMap::new(),
+        &single_page(),
+        None,
+    )
+    .expect("Rendering source list view should succeed")
+    .into_response();
+    let body = render(response);
+    assert!(!body.contains("Page 1 of 1"));
+}
+
+#[test]
+fn renders_last_download_age_on_source_list() {
+    let view_engine = build_test_tera_engine().expect("TeraView build should succeed");
+    let mut downloaded = sample_source(None);
+    downloaded.last_download_at = Some(chrono::Utc::now() - chrono::Duration::hours(5));
+    let mut idle = sample_source(None);
+    idle.id = 2;
+
+    let response = views::source::list(
+        &view_engine,
+        &vec![downloaded, idle],
+        &HashMap::new(),
+        &single_page(),
+        None,
+    )
+    .expect("Rendering source list view should succeed")
+    .into_response();
+    let body = render(response);
+
+    assert!(body.contains("5 hours ago"));
+    assert!(body.contains("never"));
+}
+
+#[test]
+fn renders_pending_first_index_for_never_refreshed_source() {
+    let view_engine = build_test_tera_engine().expect("TeraView build should succeed");
+    let pending = sample_source(None);
+    let mut empty = sample_source(None);
+    empty.id = 2;
+    empty.last_refreshed_at = Some(chrono::Utc::now());
+
+    let list = views::source::list(
+        &view_engine,
+        &vec![pending.clone()],
+        &HashMap::new(),
+        &single_page(),
+        None,
+    )
+    .expect("Rendering source list view should succeed")
+    .into_response();
+    let list = render(list);
+    assert!(list.contains(">Pending first index</span>"));
+    assert!(!list.contains(">No videos indexed</span>"));
+
+    let show = views::source::show(&view_engine, &pending, &SourceStats::default())
+        .expect("Rendering source show view should succeed")
+        .into_response();
+    assert!(render(show).contains("Pending first index:"));
+
+    let show = views::source::show(&view_engine, &empty, &SourceStats::default())
+        .expect("Rendering source show view should succeed")
+        .into_response();
+    let show = render(show);
+    assert!(show.contains("No videos indexed:"));
+    assert!(!show.contains("Pending first index:"));
+}
+
+#[test]
+fn renders_last_refresh_duration_once_known() {
+    let view_engine = build_test_tera_engine().expect("TeraView build should succeed");
+    let mut source = sample_source(Some(sample_metadata_with_unknown_tab_count()));
+    let show = |source: &sources::Model| {
+        render(
+            views::source::show(&view_engine, source, &SourceStats::default())
+                .expect("Rendering source show view should succeed")
+                .into_response(),
+        )
+    };
+
+    assert!(!show(&source).contains("Last Refresh Took:"));
+    source.last_refresh_duration_secs = Some(42);
+    assert!(show(&source).contains("Last Refresh Took:</span> 42s"));
+}
+
+#[test]
+fn renders_moved_channel_suggestion() {
+    let view_engine = build_test_tera_engine().expect("TeraView build should succeed");
+    let mut source = sample_source(Some(sample_metadata_with_unknown_tab_count()));
+    source.last_refresh_error = Some("ERROR: This channel does not exist.".to_string());
+    source.suggested_url = Some("https://www.youtube.com/channel/UC123".to_string());
+    let response = views::source::show(&view_engine, &source, &SourceStats::default())
+        .expect("Rendering source show view should succeed")
+        .into_response();
+    let body = render(response);
+
+    assert!(body.contains("Last refresh failed:</span> ERROR: This channel does not exist."));
+    assert!(body.contains("youtube.com&#x2F;channel&#x2F;UC123"));
+    assert!(body.contains("/sources/1/adopt_suggested_url"));
+}
+
+#[test]
+fn time_ago_switches_to_days() {
+    let now = chrono::Utc::now();
+    let ago = |hours| views::source::time_ago(now - chrono::Duration::hours(hours), now);
+    assert_eq!(ago(0), "less than an hour ago");
+    assert_eq!(ago(1), "1 hour ago");
+    assert_eq!(ago(47), "47 hours ago");
+    assert_eq!(ago(72), "3 days ago");
 }
@@ -0,0 +1,85 @@
+use axum::{http::header, response::IntoResponse};
+use futures_util::StreamExt;
+use localtube::{
+    job_tracking::{
+        manager::{register_download_task, TaskManager},
+        task::TaskType,
+    },
+    sse::{sse_handler, status_events, StatusEvent},
+};
+use std::time::Duration;
+
+async fn next_event<S>(events: &mut S) -> StatusEvent
+where
+    S: futures_util::Stream<Item = StatusEvent> + Unpin,
+{
+    tokio::time::timeout(Duration::from_secs(2), events.next())
+        .await
+        .expect("event should arrive in time")
+        .expect("stream should stay open")
+}
+
+#[tokio::test]
+async fn test_status_events_start_with_snapshot() {
+    let manager = TaskManager::new();
+    let _queued = manager.add_task(TaskType::DownloadVideo, "Existing".into());
+
+    let mut events = Box::pin(status_events(&manager));
+
+    let StatusEvent::Tasks(update) = next_event(&mut events).await else {
+        panic!("Expected task snapshot first");
+    };
+    assert_eq!(update.tasks.len(), 1);
+    assert_eq!(update.tasks[0].title, "Existing");
+
+    assert!(matches!(
+        next_event(&mut events).await,
+        StatusEvent::Metrics(_)
+    ));
+}
+
+#[tokio::test]
+async fn test_status_events_follow_task_changes() {
+    let manager = TaskManager::new();
+    let mut events = Box::pin(status_events(&manager));
+    next_event(&mut events).await;
+    next_event(&mut events).await;
+
+    let queued = manager.add_task(TaskType::RefreshIndex, "Refresh".into());
+    manager.update_task_status(queued.id(), "Fetching".into());
+
+    let StatusEvent::Tasks(update) = next_event(&mut events).await else {
+        panic!("Expected task update");
+    };
+    assert_eq!(update.tasks.len(), 1);
+    assert_eq!(update.tasks[0].title, "Refresh");
+}
+
+#[tokio::test]
+async fn test_sse_handler_streams_task_updates() {
+    let response = sse_handler().await.into_response();
+    assert_eq!(
+        response.headers().get(header::CONTENT_TYPE).unwrap(),
+        "text/event-stream"
+    );
+
+    let mut body = response.into_body().into_data_stream();
+    let title = "SSE handler task";
+    let _queued = register_download_task(title.into(), 1, 1);
+
+    let received = tokio::time::timeout(Duration::from_secs(2), async {
+        let mut seen = String::new();
+        while let Some(chunk) = body.next().await {
+            seen.push_str(&String::from_utf8_lossy(&chunk.unwrap()));
+            if seen.contains(title) {
+                return seen;
+            }
+        }
+        seen
+    })
+    .await
+    .expect("task update should be streamed");
+
+    assert!(received.contains("event: tasks"));
+    assert!(received.contains(title));
+}
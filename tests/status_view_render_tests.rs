@@ -1,30 +1,35 @@
 use std::collections::HashMap;
 
-use axum::body;
 use loco_rs::prelude::*;
-use tokio::runtime::Runtime;
 
 use localtube::{
+    gluetun::supervisor::GluetunStatus,
     initializers::view_engine::build_test_tera_engine,
     job_tracking::{
         metrics::{
-            AllMetrics, TaskMetrics, MAX_CONSECUTIVE_FAILURES_BEFORE_RESTART,
+            AllMetrics, StreamMetrics, TaskMetrics, MAX_CONSECUTIVE_FAILURES_BEFORE_RESTART,
             MIN_SUCCESS_AGE_BEFORE_RESTART,
         },
         task::TaskType,
     },
+    services::deps::InstallRecord,
     views,
 };
 
+mod helpers;
+use helpers::render;
+
 #[test]
 fn renders_status_without_download_metrics() {
     let view_engine = build_test_tera_engine().expect("TeraView build should succeed");
     let metrics = AllMetrics {
         tasks: HashMap::new(),
         gluetun_enabled: false,
+        stream: StreamMetrics::default(),
+        download_speed: 0,
     };
 
-    let response = views::status::show(&view_engine, &metrics)
+    let response = views::status::show(&view_engine, &metrics, &GluetunStatus::default(), None)
         .expect("Rendering status view without download metrics should succeed")
         .into_response();
 
@@ -40,9 +45,13 @@ fn renders_status_with_download_metrics() {
     tasks.insert(
         TaskType::DownloadVideo,
         TaskMetrics {
+            queued_count: 0,
+            active_count: 0,
             success_count: 1,
             failure_count: 0,
             consecutive_failures: 0,
+            retry_count: 4,
+            permanent_failure_count: 2,
             last_success_seconds_ago: Some(30),
             last_failure_seconds_ago: None,
             restart_count: 0,
@@ -56,18 +65,16 @@ fn renders_status_with_download_metrics() {
     let metrics = AllMetrics {
         tasks,
         gluetun_enabled: true,
+        stream: StreamMetrics::default(),
+        download_speed: 0,
     };
 
     let view_engine = build_test_tera_engine().expect("TeraView build should succeed");
-    let response = views::status::show(&view_engine, &metrics)
+    let response = views::status::show(&view_engine, &metrics, &GluetunStatus::default(), None)
         .expect("Rendering status view with download metrics should succeed")
         .into_response();
 
-    let runtime = Runtime::new().expect("tokio runtime should be created");
-    let body_bytes = runtime
-        .block_on(body::to_bytes(response.into_body(), usize::MAX))
-        .expect("Converting response body into bytes should succeed");
-    let body = String::from_utf8(body_bytes.to_vec()).expect("Body should be valid UTF-8");
+    let body = render(response);
 
     assert!(
         body.contains("System Status"),
@@ -77,6 +84,10 @@ fn renders_status_with_download_metrics() {
         body.contains("Restart VPN"),
         "Response body should include the manual VPN restart control when enabled"
     );
+    assert!(
+        body.contains("Retried downloads") && body.contains("Given up downloads"),
+        "Response body should include the retry and permanent failure counters"
+    );
 
     let min_success_age_minutes = MIN_SUCCESS_AGE_BEFORE_RESTART.as_secs().div_ceil(60);
     assert!(
@@ -94,23 +105,51 @@ fn renders_status_with_download_metrics() {
     );
 }
 
+#[test]
+fn renders_gluetun_control_address_when_enabled() {
+    let view_engine = build_test_tera_engine().expect("TeraView build should succeed");
+    let metrics = AllMetrics {
+        tasks: HashMap::new(),
+        gluetun_enabled: true,
+        stream: StreamMetrics::default(),
+        download_speed: 0,
+    };
+    let gluetun = GluetunStatus {
+        base_url: Some("http://gluetun:8000".to_string()),
+        reachable: Some(false),
+    };
+
+    let response = views::status::show(&view_engine, &metrics, &gluetun, None)
+        .expect("Rendering status view should succeed")
+        .into_response();
+
+    let body = render(response);
+
+    assert!(
+        body.contains("http:&#x2F;&#x2F;gluetun:8000"),
+        "Response body should show the configured control address"
+    );
+    assert!(
+        body.contains("Unreachable at last restart"),
+        "Response body should show the last known reachability"
+    );
+}
+
 #[test]
 fn renders_status_with_tailwind_dark_mode_selector_config() {
     let view_engine = build_test_tera_engine().expect("TeraView build should succeed");
     let metrics = AllMetrics {
         tasks: HashMap::new(),
         gluetun_enabled: false,
+        stream: StreamMetrics::default(),
+        download_speed: 0,
     };
 
-    let response = views::status::show(&view_engine, &metrics)
+    let response = views::status::show(&view_engine, &metrics, &GluetunStatus::default(), None)
         .expect("Rendering status view should succeed")
         .into_response();
 
-    let runtime = Runtime::new().expect("tokio runtime should be created");
-    let body_bytes = runtime
-        .block_on(body::to_bytes(response.into_body(), usize::MAX))
-        .expect("Converting response body into bytes should succeed");
-    let body = String::from_utf8(body_bytes.to_vec()).expect("Body should be valid UTF-8");
+    let body = render(response);
 
     let cdn_marker = "https://cdn.tailwindcss.com?plugins=forms,typography,aspect-ratio,line-clamp";
     let config_marker = "tailwind.config";
@@ -132,3 +171,38 @@ fn renders_status_with_tailwind_dark_mode_selector_config() {
         "Tailwind config should use selector-based dark mode for manual toggling"
     );
 }
+
+#[test]
+fn renders_last_dependency_install() {
+    let view_engine = build_test_tera_engine().expect("TeraView build should succeed");
+    let metrics = AllMetrics {
+        tasks: HashMap::new(),
+        gluetun_enabled: false,
+        stream: StreamMetrics::default(),
+        download_speed: 0,
+    };
+    let record = InstallRecord {
+        finished_at: chrono::Utc::now(),
+        success: false,
+        version: None,
+        error: Some("checksum mismatch".to_string()),
+    };
+
+    let response = views::status::show(
+        &view_engine,
+        &metrics,
+        &GluetunStatus::default(),
+        Some(&record),
+    )
+    .expect("Rendering status view should succeed")
+    .into_response();
+
+    let body = render(response);
+
+    assert!(
+        body.contains("/status/deps/reinstall")
+            || body.contains("&#x2F;status&#x2F;deps&#x2F;reinstall")
+    );
+    assert!(body.contains("Failed"));
+    assert!(body.contains("Error: checksum mismatch"));
+}
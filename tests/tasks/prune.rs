@@ -0,0 +1,127 @@
+use localtube::{
+    app::App,
+    models::_entities::{medias, sources},
+};
+use loco_rs::{boot::run_task, prelude::*, task};
+use serial_test::serial;
+
+use crate::helpers::{create_downloaded_media, create_source, TempMediaDir};
+
+/// Publication time of a video released `age` ago
+fn published(age: chrono::Duration) -> i64 {
+    (chrono::Utc::now() - age).timestamp()
+}
+
+#[tokio::test]
+#[serial]
+async fn prune_removes_only_medias_outside_the_window() {
+    let boot = boot_test_with_create_db::<App>().await.unwrap();
+    let db = &boot.app_context.db;
+    let source = create_source(db).await;
+
+    let dir = TempMediaDir::new();
+    let media_dir = localtube::ytdlp::media_directory().join(&dir.rel_dir);
+    let old = create_downloaded_media(
+        db,
+        &source,
+        &dir.rel_path("Video_[old].mkv"),
+        "old",
+        published(chrono::Duration::days(30)),
+    )
+    .await;
+    let new = create_downloaded_media(
+        db,
+        &source,
+        &dir.rel_path("Video_[new].mkv"),
+        "new",
+        published(chrono::Duration::days(1)),
+    )
+    .await;
+
+    run_task::<App>(
+        &boot.app_context,
+        Some(&"prune".to_string()),
+        &task::Vars::default(),
+    )
+    .await
+    .expect("task should run");
+
+    assert!(medias::Entity::find_by_id(old.id)
+        .one(db)
+        .await
+        .unwrap()
+        .is_none());
+    assert!(!media_dir.join("Video_[old].mkv").exists());
+    assert!(medias::Entity::find_by_id(new.id)
+        .one(db)
+        .await
+        .unwrap()
+        .is_some());
+    assert!(media_dir.join("Video_[new].mkv").exists());
+}
+
+#[tokio::test]
+#[serial]
+async fn prune_keeps_only_the_newest_max_items_medias() {
+    let boot = boot_test_with_create_db::<App>().await.unwrap();
+    let db = &boot.app_context.db;
+    let source = sources::ActiveModel {
+        url: Set("https://example.com/channel".to_string()),
+        fetch_last_days: Set(30),
+        refresh_frequency: Set(24),
+        sponsorblock: Set(String::new()),
+        max_items: Set(Some(2)),
+        ..Default::default()
+    }
+    .insert(db)
+    .await
+    .expect("source should be inserted");
+
+    let dir = TempMediaDir::new();
+    let media_dir = localtube::ytdlp::media_directory().join(&dir.rel_dir);
+    let oldest = create_downloaded_media(
+        db,
+        &source,
+        &dir.rel_path("Video_[oldest].mkv"),
+        "oldest",
+        published(chrono::Duration::days(3)),
+    )
+    .await;
+    let older = create_downloaded_media(
+        db,
+        &source,
+        &dir.rel_path("Video_[older].mkv"),
+        "older",
+        published(chrono::Duration::days(2)),
+    )
+    .await;
+    let newest = create_downloaded_media(
+        db,
+        &source,
+        &dir.rel_path("Video_[newest].mkv"),
+        "newest",
+        published(chrono::Duration::days(1)),
+    )
+    .await;
+
+    run_task::<App>(
+        &boot.app_context,
+        Some(&"prune".to_string()),
+        &task::Vars::default(),
+    )
+    .await
+    .expect("task should run");
+
+    let exists = |id| async move {
+        medias::Entity::find_by_id(id)
+            .one(db)
+            .await
+            .unwrap()
+            .is_some()
+    };
+    assert!(!exists(oldest.id).await, "over the count limit");
+    assert!(!media_dir.join("Video_[oldest].mkv").exists());
+    assert!(exists(older.id).await);
+    assert!(exists(newest.id).await);
+    assert!(media_dir.join("Video_[newest].mkv").exists());
+}
@@ -0,0 +1,71 @@
+use localtube::{
+    app::App,
+    models::_entities::{medias, sources},
+};
+use loco_rs::{boot::run_task, prelude::*, task};
+use serial_test::serial;
+
+#[tokio::test]
+#[serial]
+async fn redownload_media_clears_the_path() {
+    let boot = boot_test_with_create_db::<App>().await.unwrap();
+    let db = &boot.app_context.db;
+    let source = sources::ActiveModel {
+        url: Set("https://example.com/channel".to_string()),
+        fetch_last_days: Set(7),
+        refresh_frequency: Set(24),
+        sponsorblock: Set(String::new()),
+        ..Default::default()
+    }
+    .insert(db)
+    .await
+    .expect("source should be inserted");
+
+    let dir = format!("Redownload {}", uuid::Uuid::new_v4());
+    let media_dir = localtube::ytdlp::media_directory().join(&dir);
+    std::fs::create_dir_all(&media_dir).expect("media folder should be created");
+    std::fs::write(media_dir.join("Video_[abc].mkv"), b"media").expect("media file should exist");
+    let media = medias::ActiveModel {
+        source_id: Set(source.id),
+        url: Set("https://example.com/watch?v=abc".to_string()),
+        media_path: Set(Some(format!("{dir}/Video_[abc].mkv"))),
+        ..Default::default()
+    }
+    .insert(db)
+    .await
+    .expect("media should be inserted");
+
+    let vars = task::Vars::from_cli_args(vec![("media_id".to_string(), media.id.to_string())]);
+    // Without metadata the queued download has nothing to fetch and returns right away.
+    run_task::<App>(
+        &boot.app_context,
+        Some(&"redownload_media".to_string()),
+        &vars,
+    )
+    .await
+    .expect("task should run");
+
+    let media = medias::Entity::find_by_id(media.id)
+        .one(db)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(media.media_path, None);
+    assert!(!media_dir.join("Video_[abc].mkv").exists());
+
+    std::fs::remove_dir_all(&media_dir).expect("test folder should be removed");
+}
+
+#[tokio::test]
+#[serial]
+async fn redownload_media_requires_a_numeric_id() {
+    let boot = boot_test_with_create_db::<App>().await.unwrap();
+    let vars = task::Vars::from_cli_args(vec![("media_id".to_string(), "abc".to_string())]);
+    assert!(run_task::<App>(
+        &boot.app_context,
+        Some(&"redownload_media".to_string()),
+        &vars
+    )
+    .await
+    .is_err());
+}
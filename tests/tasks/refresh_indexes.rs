@@ -1,5 +1,14 @@
-use localtube::app::App;
-use loco_rs::{task, testing::prelude::*};
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use localtube::{
+    app::App,
+    models::_entities::sources,
+    tasks::refresh_indexes::{sweep_concurrency, sweep_sources},
+};
+use loco_rs::{prelude::*, task};
 
 use loco_rs::boot::run_task;
 use serial_test::serial;
@@ -17,3 +26,48 @@ async fn test_can_run_refresh_indexes() {
     .await
     .is_ok());
 }
+
+#[tokio::test]
+#[serial]
+async fn sweep_checks_sources_concurrently_within_the_cap() {
+    let boot = boot_test_with_create_db::<App>().await.unwrap();
+    let db = &boot.app_context.db;
+    let now = chrono::Utc::now();
+    let mut all = Vec::new();
+    for i in 0..24 {
+        let source = sources::ActiveModel {
+            url: Set(format!("https://example.com/channel{i}")),
+            fetch_last_days: Set(7),
+            refresh_frequency: Set(24),
+            sponsorblock: Set(String::new()),
+            // Every third source was just scheduled and is not due.
+            last_scheduled_refresh: Set((i % 3 == 0).then_some(now)),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+        .expect("source should be inserted");
+        all.push(source);
+    }
+
+    let running = AtomicUsize::new(0);
+    let peak = AtomicUsize::new(0);
+    let scheduled = sweep_sources(all, now, false, 4, |_source| async {
+        let now_running = running.fetch_add(1, Ordering::SeqCst) + 1;
+        peak.fetch_max(now_running, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        running.fetch_sub(1, Ordering::SeqCst);
+        Ok(())
+    })
+    .await
+    .expect("sweep should succeed");
+
+    assert_eq!(scheduled, 16);
+    let peak = peak.load(Ordering::SeqCst);
+    assert!(peak <= 4, "at most 4 sources at once, saw {peak}");
+    assert!(peak > 1, "sources are checked concurrently");
+    assert!(
+        sweep_concurrency(1) == 1,
+        "never more than the database pool"
+    );
+}
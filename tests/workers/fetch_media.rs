@@ -0,0 +1,658 @@
+use localtube::{
+    app::App,
+    download_log,
+    job_tracking::{manager::TaskManager, task::TaskType},
+    models::{
+        _entities::{medias, sources},
+        medias::MediaMetadata,
+        settings::GlobalSettings,
+        sources::SourceMetadata,
+    },
+    services::rate_limit::RateLimitBackoff,
+    workers::{
+        errors::AGE_RESTRICTED,
+        fetch_media::{media_downloads, pending_retries, FetchMediaWorker, FetchMediaWorkerArgs},
+    },
+};
+use loco_rs::{bgworker::BackgroundWorker, prelude::*};
+use serial_test::serial;
+use uuid::Uuid;
+
+use crate::helpers::{webhook_receiver, FakeYtDlp};
+
+async fn create_pending_media(ctx: &AppContext, title: &str) -> medias::Model {
+    let source_metadata = SourceMetadata {
+        uploader: "Test Channel".to_string(),
+        items: 1,
+        source_provider: "Youtube".to_string(),
+        list_kind: None,
+        list_count: None,
+        list_order: None,
+        list_tab: None,
+        list_tabs: None,
+        channel_url: None,
+    };
+    let source = sources::ActiveModel {
+        url: Set("https://example.com/channel".to_string()),
+        fetch_last_days: Set(7),
+        refresh_frequency: Set(24),
+        sponsorblock: Set(String::new()),
+        metadata: Set(Some(serde_json::to_value(source_metadata).unwrap())),
+        ..Default::default()
+    }
+    .insert(&ctx.db)
+    .await
+    .expect("source should be inserted");
+
+    let media_metadata = MediaMetadata {
+        title: title.to_string(),
+        description: None,
+        duration: 1,
+        extractor_key: "Youtube".to_string(),
+        original_url: "https://example.com/watch?v=inflight".to_string(),
+        timestamp: 0,
+        age_limit: None,
+    };
+    medias::ActiveModel {
+        source_id: Set(source.id),
+        url: Set(media_metadata.original_url.clone()),
+        video_id: Set(Some("inflight".to_string())),
+        metadata: Set(Some(serde_json::to_value(media_metadata).unwrap())),
+        ..Default::default()
+    }
+    .insert(&ctx.db)
+    .await
+    .expect("media should be inserted")
+}
+
+fn download_tasks_titled(title: &str) -> usize {
+    let tasks = TaskManager::global().tasks.lock().unwrap();
+    tasks.values().filter(|task| task.title == title).count()
+}
+
+#[tokio::test]
+#[serial]
+async fn concurrent_downloads_of_same_media_run_once() {
+    let boot = boot_test::<App>().await.unwrap();
+    let ctx = boot.app_context;
+    let title = format!("In flight {}", Uuid::new_v4());
+    let media = create_pending_media(&ctx, &title).await;
+
+    let first = FetchMediaWorker::build(&ctx);
+    let second = FetchMediaWorker::build(&ctx);
+    let args = FetchMediaWorkerArgs { media_id: media.id };
+    let (_, second_result) =
+        tokio::join!(first.perform(args.clone()), second.perform(args.clone()));
+
+    assert!(second_result.is_ok());
+    assert_eq!(download_tasks_titled(&title), 1);
+    assert!(!media_downloads().contains(&media.id));
+}
+
+#[tokio::test]
+#[serial]
+async fn download_skips_media_claimed_elsewhere() {
+    let boot = boot_test::<App>().await.unwrap();
+    let ctx = boot.app_context;
+    let title = format!("Claimed {}", Uuid::new_v4());
+    let media = create_pending_media(&ctx, &title).await;
+
+    let claim = media_downloads().try_claim(media.id);
+    assert!(claim.is_some());
+
+    let result = FetchMediaWorker::build(&ctx)
+        .perform(FetchMediaWorkerArgs { media_id: media.id })
+        .await;
+
+    assert!(result.is_ok());
+    assert_eq!(download_tasks_titled(&title), 0);
+}
+
+#[tokio::test]
+#[serial]
+async fn failed_download_log_is_served() {
+    request_with_create_db::<App, _, _>(|request, ctx| async move {
+        let title = format!("Failing {}", Uuid::new_v4());
+        let media = create_pending_media(&ctx, &title).await;
+        let _ = std::fs::remove_file(download_log::log_path(media.id));
+
+        let response = request.get(&format!("/medias/{}/log", media.id)).await;
+        response.assert_status_not_found();
+
+        let result = FetchMediaWorker::build(&ctx)
+            .perform(FetchMediaWorkerArgs { media_id: media.id })
+            .await;
+        assert!(result.is_err());
+
+        let response = request.get(&format!("/medias/{}/log", media.id)).await;
+        response.assert_status_ok();
+        let log = response.text();
+        assert!(log.contains("url: https://example.com/watch?v=inflight"));
+        let command = log
+            .lines()
+            .find_map(|line| line.strip_prefix("command: "))
+            .expect("log should start with the command line");
+        assert!(command.starts_with("libs/yt-dlp "), "{command}");
+        for flag in ["--dump-json", "--no-simulate", "--remux-video=mkv"] {
+            assert!(command.contains(flag), "{command}");
+        }
+        assert!(command.contains("'https://example.com/watch?v=inflight'"));
+        assert!(!command.contains("--cookies"), "{command}");
+        assert!(log.contains("--- stderr ---"));
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn failed_download_is_counted_as_retry() {
+    let boot = boot_test::<App>().await.unwrap();
+    let ctx = boot.app_context;
+    let media = create_pending_media(&ctx, &format!("Retried {}", Uuid::new_v4())).await;
+    let counts = || {
+        let metrics = TaskManager::global().get_metrics();
+        let download = &metrics.tasks[&TaskType::DownloadVideo];
+        (download.retry_count, download.permanent_failure_count)
+    };
+    let (retries_before, permanent_before) = counts();
+
+    let result = FetchMediaWorker::build(&ctx)
+        .perform(FetchMediaWorkerArgs { media_id: media.id })
+        .await;
+
+    assert!(result.is_err());
+    assert_eq!(counts(), (retries_before + 1, permanent_before));
+}
+
+#[tokio::test]
+#[serial]
+async fn trashed_media_is_not_downloaded_again() {
+    let boot = boot_test::<App>().await.unwrap();
+    let ctx = boot.app_context;
+    let title = format!("Trashed {}", Uuid::new_v4());
+    let media = create_pending_media(&ctx, &title).await;
+    let mut item = media.into_active_model();
+    item.trashed_at = Set(Some(chrono::Utc::now()));
+    let media = item.update(&ctx.db).await.unwrap();
+    let retries =
+        || TaskManager::global().get_metrics().tasks[&TaskType::DownloadVideo].retry_count;
+    let retries_before = retries();
+    let _ = std::fs::remove_file(download_log::log_path(media.id));
+
+    let result = FetchMediaWorker::build(&ctx)
+        .perform(FetchMediaWorkerArgs { media_id: media.id })
+        .await;
+
+    assert!(result.is_ok());
+    assert_eq!(retries(), retries_before, "nothing is queued again");
+    assert!(
+        !download_log::log_path(media.id).exists(),
+        "yt-dlp never ran"
+    );
+}
+
+#[tokio::test]
+#[serial]
+async fn upcoming_media_is_deferred_not_failed() {
+    let boot = boot_test::<App>().await.unwrap();
+    let ctx = boot.app_context;
+    let title = format!("Premiere {}", Uuid::new_v4());
+    let media = create_pending_media(&ctx, &title).await;
+    let mut item = media.into_active_model();
+    item.available_at = Set(Some(chrono::Utc::now() + chrono::Duration::hours(2)));
+    let media = item.update(&ctx.db).await.unwrap();
+    let failures = || {
+        TaskManager::global()
+            .get_metrics()
+            .tasks
+            .values()
+            .map(|task| task.failure_count)
+            .sum::<u64>()
+    };
+    let failures_before = failures();
+    let retries =
+        || TaskManager::global().get_metrics().tasks[&TaskType::DownloadVideo].retry_count;
+    let retries_before = retries();
+
+    let result = FetchMediaWorker::build(&ctx)
+        .perform(FetchMediaWorkerArgs { media_id: media.id })
+        .await;
+
+    assert!(result.is_ok());
+    assert_eq!(download_tasks_titled(&title), 0);
+    assert_eq!(failures(), failures_before);
+    assert_eq!(retries(), retries_before, "postponing is not a retry");
+    assert!(pending_retries().contains(&media.id));
+
+    // Refreshes queue it again until it premieres, that must not stack retries.
+    FetchMediaWorker::build(&ctx)
+        .perform(FetchMediaWorkerArgs { media_id: media.id })
+        .await
+        .expect("a deferred media is not a failure");
+    assert_eq!(retries(), retries_before, "the pending retry covers it");
+
+    let media = medias::Entity::find_by_id(media.id)
+        .one(&ctx.db)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(media.media_path, None);
+}
+
+async fn publish_with_min_age(ctx: &AppContext, media: medias::Model, age: chrono::Duration) {
+    let mut source = sources::Entity::find_by_id(media.source_id)
+        .one(&ctx.db)
+        .await
+        .unwrap()
+        .unwrap()
+        .into_active_model();
+    source.min_age_hours = Set(Some(2));
+    source.update(&ctx.db).await.unwrap();
+
+    let mut metadata = media.get_metadata().unwrap();
+    metadata.timestamp = (chrono::Utc::now() - age).timestamp();
+    let mut item = media.into_active_model();
+    item.metadata = Set(Some(serde_json::to_value(metadata).unwrap()));
+    item.update(&ctx.db).await.unwrap();
+}
+
+#[tokio::test]
+#[serial]
+async fn too_fresh_media_waits_for_the_source_minimum_age() {
+    let boot = boot_test::<App>().await.unwrap();
+    let ctx = boot.app_context;
+
+    let title = format!("Fresh {}", Uuid::new_v4());
+    let fresh = create_pending_media(&ctx, &title).await;
+    let fresh_id = fresh.id;
+    publish_with_min_age(&ctx, fresh, chrono::Duration::minutes(30)).await;
+    let retries =
+        || TaskManager::global().get_metrics().tasks[&TaskType::DownloadVideo].retry_count;
+    let retries_before = retries();
+    let result = FetchMediaWorker::build(&ctx)
+        .perform(FetchMediaWorkerArgs { media_id: fresh_id })
+        .await;
+    assert!(result.is_ok());
+    assert_eq!(download_tasks_titled(&title), 0);
+    assert!(pending_retries().contains(&fresh_id));
+
+    // Queued again by every refresh while it waits, the first timer covers it.
+    for _ in 0..2 {
+        FetchMediaWorker::build(&ctx)
+            .perform(FetchMediaWorkerArgs { media_id: fresh_id })
+            .await
+            .expect("a waiting media is not a failure");
+    }
+    assert_eq!(retries(), retries_before, "waiting is not a retry");
+
+    // Old enough media goes on to the download, which fails with the stub yt-dlp.
+    let title = format!("Aged {}", Uuid::new_v4());
+    let aged = create_pending_media(&ctx, &title).await;
+    let aged_id = aged.id;
+    publish_with_min_age(&ctx, aged, chrono::Duration::hours(3)).await;
+    let result = FetchMediaWorker::build(&ctx)
+        .perform(FetchMediaWorkerArgs { media_id: aged_id })
+        .await;
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+#[serial]
+async fn rate_limited_source_keeps_one_retry_per_media() {
+    let boot = boot_test::<App>().await.unwrap();
+    let ctx = boot.app_context;
+    let title = format!("Rate limited {}", Uuid::new_v4());
+    let media = create_pending_media(&ctx, &title).await;
+    RateLimitBackoff::global().record(media.source_id, std::time::Instant::now());
+    let retries =
+        || TaskManager::global().get_metrics().tasks[&TaskType::DownloadVideo].retry_count;
+    let retries_before = retries();
+
+    // Every refresh during the pause queues the media again.
+    for _ in 0..3 {
+        FetchMediaWorker::build(&ctx)
+            .perform(FetchMediaWorkerArgs { media_id: media.id })
+            .await
+            .expect("a paused source is not a failure");
+    }
+    RateLimitBackoff::global().clear(media.source_id);
+
+    assert_eq!(download_tasks_titled(&title), 0);
+    assert_eq!(
+        retries(),
+        retries_before,
+        "a paused download is not a retry"
+    );
+    assert!(pending_retries().contains(&media.id));
+}
+
+#[tokio::test]
+#[serial]
+async fn source_with_own_media_root_downloads_under_it() {
+    let boot = boot_test::<App>().await.unwrap();
+    let ctx = boot.app_context;
+    let root = std::env::temp_dir().join(format!("localtube-root-{}", Uuid::new_v4()));
+    let title = format!("Own root {}", Uuid::new_v4());
+    let media = create_pending_media(&ctx, &title).await;
+    let source = sources::Entity::find_by_id(media.source_id)
+        .one(&ctx.db)
+        .await
+        .unwrap()
+        .unwrap();
+    let mut item = source.clone().into_active_model();
+    item.media_root = Set(Some(root.to_string_lossy().to_string()));
+    item.download_archive = Set(true);
+    item.update(&ctx.db).await.unwrap();
+
+    // An archived video with its file present is adopted without running yt-dlp.
+    let dir_name = localtube::ytdlp::sanitize_dir_name("Test Channel", source.id);
+    let source_dir = root.join(&dir_name);
+    std::fs::create_dir_all(&source_dir).unwrap();
+    std::fs::write(
+        localtube::ytdlp::download_archive_path(&source_dir),
+        "youtube inflight\n",
+    )
+    .unwrap();
+    std::fs::write(source_dir.join("Title_[inflight].mkv"), b"media").unwrap();
+
+    FetchMediaWorker::build(&ctx)
+        .perform(FetchMediaWorkerArgs { media_id: media.id })
+        .await
+        .expect("archived download should be adopted");
+
+    let media = medias::Entity::find_by_id(media.id)
+        .one(&ctx.db)
+        .await
+        .unwrap()
+        .unwrap();
+    let media_path = media.media_path.expect("media should be downloaded");
+    assert_eq!(media_path, format!("{dir_name}/Title_[inflight].mkv"));
+    assert!(root.join(&media_path).is_file());
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[tokio::test]
+#[serial]
+async fn download_is_reported_to_the_source_webhook() {
+    let boot = boot_test::<App>().await.unwrap();
+    let ctx = boot.app_context;
+    let (url, received) = webhook_receiver().await;
+    let root = std::env::temp_dir().join(format!("localtube-root-{}", Uuid::new_v4()));
+    let title = format!("Webhook {}", Uuid::new_v4());
+    let media = create_pending_media(&ctx, &title).await;
+    let source = sources::Entity::find_by_id(media.source_id)
+        .one(&ctx.db)
+        .await
+        .unwrap()
+        .unwrap();
+    let mut item = source.clone().into_active_model();
+    item.media_root = Set(Some(root.to_string_lossy().to_string()));
+    item.download_archive = Set(true);
+    item.webhook_url = Set(Some(url));
+    item.update(&ctx.db).await.unwrap();
+
+    let source_dir = root.join(localtube::ytdlp::sanitize_dir_name(
+        "Test Channel",
+        source.id,
+    ));
+    std::fs::create_dir_all(&source_dir).unwrap();
+    std::fs::write(
+        localtube::ytdlp::download_archive_path(&source_dir),
+        "youtube inflight\n",
+    )
+    .unwrap();
+    std::fs::write(source_dir.join("Title_[inflight].mkv"), b"media").unwrap();
+
+    FetchMediaWorker::build(&ctx)
+        .perform(FetchMediaWorkerArgs { media_id: media.id })
+        .await
+        .expect("archived download should be adopted");
+    std::fs::remove_dir_all(&root).unwrap();
+
+    let received = received.lock().unwrap();
+    assert_eq!(received.len(), 1, "the source webhook gets the event");
+    assert_eq!(received[0]["event"], "media_downloaded");
+    assert_eq!(received[0]["media_id"], media.id);
+    assert_eq!(received[0]["title"], title.as_str());
+}
+
+#[tokio::test]
+#[serial]
+async fn video_downloaded_by_another_source_is_linked() {
+    let boot = boot_test::<App>().await.unwrap();
+    let ctx = boot.app_context;
+    let root = std::env::temp_dir().join(format!("localtube-dedupe-{}", Uuid::new_v4()));
+    let video_id = Uuid::new_v4().to_string();
+    let mut medias = Vec::new();
+    for title in ["Channel copy", "Playlist copy"] {
+        let media = create_pending_media(&ctx, title).await;
+        let mut item = media.into_active_model();
+        item.video_id = Set(Some(video_id.clone()));
+        medias.push(item.update(&ctx.db).await.unwrap());
+        let source = sources::Entity::find_by_id(medias.last().unwrap().source_id)
+            .one(&ctx.db)
+            .await
+            .unwrap()
+            .unwrap();
+        let mut source = source.into_active_model();
+        source.media_root = Set(Some(root.to_string_lossy().to_string()));
+        source.update(&ctx.db).await.unwrap();
+    }
+    let (existing, duplicate) = (&medias[0], &medias[1]);
+    let existing_path = format!(
+        "{}/Title_[{video_id}].mkv",
+        localtube::ytdlp::sanitize_dir_name("Test Channel", existing.source_id)
+    );
+    std::fs::create_dir_all(root.join(&existing_path).parent().unwrap()).unwrap();
+    std::fs::write(root.join(&existing_path), b"media").unwrap();
+    let mut item = existing.clone().into_active_model();
+    item.media_path = Set(Some(existing_path));
+    item.update(&ctx.db).await.unwrap();
+
+    GlobalSettings {
+        dedupe: Some("link".to_string()),
+        ..Default::default()
+    }
+    .apply();
+    let result = FetchMediaWorker::build(&ctx)
+        .perform(FetchMediaWorkerArgs {
+            media_id: duplicate.id,
+        })
+        .await;
+    GlobalSettings::default().apply();
+    result.expect("duplicate should be linked without downloading");
+
+    let linked = medias::Entity::find_by_id(duplicate.id)
+        .one(&ctx.db)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(linked.duplicate_of, Some(existing.id));
+    let media_path = linked
+        .media_path
+        .expect("duplicate should reference the file");
+    assert_eq!(
+        media_path,
+        format!(
+            "{}/Title_[{video_id}].mkv",
+            localtube::ytdlp::sanitize_dir_name("Test Channel", duplicate.source_id)
+        )
+    );
+    assert_eq!(std::fs::read(root.join(&media_path)).unwrap(), b"media");
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[tokio::test]
+#[serial]
+async fn video_longer_than_the_source_maximum_is_skipped() {
+    let boot = boot_test::<App>().await.unwrap();
+    let ctx = boot.app_context;
+    let title = format!("Livestream {}", Uuid::new_v4());
+    let media = create_pending_media(&ctx, &title).await;
+    let mut metadata = media.get_metadata().unwrap();
+    metadata.duration = 8 * 3600;
+    let mut item = media.clone().into_active_model();
+    item.metadata = Set(Some(serde_json::to_value(metadata).unwrap()));
+    item.update(&ctx.db).await.unwrap();
+    sources::ActiveModel {
+        id: Set(media.source_id),
+        max_duration_minutes: Set(Some(120)),
+        ..Default::default()
+    }
+    .update(&ctx.db)
+    .await
+    .unwrap();
+
+    FetchMediaWorker::build(&ctx)
+        .perform(FetchMediaWorkerArgs { media_id: media.id })
+        .await
+        .expect("an overlong video is skipped, not failed");
+
+    let skipped = medias::Entity::find_by_id(media.id)
+        .one(&ctx.db)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(skipped.media_path, None);
+    assert_eq!(
+        skipped.skip_reason.as_deref(),
+        Some("Longer than the source maximum of 120 minutes (480 minutes)")
+    );
+    assert_eq!(download_tasks_titled(&title), 0, "nothing was downloaded");
+}
+
+#[tokio::test]
+#[serial]
+async fn video_rated_above_the_source_age_limit_is_skipped() {
+    let boot = boot_test::<App>().await.unwrap();
+    let ctx = boot.app_context;
+    let title = format!("Mature {}", Uuid::new_v4());
+    let media = create_pending_media(&ctx, &title).await;
+    let mut metadata = media.get_metadata().unwrap();
+    metadata.age_limit = Some(18);
+    let mut item = media.clone().into_active_model();
+    item.metadata = Set(Some(serde_json::to_value(metadata).unwrap()));
+    item.update(&ctx.db).await.unwrap();
+    sources::ActiveModel {
+        id: Set(media.source_id),
+        age_limit: Set(Some(13)),
+        ..Default::default()
+    }
+    .update(&ctx.db)
+    .await
+    .unwrap();
+
+    FetchMediaWorker::build(&ctx)
+        .perform(FetchMediaWorkerArgs { media_id: media.id })
+        .await
+        .expect("an age-restricted video is skipped, not failed");
+
+    let skipped = medias::Entity::find_by_id(media.id)
+        .one(&ctx.db)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(skipped.media_path, None);
+    assert_eq!(
+        skipped.skip_reason.as_deref(),
+        Some("Age-restricted: rated 18+, above the source age limit of 13")
+    );
+    assert_eq!(download_tasks_titled(&title), 0, "nothing was downloaded");
+}
+
+#[tokio::test]
+#[serial]
+async fn silent_age_limit_skip_is_reported_as_age_restricted() {
+    let boot = boot_test::<App>().await.unwrap();
+    let ctx = boot.app_context;
+    let media = create_pending_media(&ctx, &format!("Unrated {}", Uuid::new_v4())).await;
+    sources::ActiveModel {
+        id: Set(media.source_id),
+        age_limit: Set(Some(13)),
+        ..Default::default()
+    }
+    .update(&ctx.db)
+    .await
+    .unwrap();
+    // With --dump-json yt-dlp skips a video over --age-limit without a word.
+    let _yt_dlp = FakeYtDlp::install(|_| "exit 0".to_string());
+
+    FetchMediaWorker::build(&ctx)
+        .perform(FetchMediaWorkerArgs { media_id: media.id })
+        .await
+        .expect("an age-restricted video is skipped, not failed");
+
+    let skipped = medias::Entity::find_by_id(media.id)
+        .one(&ctx.db)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(skipped.media_path, None);
+    assert!(
+        skipped
+            .skip_reason
+            .as_deref()
+            .is_some_and(|reason| reason.starts_with(AGE_RESTRICTED)),
+        "{:?}",
+        skipped.skip_reason
+    );
+}
+
+#[tokio::test]
+#[serial]
+async fn skipped_media_is_not_retried() {
+    let boot = boot_test::<App>().await.unwrap();
+    let ctx = boot.app_context;
+    let title = format!("Oversized {}", Uuid::new_v4());
+    let media = create_pending_media(&ctx, &title).await;
+    let reason =
+        "File is larger than max-filesize (2048000000 bytes > 1048576000 bytes). Aborting.";
+    let mut item = media.clone().into_active_model();
+    item.skip_reason = Set(Some(reason.to_string()));
+    item.update(&ctx.db).await.unwrap();
+
+    FetchMediaWorker::build(&ctx)
+        .perform(FetchMediaWorkerArgs { media_id: media.id })
+        .await
+        .expect("a skipped media is left alone");
+
+    let skipped = medias::Entity::find_by_id(media.id)
+        .one(&ctx.db)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(skipped.media_path, None);
+    assert_eq!(skipped.skip_reason.as_deref(), Some(reason));
+    assert_eq!(download_tasks_titled(&title), 0, "nothing was downloaded");
+}
+
+#[tokio::test]
+#[serial]
+async fn media_of_paused_source_stays_indexed_without_download() {
+    let boot = boot_test::<App>().await.unwrap();
+    let ctx = boot.app_context;
+    let title = format!("Paused {}", Uuid::new_v4());
+    let media = create_pending_media(&ctx, &title).await;
+    sources::ActiveModel {
+        id: Set(media.source_id),
+        download_paused: Set(true),
+        ..Default::default()
+    }
+    .update(&ctx.db)
+    .await
+    .unwrap();
+
+    FetchMediaWorker::build(&ctx)
+        .perform(FetchMediaWorkerArgs { media_id: media.id })
+        .await
+        .expect("a paused download is not a failure");
+
+    let indexed = medias::Entity::find_by_id(media.id)
+        .one(&ctx.db)
+        .await
+        .unwrap()
+        .expect("the media stays indexed");
+    assert_eq!(indexed.media_path, None);
+    assert_eq!(indexed.skip_reason, None, "resuming downloads it");
+    assert_eq!(download_tasks_titled(&title), 0, "nothing was downloaded");
+}
@@ -0,0 +1,283 @@
+use std::path::Path;
+
+use localtube::{
+    app::App,
+    models::{
+        _entities::{medias, sources},
+        sources::SourceMetadata,
+    },
+    workers::fetch_source_info::{FetchSourceInfoWorker, FetchSourceInfoWorkerArgs},
+    ytdlp::SourceListOrder,
+};
+use loco_rs::{bgworker::BackgroundWorker, prelude::*};
+use serial_test::serial;
+
+use crate::helpers::{create_downloaded_media, FakeYtDlp};
+
+#[test]
+fn jobs_queued_without_full_reindex_stay_incremental() {
+    let args: FetchSourceInfoWorkerArgs =
+        serde_json::from_value(serde_json::json!({ "source_id": 3 })).unwrap();
+    assert_eq!(args.source_id, 3);
+    assert!(!args.full_reindex);
+}
+
+/// Stand-in for yt-dlp that records its arguments and lists fixed videos
+///
+/// A listing with `--dateafter` only gets the recent videos, like yt-dlp
+/// rejecting everything published before the date. One with
+/// `--break-match-filters` stops after them with exit code 101, like yt-dlp
+/// breaking at the first older video.
+struct MockYtDlp {
+    yt_dlp: FakeYtDlp,
+}
+
+impl MockYtDlp {
+    fn install(recent: &[serde_json::Value], older: &[serde_json::Value]) -> Self {
+        let yt_dlp = FakeYtDlp::install(|dir| {
+            let dir = dir.display();
+            format!(
+                "echo \"$*\" >> '{dir}/args.log'\n\
+                 case \"$*\" in\n\
+                 *--dump-single-json*) cat '{dir}/probe.json' ;;\n\
+                 *--dateafter*) cat '{dir}/recent.jsonl' ;;\n\
+                 *--break-match-filters*) cat '{dir}/recent.jsonl'; exit 101 ;;\n\
+                 *) cat '{dir}/recent.jsonl' '{dir}/older.jsonl' ;;\n\
+                 esac"
+            )
+        });
+        let dir = &yt_dlp.dir;
+        let probe = serde_json::json!({
+            "_type": "playlist",
+            "id": "UCmock",
+            "channel_id": "UCmock",
+            "uploader": "Mock Channel",
+            "extractor_key": "Youtube",
+            // A channel too large to be scanned fully.
+            "playlist_count": 10_000,
+            "entries": [],
+        });
+        let lines = |videos: &[serde_json::Value]| {
+            videos
+                .iter()
+                .map(|video| format!("{video}\n"))
+                .collect::<String>()
+        };
+        std::fs::write(dir.join("probe.json"), probe.to_string()).unwrap();
+        std::fs::write(dir.join("recent.jsonl"), lines(recent)).unwrap();
+        std::fs::write(dir.join("older.jsonl"), lines(older)).unwrap();
+        Self { yt_dlp }
+    }
+
+    /// Folder the mock and its data live in
+    fn dir(&self) -> &Path {
+        &self.yt_dlp.dir
+    }
+
+    /// Arguments of the video listings run so far
+    fn listings(&self) -> Vec<String> {
+        std::fs::read_to_string(self.dir().join("args.log"))
+            .unwrap_or_default()
+            .lines()
+            .filter(|line| line.contains("--dump-json"))
+            .map(ToString::to_string)
+            .collect()
+    }
+}
+
+fn video(id: &str, title: &str, timestamp: i64) -> serde_json::Value {
+    serde_json::json!({
+        "id": id,
+        "title": title,
+        "duration": 60,
+        "uploader": "Mock Channel",
+        "extractor_key": "Youtube",
+        "original_url": format!("https://example.com/watch?v={id}"),
+        "timestamp": timestamp,
+        "filename": format!("{id}.mkv"),
+    })
+}
+
+/// Inserts a source indexed up to `checkpoint` with its files under `media_root`
+async fn create_indexed_source(
+    ctx: &AppContext,
+    media_root: &Path,
+    checkpoint: chrono::DateTime<chrono::Utc>,
+    list_order: Option<SourceListOrder>,
+) -> sources::Model {
+    let metadata = SourceMetadata {
+        uploader: "Mock Channel".to_string(),
+        items: 2,
+        source_provider: "Youtube".to_string(),
+        list_kind: None,
+        list_count: None,
+        list_order,
+        list_tab: None,
+        list_tabs: None,
+        channel_url: None,
+    };
+    sources::ActiveModel {
+        url: Set("https://example.com/@mock".to_string()),
+        fetch_last_days: Set(30),
+        refresh_frequency: Set(24),
+        sponsorblock: Set(String::new()),
+        media_root: Set(Some(media_root.display().to_string())),
+        metadata: Set(Some(serde_json::to_value(metadata).unwrap())),
+        index_checkpoint: Set(Some(checkpoint)),
+        ..Default::default()
+    }
+    .insert(&ctx.db)
+    .await
+    .expect("source should be inserted")
+}
+
+async fn refresh(ctx: &AppContext, source_id: i32, full_reindex: bool) {
+    FetchSourceInfoWorker::build(ctx)
+        .perform(FetchSourceInfoWorkerArgs {
+            source_id,
+            full_reindex,
+        })
+        .await
+        .expect("refresh should succeed");
+}
+
+#[tokio::test]
+#[serial]
+async fn incremental_refresh_lists_from_the_checkpoint() {
+    let boot = boot_test::<App>().await.unwrap();
+    let ctx = boot.app_context;
+    let now = chrono::Utc::now();
+    let checkpoint = now - chrono::Duration::days(2);
+    let recent = (now - chrono::Duration::days(1)).timestamp();
+    let older = (now - chrono::Duration::days(5)).timestamp();
+    let mock = MockYtDlp::install(
+        &[video("recent", "Recent", recent)],
+        &[video("older", "Older", older)],
+    );
+    let media_root = mock.dir().join("media");
+    let source = create_indexed_source(&ctx, &media_root, checkpoint, None).await;
+    create_downloaded_media(
+        &ctx.db,
+        &source,
+        "Mock Channel/recent.mkv",
+        "recent",
+        recent,
+    )
+    .await;
+    create_downloaded_media(&ctx.db, &source, "Mock Channel/older.mkv", "older", older).await;
+
+    refresh(&ctx, source.id, false).await;
+    let listings = mock.listings();
+    assert_eq!(listings.len(), 1);
+    let date = checkpoint.format("%Y%m%d").to_string();
+    assert!(
+        listings[0].contains(&format!("--dateafter {date}")),
+        "{}",
+        listings[0]
+    );
+
+    refresh(&ctx, source.id, true).await;
+    let listings = mock.listings();
+    assert_eq!(listings.len(), 2);
+    assert!(!listings[1].contains("--dateafter"), "{}", listings[1]);
+}
+
+#[tokio::test]
+#[serial]
+async fn full_reindex_reprocesses_media_older_than_the_checkpoint() {
+    let boot = boot_test::<App>().await.unwrap();
+    let ctx = boot.app_context;
+    let now = chrono::Utc::now();
+    let recent = (now - chrono::Duration::days(1)).timestamp();
+    let older = (now - chrono::Duration::days(5)).timestamp();
+    let mock = MockYtDlp::install(
+        &[video("recent", "Recent", recent)],
+        &[video("older", "Renamed", older)],
+    );
+    let media_root = mock.dir().join("media");
+    let source =
+        create_indexed_source(&ctx, &media_root, now - chrono::Duration::days(2), None).await;
+    create_downloaded_media(
+        &ctx.db,
+        &source,
+        "Mock Channel/recent.mkv",
+        "recent",
+        recent,
+    )
+    .await;
+    let media =
+        create_downloaded_media(&ctx.db, &source, "Mock Channel/older.mkv", "older", older).await;
+    let title = |media: medias::Model| media.get_metadata().map(|metadata| metadata.title);
+
+    refresh(&ctx, source.id, false).await;
+    let unchanged = medias::Entity::find_by_id(media.id)
+        .one(&ctx.db)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(title(unchanged), Some("older".to_string()));
+
+    refresh(&ctx, source.id, true).await;
+    let reprocessed = medias::Entity::find_by_id(media.id)
+        .one(&ctx.db)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(title(reprocessed), Some("Renamed".to_string()));
+}
+
+#[tokio::test]
+#[serial]
+async fn newest_first_refresh_breaks_at_the_checkpoint() {
+    let boot = boot_test::<App>().await.unwrap();
+    let ctx = boot.app_context;
+    let now = chrono::Utc::now();
+    let checkpoint = now - chrono::Duration::days(2);
+    let recent = (now - chrono::Duration::days(1)).timestamp();
+    let older = (now - chrono::Duration::days(5)).timestamp();
+    let mock = MockYtDlp::install(
+        &[video("recent", "Recent", recent)],
+        &[video("older", "Older", older)],
+    );
+    let media_root = mock.dir().join("media");
+    let source = create_indexed_source(
+        &ctx,
+        &media_root,
+        checkpoint,
+        Some(SourceListOrder::NewestFirst),
+    )
+    .await;
+    create_downloaded_media(
+        &ctx.db,
+        &source,
+        "Mock Channel/recent.mkv",
+        "recent",
+        recent,
+    )
+    .await;
+
+    // yt-dlp exits with 101 after the checkpoint item, that is the end of the list.
+    refresh(&ctx, source.id, false).await;
+    let listings = mock.listings();
+    assert_eq!(listings.len(), 1);
+    let date = checkpoint.format("%Y%m%d").to_string();
+    assert!(
+        listings[0].contains(&format!("--break-match-filters upload_date>=?{date}")),
+        "{}",
+        listings[0]
+    );
+    assert!(!listings[0].contains("--dateafter"), "{}", listings[0]);
+    drop(mock);
+
+    // Nothing published since the checkpoint is not a failure either.
+    let mock = MockYtDlp::install(&[], &[video("older", "Older", older)]);
+    let source = create_indexed_source(
+        &ctx,
+        &mock.dir().join("media"),
+        checkpoint,
+        Some(SourceListOrder::NewestFirst),
+    )
+    .await;
+    refresh(&ctx, source.id, false).await;
+    assert_eq!(mock.listings().len(), 1);
+}
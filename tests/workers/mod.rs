@@ -1 +1,3 @@
 // workers mod
+mod fetch_media;
+mod fetch_source_info;
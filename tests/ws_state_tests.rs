@@ -1,6 +1,10 @@
-use localtube::job_tracking::{
-    manager::TaskManager,
-    task::{TaskState, TaskType},
+use futures_util::StreamExt;
+use localtube::{
+    job_tracking::{
+        manager::TaskManager,
+        task::{TaskState, TaskType},
+    },
+    ws::task_updates,
 };
 use std::sync::Arc;
 use std::time::Duration;
@@ -23,14 +27,15 @@ async fn test_queued_to_active_transition() {
     let queued = manager.add_task(TaskType::DownloadVideo, "Test Task".into());
 
     // Task should start in Queued state
-    let tasks = manager.tasks.lock().unwrap();
-    let task_status = tasks.get(queued.id()).unwrap();
-    assert!(
-        matches!(task_status.state, TaskState::Queued),
-        "Expected Queued state, got {:?}",
-        task_status.state
-    );
-    drop(tasks);
+    {
+        let tasks = manager.tasks.lock().unwrap();
+        let task_status = tasks.get(queued.id()).unwrap();
+        assert!(
+            matches!(task_status.state, TaskState::Queued),
+            "Expected Queued state, got {:?}",
+            task_status.state
+        );
+    }
 
     // Transition to active by acquiring permit
     let active = queued.start(sem.clone()).await;
@@ -66,14 +71,20 @@ async fn test_tasks_queue_when_semaphore_full() {
     tokio::time::sleep(Duration::from_millis(50)).await;
 
     // Should still be Queued (blocked on semaphore)
-    let tasks = manager.tasks.lock().unwrap();
-    let task_status = tasks.get(&id).unwrap();
-    assert!(
-        matches!(task_status.state, TaskState::Queued),
-        "Expected Queued state while waiting, got {:?}",
-        task_status.state
-    );
-    drop(tasks);
+    {
+        let tasks = manager.tasks.lock().unwrap();
+        let task_status = tasks.get(&id).unwrap();
+        assert!(
+            matches!(task_status.state, TaskState::Queued),
+            "Expected Queued state while waiting, got {:?}",
+            task_status.state
+        );
+        assert_eq!(
+            task_status.status.as_deref(),
+            Some(TaskType::DownloadVideo.slot_wait_status()),
+            "Waiting on the semaphore should be visible in the status"
+        );
+    }
 
     // Release one permit
     drop(_p1);
@@ -87,6 +98,10 @@ async fn test_tasks_queue_when_semaphore_full() {
         "Expected InProgress after permit available, got {:?}",
         task_status.state
     );
+    assert_eq!(
+        task_status.status, None,
+        "The waiting status should be cleared once started"
+    );
 }
 
 #[tokio::test]
@@ -184,13 +199,14 @@ async fn test_concurrent_task_limits() {
     let _a2 = q2.start(sem.clone()).await;
 
     // Both should be InProgress
-    let tasks = manager.tasks.lock().unwrap();
-    let in_progress_count = tasks
-        .values()
-        .filter(|t| matches!(t.state, TaskState::InProgress))
-        .count();
-    assert_eq!(in_progress_count, 2, "Expected 2 InProgress tasks");
-    drop(tasks);
+    {
+        let tasks = manager.tasks.lock().unwrap();
+        let in_progress_count = tasks
+            .values()
+            .filter(|t| matches!(t.state, TaskState::InProgress))
+            .count();
+        assert_eq!(in_progress_count, 2, "Expected 2 InProgress tasks");
+    }
 
     // Try to start a third - should block
     let q3 = manager.add_task(TaskType::DownloadVideo, "Concurrent 3".into());
@@ -202,18 +218,19 @@ async fn test_concurrent_task_limits() {
     tokio::time::sleep(Duration::from_millis(50)).await;
 
     // Third should still be Queued
-    let tasks = manager.tasks.lock().unwrap();
-    let task3_status = tasks.get(&id3).unwrap();
-    assert!(
-        matches!(task3_status.state, TaskState::Queued),
-        "Third task should be Queued"
-    );
-    let in_progress_count = tasks
-        .values()
-        .filter(|t| matches!(t.state, TaskState::InProgress))
-        .count();
-    assert_eq!(in_progress_count, 2, "Should never exceed 2 InProgress");
-    drop(tasks);
+    {
+        let tasks = manager.tasks.lock().unwrap();
+        let task3_status = tasks.get(&id3).unwrap();
+        assert!(
+            matches!(task3_status.state, TaskState::Queued),
+            "Third task should be Queued"
+        );
+        let in_progress_count = tasks
+            .values()
+            .filter(|t| matches!(t.state, TaskState::InProgress))
+            .count();
+        assert_eq!(in_progress_count, 2, "Should never exceed 2 InProgress");
+    }
 
     // Complete one task to free a permit
     a1.complete();
@@ -324,3 +341,360 @@ async fn test_cleanup_dropped_in_progress_task() {
         "Dropped in-progress tasks should be reclaimed"
     );
 }
+
+#[tokio::test]
+async fn test_weighted_task_holds_multiple_permits() {
+    let manager = test_manager();
+    let sem = test_semaphore();
+
+    let heavy = manager.add_task(TaskType::DownloadVideo, "Heavy Task".into());
+    let heavy = heavy.start_weighted(sem.clone(), 2).await;
+    assert_eq!(sem.available_permits(), 0);
+
+    let light = manager.add_task(TaskType::DownloadVideo, "Light Task".into());
+    let light_id = light.id().to_string();
+    let sem_for_task = sem.clone();
+    let handle = tokio::spawn(async move { light.start(sem_for_task).await });
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    {
+        let tasks = manager.tasks.lock().unwrap();
+        assert!(
+            matches!(tasks.get(&light_id).unwrap().state, TaskState::Queued),
+            "Light task should wait while the heavy task holds both permits"
+        );
+    }
+
+    heavy.complete();
+    let light = tokio::time::timeout(Duration::from_secs(1), handle)
+        .await
+        .expect("light task should start once permits are released")
+        .unwrap();
+    assert_eq!(sem.available_permits(), 1);
+    light.complete();
+    assert_eq!(sem.available_permits(), 2);
+}
+
+#[tokio::test]
+async fn test_task_updates_snapshot_then_every_change() {
+    let manager = test_manager();
+    let _existing = manager.add_task(TaskType::DownloadVideo, "Existing".into());
+
+    let mut updates = Box::pin(task_updates(&manager));
+    let snapshot = updates.next().await.expect("snapshot should be sent");
+    assert_eq!(snapshot.tasks.len(), 1);
+
+    // Only the snapshot goes out on connect, no duplicate broadcast follows it.
+    assert!(
+        tokio::time::timeout(Duration::from_millis(50), updates.next())
+            .await
+            .is_err(),
+        "No update should follow the snapshot until a task changes"
+    );
+
+    let added = manager.add_task(TaskType::RefreshIndex, "Added".into());
+    let update = tokio::time::timeout(Duration::from_secs(1), updates.next())
+        .await
+        .expect("update should arrive")
+        .expect("stream should stay open");
+    assert!(update.tasks.iter().any(|task| task.id == added.id()));
+
+    added.update_title("Renamed".into());
+    let update = tokio::time::timeout(Duration::from_secs(1), updates.next())
+        .await
+        .expect("update should arrive")
+        .expect("stream should stay open");
+    assert!(update.tasks.iter().any(|task| task.title == "Renamed"));
+}
+
+#[tokio::test]
+async fn test_update_burst_does_not_lag_subscriber() {
+    let manager = test_manager();
+    let mut rx = manager.tx.subscribe();
+
+    // Well beyond the channel capacity if every mutation was broadcast.
+    let tasks: Vec<_> = (0..300)
+        .map(|n| manager.add_task(TaskType::DownloadVideo, format!("Burst {n}")))
+        .collect();
+    for task in &tasks {
+        task.update_title(format!("{} renamed", task.id()));
+    }
+
+    let mut received = 0;
+    let last = loop {
+        let update = tokio::time::timeout(Duration::from_secs(1), rx.recv())
+            .await
+            .expect("coalesced update should arrive")
+            .expect("subscriber should not lag");
+        received += 1;
+        if update.tasks.len() == tasks.len()
+            && update
+                .tasks
+                .iter()
+                .all(|task| task.title.ends_with("renamed"))
+        {
+            break update;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    };
+    assert_eq!(last.tasks.len(), 300);
+    assert!(received < 10, "{received} updates for a single burst");
+}
+
+#[tokio::test]
+async fn test_active_download_speeds_are_summed() {
+    let manager = test_manager();
+    let sem = test_semaphore();
+    let first = manager
+        .add_task(TaskType::DownloadVideo, "First".into())
+        .start(sem.clone())
+        .await;
+    let second = manager
+        .add_task(TaskType::DownloadVideo, "Second".into())
+        .start(sem.clone())
+        .await;
+    // Queued downloads and other task types do not transfer media.
+    let queued = manager.add_task(TaskType::DownloadVideo, "Queued".into());
+    manager.update_task_speed(queued.id(), Some(1_000_000));
+
+    first.update_speed(Some(1_500_000));
+    second.update_speed(Some(500_000));
+    assert_eq!(manager.get_metrics().download_speed, 2_000_000);
+
+    second.update_speed(None);
+    assert_eq!(manager.get_metrics().download_speed, 1_500_000);
+
+    first.complete();
+    assert_eq!(manager.get_metrics().download_speed, 0);
+}
+
+// Outside a runtime every update is sent right away, without coalescing.
+#[test]
+fn test_broadcast_capacity_bounds_lag() {
+    use tokio::sync::broadcast::error::TryRecvError;
+
+    let small = TaskManager::with_capacities(2, 2);
+    let mut rx = small.tx.subscribe();
+    let _tasks: Vec<_> = (0..5)
+        .map(|i| small.add_task(TaskType::DownloadVideo, format!("Task {i}")))
+        .collect();
+    assert!(
+        matches!(rx.try_recv(), Err(TryRecvError::Lagged(3))),
+        "A tiny channel should drop the oldest updates"
+    );
+
+    let large = TaskManager::with_capacities(64, 64);
+    let mut rx = large.tx.subscribe();
+    let _tasks: Vec<_> = (0..5)
+        .map(|i| large.add_task(TaskType::DownloadVideo, format!("Task {i}")))
+        .collect();
+    for _ in 0..5 {
+        assert!(
+            rx.try_recv().is_ok(),
+            "A large channel should keep every update"
+        );
+    }
+    assert!(matches!(rx.try_recv(), Err(TryRecvError::Empty)));
+}
+
+#[tokio::test]
+async fn queued_downloads_report_their_place_in_line() {
+    let manager = test_manager();
+    let first = manager.add_task(TaskType::DownloadVideo, "First".into());
+    let refresh = manager.add_task(TaskType::RefreshIndex, "Refresh".into());
+    let second = manager.add_task(TaskType::DownloadVideo, "Second".into());
+    let third = manager.add_task(TaskType::DownloadVideo, "Third".into());
+
+    let position = |id: &str| {
+        manager
+            .task_snapshot()
+            .tasks
+            .into_iter()
+            .find(|task| task.id == id)
+            .and_then(|task| task.queue_position)
+    };
+    assert_eq!(position(first.id()), Some(1));
+    assert_eq!(position(second.id()), Some(2));
+    assert_eq!(position(third.id()), Some(3));
+    assert_eq!(position(refresh.id()), None);
+
+    // Started downloads leave the line and the rest moves up.
+    let active = first.start(test_semaphore()).await;
+    assert_eq!(position(active.id()), None);
+    assert_eq!(position(second.id()), Some(1));
+    assert_eq!(position(third.id()), Some(2));
+}
+
+#[tokio::test]
+async fn prioritized_source_downloads_start_first() {
+    let manager = test_manager();
+    let sem = Arc::new(Semaphore::new(1));
+    let running = manager
+        .add_task(TaskType::DownloadVideo, "Running".into())
+        .start(sem.clone())
+        .await;
+
+    let mut waiting = Vec::new();
+    for (title, source_id) in [("Backlog 1", 1), ("Backlog 2", 1), ("New upload", 2)] {
+        let queued =
+            manager.add_media_task(TaskType::DownloadVideo, title.into(), Some(source_id), None);
+        let id = queued.id().to_string();
+        let sem = sem.clone();
+        waiting.push((id, tokio::spawn(async move { queued.start(sem).await })));
+        // Lets every task join the line before the next one is added.
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    let position = |id: &str| {
+        manager
+            .task_snapshot()
+            .tasks
+            .into_iter()
+            .find(|task| task.id == id)
+            .and_then(|task| task.queue_position)
+    };
+    assert_eq!(position(&waiting[2].0), Some(3));
+
+    assert_eq!(manager.prioritize_source_downloads(2), 1);
+    assert_eq!(manager.prioritize_source_downloads(3), 0);
+    assert_eq!(position(&waiting[2].0), Some(1));
+    assert_eq!(position(&waiting[0].0), Some(2));
+    assert_eq!(position(&waiting[1].0), Some(3));
+
+    running.complete();
+    let (new_upload_id, new_upload) = waiting.pop().unwrap();
+    let new_upload = tokio::time::timeout(Duration::from_secs(1), new_upload)
+        .await
+        .expect("the prioritized download starts once the slot frees up")
+        .unwrap();
+    assert_eq!(new_upload.id(), new_upload_id);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(
+        waiting.iter().all(|(_, handle)| !handle.is_finished()),
+        "the backlog keeps waiting"
+    );
+
+    new_upload.complete();
+    let first = waiting.remove(0);
+    let first = tokio::time::timeout(Duration::from_secs(1), first.1)
+        .await
+        .expect("the backlog continues in its order")
+        .unwrap();
+    assert_eq!(position(&waiting[0].0), Some(1));
+    first.complete();
+    tokio::time::timeout(Duration::from_secs(1), waiting.remove(0).1)
+        .await
+        .expect("the last download starts")
+        .unwrap()
+        .complete();
+}
+
+#[tokio::test]
+async fn many_waiters_start_one_at_a_time_in_order() {
+    let manager = test_manager();
+    let sem = Arc::new(Semaphore::new(1));
+    let mut running = manager
+        .add_task(TaskType::DownloadVideo, "Running".into())
+        .start(sem.clone())
+        .await;
+
+    let mut waiting = Vec::new();
+    for n in 0..50 {
+        let queued = manager.add_task(TaskType::DownloadVideo, format!("Waiting {n}"));
+        let id = queued.id().to_string();
+        let sem = sem.clone();
+        waiting.push((id, tokio::spawn(async move { queued.start(sem).await })));
+    }
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    for (id, handle) in waiting {
+        running.complete();
+        let next = tokio::time::timeout(Duration::from_secs(1), handle)
+            .await
+            .expect("the next waiter gets the released slot")
+            .unwrap();
+        assert_eq!(next.id(), id, "waiters start in queue order");
+        assert_eq!(sem.available_permits(), 0);
+        running = next;
+    }
+    running.complete();
+    assert_eq!(sem.available_permits(), 1);
+}
+
+#[tokio::test]
+async fn added_permits_wake_the_waiting_tasks() {
+    let manager = test_manager();
+    let sem = Arc::new(Semaphore::new(1));
+    let running = manager
+        .add_task(TaskType::DownloadVideo, "Running".into())
+        .start(sem.clone())
+        .await;
+    let queued = manager.add_task(TaskType::DownloadVideo, "Waiting".into());
+    let sem_for_task = sem.clone();
+    let handle = tokio::spawn(async move { queued.start(sem_for_task).await });
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert!(!handle.is_finished());
+
+    sem.add_permits(1);
+    manager.slots_added(&sem);
+    let started = tokio::time::timeout(Duration::from_millis(200), handle)
+        .await
+        .expect("the waiting task starts without polling")
+        .unwrap();
+    started.complete();
+    running.complete();
+    assert_eq!(sem.available_permits(), 2);
+}
+
+#[tokio::test]
+async fn task_snapshot_reports_task_ages() {
+    let manager = test_manager();
+    let running = manager.add_task(TaskType::DownloadVideo, "Running".into());
+    let done = manager.add_task(TaskType::DownloadVideo, "Done".into());
+    let done_id = done.id().to_string();
+    done.start(Arc::new(Semaphore::new(1))).await.complete();
+
+    let snapshot = manager.task_snapshot();
+    let task = |id: &str| {
+        snapshot
+            .tasks
+            .iter()
+            .find(|task| task.id == id)
+            .cloned()
+            .unwrap()
+    };
+    let running = task(running.id());
+    assert!(
+        (0.0..1.0).contains(&running.created_seconds_ago),
+        "a fresh task is about 0s old, got {}",
+        running.created_seconds_ago
+    );
+    assert_eq!(running.completed_seconds_ago, None);
+    let done = task(&done_id);
+    assert!(done.completed_seconds_ago.is_some_and(|ago| ago < 1.0));
+    assert!(done.completed_seconds_ago <= Some(done.created_seconds_ago));
+}
+
+#[tokio::test]
+async fn task_snapshot_carries_source_and_media_ids() {
+    let manager = test_manager();
+    let download =
+        manager.add_media_task(TaskType::DownloadVideo, "Download".into(), Some(4), Some(9));
+    let refresh = manager.add_media_task(TaskType::RefreshIndex, "Refresh".into(), Some(4), None);
+    let other = manager.add_task(TaskType::DownloadVideo, "Other".into());
+
+    let snapshot = serde_json::to_value(manager.task_snapshot()).unwrap();
+    let task = |id: &str| {
+        snapshot["tasks"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|task| task["id"] == id)
+            .cloned()
+            .unwrap()
+    };
+    assert_eq!(task(download.id())["source_id"], 4);
+    assert_eq!(task(download.id())["media_id"], 9);
+    assert_eq!(task(refresh.id())["source_id"], 4);
+    assert!(task(refresh.id())["media_id"].is_null());
+    assert!(task(other.id())["source_id"].is_null());
+}